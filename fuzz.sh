@@ -1,7 +1,15 @@
 #!/usr/bin/env bash
 
+# Usage: ./fuzz.sh [png|webp]
+target=${1:-png}
+
 cd fuzz
 cargo clean
 rm Cargo.lock
 cargo afl build
-cargo afl fuzz -i in -o out target/debug/fuzz
\ No newline at end of file
+
+if [ "$target" = "webp" ]; then
+  cargo afl fuzz -i in-webp -o out-webp target/debug/webp
+else
+  cargo afl fuzz -i in -o out target/debug/fuzz
+fi
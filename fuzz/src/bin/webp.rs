@@ -0,0 +1,9 @@
+use afl::fuzz;
+use iris::webp::WebpDecoder;
+
+fn main() {
+    fuzz!(|data: &[u8]| {
+        let mut decoder = WebpDecoder::new(data);
+        let _ = decoder.decode();
+    });
+}
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, bail, Result};
+use iris::gif::GifEncoder;
+use iris::png::{
+    diff::{blink, side_by_side, DiffOptions},
+    grammar::Png,
+    PngDecoder, PngEncoder,
+};
+use std::fs;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: iris-diff <reference> <test> [--threshold <0-1>] \
+[--amplify <factor>] [--amplified <output.png>] [--heatmap <output.png>] \
+[--highlight <output.png>] [--side-by-side <output.png>] [--blink <output.gif>] \
+[--blink-interval <ms>] [--max-mismatched <pixels>]";
+
+fn read_png(image_path: &str) -> Result<Png> {
+    let image_data = fs::read(image_path)?;
+    let image = PngDecoder::new(&image_data).decode()?;
+
+    Ok(image)
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| anyhow!("{} expects a number.\n{}", flag, USAGE))
+}
+
+fn output_path(flag: &str, value: Option<String>) -> Result<Option<String>> {
+    value
+        .map(Some)
+        .ok_or_else(|| anyhow!("{} expects an output path.\n{}", flag, USAGE))
+}
+
+fn write_png(path: &Option<String>, png: impl FnOnce() -> Png) -> Result<()> {
+    if let Some(path) = path {
+        fs::write(path, PngEncoder::new(&png()).encode()?)?;
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (reference_image, test_image) = match (args.next(), args.next()) {
+        (Some(reference_image_path), Some(test_image_path)) => (
+            read_png(&reference_image_path)?,
+            read_png(&test_image_path)?,
+        ),
+        _ => bail!(USAGE),
+    };
+
+    let mut options = DiffOptions::default();
+    let (mut amplified, mut heatmap, mut highlight, mut combined, mut blinking) =
+        (None, None, None, None, None);
+    let mut blink_interval = Duration::from_millis(500);
+    let mut max_mismatched = None;
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--threshold" => options.threshold = parse_value(&flag, args.next())?,
+            "--amplify" => options.amplification = parse_value(&flag, args.next())?,
+            "--amplified" => amplified = output_path(&flag, args.next())?,
+            "--heatmap" => heatmap = output_path(&flag, args.next())?,
+            "--highlight" => highlight = output_path(&flag, args.next())?,
+            "--side-by-side" => combined = output_path(&flag, args.next())?,
+            "--blink" => blinking = output_path(&flag, args.next())?,
+            "--blink-interval" => {
+                blink_interval = Duration::from_millis(parse_value(&flag, args.next())?)
+            }
+            "--max-mismatched" => max_mismatched = Some(parse_value(&flag, args.next())?),
+            foreign => bail!("Unknown option: {}\n{}", foreign, USAGE),
+        }
+    }
+
+    let diff = test_image.diff(&reference_image, options)?;
+    let mismatched = diff.mismatched();
+
+    println!(
+        "mismatched pixels: {} ({:.4}%)\tmax distance: {}",
+        mismatched,
+        diff.mismatch_ratio() * 100.0,
+        diff.max_distance()
+    );
+
+    write_png(&amplified, || diff.amplified())?;
+    write_png(&heatmap, || diff.heatmap())?;
+    write_png(&highlight, || diff.highlight(&reference_image))?;
+    write_png(&combined, || {
+        side_by_side(&[
+            &reference_image,
+            &test_image,
+            &diff.highlight(&reference_image),
+        ])
+    })?;
+
+    if let Some(path) = blinking {
+        let frames = blink(&reference_image, &test_image, blink_interval);
+        fs::write(path, GifEncoder::new(&frames).encode()?)?;
+    }
+
+    if let Some(max_mismatched) = max_mismatched {
+        if mismatched > max_mismatched {
+            bail!(
+                "{} pixels mismatched, more than the {} allowed.",
+                mismatched,
+                max_mismatched
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,20 @@
-use anyhow::Result;
-use iris::font::grammar::{Glyph, GlyphData};
+use anyhow::{anyhow, Result};
+use iris::font::grammar::TrueTypeFontFile;
+use iris::font::path::{GlyphOutline, PathCommand};
+use iris::font::raster::{GlyphBitmap, Rasterizer};
+use iris::font::sdf::SdfOptions;
 use iris::font::shaper::TrueTypeFontShaper;
 use iris::font::TrueTypeFontParser;
+use iris::png::PngEncoder;
+use iris::util::affine::Affine;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
+const USAGE: &str = "Usage: iris-lato-glyphs [--png <output.png>] [--sdf <atlas.png>] \
+                     [--single-channel] [--size <pixels>] <phrase>...";
+
 fn dom_new_canvas(i: usize, width: usize, height: usize) -> String {
     let mut out = String::new();
     out += &format!(
@@ -19,67 +27,64 @@ fn dom_new_canvas(i: usize, width: usize, height: usize) -> String {
     out
 }
 
-fn draw_glyph_to_canvas(glyph: &Glyph, key: usize) -> Result<String> {
-    let GlyphData::Simple(simple_glyph) = &glyph.data else {
-        todo!("how does compound glyphs look on canvas?");
-    };
-
+fn draw_glyph_to_canvas(glyph: &GlyphOutline, key: usize) -> String {
     let mut out = String::new();
     out += &format!("ctx{key}.translate(0, newCanvas{key}.height - 300);\n");
     out += &format!("ctx{key}.scale(0.5, -0.5);\n");
 
     out += &format!("ctx{key}.beginPath()\n");
 
-    let mut implicit_on_curve_points = vec![];
-
-    let mut start_index = 0;
-
-    for end_index in &simple_glyph.end_points_of_contours {
-        let end_index = *end_index as usize;
-
-        let mut i = start_index;
-
-        let (x, y) = simple_glyph.coordinates[i];
-        out += &format!("ctx{key}.moveTo({}, {});\n", x, y);
-
-        i += 1;
-
-        while i <= end_index {
-            // let (prev_x, prev_y) = simple_glyph.coordinates[i - 1];
-            let prev_on_curve = simple_glyph.on_curve(i - 1);
-
-            let (curr_x, curr_y) = simple_glyph.coordinates[i];
-            let start_on_curve = simple_glyph.on_curve(i);
-
-            match (prev_on_curve, start_on_curve) {
-                (true, true) => {
-                    // // an implicit off-curve point.
-                    // out += &format!(
-                    //     "ctx{key}.quadraticCurveTo({}, {}, {}, {});\n",
-                    //     mid_x, mid_y, curr_x, curr_y,
-                    // );
-
-                    implicit_on_curve_points.push(simple_glyph.interpolate_with_prev(i)?);
-
-                    out += &format!("ctx{key}.lineTo({}, {});\n", curr_x, curr_y);
-                }
-                _ => {
-                    out += &format!("ctx{key}.lineTo({}, {});\n", curr_x, curr_y);
-                }
+    for command in &glyph.commands {
+        out += &match *command {
+            PathCommand::MoveTo(x, y) => format!("ctx{key}.moveTo({}, {});\n", x, y),
+            PathCommand::LineTo(x, y) => format!("ctx{key}.lineTo({}, {});\n", x, y),
+            PathCommand::QuadTo(cx, cy, x, y) => {
+                format!("ctx{key}.quadraticCurveTo({}, {}, {}, {});\n", cx, cy, x, y)
             }
-
-            i += 1
-        }
-
-        out += &format!("ctx{key}.closePath();\n");
-
-        start_index = end_index + 1;
+            PathCommand::Close => format!("ctx{key}.closePath();\n"),
+        };
     }
 
     out += &format!("ctx{key}.lineWidth = 9;\n");
     out += &format!("ctx{key}.stroke();\n");
 
-    Ok(out)
+    out
+}
+
+/// Shapes the phrase on a bitmap from the ascent down to the descent.
+fn rasterize_text(ttf: &TrueTypeFontFile, phrase: &str, pixel_size: f32) -> Result<GlyphBitmap> {
+    let scale = ttf.pixel_scale(pixel_size);
+    let glyphs = TrueTypeFontShaper::from(ttf)
+        .with_pixel_size(pixel_size)
+        .shape(phrase);
+
+    let ascent = (ttf.hhea_table.ascent as f32 * scale).ceil();
+    let width = glyphs
+        .iter()
+        .map(|glyph| glyph.x_advance)
+        .sum::<f32>()
+        .ceil();
+    let height = ascent - (ttf.hhea_table.descent as f32 * scale).floor();
+
+    let mut rasterizer = Rasterizer::new(width as usize, height as usize);
+    let mut pen = (0.0, ascent);
+
+    for glyph in glyphs {
+        let transform = Affine::scale(scale, -scale).then(&Affine::translate(
+            pen.0 + glyph.x_offset,
+            pen.1 - glyph.y_offset,
+        ));
+        rasterizer.draw_outline(&ttf.glyph_outline(glyph.glyph_index)?, &transform);
+        pen = (pen.0 + glyph.x_advance, pen.1 - glyph.y_advance);
+    }
+
+    Ok(GlyphBitmap {
+        width: width as u32,
+        height: height as u32,
+        left: 0,
+        top: ascent as i32,
+        coverage: rasterizer.coverage(),
+    })
 }
 
 fn index_html() -> String {
@@ -102,26 +107,79 @@ fn index_html() -> String {
 }
 
 fn main() -> Result<()> {
-    let args = std::env::args().skip(1);
-    let phrase = args.collect::<Vec<String>>().join(" ");
+    let mut args = std::env::args().skip(1);
+    let mut words = vec![];
+    let mut png_path = None;
+    let mut sdf_path = None;
+    let mut multi_channel = true;
+    let mut pixel_size = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--png" => png_path = Some(args.next().ok_or_else(|| anyhow!(USAGE))?),
+            "--sdf" => sdf_path = Some(args.next().ok_or_else(|| anyhow!(USAGE))?),
+            "--single-channel" => multi_channel = false,
+            "--size" => {
+                pixel_size = Some(
+                    args.next()
+                        .and_then(|size| size.parse().ok())
+                        .ok_or_else(|| anyhow!(USAGE))?,
+                )
+            }
+            _ => words.push(arg),
+        }
+    }
 
-    let ttf_file = fs::read("./src/font/lato-regular.ttf")?;
+    let phrase = words.join(" ");
+
+    let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
     let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
-    let shaper = TrueTypeFontShaper::from(&ttf);
 
-    let glyphs = shaper.shape(&phrase);
+    if let Some(sdf_path) = sdf_path {
+        // Without a phrase, the printable ASCII characters.
+        let characters = if phrase.is_empty() {
+            (' '..='~').collect()
+        } else {
+            phrase
+        };
+        let options = SdfOptions {
+            pixel_size: pixel_size.unwrap_or(48.0),
+            multi_channel,
+            ..Default::default()
+        };
+
+        let atlas = ttf.sdf_atlas(&characters, &options)?;
+        let json_path = Path::new(&sdf_path).with_extension("json");
+        fs::write(&sdf_path, PngEncoder::new(&atlas.to_png()).encode()?)?;
+        fs::write(&json_path, atlas.to_json())?;
+        println!("Done!\t{}\t{}", sdf_path, json_path.display());
+
+        return Ok(());
+    }
+
+    if let Some(png_path) = png_path {
+        let text = rasterize_text(&ttf, &phrase, pixel_size.unwrap_or(64.0))?;
+        fs::write(&png_path, PngEncoder::new(&text.to_png()).encode()?)?;
+        println!("Done!\t{}", png_path);
+
+        return Ok(());
+    }
+
+    let glyph_indices = TrueTypeFontShaper::from(&ttf)
+        .shape(&phrase)
+        .into_iter()
+        .map(|glyph| glyph.glyph_index);
 
     let mut render_js_code = String::new();
     render_js_code += "const contentDiv = document.getElementById(\"content\")\n";
 
-    for (i, glyph) in glyphs.into_iter().enumerate() {
-        if !glyph.is_simple() {
-            continue;
-        }
+    for (i, glyph_index) in glyph_indices.enumerate() {
+        let description = &ttf.glyph_table.glyphs[glyph_index as usize].description;
+        let glyph = ttf.glyph_outline(glyph_index)?;
 
-        render_js_code += &dom_new_canvas(i, glyph.description.width(), glyph.description.height());
+        render_js_code += &dom_new_canvas(i, description.width(), description.height());
         render_js_code += &format!("const ctx{} = newCanvas{}.getContext(\"2d\");\n", i, i);
-        render_js_code += &draw_glyph_to_canvas(glyph, i)?;
+        render_js_code += &draw_glyph_to_canvas(&glyph, i);
         render_js_code += &format!("contentDiv.appendChild(newCanvas{});\n\n", i);
     }
 
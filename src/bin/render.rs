@@ -0,0 +1,240 @@
+use anyhow::{anyhow, bail, Result};
+use iris::{
+    bmp::{BmpDecoder, BmpEncoder},
+    cube::{grammar::LutInterpolation, CubeDecoder},
+    farbfeld::{FarbfeldDecoder, FarbfeldEncoder},
+    hdr::{tone_map::ToneMap, HdrDecoder},
+    jpeg::JpegEncoder,
+    ops::{self, Crop, Curves, LinearImage, TransformAction},
+    png::{PngDecoder, PngEncoder},
+    pnm::{grammar::Magic, PnmDecoder, PnmEncoder},
+    renderer::{CpuRenderer, OffscreenRenderer, RenderSettings, TextLabel},
+    tga::{TgaDecoder, TgaEncoder},
+    webp::WebpDecoder,
+};
+use pollster::block_on;
+use std::path::Path;
+
+const USAGE: &str =
+    "Usage: iris-render <input> <output> [--grayscale] [--invert] [--blur <radius>] \
+[--sharpen <factor>] [--edge-detect] [--exposure <stops>] [--tone-map none|reinhard|aces] \
+[--flip-x] [--flip-y] [--rotate <degrees>] [--scale <x> <y>] [--skew <x degrees> <y degrees>] \
+[--translate <x> <y>] [--crop <x> <y> <width> <height>] [--levels <black> <white> <gamma>] \
+[--curves s-curve|fade|cross-process] [--brightness <n>] [--contrast <n>] [--hue <degrees>] \
+[--saturation <n>] [--lightness <n>] [--temperature <n>] [--tint <n>] [--lut <file.cube>] \
+[--lut-interpolation trilinear|tetrahedral] [--equalize] [--clahe <tiles> <clip limit>] \
+[--label <x> <y> <pixel size> <text>]... [--fallback] [--cpu]";
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| anyhow!("{} expects a number.\n{}", flag, USAGE))
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (input_path, output_path) = match (args.next(), args.next()) {
+        (Some(input_path), Some(output_path)) => (input_path, output_path),
+        _ => bail!(USAGE),
+    };
+
+    let mut settings = RenderSettings::default();
+    let mut force_fallback_adapter = false;
+    let mut force_cpu = false;
+    // Equalization needs the whole rendered histogram, so it runs on the CPU afterwards.
+    let mut equalize = false;
+    let mut clahe = None;
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--grayscale" => settings.grayscale = true,
+            "--invert" => settings.invert = true,
+            "--blur" => settings.blur_radius = Some(parse_value(&flag, args.next())?),
+            "--sharpen" => settings.sharpen_factor = Some(parse_value(&flag, args.next())?),
+            "--edge-detect" => settings.edge_detect = true,
+            "--exposure" => settings.exposure = parse_value(&flag, args.next())?,
+            "--tone-map" => {
+                settings.tone_map = match args.next().as_deref() {
+                    Some("none") => ToneMap::None,
+                    Some("reinhard") => ToneMap::Reinhard,
+                    Some("aces") => ToneMap::Aces,
+                    _ => bail!("--tone-map expects none, reinhard or aces.\n{}", USAGE),
+                }
+            }
+            "--flip-x" => settings.transform.push(TransformAction::FlipX),
+            "--flip-y" => settings.transform.push(TransformAction::FlipY),
+            "--rotate" => {
+                let action = match parse_value(&flag, args.next())? {
+                    90.0 => TransformAction::Rotate90,
+                    180.0 => TransformAction::Rotate180,
+                    270.0 | -90.0 => TransformAction::Rotate270,
+                    degrees => TransformAction::Rotate(degrees),
+                };
+                settings.transform.push(action);
+            }
+            "--scale" => settings.transform.push(TransformAction::Scale(
+                parse_value(&flag, args.next())?,
+                parse_value(&flag, args.next())?,
+            )),
+            "--skew" => settings.transform.push(TransformAction::Skew(
+                parse_value(&flag, args.next())?,
+                parse_value(&flag, args.next())?,
+            )),
+            "--translate" => settings.transform.push(TransformAction::Translate(
+                parse_value(&flag, args.next())?,
+                parse_value(&flag, args.next())?,
+            )),
+            "--crop" => settings.transform.set_crop(Some(Crop {
+                x: parse_value(&flag, args.next())?,
+                y: parse_value(&flag, args.next())?,
+                width: parse_value(&flag, args.next())?,
+                height: parse_value(&flag, args.next())?,
+            })),
+            "--levels" => {
+                let levels = &mut settings.adjustments.levels;
+                levels.black = parse_value(&flag, args.next())?;
+                levels.white = parse_value(&flag, args.next())?;
+                levels.gamma = parse_value(&flag, args.next())?;
+            }
+            "--curves" => {
+                settings.adjustments.curves = match args.next().as_deref() {
+                    Some("s-curve") => Curves::s_curve(),
+                    Some("fade") => Curves::fade(),
+                    Some("cross-process") => Curves::cross_process(),
+                    _ => bail!(
+                        "--curves expects s-curve, fade or cross-process.\n{}",
+                        USAGE
+                    ),
+                }
+            }
+            "--brightness" => settings.adjustments.brightness = parse_value(&flag, args.next())?,
+            "--contrast" => settings.adjustments.contrast = parse_value(&flag, args.next())?,
+            "--hue" => settings.adjustments.hue = parse_value(&flag, args.next())?,
+            "--saturation" => settings.adjustments.saturation = parse_value(&flag, args.next())?,
+            "--lightness" => settings.adjustments.lightness = parse_value(&flag, args.next())?,
+            "--temperature" => settings.adjustments.temperature = parse_value(&flag, args.next())?,
+            "--tint" => settings.adjustments.tint = parse_value(&flag, args.next())?,
+            "--lut" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--lut expects a .cube file.\n{}", USAGE))?;
+                settings.lut = Some(CubeDecoder::new(&std::fs::read(path)?).decode()?);
+            }
+            "--lut-interpolation" => {
+                settings.lut_interpolation = match args.next().as_deref() {
+                    Some("trilinear") => LutInterpolation::Trilinear,
+                    Some("tetrahedral") => LutInterpolation::Tetrahedral,
+                    _ => bail!(
+                        "--lut-interpolation expects trilinear or tetrahedral.\n{}",
+                        USAGE
+                    ),
+                }
+            }
+            "--equalize" => equalize = true,
+            "--clahe" => {
+                let tiles = parse_value(&flag, args.next())?;
+                clahe = Some(((tiles, tiles), parse_value(&flag, args.next())?));
+            }
+            "--label" => {
+                let position = (
+                    parse_value(&flag, args.next())?,
+                    parse_value(&flag, args.next())?,
+                );
+                let pixel_size = parse_value(&flag, args.next())?;
+                let text = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--label expects some text.\n{}", USAGE))?;
+
+                settings.labels.push(TextLabel {
+                    background: Some([0.0, 0.0, 0.0, 0.6]),
+                    ..TextLabel::new(text, position, pixel_size)
+                });
+            }
+            "--fallback" => force_fallback_adapter = true,
+            "--cpu" => force_cpu = true,
+            foreign => bail!("Unknown option: {}\n{}", foreign, USAGE),
+        }
+    }
+
+    let content = std::fs::read(&input_path)?;
+
+    // Without any adapter, not even a software one, the same pipeline runs on the CPU.
+    let renderer = if force_cpu {
+        None
+    } else {
+        match block_on(OffscreenRenderer::new(force_fallback_adapter)) {
+            Ok(renderer) => Some(renderer),
+            Err(e) => {
+                eprintln!("{e}, rendering on the CPU instead.");
+                None
+            }
+        }
+    };
+
+    let mut rendered = match extension(&input_path).as_str() {
+        "hdr" => {
+            let image = HdrDecoder::new(&content).decode()?;
+
+            match &renderer {
+                Some(renderer) => renderer.render_hdr(&image, &settings)?,
+                None => CpuRenderer.render_hdr(&image, &settings)?,
+            }
+        }
+        extension => {
+            let png = match extension {
+                "bmp" => BmpDecoder::new(&content).decode()?,
+                "tga" => TgaDecoder::new(&content).decode()?,
+                "pbm" | "pgm" | "ppm" | "pam" | "pnm" => PnmDecoder::new(&content).decode()?,
+                "ff" => FarbfeldDecoder::new(&content).decode()?,
+                "webp" => WebpDecoder::new(&content).decode()?,
+                _ => PngDecoder::new(&content).decode()?,
+            };
+
+            match &renderer {
+                Some(renderer) => renderer.render(&png, &settings)?,
+                None => CpuRenderer.render(&png, &settings)?,
+            }
+        }
+    };
+
+    if equalize || clahe.is_some() {
+        let mut image = LinearImage::from_png(&rendered);
+        if let Some((tiles, clip_limit)) = clahe {
+            image = ops::clahe(&image, tiles, clip_limit);
+        }
+        if equalize {
+            image = ops::equalize(&image);
+        }
+        rendered = image.to_png();
+    }
+
+    let encoded = match extension(&output_path).as_str() {
+        "jpg" | "jpeg" => JpegEncoder::new(&rendered).encode()?,
+        "bmp" => BmpEncoder::new(&rendered).encode()?,
+        "tga" => TgaEncoder::new(&rendered).encode()?,
+        "ppm" => PnmEncoder::new(&rendered)
+            .with_magic(Magic::Pixmap)
+            .encode()?,
+        "pgm" => PnmEncoder::new(&rendered)
+            .with_magic(Magic::Graymap)
+            .encode()?,
+        "pam" => PnmEncoder::new(&rendered).encode()?,
+        "ff" => FarbfeldEncoder::new(&rendered).encode()?,
+        "png" => PngEncoder::new(&rendered).encode()?,
+        foreign => bail!(
+            "No encoder for .{} output. Write .png, .jpg, .bmp, .tga, .ppm, .pgm, .pam or .ff.",
+            foreign
+        ),
+    };
+
+    std::fs::write(&output_path, encoded)?;
+
+    Ok(())
+}
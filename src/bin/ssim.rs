@@ -1,7 +1,129 @@
-use anyhow::{anyhow, Result};
-use iris::png::{grammar::Png, PngDecoder};
+use anyhow::{anyhow, bail, Result};
+use iris::png::{
+    grammar::Png,
+    metrics::{psnr, ChannelValues, DeltaE},
+    PngDecoder, PngEncoder,
+};
 use std::fs;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage: iris-ssim <reference> <test> \
+[--metric ssim|windowed-ssim|ms-ssim|dssim|mse|psnr|delta-e]... [--premultiply] [--json] \
+[--map <output.png>]";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Metric {
+    Ssim,
+    WindowedSsim,
+    MsSsim,
+    Dssim,
+    Mse,
+    Psnr,
+    DeltaE,
+}
+
+impl Metric {
+    const ALL: [Self; 7] = [
+        Self::Ssim,
+        Self::WindowedSsim,
+        Self::MsSsim,
+        Self::Dssim,
+        Self::Mse,
+        Self::Psnr,
+        Self::DeltaE,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Ssim => "ssim",
+            Self::WindowedSsim => "windowed-ssim",
+            Self::MsSsim => "ms-ssim",
+            Self::Dssim => "dssim",
+            Self::Mse => "mse",
+            Self::Psnr => "psnr",
+            Self::DeltaE => "delta-e",
+        }
+    }
+
+    fn compute(self, test_image: &Png, reference_image: &Png) -> Result<Score> {
+        Ok(match self {
+            Self::Ssim => Score::Scalar(test_image.compute_sim(reference_image)? as f64),
+            Self::WindowedSsim => {
+                Score::Scalar(test_image.compute_windowed_ssim(reference_image)? as f64)
+            }
+            Self::MsSsim => Score::Scalar(test_image.compute_ms_ssim(reference_image)? as f64),
+            Self::Dssim => Score::Scalar(test_image.compute_dssim(reference_image)? as f64),
+            Self::Mse => Score::Channels(test_image.compute_mse(reference_image)?, None),
+            Self::Psnr => {
+                let mse = test_image.compute_mse(reference_image)?;
+                Score::Channels(
+                    test_image.compute_psnr(reference_image)?,
+                    Some(psnr(mse.rgb())),
+                )
+            }
+            Self::DeltaE => Score::DeltaE(test_image.compute_delta_e(reference_image)?),
+        })
+    }
+}
+
+enum Score {
+    Scalar(f64),
+    /// Per channel values, with the overall color figure when it isn't the plain mean.
+    Channels(ChannelValues, Option<f64>),
+    DeltaE(DeltaE),
+}
+
+impl Score {
+    fn fields(&self) -> Vec<(&'static str, f64)> {
+        match *self {
+            Self::Scalar(value) => vec![("value", value)],
+            Self::Channels(channels, rgb) => vec![
+                ("red", channels.red),
+                ("green", channels.green),
+                ("blue", channels.blue),
+                ("alpha", channels.alpha),
+                ("rgb", rgb.unwrap_or(channels.rgb())),
+            ],
+            Self::DeltaE(delta_e) => vec![("mean", delta_e.mean), ("max", delta_e.max)],
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Self::Scalar(value) => value.to_string(),
+            _ => self
+                .fields()
+                .iter()
+                .map(|(name, value)| format!("{name}: {value}"))
+                .collect::<Vec<_>>()
+                .join("  "),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            Self::Scalar(value) => json_number(*value),
+            _ => {
+                let fields = self
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| format!("\"{name}\": {}", json_number(*value)))
+                    .collect::<Vec<_>>();
+
+                format!("{{{}}}", fields.join(", "))
+            }
+        }
+    }
+}
+
+/// JSON has no infinity, which is what PSNR reports for identical channels.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
 
 fn read_png(image_path: &str) -> Result<Png> {
     let image_data = fs::read(image_path)?;
@@ -12,21 +134,91 @@ fn read_png(image_path: &str) -> Result<Png> {
 
 fn main() -> Result<()> {
     let mut args = std::env::args().skip(1);
-    let (reference_image, test_image) = match (args.next(), args.next()) {
+    let (mut reference_image, mut test_image) = match (args.next(), args.next()) {
         (Some(reference_image_path), Some(test_image_path)) => (
             read_png(&reference_image_path)?,
             read_png(&test_image_path)?,
         ),
-        _ => {
-            return Err(anyhow!(
-                "Provide paths to a reference image AND a test image."
-            ))
-        }
+        _ => bail!(
+            "Provide paths to a reference image AND a test image.\n{}",
+            USAGE
+        ),
     };
 
-    let now = Instant::now();
-    let ssim = reference_image.compute_sim(&test_image)?;
-    println!("ssim score: {}\telapsed: {:?}", ssim, now.elapsed());
+    let mut metrics = vec![];
+    let mut premultiply = false;
+    let mut json = false;
+    let mut map_path = None;
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--metric" => {
+                let name = args.next();
+                let metric = Metric::ALL
+                    .into_iter()
+                    .find(|metric| Some(metric.name()) == name.as_deref())
+                    .ok_or_else(|| anyhow!("--metric expects a metric name.\n{}", USAGE))?;
+                metrics.push(metric);
+            }
+            "--premultiply" => premultiply = true,
+            "--json" => json = true,
+            "--map" => {
+                map_path = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--map expects an output path.\n{}", USAGE))?,
+                )
+            }
+            foreign => bail!("Unknown option: {}\n{}", foreign, USAGE),
+        }
+    }
+
+    if premultiply {
+        reference_image = reference_image.premultiplied();
+        test_image = test_image.premultiplied();
+    }
+
+    // Without a selection every metric is reported, skipping the ones the images are too small
+    // for. Metrics asked for by name fail loudly instead.
+    let selected = !metrics.is_empty();
+    if !selected {
+        metrics = Metric::ALL.to_vec();
+    }
+
+    let mut scores: Vec<(Metric, Option<Score>, Duration)> = vec![];
+    for metric in metrics {
+        let now = Instant::now();
+        let score = match metric.compute(&test_image, &reference_image) {
+            Ok(score) => Some(score),
+            Err(e) if !selected => {
+                eprintln!("{}: {}", metric.name(), e);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        scores.push((metric, score, now.elapsed()));
+    }
+
+    if json {
+        let fields = scores
+            .iter()
+            .map(|(metric, score, _)| {
+                let value = score.as_ref().map_or("null".to_string(), Score::to_json);
+                format!("\"{}\": {}", metric.name(), value)
+            })
+            .collect::<Vec<_>>();
+
+        println!("{{{}}}", fields.join(", "));
+    } else {
+        for (metric, score, elapsed) in &scores {
+            let value = score.as_ref().map_or("n/a".to_string(), Score::to_text);
+            println!("{:<14} {}\telapsed: {:?}", metric.name(), value, elapsed);
+        }
+    }
+
+    if let Some(map_path) = map_path {
+        let ssim_map = test_image.ssim_map(&reference_image)?;
+        fs::write(map_path, PngEncoder::new(&ssim_map.to_png()).encode()?)?;
+    }
 
     Ok(())
 }
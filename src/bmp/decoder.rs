@@ -0,0 +1,538 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::bmp::grammar::{
+    BitmapHeader, ChannelMasks, Compression, CORE_HEADER_SIZE, INFO_HEADER_SIZE,
+};
+use crate::png::grammar::{ColorType, Png};
+use crate::util::read_bytes::{U16_BYTES, U32_BYTES, U8_BYTES};
+use crate::{eof, read_le};
+
+#[derive(Debug)]
+pub struct BmpDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> BmpDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Png> {
+        ensure!(
+            self.read_slice(2)? == b"BM",
+            "Invalid BMP file: incorrect signature."
+        );
+
+        let _file_size = self.read_u32()?;
+        let _reserved = self.read_u32()?;
+        let pixel_offset = self.read_u32()? as usize;
+
+        let header = self.parse_bitmap_header()?;
+        let palette = self.parse_palette(&header)?;
+
+        ensure!(
+            header.width > 0 && header.height > 0,
+            "BMP dimensions must be positive."
+        );
+        ensure!(
+            (header.width as u64 * header.height as u64) < (1 << 30),
+            "BMP dimensions are too large: {}x{}.",
+            header.width,
+            header.height
+        );
+
+        ensure!(
+            pixel_offset <= self.data.len(),
+            "Pixel data offset is out of bounds."
+        );
+        self.cursor = pixel_offset;
+
+        // Decode into top-down RGBA, then drop the alpha channel if the image has none.
+        let rgba = match header.compression {
+            Compression::Rle8 | Compression::Rle4 => self.decode_rle(&header, &palette)?,
+            Compression::Rgb | Compression::Bitfields | Compression::AlphaBitfields => {
+                self.decode_uncompressed(&header, &palette)?
+            }
+        };
+
+        let has_alpha = header.masks.is_some_and(|m| m.alpha != 0)
+            // Many writers leave the alpha channel zeroed out, which means opaque in practice.
+            && rgba.chunks_exact(4).any(|p| p[3] != 0);
+
+        let (color_type, pixel_buffer) = if has_alpha {
+            (ColorType::RGBA, rgba)
+        } else {
+            let rgb = rgba
+                .chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]])
+                .collect();
+
+            (ColorType::RGB, rgb)
+        };
+
+        Ok(Png {
+            width: header.width,
+            height: header.height,
+            gamma: 0,
+            color_type,
+            pixel_buffer,
+            exif: None,
+        })
+    }
+
+    fn parse_bitmap_header(&mut self) -> Result<BitmapHeader> {
+        let header_start = self.cursor;
+        let header_size = self.read_u32()?;
+
+        if header_size == CORE_HEADER_SIZE {
+            let width = self.read_u16()? as u32;
+            let height = self.read_u16()? as u32;
+            let _planes = self.read_u16()?;
+            let bits_per_pixel = self.read_u16()?;
+
+            return Ok(BitmapHeader {
+                header_size,
+                width,
+                height,
+                top_down: false,
+                bits_per_pixel,
+                compression: Compression::Rgb,
+                colors_used: 0,
+                masks: None,
+            });
+        }
+
+        ensure!(
+            header_size >= INFO_HEADER_SIZE,
+            "Unrecognized BMP header size: {}",
+            header_size
+        );
+
+        let width = self.read_i32()?;
+        let height = self.read_i32()?;
+        let _planes = self.read_u16()?;
+        let bits_per_pixel = self.read_u16()?;
+        let compression = Compression::try_from(self.read_u32()?)?;
+        let _image_size = self.read_u32()?;
+        let _x_pixels_per_meter = self.read_i32()?;
+        let _y_pixels_per_meter = self.read_i32()?;
+        let colors_used = self.read_u32()?;
+        let _colors_important = self.read_u32()?;
+
+        ensure!(width > 0, "BMP width must be positive.");
+
+        // V2 and later headers embed the masks, BITMAPINFOHEADER appends them after the header.
+        let explicit_masks = match compression {
+            Compression::Bitfields | Compression::AlphaBitfields => {
+                let red = self.read_u32()?;
+                let green = self.read_u32()?;
+                let blue = self.read_u32()?;
+                let alpha = if compression == Compression::AlphaBitfields || header_size > 52 {
+                    self.read_u32()?
+                } else {
+                    0
+                };
+
+                Some(ChannelMasks {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                })
+            }
+            _ => {
+                if header_size > 56 {
+                    // A V4/V5 header may carry an alpha mask even for BI_RGB.
+                    self.cursor = header_start + INFO_HEADER_SIZE as usize + 12;
+                    let alpha = self.read_u32()?;
+                    (bits_per_pixel == 32 && alpha != 0).then_some(ChannelMasks {
+                        alpha,
+                        ..ChannelMasks::XRGB8888
+                    })
+                } else {
+                    None
+                }
+            }
+        };
+
+        let masks = explicit_masks.or(match bits_per_pixel {
+            16 => Some(ChannelMasks::RGB555),
+            32 => Some(ChannelMasks::XRGB8888),
+            _ => None,
+        });
+
+        // Skip over the rest of the header, past the masks of a BITMAPINFOHEADER if there were any.
+        let header_end = header_start + header_size as usize;
+        self.cursor = self.cursor.max(header_end);
+
+        Ok(BitmapHeader {
+            header_size,
+            width: width as u32,
+            height: height.unsigned_abs(),
+            top_down: height < 0,
+            bits_per_pixel,
+            compression,
+            colors_used,
+            masks,
+        })
+    }
+
+    fn parse_palette(&mut self, header: &BitmapHeader) -> Result<Vec<[u8; 4]>> {
+        if header.bits_per_pixel > 8 {
+            return Ok(vec![]);
+        }
+
+        let max_colors = 1usize << header.bits_per_pixel;
+        let len = match header.colors_used as usize {
+            0 => max_colors,
+            n => n.min(max_colors),
+        };
+
+        let entry_size = if header.header_size == CORE_HEADER_SIZE {
+            3
+        } else {
+            4
+        };
+
+        let palette = self
+            .read_slice(len * entry_size)?
+            .chunks_exact(entry_size)
+            .map(|bgr| [bgr[2], bgr[1], bgr[0], 255])
+            .collect();
+
+        Ok(palette)
+    }
+
+    fn decode_uncompressed(
+        &mut self,
+        header: &BitmapHeader,
+        palette: &[[u8; 4]],
+    ) -> Result<Vec<u8>> {
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let bits_per_pixel = header.bits_per_pixel as usize;
+
+        let row_size = (width * bits_per_pixel).div_ceil(32) * 4;
+        let mut rgba = vec![0; width * height * 4];
+
+        for row in 0..height {
+            let y = if header.top_down {
+                row
+            } else {
+                height - 1 - row
+            };
+            let data = self.read_slice(row_size)?;
+            let out = &mut rgba[y * width * 4..(y + 1) * width * 4];
+
+            for (x, pixel) in out.chunks_exact_mut(4).enumerate() {
+                let color = match bits_per_pixel {
+                    1 | 2 | 4 | 8 => {
+                        let bit = x * bits_per_pixel;
+                        let shift = 8 - bits_per_pixel - bit % 8;
+                        let index = (data[bit / 8] >> shift) & ((1 << bits_per_pixel) - 1) as u8;
+
+                        Self::palette_color(palette, index)?
+                    }
+                    24 => [data[x * 3 + 2], data[x * 3 + 1], data[x * 3], 255],
+                    16 | 32 => {
+                        let bytes = bits_per_pixel / 8;
+                        let mut value = [0; 4];
+                        value[..bytes].copy_from_slice(&data[x * bytes..(x + 1) * bytes]);
+
+                        let Some(masks) = header.masks else {
+                            bail!("Missing channel masks for {}-bit BMP.", bits_per_pixel);
+                        };
+
+                        Self::masked_color(u32::from_le_bytes(value), &masks)
+                    }
+                    foreign => bail!("Unsupported BMP bit depth: {}", foreign),
+                };
+
+                pixel.copy_from_slice(&color);
+            }
+        }
+
+        Ok(rgba)
+    }
+
+    fn decode_rle(&mut self, header: &BitmapHeader, palette: &[[u8; 4]]) -> Result<Vec<u8>> {
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let is_rle4 = header.compression == Compression::Rle4;
+
+        ensure!(
+            header.bits_per_pixel == if is_rle4 { 4 } else { 8 },
+            "RLE compression does not match the bit depth."
+        );
+
+        let mut indices = vec![0u8; width * height];
+        // Coordinates in file order, which is bottom-up.
+        let (mut x, mut row) = (0usize, 0usize);
+
+        let mut put = |x: usize, row: usize, index: u8| {
+            if x < width && row < height {
+                let y = if header.top_down {
+                    row
+                } else {
+                    height - 1 - row
+                };
+                indices[y * width + x] = index;
+            }
+        };
+
+        loop {
+            let count = self.read_u8()? as usize;
+            let value = self.read_u8()?;
+
+            if count > 0 {
+                for i in 0..count {
+                    let index = if is_rle4 {
+                        if i % 2 == 0 {
+                            value >> 4
+                        } else {
+                            value & 0x0F
+                        }
+                    } else {
+                        value
+                    };
+
+                    put(x, row, index);
+                    x += 1;
+                }
+
+                continue;
+            }
+
+            match value {
+                // End of line.
+                0 => {
+                    x = 0;
+                    row += 1;
+                }
+                // End of bitmap.
+                1 => break,
+                // Delta.
+                2 => {
+                    x += self.read_u8()? as usize;
+                    row += self.read_u8()? as usize;
+                }
+                // Absolute mode, padded to a 16-bit boundary.
+                n => {
+                    let n = n as usize;
+                    let len = if is_rle4 { n.div_ceil(2) } else { n };
+                    let data = self.read_slice(len)?;
+
+                    for i in 0..n {
+                        let index = if is_rle4 {
+                            if i % 2 == 0 {
+                                data[i / 2] >> 4
+                            } else {
+                                data[i / 2] & 0x0F
+                            }
+                        } else {
+                            data[i]
+                        };
+
+                        put(x, row, index);
+                        x += 1;
+                    }
+
+                    if len % 2 == 1 {
+                        self.read_u8()?;
+                    }
+                }
+            }
+
+            if row >= height {
+                break;
+            }
+        }
+
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for &index in &indices {
+            rgba.extend_from_slice(&Self::palette_color(palette, index)?);
+        }
+
+        Ok(rgba)
+    }
+
+    fn palette_color(palette: &[[u8; 4]], index: u8) -> Result<[u8; 4]> {
+        palette
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Palette index {} is out of range.", index))
+    }
+
+    const fn masked_color(value: u32, masks: &ChannelMasks) -> [u8; 4] {
+        let alpha = if masks.alpha == 0 {
+            255
+        } else {
+            ChannelMasks::extract(value, masks.alpha)
+        };
+
+        [
+            ChannelMasks::extract(value, masks.red),
+            ChannelMasks::extract(value, masks.green),
+            ChannelMasks::extract(value, masks.blue),
+            alpha,
+        ]
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        self.eof(len)?;
+
+        let slice = &self.data[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        Ok(slice)
+    }
+
+    eof!();
+    read_le!(read_u8, u8, U8_BYTES);
+    read_le!(read_u16, u16, U16_BYTES);
+    read_le!(read_u32, u32, U32_BYTES);
+    read_le!(read_i32, i32, U32_BYTES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bmp::BmpEncoder;
+    use image::{ImageFormat, RgbImage, RgbaImage};
+    use std::io::Cursor;
+
+    fn bmp_file(header: &[u8], palette: &[u8], pixels: &[u8]) -> Vec<u8> {
+        let offset = 14 + header.len() + palette.len();
+
+        let mut out = b"BM".to_vec();
+        out.extend_from_slice(&((offset + pixels.len()) as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        out.extend_from_slice(header);
+        out.extend_from_slice(palette);
+        out.extend_from_slice(pixels);
+
+        out
+    }
+
+    fn info_header(
+        width: i32,
+        height: i32,
+        bits_per_pixel: u16,
+        compression: u32,
+        colors_used: u32,
+    ) -> Vec<u8> {
+        let mut header = INFO_HEADER_SIZE.to_le_bytes().to_vec();
+        header.extend_from_slice(&width.to_le_bytes());
+        header.extend_from_slice(&height.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        header.extend_from_slice(&compression.to_le_bytes());
+        header.extend_from_slice(&[0; 12]);
+        header.extend_from_slice(&colors_used.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+
+        header
+    }
+
+    #[test]
+    fn test_reference_rgb() -> Result<()> {
+        let reference = RgbImage::from_fn(7, 5, |x, y| image::Rgb([x as u8 * 30, y as u8 * 50, 7]));
+
+        let mut encoded = Vec::new();
+        reference.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Bmp)?;
+
+        let png = BmpDecoder::new(&encoded).decode()?;
+        assert_eq!(png.color_type, ColorType::RGB);
+        assert_eq!(png.pixel_buffer, reference.into_raw());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_rgba() -> Result<()> {
+        let reference = RgbaImage::from_fn(3, 4, |x, y| {
+            image::Rgba([x as u8 * 80, y as u8 * 60, 9, 100])
+        });
+
+        let mut encoded = Vec::new();
+        reference.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Bmp)?;
+
+        let png = BmpDecoder::new(&encoded).decode()?;
+        assert_eq!(png.color_type, ColorType::RGBA);
+        assert_eq!(png.pixel_buffer, reference.into_raw());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rle8() -> Result<()> {
+        let palette = [0, 0, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0];
+        // 4x2, bottom row: run of 4 red; top row: absolute [blue, red, blue], delta to the end.
+        let pixels = [4, 1, 0, 0, 0, 3, 2, 1, 2, 0, 0, 1];
+
+        let file = bmp_file(&info_header(4, 2, 8, 1, 3), &palette, &pixels);
+        let png = BmpDecoder::new(&file).decode()?;
+
+        assert_eq!(
+            png.pixel_buffer,
+            [
+                0, 0, 255, 255, 0, 0, 0, 0, 255, 0, 0, 0, //
+                255, 0, 0, 255, 0, 0, 255, 0, 0, 255, 0, 0,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rle4() -> Result<()> {
+        let palette = [0, 0, 0, 0, 255, 255, 255, 0];
+        // A 5x1 image alternating white and black, followed by end of bitmap.
+        let pixels = [5, 0x10, 0, 1];
+
+        let file = bmp_file(&info_header(5, 1, 4, 2, 2), &palette, &pixels);
+        let png = BmpDecoder::new(&file).decode()?;
+
+        assert_eq!(
+            png.pixel_buffer,
+            [255, 255, 255, 0, 0, 0, 255, 255, 255, 0, 0, 0, 255, 255, 255]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitfields_565() -> Result<()> {
+        let mut header = info_header(2, -1, 16, 3, 0);
+        for mask in [0xF800u32, 0x07E0, 0x001F] {
+            header.extend_from_slice(&mask.to_le_bytes());
+        }
+
+        let pixels = [0x00, 0xF8, 0x1F, 0x00];
+        let file = bmp_file(&header, &[], &pixels);
+
+        let png = BmpDecoder::new(&file).decode()?;
+        assert_eq!(png.pixel_buffer, [255, 0, 0, 0, 0, 255]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        for path in ["./test_suite/basn2c08.png", "./test_suite/basn6a08.png"] {
+            let content = std::fs::read(path)?;
+            let png = crate::png::PngDecoder::new(&content).decode()?;
+
+            let encoded = BmpEncoder::new(&png).encode()?;
+            let decoded = BmpDecoder::new(&encoded).decode()?;
+
+            assert_eq!(decoded.dimensions(), png.dimensions());
+            assert_eq!(decoded.color_type, png.color_type);
+            assert_eq!(decoded.pixel_buffer, png.pixel_buffer);
+        }
+
+        Ok(())
+    }
+}
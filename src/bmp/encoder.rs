@@ -0,0 +1,126 @@
+use anyhow::{ensure, Result};
+
+use crate::bmp::grammar::{
+    ChannelMasks, Compression, FILE_HEADER_SIZE, INFO_HEADER_SIZE, V4_HEADER_SIZE,
+};
+use crate::png::grammar::{ColorType, Png};
+
+/// `LCS_sRGB`, the color space tag of a BITMAPV4HEADER.
+const SRGB_COLOR_SPACE: u32 = 0x7352_4742;
+
+/// Writes 24-bit BI_RGB bitmaps, or 32-bit bitfield bitmaps with a V4 header for images with alpha.
+#[derive(Debug)]
+pub struct BmpEncoder<'a> {
+    png: &'a Png,
+}
+
+impl<'a> BmpEncoder<'a> {
+    pub const fn new(png: &'a Png) -> Self {
+        Self { png }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.png.dimensions();
+        ensure!(
+            width > 0 && height > 0 && width <= i32::MAX as u32 && height <= i32::MAX as u32,
+            "Invalid BMP dimensions: {}x{}.",
+            width,
+            height
+        );
+
+        let has_alpha = matches!(
+            self.png.color_type,
+            ColorType::RGBA | ColorType::GrayscaleAlpha
+        );
+
+        let (header_size, bits_per_pixel) = if has_alpha {
+            (V4_HEADER_SIZE, 32)
+        } else {
+            (INFO_HEADER_SIZE, 24)
+        };
+
+        let row_size = (width as usize * bits_per_pixel).div_ceil(32) * 4;
+        let image_size = row_size * height as usize;
+        let pixel_offset = FILE_HEADER_SIZE + header_size;
+
+        let mut out = Vec::with_capacity(pixel_offset as usize + image_size);
+
+        out.extend_from_slice(b"BM");
+        out.extend_from_slice(&(pixel_offset + image_size as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&pixel_offset.to_le_bytes());
+
+        out.extend_from_slice(&header_size.to_le_bytes());
+        out.extend_from_slice(&(width as i32).to_le_bytes());
+        out.extend_from_slice(&(height as i32).to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&(bits_per_pixel as u16).to_le_bytes());
+
+        let compression = if has_alpha {
+            Compression::Bitfields
+        } else {
+            Compression::Rgb
+        };
+        out.extend_from_slice(&(compression as u32).to_le_bytes());
+        out.extend_from_slice(&(image_size as u32).to_le_bytes());
+        // 72 DPI.
+        out.extend_from_slice(&2835i32.to_le_bytes());
+        out.extend_from_slice(&2835i32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        if has_alpha {
+            let masks = ChannelMasks::ARGB8888;
+            for mask in [masks.red, masks.green, masks.blue, masks.alpha] {
+                out.extend_from_slice(&mask.to_le_bytes());
+            }
+
+            out.extend_from_slice(&SRGB_COLOR_SPACE.to_le_bytes());
+            // CIE endpoints and gamma are ignored for sRGB.
+            out.extend_from_slice(&[0; 48]);
+        }
+
+        let padding = row_size - width as usize * bits_per_pixel / 8;
+
+        if has_alpha {
+            let rgba = self.png.to_rgba8();
+            for row in rgba.chunks_exact(width as usize * 4).rev() {
+                out.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]));
+                out.extend(std::iter::repeat_n(0, padding));
+            }
+        } else {
+            let rgb = self.png.to_rgb8();
+            for row in rgb.chunks_exact(width as usize * 3).rev() {
+                out.extend(row.chunks_exact(3).flat_map(|p| [p[2], p[1], p[0]]));
+                out.extend(std::iter::repeat_n(0, padding));
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_reference_decoder() -> Result<()> {
+        let png = Png {
+            width: 5,
+            height: 3,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: (0..5 * 3 * 4).map(|i| (i * 17) as u8).collect(),
+            exif: None,
+        };
+
+        let encoded = BmpEncoder::new(&png).encode()?;
+        let reference = image::load_from_memory_with_format(&encoded, image::ImageFormat::Bmp)?;
+
+        assert_eq!(reference.into_rgba8().into_raw(), png.pixel_buffer);
+
+        Ok(())
+    }
+}
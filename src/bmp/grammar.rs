@@ -0,0 +1,90 @@
+use anyhow::{bail, Result};
+
+pub const FILE_HEADER_SIZE: u32 = 14;
+
+pub const CORE_HEADER_SIZE: u32 = 12;
+pub const INFO_HEADER_SIZE: u32 = 40;
+pub const V4_HEADER_SIZE: u32 = 108;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    Rgb = 0,
+    Rle8 = 1,
+    Rle4 = 2,
+    Bitfields = 3,
+    AlphaBitfields = 6,
+}
+
+impl TryFrom<u32> for Compression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        let compression = match value {
+            0 => Self::Rgb,
+            1 => Self::Rle8,
+            2 => Self::Rle4,
+            3 => Self::Bitfields,
+            6 => Self::AlphaBitfields,
+            foreign => bail!("Unsupported BMP compression: {}", foreign),
+        };
+
+        Ok(compression)
+    }
+}
+
+#[derive(Debug)]
+pub struct BitmapHeader {
+    pub(crate) header_size: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Rows are stored bottom-up unless the header height is negative.
+    pub(crate) top_down: bool,
+    pub(crate) bits_per_pixel: u16,
+    pub(crate) compression: Compression,
+    pub(crate) colors_used: u32,
+    pub(crate) masks: Option<ChannelMasks>,
+}
+
+/// Bit masks locating each channel inside a 16 or 32-bit pixel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChannelMasks {
+    pub(crate) red: u32,
+    pub(crate) green: u32,
+    pub(crate) blue: u32,
+    pub(crate) alpha: u32,
+}
+
+impl ChannelMasks {
+    /// 5 bits per channel, the layout of uncompressed 16-bit bitmaps.
+    pub(crate) const RGB555: Self = Self {
+        red: 0x7C00,
+        green: 0x03E0,
+        blue: 0x001F,
+        alpha: 0,
+    };
+
+    /// The layout of uncompressed 32-bit bitmaps. The high byte is unused.
+    pub(crate) const XRGB8888: Self = Self {
+        red: 0x00FF_0000,
+        green: 0x0000_FF00,
+        blue: 0x0000_00FF,
+        alpha: 0,
+    };
+
+    pub(crate) const ARGB8888: Self = Self {
+        alpha: 0xFF00_0000,
+        ..Self::XRGB8888
+    };
+
+    /// Extracts the channel selected by `mask` from `pixel`, scaled to 8 bits.
+    pub(crate) const fn extract(pixel: u32, mask: u32) -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+
+        let value = (pixel & mask) >> mask.trailing_zeros();
+        let max = mask >> mask.trailing_zeros();
+
+        ((value as u64 * 255 + max as u64 / 2) / max as u64) as u8
+    }
+}
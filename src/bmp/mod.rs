@@ -0,0 +1,6 @@
+pub use decoder::*;
+pub use encoder::*;
+pub mod grammar;
+
+mod decoder;
+mod encoder;
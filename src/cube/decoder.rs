@@ -0,0 +1,193 @@
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::cube::grammar::CubeLut;
+
+/// Parses Adobe and DaVinci Resolve `.cube` 3D LUTs.
+#[derive(Debug)]
+pub struct CubeDecoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CubeDecoder<'a> {
+    /// The largest lattice the spec allows.
+    const MAX_SIZE: u32 = 256;
+
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn decode(&self) -> Result<CubeLut> {
+        let text = std::str::from_utf8(self.data)
+            .map_err(|_| anyhow!("Invalid cube LUT: the file isn't UTF-8 text."))?;
+
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            // Table rows start with a number, everything else is a keyword.
+            if keyword.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.')) {
+                table.push(Self::parse_triple(line, line_number)?);
+                continue;
+            }
+
+            ensure!(
+                table.is_empty(),
+                "Invalid cube LUT: {} on line {} follows the table data.",
+                keyword,
+                line_number + 1
+            );
+
+            match keyword {
+                "TITLE" => title = Some(rest.trim_matches('"').to_string()),
+                "LUT_3D_SIZE" => {
+                    let n = rest
+                        .parse::<u32>()
+                        .map_err(|_| anyhow!("Invalid cube LUT size: {}", rest))?;
+                    ensure!(
+                        (2..=Self::MAX_SIZE).contains(&n),
+                        "Invalid cube LUT size: {}. Sizes range from 2 to {}.",
+                        n,
+                        Self::MAX_SIZE
+                    );
+                    size = Some(n);
+                }
+                "LUT_1D_SIZE" => bail!("Unsupported cube LUT: only 3D LUTs are supported."),
+                "DOMAIN_MIN" => domain_min = Self::parse_triple(rest, line_number)?,
+                "DOMAIN_MAX" => domain_max = Self::parse_triple(rest, line_number)?,
+                // Resolve writes the domain as a single range for all three channels.
+                "LUT_3D_INPUT_RANGE" => {
+                    let (min, max) = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                        [min, max] => (min.parse::<f32>()?, max.parse::<f32>()?),
+                        _ => bail!("Invalid cube LUT input range: {}", rest),
+                    };
+                    domain_min = [min; 3];
+                    domain_max = [max; 3];
+                }
+                // Other keywords, like Resolve's LUT_IN_VIDEO_RANGE, don't change the table.
+                _ => log::warn!("Ignoring cube LUT keyword {}", keyword),
+            }
+        }
+
+        let size = size.ok_or_else(|| anyhow!("Invalid cube LUT: missing LUT_3D_SIZE."))?;
+        ensure!(
+            table.len() == size.pow(3) as usize,
+            "Invalid cube LUT: expected {} entries for size {}, found {}.",
+            size.pow(3),
+            size,
+            table.len()
+        );
+        ensure!(
+            (0..3).all(|c| domain_min[c] < domain_max[c]),
+            "Invalid cube LUT domain: {:?} to {:?}.",
+            domain_min,
+            domain_max
+        );
+
+        Ok(CubeLut {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    fn parse_triple(text: &str, line_number: usize) -> Result<[f32; 3]> {
+        let mut values = text.split_whitespace().map(str::parse::<f32>);
+
+        match (values.next(), values.next(), values.next(), values.next()) {
+            (Some(Ok(r)), Some(Ok(g)), Some(Ok(b)), None) => Ok([r, g, b]),
+            _ => bail!(
+                "Invalid cube LUT: expected three numbers on line {}, found {}",
+                line_number + 1,
+                text
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::grammar::LutInterpolation;
+
+    const SWAP_RED_BLUE: &str = "# Created by hand
+TITLE \"Swap red and blue\"
+LUT_3D_SIZE 2
+
+0 0 0
+0 0 1
+0 1 0
+0 1 1
+1 0 0
+1 0 1
+1 1 0
+1 1 1
+";
+
+    #[test]
+    fn test_decode() -> Result<()> {
+        let lut = CubeDecoder::new(SWAP_RED_BLUE.as_bytes()).decode()?;
+
+        assert_eq!(lut.title(), Some("Swap red and blue"));
+        assert_eq!(lut.size(), 2);
+        assert_eq!(lut.domain_min(), [0.0; 3]);
+        assert_eq!(lut.domain_max(), [1.0; 3]);
+
+        let swapped = lut.apply([0.2, 0.4, 0.9], LutInterpolation::Tetrahedral);
+        assert!(swapped
+            .iter()
+            .zip([0.9, 0.4, 0.2])
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_domain() -> Result<()> {
+        let cube = SWAP_RED_BLUE.replace(
+            "LUT_3D_SIZE 2",
+            "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2",
+        );
+        let lut = CubeDecoder::new(cube.as_bytes()).decode()?;
+        assert_eq!(lut.domain_max(), [2.0; 3]);
+
+        let cube = SWAP_RED_BLUE.replace(
+            "LUT_3D_SIZE 2",
+            "LUT_3D_INPUT_RANGE -0.5 1.5\nLUT_3D_SIZE 2",
+        );
+        let lut = CubeDecoder::new(cube.as_bytes()).decode()?;
+        assert_eq!(lut.domain_min(), [-0.5; 3]);
+        assert_eq!(lut.domain_max(), [1.5; 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let truncated = SWAP_RED_BLUE.replace("1 1 1\n", "");
+        assert!(CubeDecoder::new(truncated.as_bytes()).decode().is_err());
+
+        let no_size = SWAP_RED_BLUE.replace("LUT_3D_SIZE 2", "");
+        assert!(CubeDecoder::new(no_size.as_bytes()).decode().is_err());
+
+        let one_dimensional = SWAP_RED_BLUE.replace("LUT_3D_SIZE 2", "LUT_1D_SIZE 8");
+        assert!(CubeDecoder::new(one_dimensional.as_bytes())
+            .decode()
+            .is_err());
+
+        let bad_row = SWAP_RED_BLUE.replace("0 1 1", "0 1");
+        assert!(CubeDecoder::new(bad_row.as_bytes()).decode().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::util::color::f32_to_f16;
+use anyhow::{bail, Result};
+
+/// How a color between lattice points of a [`CubeLut`] is interpolated. The discriminants match
+/// the renderer's `lut` uniform, where 0 means no LUT.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum LutInterpolation {
+    /// Blends the 8 corners of the enclosing cell, what the texture sampler does in hardware.
+    #[default]
+    Trilinear = 1,
+    /// Blends the 4 corners of the tetrahedron the color falls in, which keeps the neutral axis
+    /// free of hue shifts. Most grading applications default to it.
+    Tetrahedral = 2,
+}
+
+impl LutInterpolation {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Trilinear => Self::Tetrahedral,
+            Self::Tetrahedral => Self::Trilinear,
+        }
+    }
+}
+
+impl TryFrom<u32> for LutInterpolation {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        let interpolation = match value {
+            1 => Self::Trilinear,
+            2 => Self::Tetrahedral,
+            foreign => bail!("Unknown LUT interpolation: {}", foreign),
+        };
+
+        Ok(interpolation)
+    }
+}
+
+/// A 3D color lookup table. Inputs in `[domain_min, domain_max]` map onto a `size`^3 lattice of
+/// output colors, with red varying fastest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+    pub(crate) title: Option<String>,
+    pub(crate) size: u32,
+    pub(crate) domain_min: [f32; 3],
+    pub(crate) domain_max: [f32; 3],
+    pub(crate) table: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    /// A LUT that leaves colors unchanged.
+    pub fn identity(size: u32) -> Self {
+        let scale = 1.0 / (size - 1) as f32;
+        let table = (0..size)
+            .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+            .map(|(r, g, b)| [r as f32 * scale, g as f32 * scale, b as f32 * scale])
+            .collect();
+
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub const fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub const fn domain_min(&self) -> [f32; 3] {
+        self.domain_min
+    }
+
+    pub const fn domain_max(&self) -> [f32; 3] {
+        self.domain_max
+    }
+
+    pub fn table(&self) -> &[[f32; 3]] {
+        &self.table
+    }
+
+    fn lattice(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        let size = self.size as usize;
+        self.table[r + g * size + b * size * size]
+    }
+
+    /// Looks up `rgb`, clamped to the domain.
+    pub fn apply(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+        let max_index = (self.size - 1) as f32;
+        let mut base = [0; 3];
+        let mut fraction = [0.0; 3];
+
+        for c in 0..3 {
+            let range = self.domain_max[c] - self.domain_min[c];
+            let x = ((rgb[c] - self.domain_min[c]) / range).clamp(0.0, 1.0) * max_index;
+            let i = (x.floor() as usize).min(self.size as usize - 2);
+
+            base[c] = i;
+            fraction[c] = x - i as f32;
+        }
+
+        match interpolation {
+            LutInterpolation::Trilinear => self.trilinear(base, fraction),
+            LutInterpolation::Tetrahedral => self.tetrahedral(base, fraction),
+        }
+    }
+
+    fn trilinear(&self, [r, g, b]: [usize; 3], [fr, fg, fb]: [f32; 3]) -> [f32; 3] {
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+
+        let c00 = lerp(self.lattice(r, g, b), self.lattice(r + 1, g, b), fr);
+        let c10 = lerp(self.lattice(r, g + 1, b), self.lattice(r + 1, g + 1, b), fr);
+        let c01 = lerp(self.lattice(r, g, b + 1), self.lattice(r + 1, g, b + 1), fr);
+        let c11 = lerp(
+            self.lattice(r, g + 1, b + 1),
+            self.lattice(r + 1, g + 1, b + 1),
+            fr,
+        );
+
+        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+    }
+
+    /// The cell is split into six tetrahedra along its black to white diagonal. Walking from the
+    /// black corner, each step moves along the axis with the next largest fraction.
+    fn tetrahedral(&self, [r, g, b]: [usize; 3], fraction: [f32; 3]) -> [f32; 3] {
+        let mut axes = [0, 1, 2];
+        axes.sort_by(|&a, &b| fraction[b].total_cmp(&fraction[a]));
+
+        let mut corner = [r, g, b];
+        let mut out = self.lattice(r, g, b).map(|v| v * (1.0 - fraction[axes[0]]));
+
+        for (step, &axis) in axes.iter().enumerate() {
+            corner[axis] += 1;
+
+            let next_fraction = axes.get(step + 1).map_or(0.0, |&a| fraction[a]);
+            let weight = fraction[axis] - next_fraction;
+            let color = self.lattice(corner[0], corner[1], corner[2]);
+
+            for c in 0..3 {
+                out[c] += color[c] * weight;
+            }
+        }
+
+        out
+    }
+
+    /// RGBA half floats in lattice order, the layout of an `Rgba16Float` 3D texture.
+    pub fn to_rgba_f16(&self) -> Vec<u16> {
+        self.table
+            .iter()
+            .flat_map(|&[r, g, b]| [f32_to_f16(r), f32_to_f16(g), f32_to_f16(b), 0x3C00])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn test_identity_is_exact() {
+        let lut = CubeLut::identity(17);
+
+        for rgb in [[0.0, 0.0, 0.0], [0.3, 0.61, 0.97], [1.0, 0.5, 0.02]] {
+            assert!(approx_eq(lut.apply(rgb, LutInterpolation::Trilinear), rgb));
+            assert!(approx_eq(
+                lut.apply(rgb, LutInterpolation::Tetrahedral),
+                rgb
+            ));
+        }
+    }
+
+    #[test]
+    fn test_interpolation_on_lattice_points() {
+        // Squaring each channel, which neither interpolation reproduces between lattice points.
+        let mut lut = CubeLut::identity(5);
+        lut.table.iter_mut().for_each(|c| *c = c.map(|v| v * v));
+
+        let on_lattice = [0.25, 0.5, 1.0];
+        let squared = [0.0625, 0.25, 1.0];
+        assert!(approx_eq(
+            lut.apply(on_lattice, LutInterpolation::Trilinear),
+            squared
+        ));
+        assert!(approx_eq(
+            lut.apply(on_lattice, LutInterpolation::Tetrahedral),
+            squared
+        ));
+
+        // Both are exact for separable LUTs, where every output channel follows its input.
+        let between = [0.1, 0.6, 0.9];
+        let expected = [0.025, 0.375, 0.825];
+        assert!(approx_eq(
+            lut.apply(between, LutInterpolation::Trilinear),
+            expected
+        ));
+        assert!(approx_eq(
+            lut.apply(between, LutInterpolation::Tetrahedral),
+            expected
+        ));
+    }
+
+    #[test]
+    fn test_tetrahedral_keeps_grays_on_the_diagonal() {
+        // A LUT mixing channels, so trilinear interpolation pulls grays off the neutral axis.
+        let mut lut = CubeLut::identity(2);
+        lut.table[1] = [1.0, 1.0, 0.0];
+
+        let gray = lut.apply([0.5, 0.5, 0.5], LutInterpolation::Tetrahedral);
+        assert!(approx_eq(gray, [0.5, 0.5, 0.5]));
+
+        let gray = lut.apply([0.5, 0.5, 0.5], LutInterpolation::Trilinear);
+        assert!(!approx_eq(gray, [0.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn test_domain() {
+        let mut lut = CubeLut::identity(9);
+        lut.domain_min = [-1.0; 3];
+        lut.domain_max = [3.0; 3];
+
+        let rgb = lut.apply([1.0, -1.0, 5.0], LutInterpolation::Trilinear);
+        assert!(approx_eq(rgb, [0.5, 0.0, 1.0]));
+    }
+}
@@ -0,0 +1,4 @@
+pub use decoder::*;
+pub mod grammar;
+
+mod decoder;
@@ -0,0 +1,116 @@
+use anyhow::{ensure, Result};
+
+use crate::farbfeld::{HEADER_SIZE, MAGIC};
+use crate::png::grammar::{ColorType, Png};
+
+/// Decodes farbfeld's 16-bit RGBA samples down to 8 bits per channel.
+#[derive(Debug)]
+pub struct FarbfeldDecoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> FarbfeldDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn decode(&self) -> Result<Png> {
+        ensure!(
+            self.data.len() >= HEADER_SIZE && self.data.starts_with(MAGIC),
+            "Invalid farbfeld file: incorrect signature."
+        );
+
+        let width = u32::from_be_bytes(self.data[8..12].try_into()?);
+        let height = u32::from_be_bytes(self.data[12..16].try_into()?);
+        ensure!(
+            width > 0 && height > 0 && (width as u64 * height as u64) < (1 << 30),
+            "Invalid farbfeld dimensions: {}x{}.",
+            width,
+            height
+        );
+
+        let len = width as u64 * height as u64 * 8;
+        ensure!(
+            (HEADER_SIZE as u64 + len) <= self.data.len() as u64,
+            "Unexpected EOF. {}x{} farbfeld image needs {} bytes of pixel data.",
+            width,
+            height,
+            len
+        );
+
+        let pixel_buffer = self.data[HEADER_SIZE..HEADER_SIZE + len as usize]
+            .chunks_exact(2)
+            .map(|sample| sample[0])
+            .collect();
+
+        Ok(Png {
+            width,
+            height,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer,
+            exif: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::farbfeld::FarbfeldEncoder;
+    use crate::png::PngDecoder;
+    use image::ImageFormat;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let content = std::fs::read("./test_suite/basn6a08.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        let encoded = FarbfeldEncoder::new(&png).encode()?;
+        let decoded = FarbfeldDecoder::new(&encoded).decode()?;
+
+        assert_eq!(decoded.dimensions(), png.dimensions());
+        assert_eq!(decoded.pixel_buffer, png.pixel_buffer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_empty_and_huge_dimensions() {
+        for (width, height) in [(0, 4), (4, 0), (u32::MAX, u32::MAX)] {
+            let mut header = MAGIC.to_vec();
+            header.extend_from_slice(&u32::to_be_bytes(width));
+            header.extend_from_slice(&u32::to_be_bytes(height));
+
+            assert!(FarbfeldDecoder::new(&header).decode().is_err());
+        }
+    }
+
+    #[test]
+    fn test_reference() -> Result<()> {
+        let reference = image::open("./test_suite/basn2c08.png")?.into_rgba16();
+
+        let mut encoded = Vec::new();
+        reference.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Farbfeld)?;
+
+        let decoded = FarbfeldDecoder::new(&encoded).decode()?;
+        let expected = reference
+            .into_raw()
+            .iter()
+            .map(|&s| (s >> 8) as u8)
+            .collect::<Vec<_>>();
+
+        assert_eq!(decoded.pixel_buffer, expected);
+
+        let encoded = FarbfeldEncoder::new(&decoded).encode()?;
+        let decoded_reference =
+            image::load_from_memory_with_format(&encoded, ImageFormat::Farbfeld)?;
+        assert_eq!(
+            decoded_reference.into_rgba8().into_raw(),
+            decoded.pixel_buffer
+        );
+
+        Ok(())
+    }
+}
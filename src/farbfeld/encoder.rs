@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+use crate::farbfeld::{HEADER_SIZE, MAGIC};
+use crate::png::grammar::Png;
+
+#[derive(Debug)]
+pub struct FarbfeldEncoder<'a> {
+    png: &'a Png,
+}
+
+impl<'a> FarbfeldEncoder<'a> {
+    pub const fn new(png: &'a Png) -> Self {
+        Self { png }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.png.dimensions();
+        let rgba = self.png.to_rgba8();
+
+        let mut out = Vec::with_capacity(HEADER_SIZE + rgba.len() * 2);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&width.to_be_bytes());
+        out.extend_from_slice(&height.to_be_bytes());
+
+        // Widen each sample so that 255 maps to 65535.
+        for &sample in rgba.iter() {
+            out.extend_from_slice(&(sample as u16 * 257).to_be_bytes());
+        }
+
+        Ok(out)
+    }
+}
@@ -0,0 +1,10 @@
+pub use decoder::*;
+pub use encoder::*;
+
+mod decoder;
+mod encoder;
+
+pub const MAGIC: &[u8; 8] = b"farbfeld";
+
+/// Magic number followed by the big-endian width and height.
+pub const HEADER_SIZE: usize = 16;
@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 
 use anyhow::{anyhow, bail, ensure, Result};
 
+use crate::font::layout::{GdefTable, GposTable, GsubTable};
 use crate::util::read_bytes::{U16_BYTES, U8_BYTES};
 
 pub type ShortFrac = i16;
@@ -21,6 +22,10 @@ pub struct TrueTypeFontFile<'a> {
     pub hmtx_table: HMtxTable,
     pub cmap_table: CMapTable,
     pub glyph_table: GlyphTable,
+    pub kern_table: Option<KernTable>,
+    pub gpos_table: Option<GposTable>,
+    pub gsub_table: Option<GsubTable>,
+    pub gdef_table: Option<GdefTable>,
 }
 
 #[derive(Debug)]
@@ -83,6 +88,9 @@ pub enum TableTag<'a> {
     // Optional tags below
     CVT,
     FPgm,
+    GDef,
+    GPos,
+    GSub,
     HDMx,
     Kern,
     OS2,
@@ -108,6 +116,9 @@ impl<'a> TryFrom<&'a [u8; 4]> for TableTag<'a> {
             // optional tags below
             b"cvt " => Self::CVT,
             b"fpgm" => Self::FPgm,
+            b"GDEF" => Self::GDef,
+            b"GPOS" => Self::GPos,
+            b"GSUB" => Self::GSub,
             b"hdmx" => Self::HDMx,
             b"kern" => Self::Kern,
             b"OS/2" => Self::OS2,
@@ -155,18 +166,163 @@ impl CMapTable {
             _ => None,
         })
     }
+
+    pub fn format_14(&self) -> Option<&CMapFormat14> {
+        self.subtables.iter().find_map(|(cmap, _)| match cmap {
+            CMapSubtable::Fourteen(cmap_14) => Some(cmap_14),
+            _ => None,
+        })
+    }
+
+    /// The subtable characters are looked up in. Full Unicode subtables win over BMP ones,
+    /// which win over legacy Macintosh Roman ones, and symbol subtables come last.
+    pub fn best_subtable(&self) -> Option<&CMapSubtable> {
+        self.ranked_subtable().map(|(_, subtable)| subtable)
+    }
+
+    fn ranked_subtable(&self) -> Option<(u8, &CMapSubtable)> {
+        self.subtables
+            .iter()
+            .filter_map(|(subtable, platform_doubles)| {
+                let rank = platform_doubles
+                    .iter()
+                    .filter_map(|platform_double| subtable.unicode_rank(platform_double))
+                    .min()?;
+
+                Some((rank, subtable))
+            })
+            .min_by_key(|(rank, _)| *rank)
+    }
+
+    /// The glyph for a character, 0 being the missing character glyph.
+    pub fn glyph_index(&self, ch: char) -> u16 {
+        match self.ranked_subtable() {
+            // Macintosh Roman subtables are indexed by Mac OS Roman codes, not code points.
+            Some((CMapSubtable::MAC_ROMAN_RANK, subtable)) => {
+                mac_roman_code(ch).map_or(0, |code| subtable.find_glyph_index(code))
+            }
+            // Symbol fonts usually put their glyphs at U+F020 to U+F0FF, where Windows looks up
+            // the first 256 code points.
+            Some((CMapSubtable::SYMBOL_RANK, subtable)) => {
+                match subtable.find_glyph_index(ch as u32) {
+                    0 if (ch as u32) < 0x100 => subtable.find_glyph_index(0xF000 | ch as u32),
+                    glyph => glyph,
+                }
+            }
+            Some((_, subtable)) => subtable.find_glyph_index(ch as u32),
+            None => 0,
+        }
+    }
+
+    /// The glyph for a character followed by a variation selector, like U+FE0F to ask for emoji
+    /// presentation. Sequences the font doesn't list fall back to the character alone.
+    pub fn variation_glyph_index(&self, ch: char, variation_selector: char) -> u16 {
+        let glyph = self.format_14().and_then(|cmap_14| {
+            match cmap_14.find_variation(ch as u32, variation_selector as u32)? {
+                Variation::Default => None,
+                Variation::Glyph(glyph_id) => Some(glyph_id),
+            }
+        });
+
+        glyph.unwrap_or_else(|| self.glyph_index(ch))
+    }
+}
+
+/// The characters of Mac OS Roman codes 0x80 to 0xFF. Codes below are ASCII.
+#[rustfmt::skip]
+const MAC_ROMAN: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è',
+    'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü',
+    '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø',
+    '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø',
+    '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{A0}', 'À', 'Ã', 'Õ', 'Œ', 'œ',
+    '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ',
+    '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô',
+    '\u{F8FF}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ',
+];
+
+fn mac_roman_code(ch: char) -> Option<u32> {
+    if ch.is_ascii() {
+        return Some(ch as u32);
+    }
+
+    MAC_ROMAN
+        .iter()
+        .position(|&mac_roman| mac_roman == ch)
+        .map(|i| 0x80 + i as u32)
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CMapSubtable {
     Zero(CMapFormat0),
-    // Two(CMapFormat2),
+    Two(CMapFormat2),
     Four(CMapFormat4),
-    // Six(CMapFormat6),
-    // Eight(CMapFormat8),
-    // Ten(CMapFormat10),
+    Six(CMapFormat6),
+    Eight(CMapFormat8),
+    Ten(CMapFormat10),
     Twelve(CMapFormat12),
-    // Fourteen(CMapFormat14),
+    Thirteen(CMapFormat13),
+    Fourteen(CMapFormat14),
+}
+
+impl CMapSubtable {
+    const MAC_ROMAN_RANK: u8 = 5;
+    const SYMBOL_RANK: u8 = 6;
+
+    /// Maps a character code in the subtable's encoding to a glyph, 0 when it isn't mapped.
+    /// Format 14 only holds variation sequences and maps nothing on its own.
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        match self {
+            Self::Zero(cmap) => cmap.find_glyph_index(code),
+            Self::Two(cmap) => cmap.find_glyph_index(code),
+            Self::Four(cmap) => cmap.find_glyph_index(code),
+            Self::Six(cmap) => cmap.find_glyph_index(code),
+            Self::Eight(cmap) => cmap.find_glyph_index(code),
+            Self::Ten(cmap) => cmap.find_glyph_index(code),
+            Self::Twelve(cmap) => cmap.find_glyph_index(code),
+            Self::Thirteen(cmap) => cmap.find_glyph_index(code),
+            Self::Fourteen(_) => 0,
+        }
+    }
+
+    /// How suitable the subtable is for looking up Unicode characters under an encoding, lower
+    /// being better. `None` for encodings that aren't Unicode.
+    fn unicode_rank(&self, platform_double: &PlatformDouble) -> Option<u8> {
+        let full_unicode = matches!(
+            (
+                platform_double.platform,
+                platform_double.platform_specific_id
+            ),
+            (Platform::Unicode, 4 | 6) | (Platform::Microsoft, 10)
+        );
+        let unicode = full_unicode
+            || matches!(
+                (
+                    platform_double.platform,
+                    platform_double.platform_specific_id
+                ),
+                (Platform::Unicode, 0..=3) | (Platform::Microsoft, 1)
+            );
+        let mac_roman = platform_double.platform == Platform::Macintosh
+            && platform_double.platform_specific_id == 0;
+        let symbol = platform_double.platform == Platform::Microsoft
+            && platform_double.platform_specific_id == 0;
+
+        let rank = match self {
+            Self::Twelve(_) if full_unicode => 0,
+            Self::Eight(_) | Self::Ten(_) if full_unicode => 1,
+            Self::Four(_) if unicode => 2,
+            Self::Six(_) | Self::Zero(_) if unicode => 3,
+            Self::Twelve(_) if unicode => 3,
+            // A last resort font maps whole ranges to a single glyph.
+            Self::Thirteen(_) if unicode => 4,
+            Self::Zero(_) | Self::Six(_) if mac_roman => Self::MAC_ROMAN_RANK,
+            Self::Four(_) | Self::Six(_) | Self::Zero(_) if symbol => Self::SYMBOL_RANK,
+            _ => return None,
+        };
+
+        Some(rank)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -175,14 +331,71 @@ pub struct CMapFormat0 {
     pub glyph_index_array: Vec<u8>,
 }
 
+impl CMapFormat0 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        self.glyph_index_array
+            .get(code as usize)
+            .map_or(0, |&glyph| glyph as u16)
+    }
+}
+
+/// High-byte mapping through a table, for the double byte encodings of Chinese, Japanese and
+/// Korean.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapFormat2 {
     pub language: u16,
+    /// The subheader index times 8 for each high byte. Single byte codes map to subheader 0.
     pub subheader_keys: [u16; 256],
     pub subheaders: Vec<CMapSubHeader>,
     pub glyph_index_array: Vec<u16>,
 }
 
+impl CMapFormat2 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        if code > 0xFFFF {
+            return 0;
+        }
+
+        let (high, low) = ((code >> 8) as usize, (code & 0xFF) as u16);
+        let (k, low) = if high == 0 {
+            // Subheader 0 is reserved for single byte codes.
+            match self.subheader_keys[low as usize] {
+                0 => (0, low),
+                _ => return 0,
+            }
+        } else {
+            match self.subheader_keys[high] as usize / 8 {
+                0 => return 0,
+                k => (k, low),
+            }
+        };
+
+        let Some(subheader) = self.subheaders.get(k) else {
+            return 0;
+        };
+
+        if low < subheader.first_code || low - subheader.first_code >= subheader.entry_count {
+            return 0;
+        }
+
+        // `id_range_offset` counts bytes from itself, the last field of the subheader, to the
+        // first glyph of the subheader's range.
+        let offset_position = k * 8 + 6;
+        let array_position = self.subheaders.len() * 8;
+        let Some(index) = (offset_position + subheader.id_range_offset as usize)
+            .checked_sub(array_position)
+            .map(|bytes| bytes / 2 + (low - subheader.first_code) as usize)
+        else {
+            return 0;
+        };
+
+        match self.glyph_index_array.get(index) {
+            None | Some(0) => 0,
+            Some(&glyph) => glyph.wrapping_add(subheader.id_delta as u16),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapSubHeader {
     pub first_code: u16,
@@ -206,8 +419,11 @@ pub struct CMapFormat4 {
 }
 
 impl CMapFormat4 {
-    pub fn find_glyph_index(&self, ch: char) -> usize {
-        let char_code = ch as u16; // we can guarantee all characters are in BMP.
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        // Format 4 only covers the BMP.
+        let Ok(char_code) = u16::try_from(code) else {
+            return 0;
+        };
 
         // 1. Search for the first `end_code` greater than or equal to the character code to be mapped.
         let i = self
@@ -215,6 +431,10 @@ impl CMapFormat4 {
             .binary_search(&char_code)
             .unwrap_or_else(|i| i);
 
+        if i >= self.end_codes.len() {
+            return 0;
+        }
+
         let start_code = self.start_codes[i];
 
         if start_code > char_code {
@@ -225,10 +445,21 @@ impl CMapFormat4 {
         let id_range_offset = self.id_range_offset[i];
 
         if id_range_offset == 0 {
-            return ((self.id_deltas[i] as u32 + char_code as u32) & 0xFFFF) as usize;
+            return self.id_deltas[i].wrapping_add(char_code);
         }
 
-        (id_range_offset + id_range_offset / 2 + (char_code - start_code)) as usize
+        // `id_range_offset` counts bytes from itself into the glyph index array, which directly
+        // follows the id range offsets.
+        let seg_count = self.id_range_offset.len();
+        let index = (id_range_offset / 2) as usize + (char_code - start_code) as usize + i;
+
+        match index
+            .checked_sub(seg_count)
+            .and_then(|index| self.glyph_index_array.get(index))
+        {
+            None | Some(0) => 0,
+            Some(&glyph) => glyph.wrapping_add(self.id_deltas[i]),
+        }
     }
 }
 
@@ -239,20 +470,59 @@ pub struct CMapFormat6 {
     pub glyph_index_array: Vec<u16>,
 }
 
+impl CMapFormat6 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        code.checked_sub(self.first_code as u32)
+            .and_then(|i| self.glyph_index_array.get(i as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// Mixed 16 and 32 bit codes, for surrogate-like encodings.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapFormat8 {
     pub language: u32,
-    pub is_32: [u8; 65536],
+    /// A bit per 16-bit value, set when it's the high half of a 32-bit code.
+    pub is_32: Vec<u8>,
     pub groups: Vec<CMapIndividualGroup>,
 }
 
+impl CMapFormat8 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        CMapIndividualGroup::find(&self.groups, code)
+            .and_then(|group| group.sequential_glyph(code))
+            .unwrap_or(0)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapIndividualGroup {
     pub start_char_code: u32,
     pub end_char_code: u32,
+    /// The glyph of `start_char_code`. Format 13 maps the whole group to this glyph.
     pub start_glyph_code: u32,
 }
 
+impl CMapIndividualGroup {
+    /// Groups are sorted by their start code and don't overlap.
+    fn find(groups: &[Self], code: u32) -> Option<&Self> {
+        let i = groups.partition_point(|group| group.end_char_code < code);
+
+        groups.get(i).filter(|group| group.start_char_code <= code)
+    }
+
+    /// The glyph `code` maps to in a group that counts up from its start glyph. `None` when
+    /// that runs past the last glyph id.
+    fn sequential_glyph(&self, code: u32) -> Option<u16> {
+        let glyph = self
+            .start_glyph_code
+            .checked_add(code - self.start_char_code)?;
+
+        u16::try_from(glyph).ok()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapFormat10 {
     pub language: u32,
@@ -261,41 +531,117 @@ pub struct CMapFormat10 {
     pub glyphs: Vec<u16>,
 }
 
+impl CMapFormat10 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        code.checked_sub(self.start_char_code)
+            .and_then(|i| self.glyphs.get(i as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapFormat12 {
     pub language: u32,
     pub groups: Vec<CMapIndividualGroup>,
 }
 
+impl CMapFormat12 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        CMapIndividualGroup::find(&self.groups, code)
+            .and_then(|group| group.sequential_glyph(code))
+            .unwrap_or(0)
+    }
+}
+
+/// Many-to-one range mappings, used by last resort fonts.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CMapFormat13 {
+    pub language: u32,
+    pub groups: Vec<CMapIndividualGroup>,
+}
+
+impl CMapFormat13 {
+    pub fn find_glyph_index(&self, code: u32) -> u16 {
+        CMapIndividualGroup::find(&self.groups, code)
+            .map_or(0, |group| group.start_glyph_code as u16)
+    }
+}
+
+/// Unicode variation sequences, a base character followed by a variation selector.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CMapFormat14 {
     pub records: Vec<VariationSelectorRecord>,
 }
 
+impl CMapFormat14 {
+    /// How the font draws a variation sequence, `None` when it doesn't support it.
+    pub fn find_variation(&self, code: u32, variation_selector: u32) -> Option<Variation> {
+        let i = self
+            .records
+            .binary_search_by_key(&variation_selector, |record| record.variation_selector)
+            .ok()?;
+        let record = &self.records[i];
+
+        let is_default = record.default_uvs.as_ref().is_some_and(|table| {
+            let i = table
+                .unicode_value_ranges
+                .partition_point(|range| range.start_unicode_value <= code);
+
+            i > 0 && {
+                let range = &table.unicode_value_ranges[i - 1];
+                code - range.start_unicode_value <= range.additional_count as u32
+            }
+        });
+
+        if is_default {
+            return Some(Variation::Default);
+        }
+
+        let table = record.non_default_uvs.as_ref()?;
+        let i = table
+            .uvs_mappings
+            .binary_search_by_key(&code, |mapping| mapping.unicode_value)
+            .ok()?;
+
+        Some(Variation::Glyph(table.uvs_mappings[i].glyph_id))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Variation {
+    /// The sequence looks like the base character in the Unicode cmap.
+    Default,
+    /// The sequence has a glyph of its own.
+    Glyph(u16),
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct VariationSelectorRecord {
     pub variation_selector: u32, // actually a u24
     pub default_uvs_offset: u32,
     pub non_default_uvs_offset: u32,
+    pub default_uvs: Option<DefaultUVSTable>,
+    pub non_default_uvs: Option<NonDefaultUVSTable>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DefaultUVSTable {
     pub unicode_value_ranges: Vec<UnicodeValueRange>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct UnicodeValueRange {
     pub start_unicode_value: u32, // note this is a u24
     pub additional_count: u8,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct NonDefaultUVSTable {
     pub uvs_mappings: Vec<UnicodeValueMap>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct UnicodeValueMap {
     pub unicode_value: u32, // note this is a u24
     pub glyph_id: u16,
@@ -429,6 +775,100 @@ pub struct HMtxTable {
     pub left_side_bearing: Vec<FWord>,
 }
 
+/// The legacy kerning table, in either the Microsoft or the Apple layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernTable {
+    pub subtables: Vec<KernSubtable>,
+}
+
+impl KernTable {
+    /// The horizontal kerning between two glyphs, in font units.
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        self.subtables
+            .iter()
+            .filter(|subtable| subtable.horizontal && !subtable.minimum && !subtable.cross_stream)
+            .fold(0, |kerning, subtable| {
+                match subtable.data.kerning(left, right) {
+                    Some(value) if subtable.override_accumulator => value,
+                    Some(value) => kerning.saturating_add(value),
+                    None => kerning,
+                }
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernSubtable {
+    pub horizontal: bool,
+    /// Whether the values are minimum distances rather than adjustments.
+    pub minimum: bool,
+    /// Whether the values move glyphs perpendicular to the line, up and down in horizontal text.
+    pub cross_stream: bool,
+    /// Whether the value replaces the kerning of the subtables before rather than adding to it.
+    pub override_accumulator: bool,
+    pub data: KernData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KernData {
+    /// Format 0, a list of pairs sorted by left then right glyph.
+    Pairs(Vec<KernPair>),
+    /// Format 2, a table of values by the class of either glyph.
+    Classes {
+        left: KernClassTable,
+        right: KernClassTable,
+        /// Row by left class, then column by right class.
+        values: Vec<i16>,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KernPair {
+    pub left: u16,
+    pub right: u16,
+    pub value: FWord,
+}
+
+/// The classes of a run of glyphs, numbered in order from 0. Format 2 subtables store byte
+/// offsets into the value table instead, which the parser turns into these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernClassTable {
+    pub first_glyph: u16,
+    pub classes: Vec<u16>,
+    pub class_count: u16,
+}
+
+impl KernClassTable {
+    pub fn class(&self, glyph: u16) -> Option<u16> {
+        glyph
+            .checked_sub(self.first_glyph)
+            .and_then(|i| self.classes.get(i as usize))
+            .copied()
+    }
+}
+
+impl KernData {
+    pub fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        match self {
+            Self::Pairs(pairs) => pairs
+                .binary_search_by_key(&(left, right), |pair| (pair.left, pair.right))
+                .ok()
+                .map(|i| pairs[i].value),
+            Self::Classes {
+                left: left_classes,
+                right: right_classes,
+                values,
+            } => {
+                let row = left_classes.class(left)? as usize;
+                let column = right_classes.class(right)? as usize;
+                values
+                    .get(row * right_classes.class_count as usize + column)
+                    .copied()
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct GlyphTable {
     pub glyphs: Vec<Glyph>,
@@ -441,6 +881,26 @@ pub struct Glyph {
 }
 
 impl Glyph {
+    /// A glyph without contours, like the space, which has no data in glyf at all.
+    pub const fn empty() -> Self {
+        Self {
+            description: GlyphDescription {
+                number_of_contours: 0,
+                x_min: 0,
+                y_min: 0,
+                x_max: 0,
+                y_max: 0,
+            },
+            data: GlyphData::Simple(SimpleGlyph {
+                end_points_of_contours: vec![],
+                instruction_length: 0,
+                instructions: vec![],
+                flags: vec![],
+                coordinates: vec![],
+            }),
+        }
+    }
+
     pub const fn is_simple(&self) -> bool {
         matches!(self.data, GlyphData::Simple(_))
     }
@@ -488,15 +948,6 @@ impl SimpleGlyph {
     pub fn on_curve(&self, i: usize) -> bool {
         self.flags[i].on_curve()
     }
-
-    pub fn interpolate_with_prev(&self, i: usize) -> Result<(i16, i16)> {
-        ensure!(i > 0);
-
-        let (x, y) = self.coordinates[i - 1];
-        let (next_x, next_y) = self.coordinates[i];
-
-        Ok(((next_x - x) / 2, (next_y - y) / 2))
-    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -557,39 +1008,47 @@ impl ComponentGlyphFlag {
     }
 
     pub const fn args_are_xy_values(&self) -> bool {
-        self.0 & 0b10 << 1 == 1
+        (self.0 & 0b10) >> 1 == 1
     }
 
     pub const fn round_xy_to_grid(&self) -> bool {
-        self.0 & 0b100 << 2 == 1
+        (self.0 & 0b100) >> 2 == 1
     }
 
     pub const fn we_have_a_scale(&self) -> bool {
-        self.0 & 0b1000 << 3 == 1
+        (self.0 & 0b1000) >> 3 == 1
     }
 
     pub const fn more_components(&self) -> bool {
-        self.0 & 0b100000 << 5 == 1
+        (self.0 & 0b100000) >> 5 == 1
     }
 
     pub const fn we_have_an_xy_scale(&self) -> bool {
-        self.0 & 0b1000000 << 6 == 1
+        (self.0 & 0b1000000) >> 6 == 1
     }
 
     pub const fn we_have_two_by_two(&self) -> bool {
-        self.0 & 0b10000000 << 7 == 1
+        (self.0 & 0b10000000) >> 7 == 1
     }
 
     pub const fn we_have_instructions(&self) -> bool {
-        self.0 & 0b100000000 << 8 == 1
+        (self.0 & 0b100000000) >> 8 == 1
     }
 
     pub const fn use_my_metrics(&self) -> bool {
-        self.0 & 0b1000000000 << 9 == 1
+        (self.0 & 0b1000000000) >> 9 == 1
     }
 
     pub const fn overlap_compound(&self) -> bool {
-        self.0 & 0b10000000000 << 10 == 1
+        (self.0 & 0b10000000000) >> 10 == 1
+    }
+
+    pub const fn scaled_component_offset(&self) -> bool {
+        (self.0 & 0b100000000000) >> 11 == 1
+    }
+
+    pub const fn unscaled_component_offset(&self) -> bool {
+        (self.0 & 0b1000000000000) >> 12 == 1
     }
 }
 
@@ -607,3 +1066,189 @@ pub enum ComponentGlyphTransformation {
         y_scale: F2Dot14,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(
+        start_char_code: u32,
+        end_char_code: u32,
+        start_glyph_code: u32,
+    ) -> CMapIndividualGroup {
+        CMapIndividualGroup {
+            start_char_code,
+            end_char_code,
+            start_glyph_code,
+        }
+    }
+
+    fn platform(platform: Platform, platform_specific_id: u16) -> Vec<PlatformDouble> {
+        vec![PlatformDouble {
+            platform,
+            platform_specific_id,
+        }]
+    }
+
+    #[test]
+    fn test_format_2_high_byte_mapping() {
+        let mut subheader_keys = [0; 256];
+        subheader_keys[0x81] = 8;
+
+        let mut glyph_index_array = (0..256).collect::<Vec<u16>>();
+        glyph_index_array.extend([100, 0]);
+
+        let cmap = CMapFormat2 {
+            language: 0,
+            subheader_keys,
+            subheaders: vec![
+                CMapSubHeader {
+                    first_code: 0,
+                    entry_count: 256,
+                    id_delta: 0,
+                    id_range_offset: 10,
+                },
+                CMapSubHeader {
+                    first_code: 0x40,
+                    entry_count: 2,
+                    id_delta: 3,
+                    id_range_offset: 514,
+                },
+            ],
+            glyph_index_array,
+        };
+
+        assert_eq!(cmap.find_glyph_index(0x41), 0x41);
+        assert_eq!(cmap.find_glyph_index(0x8140), 103);
+        assert_eq!(cmap.find_glyph_index(0x8141), 0);
+        assert_eq!(cmap.find_glyph_index(0x8142), 0);
+        // 0x81 starts a double byte code, and 0x41 never does.
+        assert_eq!(cmap.find_glyph_index(0x81), 0);
+        assert_eq!(cmap.find_glyph_index(0x4140), 0);
+    }
+
+    #[test]
+    fn test_trimmed_and_grouped_formats() {
+        let cmap_6 = CMapFormat6 {
+            language: 0,
+            first_code: 0x20,
+            glyph_index_array: vec![3, 4],
+        };
+        assert_eq!(cmap_6.find_glyph_index(0x21), 4);
+        assert_eq!(cmap_6.find_glyph_index(0x1F), 0);
+        assert_eq!(cmap_6.find_glyph_index(0x22), 0);
+
+        let cmap_10 = CMapFormat10 {
+            language: 0,
+            start_char_code: 0x1F600,
+            num_chars: 1,
+            glyphs: vec![9],
+        };
+        assert_eq!(cmap_10.find_glyph_index(0x1F600), 9);
+        assert_eq!(cmap_10.find_glyph_index(0x1F601), 0);
+
+        let groups = vec![group(0x41, 0x43, 5), group(0x1F600, 0x1F64F, 100)];
+        let cmap_12 = CMapFormat12 {
+            language: 0,
+            groups,
+        };
+        assert_eq!(cmap_12.find_glyph_index(0x42), 6);
+        assert_eq!(cmap_12.find_glyph_index(0x1F601), 101);
+        assert_eq!(cmap_12.find_glyph_index(0x44), 0);
+
+        // Groups whose glyphs would count past the last glyph id map nothing.
+        let cmap_12 = CMapFormat12 {
+            language: 0,
+            groups: vec![group(0x41, 0x42, 0xFFFF), group(0x43, 0x44, u32::MAX)],
+        };
+        assert_eq!(cmap_12.find_glyph_index(0x41), 0xFFFF);
+        assert_eq!(cmap_12.find_glyph_index(0x42), 0);
+        assert_eq!(cmap_12.find_glyph_index(0x44), 0);
+
+        let cmap_13 = CMapFormat13 {
+            language: 0,
+            groups: vec![group(0x4E00, 0x9FFF, 42)],
+        };
+        assert_eq!(cmap_13.find_glyph_index(0x6C34), 42);
+        assert_eq!(cmap_13.find_glyph_index(0x41), 0);
+    }
+
+    #[test]
+    fn test_prefers_full_unicode_subtable() {
+        let bmp = CMapSubtable::Six(CMapFormat6 {
+            language: 0,
+            first_code: 0x41,
+            glyph_index_array: vec![1],
+        });
+        let full = CMapSubtable::Twelve(CMapFormat12 {
+            language: 0,
+            groups: vec![group(0x41, 0x41, 2), group(0x1F600, 0x1F600, 3)],
+        });
+        let mac_roman = CMapSubtable::Zero(CMapFormat0 {
+            language: 0,
+            glyph_index_array: vec![4; 256],
+        });
+
+        let cmap = CMapTable {
+            subtables: BTreeMap::from([
+                (bmp, platform(Platform::Microsoft, 1)),
+                (full, platform(Platform::Microsoft, 10)),
+                (mac_roman, platform(Platform::Macintosh, 0)),
+            ]),
+        };
+
+        assert!(matches!(
+            cmap.best_subtable(),
+            Some(CMapSubtable::Twelve(_))
+        ));
+        assert_eq!(cmap.glyph_index('A'), 2);
+        assert_eq!(cmap.glyph_index('\u{1F600}'), 3);
+        // Without a Unicode cmap, variation sequences fall back to the base character.
+        assert_eq!(cmap.variation_glyph_index('A', '\u{FE0F}'), 2);
+
+        let symbols_only = CMapTable {
+            subtables: BTreeMap::from([(
+                CMapSubtable::Four(CMapFormat4 {
+                    language: 0,
+                    seg_count_x2: 6,
+                    search_range: 4,
+                    entry_selector: 1,
+                    range_shift: 2,
+                    end_codes: vec![0x2192, 0xF041, 0xFFFF],
+                    start_codes: vec![0x2192, 0xF041, 0xFFFF],
+                    id_deltas: vec![9u16.wrapping_sub(0x2192), 7u16.wrapping_sub(0xF041), 1],
+                    id_range_offset: vec![0, 0, 0],
+                    glyph_index_array: vec![],
+                }),
+                platform(Platform::Microsoft, 0),
+            )]),
+        };
+        // Symbol fonts are looked up in the private use area, unless they map the character.
+        assert!(symbols_only.best_subtable().is_some());
+        assert_eq!(symbols_only.glyph_index('A'), 7);
+        assert_eq!(symbols_only.glyph_index('\u{F041}'), 7);
+        assert_eq!(symbols_only.glyph_index('\u{2192}'), 9);
+        assert_eq!(symbols_only.glyph_index('B'), 0);
+    }
+
+    #[test]
+    fn test_mac_roman_subtable() {
+        let cmap = CMapTable {
+            subtables: BTreeMap::from([(
+                CMapSubtable::Zero(CMapFormat0 {
+                    language: 0,
+                    glyph_index_array: (0..=255).collect(),
+                }),
+                platform(Platform::Macintosh, 0),
+            )]),
+        };
+
+        // Characters past ASCII are looked up by their Mac OS Roman code.
+        assert_eq!(cmap.glyph_index('A'), 0x41);
+        assert_eq!(cmap.glyph_index('é'), 0x8E);
+        assert_eq!(cmap.glyph_index('€'), 0xDB);
+        assert_eq!(cmap.glyph_index('ˇ'), 0xFF);
+        assert_eq!(cmap.glyph_index('\u{8E}'), 0);
+        assert_eq!(cmap.glyph_index('中'), 0);
+    }
+}
@@ -0,0 +1,712 @@
+//! OpenType layout tables.
+//!
+//! GPOS positions glyphs and GSUB substitutes them. Both select lookups through a script list,
+//! a feature list and a lookup list, and find the glyphs a subtable applies to through
+//! coverage and class definition tables.
+//!
+//! https://learn.microsoft.com/en-us/typography/opentype/spec/chapter2
+
+pub type Tag = [u8; 4];
+
+/// The script lookups fall back to when a font doesn't list the one asked for.
+pub const DEFAULT_SCRIPT: Tag = *b"DFLT";
+
+/// The glyphs a subtable applies to, each with its index into the subtable's arrays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Coverage {
+    Glyphs(Vec<u16>),
+    Ranges(Vec<RangeRecord>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RangeRecord {
+    pub start_glyph: u16,
+    pub end_glyph: u16,
+    pub start_coverage_index: u16,
+}
+
+impl Coverage {
+    pub fn index(&self, glyph: u16) -> Option<u16> {
+        match self {
+            Self::Glyphs(glyphs) => glyphs.binary_search(&glyph).ok().map(|i| i as u16),
+            Self::Ranges(ranges) => {
+                let i = ranges.partition_point(|range| range.end_glyph < glyph);
+                ranges
+                    .get(i)
+                    .filter(|range| range.start_glyph <= glyph)
+                    .map(|range| range.start_coverage_index + glyph - range.start_glyph)
+            }
+        }
+    }
+}
+
+/// Sorts glyphs into classes. Glyphs it doesn't list are in class 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassDef {
+    Glyphs { start_glyph: u16, classes: Vec<u16> },
+    Ranges(Vec<ClassRangeRecord>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClassRangeRecord {
+    pub start_glyph: u16,
+    pub end_glyph: u16,
+    pub class: u16,
+}
+
+impl ClassDef {
+    pub fn class(&self, glyph: u16) -> u16 {
+        match self {
+            Self::Glyphs {
+                start_glyph,
+                classes,
+            } => glyph
+                .checked_sub(*start_glyph)
+                .and_then(|i| classes.get(i as usize))
+                .copied()
+                .unwrap_or(0),
+            Self::Ranges(ranges) => {
+                let i = ranges.partition_point(|range| range.end_glyph < glyph);
+                ranges
+                    .get(i)
+                    .filter(|range| range.start_glyph <= glyph)
+                    .map_or(0, |range| range.class)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptRecord {
+    pub tag: Tag,
+    pub script: Script,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub default_lang_sys: Option<LangSys>,
+    pub lang_sys_records: Vec<LangSysRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LangSysRecord {
+    pub tag: Tag,
+    pub lang_sys: LangSys,
+}
+
+/// The features a language system uses, as indices into the feature list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LangSys {
+    pub required_feature_index: Option<u16>,
+    pub feature_indices: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureRecord {
+    pub tag: Tag,
+    pub lookup_indices: Vec<u16>,
+}
+
+/// Lookup flag bits. Cursive attachments of right to left lookups attach the last glyph in a
+/// chain to the baseline rather than the first. The others skip glyphs by their GDEF class.
+pub const RIGHT_TO_LEFT: u16 = 0x0001;
+pub const IGNORE_BASE_GLYPHS: u16 = 0x0002;
+pub const IGNORE_LIGATURES: u16 = 0x0004;
+pub const IGNORE_MARKS: u16 = 0x0008;
+/// Skips the marks outside one of GDEF's mark glyph sets.
+pub const USE_MARK_FILTERING_SET: u16 = 0x0010;
+/// Skips the marks of any other GDEF mark attachment class than the one in the high byte.
+pub const MARK_ATTACHMENT_TYPE: u16 = 0xFF00;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lookup<S> {
+    pub lookup_flag: u16,
+    /// Tried in order until one applies. Extension subtables are resolved while parsing.
+    pub subtables: Vec<S>,
+    pub mark_filtering_set: Option<u16>,
+}
+
+impl<S> Lookup<S> {
+    /// Whether the lookup passes over `glyph`, as its flags ask for. Without GDEF no glyph has
+    /// a class, so none is skipped.
+    pub fn ignores(&self, gdef: Option<&GdefTable>, glyph: u16) -> bool {
+        let Some(gdef) = gdef else {
+            return false;
+        };
+
+        match gdef.glyph_class(glyph) {
+            GdefTable::BASE_GLYPH => self.lookup_flag & IGNORE_BASE_GLYPHS != 0,
+            GdefTable::LIGATURE_GLYPH => self.lookup_flag & IGNORE_LIGATURES != 0,
+            GdefTable::MARK_GLYPH => {
+                let attachment_type = (self.lookup_flag & MARK_ATTACHMENT_TYPE) >> 8;
+                let filtered_out = self.mark_filtering_set.is_some_and(|set| {
+                    gdef.mark_glyph_sets
+                        .get(set as usize)
+                        .map_or(true, |coverage| coverage.index(glyph).is_none())
+                });
+
+                self.lookup_flag & IGNORE_MARKS != 0
+                    || filtered_out
+                    || (attachment_type != 0
+                        && gdef.mark_attachment_class(glyph) != attachment_type)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The script, feature and lookup lists GPOS and GSUB share, with subtables of type `S`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutTable<S> {
+    pub scripts: Vec<ScriptRecord>,
+    pub features: Vec<FeatureRecord>,
+    pub lookups: Vec<Lookup<S>>,
+}
+
+impl<S> LayoutTable<S> {
+    /// The lookups of `features` in the default language of `script`, or of the default script
+    /// when the font doesn't have that one, along with those of the language's required feature
+    /// whichever features are asked for. They are in lookup list order, the order they apply in.
+    pub fn lookups(&self, script: &Tag, features: &[Tag]) -> Vec<&Lookup<S>> {
+        let lang_sys = [script, &DEFAULT_SCRIPT].into_iter().find_map(|tag| {
+            self.scripts
+                .iter()
+                .find(|record| record.tag == *tag)
+                .and_then(|record| record.script.default_lang_sys.as_ref())
+        });
+
+        let Some(lang_sys) = lang_sys else {
+            return vec![];
+        };
+
+        let required = lang_sys
+            .required_feature_index
+            .and_then(|i| self.features.get(i as usize));
+        let requested = lang_sys
+            .feature_indices
+            .iter()
+            .filter_map(|&i| self.features.get(i as usize))
+            .filter(|feature| features.contains(&feature.tag));
+
+        let mut lookup_indices = required
+            .into_iter()
+            .chain(requested)
+            .flat_map(|feature| feature.lookup_indices.iter().copied())
+            .collect::<Vec<_>>();
+
+        lookup_indices.sort_unstable();
+        lookup_indices.dedup();
+
+        lookup_indices
+            .into_iter()
+            .filter_map(|i| self.lookups.get(i as usize))
+            .collect()
+    }
+}
+
+/// Glyph classes for the layout tables, so lookups can tell bases from marks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GdefTable {
+    pub glyph_class_def: Option<ClassDef>,
+    pub mark_attach_class_def: Option<ClassDef>,
+    pub mark_glyph_sets: Vec<Coverage>,
+}
+
+impl GdefTable {
+    pub const BASE_GLYPH: u16 = 1;
+    pub const LIGATURE_GLYPH: u16 = 2;
+    pub const MARK_GLYPH: u16 = 3;
+    pub const COMPONENT_GLYPH: u16 = 4;
+
+    pub fn glyph_class(&self, glyph: u16) -> u16 {
+        self.glyph_class_def
+            .as_ref()
+            .map_or(0, |class_def| class_def.class(glyph))
+    }
+
+    pub fn is_mark(&self, glyph: u16) -> bool {
+        self.glyph_class(glyph) == Self::MARK_GLYPH
+    }
+
+    pub fn mark_attachment_class(&self, glyph: u16) -> u16 {
+        self.mark_attach_class_def
+            .as_ref()
+            .map_or(0, |class_def| class_def.class(glyph))
+    }
+}
+
+pub type GposTable = LayoutTable<GposSubtable>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GposSubtable {
+    PairAdjustment(PairAdjustment),
+    /// Lookup type 3, which joins the exit of a glyph to the entry of the next.
+    Cursive(CursiveAttachment),
+    /// Lookup type 4, which places marks on the glyph before them.
+    MarkToBase(MarkAttachment),
+    /// Lookup type 5, which places marks on a component of the ligature before them.
+    MarkToLigature(MarkToLigature),
+    /// Lookup type 6, which places marks on the mark before them.
+    MarkToMark(MarkAttachment),
+    /// Lookup types the shaper doesn't apply yet, or subtables in a format it doesn't know.
+    Unsupported {
+        lookup_type: u16,
+    },
+}
+
+/// Adjustments to a glyph's position and advance, in font units. Device tables, which only
+/// correct specific pixel sizes, are skipped.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ValueRecord {
+    pub x_placement: i16,
+    pub y_placement: i16,
+    pub x_advance: i16,
+    pub y_advance: i16,
+}
+
+/// GPOS lookup type 2, which adjusts pairs of glyphs like kerning does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairAdjustment {
+    /// Format 1, with the second glyphs listed for every first glyph in the coverage.
+    Glyphs {
+        coverage: Coverage,
+        /// Whether the second glyph gets a value record, in which case the pair is done with.
+        /// Otherwise the second glyph may start the next pair.
+        adjusts_second: bool,
+        pair_sets: Vec<Vec<PairValueRecord>>,
+    },
+    /// Format 2, with a value for every pair of classes.
+    Classes {
+        coverage: Coverage,
+        adjusts_second: bool,
+        class_def_1: ClassDef,
+        class_def_2: ClassDef,
+        class_2_count: u16,
+        /// Row by first glyph class, then column by second glyph class.
+        records: Vec<[ValueRecord; 2]>,
+    },
+}
+
+/// A point on a glyph, in font units, that glyphs attach to.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Anchor {
+    pub x: i16,
+    pub y: i16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MarkRecord {
+    pub mark_class: u16,
+    pub anchor: Anchor,
+}
+
+/// Marks and the glyphs they attach to, which are marks themselves for mark-to-mark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkAttachment {
+    pub mark_coverage: Coverage,
+    pub base_coverage: Coverage,
+    pub marks: Vec<MarkRecord>,
+    /// The anchor of every base glyph for each mark class.
+    pub base_anchors: Vec<Vec<Option<Anchor>>>,
+}
+
+impl MarkAttachment {
+    /// The anchors of `mark` and of `base` that line up, when the subtable covers both.
+    pub fn anchors(&self, mark: u16, base: u16) -> Option<(Anchor, Anchor)> {
+        let mark_record = self.marks.get(self.mark_coverage.index(mark)? as usize)?;
+        let base_anchors = self
+            .base_anchors
+            .get(self.base_coverage.index(base)? as usize)?;
+
+        let base_anchor = (*base_anchors.get(mark_record.mark_class as usize)?)?;
+        Some((mark_record.anchor, base_anchor))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkToLigature {
+    pub mark_coverage: Coverage,
+    pub ligature_coverage: Coverage,
+    pub marks: Vec<MarkRecord>,
+    /// The anchors of every ligature for each of its components, then each mark class.
+    pub ligature_anchors: Vec<Vec<Vec<Option<Anchor>>>>,
+}
+
+impl MarkToLigature {
+    /// The anchors of `mark` and of the last component of `ligature`, the one a mark after the
+    /// ligature belongs to.
+    pub fn anchors(&self, mark: u16, ligature: u16) -> Option<(Anchor, Anchor)> {
+        let mark_record = self.marks.get(self.mark_coverage.index(mark)? as usize)?;
+        let components = self
+            .ligature_anchors
+            .get(self.ligature_coverage.index(ligature)? as usize)?;
+
+        let ligature_anchor = (*components.last()?.get(mark_record.mark_class as usize)?)?;
+        Some((mark_record.anchor, ligature_anchor))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursiveAttachment {
+    pub coverage: Coverage,
+    pub entry_exits: Vec<EntryExitRecord>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EntryExitRecord {
+    pub entry: Option<Anchor>,
+    pub exit: Option<Anchor>,
+}
+
+impl CursiveAttachment {
+    /// The exit anchor of `first` and the entry anchor of `second`, when they join.
+    pub fn anchors(&self, first: u16, second: u16) -> Option<(Anchor, Anchor)> {
+        let record = |glyph| {
+            self.entry_exits
+                .get(self.coverage.index(glyph)? as usize)
+                .copied()
+        };
+
+        Some((record(first)?.exit?, record(second)?.entry?))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PairValueRecord {
+    pub second_glyph: u16,
+    pub values: [ValueRecord; 2],
+}
+
+impl PairAdjustment {
+    /// The adjustments to `first` and `second`, when the subtable covers the pair.
+    pub fn find(&self, first: u16, second: u16) -> Option<[ValueRecord; 2]> {
+        match self {
+            Self::Glyphs {
+                coverage,
+                pair_sets,
+                ..
+            } => {
+                let pair_set = pair_sets.get(coverage.index(first)? as usize)?;
+                pair_set
+                    .binary_search_by_key(&second, |record| record.second_glyph)
+                    .ok()
+                    .map(|i| pair_set[i].values)
+            }
+            Self::Classes {
+                coverage,
+                class_def_1,
+                class_def_2,
+                class_2_count,
+                records,
+                ..
+            } => {
+                coverage.index(first)?;
+
+                let class_2 = class_def_2.class(second);
+                if class_2 >= *class_2_count {
+                    return None;
+                }
+
+                let i = class_def_1.class(first) as usize * *class_2_count as usize;
+                records.get(i + class_2 as usize).copied()
+            }
+        }
+    }
+
+    pub const fn adjusts_second(&self) -> bool {
+        match self {
+            Self::Glyphs { adjusts_second, .. } | Self::Classes { adjusts_second, .. } => {
+                *adjusts_second
+            }
+        }
+    }
+}
+
+pub type GsubTable = LayoutTable<GsubSubtable>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GsubSubtable {
+    /// Lookup type 1, one glyph for another.
+    Single(SingleSubstitution),
+    /// Lookup type 2, one glyph for several.
+    Multiple {
+        coverage: Coverage,
+        sequences: Vec<Vec<u16>>,
+    },
+    /// Lookup type 3, one glyph for one of a set of alternates.
+    Alternate {
+        coverage: Coverage,
+        alternate_sets: Vec<Vec<u16>>,
+    },
+    /// Lookup type 4, several glyphs for one.
+    Ligature {
+        coverage: Coverage,
+        /// Ligatures by first glyph, in order of preference.
+        ligature_sets: Vec<Vec<Ligature>>,
+    },
+    /// Lookup types the shaper doesn't apply yet, like contextual substitutions, or subtables
+    /// in a format it doesn't know.
+    Unsupported { lookup_type: u16 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SingleSubstitution {
+    /// Format 1, adding a delta to the glyph index modulo 65536.
+    Delta {
+        coverage: Coverage,
+        delta_glyph_id: i16,
+    },
+    /// Format 2, with a substitute for every glyph in the coverage.
+    Glyphs {
+        coverage: Coverage,
+        substitutes: Vec<u16>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ligature {
+    pub ligature_glyph: u16,
+    /// The glyphs after the first one that make up the ligature.
+    pub components: Vec<u16>,
+}
+
+impl GsubSubtable {
+    /// What the glyphs at the start of `glyphs` become, and how many of them it replaces. Of
+    /// alternates, the first is picked.
+    pub fn substitute(&self, glyphs: &[u16]) -> Option<(usize, Vec<u16>)> {
+        let &first = glyphs.first()?;
+
+        match self {
+            Self::Single(SingleSubstitution::Delta {
+                coverage,
+                delta_glyph_id,
+            }) => {
+                coverage.index(first)?;
+                Some((1, vec![first.wrapping_add_signed(*delta_glyph_id)]))
+            }
+            Self::Single(SingleSubstitution::Glyphs {
+                coverage,
+                substitutes,
+            }) => {
+                let substitute = *substitutes.get(coverage.index(first)? as usize)?;
+                Some((1, vec![substitute]))
+            }
+            Self::Multiple {
+                coverage,
+                sequences,
+            } => {
+                let sequence = sequences.get(coverage.index(first)? as usize)?;
+                Some((1, sequence.clone()))
+            }
+            Self::Alternate {
+                coverage,
+                alternate_sets,
+            } => {
+                let alternates = alternate_sets.get(coverage.index(first)? as usize)?;
+                Some((1, vec![*alternates.first()?]))
+            }
+            Self::Ligature {
+                coverage,
+                ligature_sets,
+            } => {
+                let ligatures = ligature_sets.get(coverage.index(first)? as usize)?;
+                ligatures
+                    .iter()
+                    .find(|ligature| glyphs[1..].starts_with(&ligature.components))
+                    .map(|ligature| (ligature.components.len() + 1, vec![ligature.ligature_glyph]))
+            }
+            Self::Unsupported { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage_and_class_lookup() {
+        let glyphs = Coverage::Glyphs(vec![3, 7, 9]);
+        assert_eq!(glyphs.index(7), Some(1));
+        assert_eq!(glyphs.index(8), None);
+
+        let ranges = Coverage::Ranges(vec![
+            RangeRecord {
+                start_glyph: 10,
+                end_glyph: 12,
+                start_coverage_index: 0,
+            },
+            RangeRecord {
+                start_glyph: 20,
+                end_glyph: 20,
+                start_coverage_index: 3,
+            },
+        ]);
+        assert_eq!(ranges.index(11), Some(1));
+        assert_eq!(ranges.index(20), Some(3));
+        assert_eq!(ranges.index(13), None);
+        assert_eq!(ranges.index(21), None);
+
+        let classes = ClassDef::Glyphs {
+            start_glyph: 5,
+            classes: vec![1, 2],
+        };
+        assert_eq!(classes.class(6), 2);
+        assert_eq!(classes.class(4), 0);
+        assert_eq!(classes.class(7), 0);
+    }
+
+    #[test]
+    fn test_lookups_follow_the_script_and_list_order() {
+        let lang_sys = |feature_indices: Vec<u16>| LangSys {
+            required_feature_index: None,
+            feature_indices,
+        };
+        let lookup = |lookup_flag| Lookup::<()> {
+            lookup_flag,
+            subtables: vec![],
+            mark_filtering_set: None,
+        };
+
+        let table = LayoutTable {
+            scripts: vec![
+                ScriptRecord {
+                    tag: DEFAULT_SCRIPT,
+                    script: Script {
+                        default_lang_sys: Some(lang_sys(vec![0])),
+                        lang_sys_records: vec![],
+                    },
+                },
+                ScriptRecord {
+                    tag: *b"latn",
+                    script: Script {
+                        default_lang_sys: Some(lang_sys(vec![1, 0])),
+                        lang_sys_records: vec![],
+                    },
+                },
+                ScriptRecord {
+                    tag: *b"arab",
+                    script: Script {
+                        default_lang_sys: Some(LangSys {
+                            required_feature_index: Some(1),
+                            feature_indices: vec![0],
+                        }),
+                        lang_sys_records: vec![],
+                    },
+                },
+            ],
+            features: vec![
+                FeatureRecord {
+                    tag: *b"kern",
+                    lookup_indices: vec![2],
+                },
+                FeatureRecord {
+                    tag: *b"mark",
+                    lookup_indices: vec![0, 1],
+                },
+            ],
+            lookups: vec![lookup(0), lookup(1), lookup(2)],
+        };
+
+        let flags = |script: &Tag, features: &[Tag]| {
+            table
+                .lookups(script, features)
+                .iter()
+                .map(|lookup| lookup.lookup_flag)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(flags(b"latn", &[*b"kern", *b"mark"]), [0, 1, 2]);
+        assert_eq!(flags(b"latn", &[*b"kern"]), [2]);
+        // Scripts the font doesn't know fall back to the default one.
+        assert_eq!(flags(b"cyrl", &[*b"kern", *b"mark"]), [2]);
+        // The required feature applies whatever is asked for.
+        assert_eq!(flags(b"arab", &[*b"liga"]), [0, 1]);
+        assert_eq!(flags(b"arab", &[*b"kern"]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_lookup_flags_skip_glyph_classes() {
+        // Glyph 3 is a base, and glyphs 7 and 8 marks of attachment classes 1 and 2.
+        let gdef = GdefTable {
+            glyph_class_def: Some(ClassDef::Glyphs {
+                start_glyph: 3,
+                classes: vec![1, 0, 0, 0, 3, 3],
+            }),
+            mark_attach_class_def: Some(ClassDef::Glyphs {
+                start_glyph: 7,
+                classes: vec![1, 2],
+            }),
+            mark_glyph_sets: vec![Coverage::Glyphs(vec![8])],
+        };
+        let ignored = |lookup_flag, mark_filtering_set, glyph| {
+            Lookup::<()> {
+                lookup_flag,
+                subtables: vec![],
+                mark_filtering_set,
+            }
+            .ignores(Some(&gdef), glyph)
+        };
+
+        assert!(ignored(IGNORE_MARKS, None, 7));
+        assert!(!ignored(IGNORE_MARKS, None, 3));
+        assert!(ignored(IGNORE_BASE_GLYPHS, None, 3));
+        assert!(!ignored(IGNORE_LIGATURES, None, 3));
+
+        // Only marks of the attachment type, or in the filtering set, are kept.
+        assert!(!ignored(0x0100, None, 7));
+        assert!(ignored(0x0100, None, 8));
+        assert!(ignored(USE_MARK_FILTERING_SET, Some(0), 7));
+        assert!(!ignored(USE_MARK_FILTERING_SET, Some(0), 8));
+        assert!(ignored(USE_MARK_FILTERING_SET, Some(1), 8));
+
+        // Without GDEF nothing is skipped.
+        let lookup = Lookup::<()> {
+            lookup_flag: IGNORE_MARKS,
+            subtables: vec![],
+            mark_filtering_set: None,
+        };
+        assert!(!lookup.ignores(None, 7));
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let coverage = Coverage::Glyphs(vec![4, 6]);
+
+        let single = GsubSubtable::Single(SingleSubstitution::Delta {
+            coverage: coverage.clone(),
+            delta_glyph_id: -2,
+        });
+        assert_eq!(single.substitute(&[6, 4]), Some((1, vec![4])));
+        assert_eq!(single.substitute(&[5]), None);
+        assert_eq!(single.substitute(&[]), None);
+
+        let multiple = GsubSubtable::Multiple {
+            coverage: coverage.clone(),
+            sequences: vec![vec![1, 2], vec![]],
+        };
+        assert_eq!(multiple.substitute(&[4]), Some((1, vec![1, 2])));
+        assert_eq!(multiple.substitute(&[6]), Some((1, vec![])));
+
+        let ligature = GsubSubtable::Ligature {
+            coverage,
+            ligature_sets: vec![
+                vec![
+                    Ligature {
+                        ligature_glyph: 40,
+                        components: vec![4, 6],
+                    },
+                    Ligature {
+                        ligature_glyph: 41,
+                        components: vec![4],
+                    },
+                ],
+                vec![],
+            ],
+        };
+        assert_eq!(ligature.substitute(&[4, 4, 6, 9]), Some((3, vec![40])));
+        assert_eq!(ligature.substitute(&[4, 4, 9]), Some((2, vec![41])));
+        assert_eq!(ligature.substitute(&[4, 9]), None);
+        assert_eq!(ligature.substitute(&[6, 4]), None);
+    }
+}
@@ -3,5 +3,10 @@
 pub use parser::*;
 
 pub mod grammar;
+pub mod layout;
+pub mod outline;
+pub mod path;
+pub mod raster;
+pub mod sdf;
 mod parser;
 pub mod shaper;
@@ -0,0 +1,433 @@
+//! Glyph outlines with compound glyphs resolved, so every glyph is a flat list of contours in
+//! font units no matter how many components it was assembled from.
+
+use crate::font::grammar::{
+    ComponentGlyph, ComponentGlyphArgument, ComponentGlyphTransformation, F2Dot14, GlyphData,
+    TrueTypeFontFile,
+};
+use crate::util::affine::Affine;
+use anyhow::{anyhow, ensure, Result};
+
+/// How deeply components may nest, whatever the font says. Fonts rarely go past 2 or 3.
+const MAX_COMPONENT_DEPTH: usize = 16;
+
+/// Points, contours and components a flattened outline may hold. TrueType numbers points with
+/// a u16, so no glyph needs more, but components reused at every level of nesting could
+/// otherwise multiply into billions.
+const MAX_OUTLINE_ITEMS: usize = u16::MAX as usize;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutlinePoint {
+    pub x: f32,
+    pub y: f32,
+    /// Off-curve points are quadratic control points. Two in a row imply an on-curve point
+    /// halfway between them.
+    pub on_curve: bool,
+}
+
+pub type Contour = Vec<OutlinePoint>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphContours {
+    pub contours: Vec<Contour>,
+    /// The horizontal metrics from hmtx, taken from a component when it sets `USE_MY_METRICS`.
+    pub advance_width: u16,
+    pub left_side_bearing: i16,
+}
+
+impl GlyphContours {
+    pub fn points(&self) -> impl Iterator<Item = &OutlinePoint> {
+        self.contours.iter().flatten()
+    }
+}
+
+impl TrueTypeFontFile<'_> {
+    /// The contours of a glyph with every component placed, transformed and flattened into one
+    /// list. Fails on component cycles, on nesting deeper than maxp allows or 16 levels,
+    /// and on outlines of more than 65535 points, contours or components.
+    pub fn outline(&self, glyph_index: u16) -> Result<GlyphContours> {
+        let mut contours = vec![];
+        let mut budget = OutlineBudget {
+            points: MAX_OUTLINE_ITEMS,
+            contours: MAX_OUTLINE_ITEMS,
+            components: MAX_OUTLINE_ITEMS,
+        };
+        let metrics_index =
+            self.flatten_glyph(glyph_index, &mut vec![], &mut budget, &mut contours)?;
+        let (advance_width, left_side_bearing) = self.horizontal_metrics(metrics_index);
+
+        Ok(GlyphContours {
+            contours,
+            advance_width,
+            left_side_bearing,
+        })
+    }
+
+    /// The advance width and left side bearing of a glyph. Glyphs past the last long metric
+    /// share its advance width.
+    pub fn horizontal_metrics(&self, glyph_index: u16) -> (u16, i16) {
+        let h_metrics = &self.hmtx_table.h_metrics;
+        let i = glyph_index as usize;
+
+        let advance_width = h_metrics
+            .get(i.min(h_metrics.len().saturating_sub(1)))
+            .map_or(0, |metric| metric.advance_width);
+
+        let left_side_bearing = h_metrics.get(i).map_or_else(
+            || {
+                let left_side_bearings = &self.hmtx_table.left_side_bearing;
+                left_side_bearings
+                    .get(i - h_metrics.len())
+                    .copied()
+                    .unwrap_or(0)
+            },
+            |metric| metric.left_side_bearing,
+        );
+
+        (advance_width, left_side_bearing)
+    }
+
+    /// Glyphs without outlines, like the space, have no data in glyf at all.
+    fn is_empty_glyph(&self, glyph_index: u16) -> bool {
+        let i = glyph_index as usize;
+        self.loca_table.get(i) == self.loca_table.get(i + 1)
+    }
+
+    fn max_component_depth(&self) -> usize {
+        match self.maxp_table.max_component_depth {
+            0 => MAX_COMPONENT_DEPTH,
+            depth => (depth as usize).min(MAX_COMPONENT_DEPTH),
+        }
+    }
+
+    /// Appends the contours of `glyph_index` to `contours` and returns the glyph whose metrics it
+    /// uses. `path` holds the compound glyphs being resolved, outermost first, and `budget` what
+    /// the whole outline may still take.
+    fn flatten_glyph(
+        &self,
+        glyph_index: u16,
+        path: &mut Vec<u16>,
+        budget: &mut OutlineBudget,
+        contours: &mut Vec<Contour>,
+    ) -> Result<u16> {
+        ensure!(
+            !path.contains(&glyph_index),
+            "Glyph {} is a component of itself through {:?}.",
+            glyph_index,
+            path
+        );
+        ensure!(
+            path.len() <= self.max_component_depth(),
+            "Components of glyph {} nest deeper than {} levels.",
+            path[0],
+            self.max_component_depth()
+        );
+
+        if self.is_empty_glyph(glyph_index) {
+            return Ok(glyph_index);
+        }
+
+        let glyph = self
+            .glyph_table
+            .glyphs
+            .get(glyph_index as usize)
+            .ok_or_else(|| anyhow!("Glyph {} is out of bounds.", glyph_index))?;
+
+        let compound_glyph = match &glyph.data {
+            GlyphData::Simple(simple_glyph) => {
+                let mut start = 0;
+
+                for &end in &simple_glyph.end_points_of_contours {
+                    let end = end as usize;
+                    ensure!(
+                        start <= end && end < simple_glyph.coordinates.len(),
+                        "Contour of glyph {} ends out of bounds.",
+                        glyph_index
+                    );
+                    budget.take_contour(end + 1 - start)?;
+
+                    let contour = (start..=end)
+                        .map(|i| {
+                            let (x, y) = simple_glyph.coordinates[i];
+                            OutlinePoint {
+                                x: x as f32,
+                                y: y as f32,
+                                on_curve: simple_glyph.on_curve(i),
+                            }
+                        })
+                        .collect();
+
+                    contours.push(contour);
+                    start = end + 1;
+                }
+
+                return Ok(glyph_index);
+            }
+            GlyphData::Compound(compound_glyph) => compound_glyph,
+        };
+
+        path.push(glyph_index);
+
+        let mut metrics_index = glyph_index;
+        let mut placed: Vec<Contour> = vec![];
+
+        for component in &compound_glyph.components {
+            budget.take_component()?;
+
+            let mut component_contours = vec![];
+            let component_metrics =
+                self.flatten_glyph(component.glyph_index, path, budget, &mut component_contours)?;
+
+            let transform = component_transform(component);
+            for point in component_contours.iter_mut().flatten() {
+                (point.x, point.y) = transform.apply((point.x, point.y));
+            }
+
+            let (dx, dy) = component_offset(component, &transform, &placed, &component_contours)
+                .map_err(|e| anyhow!("Component of glyph {}: {}", glyph_index, e))?;
+            for point in component_contours.iter_mut().flatten() {
+                point.x += dx;
+                point.y += dy;
+            }
+
+            if component.flag.use_my_metrics() {
+                metrics_index = component_metrics;
+            }
+
+            placed.extend(component_contours);
+        }
+
+        path.pop();
+        contours.extend(placed);
+
+        Ok(metrics_index)
+    }
+}
+
+/// What is left of the points, contours and components an outline may hold.
+struct OutlineBudget {
+    points: usize,
+    contours: usize,
+    components: usize,
+}
+
+impl OutlineBudget {
+    fn take_contour(&mut self, points: usize) -> Result<()> {
+        ensure!(
+            self.contours > 0 && points <= self.points,
+            "Outline has more than {} points or contours.",
+            MAX_OUTLINE_ITEMS
+        );
+        self.contours -= 1;
+        self.points -= points;
+
+        Ok(())
+    }
+
+    fn take_component(&mut self) -> Result<()> {
+        ensure!(
+            self.components > 0,
+            "Outline has more than {} components.",
+            MAX_OUTLINE_ITEMS
+        );
+        self.components -= 1;
+
+        Ok(())
+    }
+}
+
+fn f2dot14(value: F2Dot14) -> f32 {
+    value as f32 / (1 << 14) as f32
+}
+
+/// The linear part of a component's placement.
+fn component_transform(component: &ComponentGlyph) -> Affine {
+    match component.transformation {
+        ComponentGlyphTransformation::Uniform(scale) => {
+            Affine::scale(f2dot14(scale), f2dot14(scale))
+        }
+        ComponentGlyphTransformation::NonUniform { x_scale, y_scale } => {
+            Affine::scale(f2dot14(x_scale), f2dot14(y_scale))
+        }
+        ComponentGlyphTransformation::Affine {
+            x_scale,
+            scale_01,
+            scale_10,
+            y_scale,
+        } => Affine::new(
+            f2dot14(x_scale),
+            f2dot14(scale_01),
+            f2dot14(scale_10),
+            f2dot14(y_scale),
+            0.0,
+            0.0,
+        ),
+    }
+}
+
+/// Where a transformed component moves to. Offsets are either given outright, or by matching a
+/// point of the glyph so far with a point of the component.
+fn component_offset(
+    component: &ComponentGlyph,
+    transform: &Affine,
+    placed: &[Contour],
+    component_contours: &[Contour],
+) -> Result<(f32, f32)> {
+    match (&component.arg_1, &component.arg_2) {
+        (&ComponentGlyphArgument::Coord(x), &ComponentGlyphArgument::Coord(y)) => {
+            let offset = (x as f32, y as f32);
+
+            // Offsets are in the parent's space unless the font asks for them to be scaled too,
+            // as Apple's rasterizer used to do.
+            if component.flag.scaled_component_offset()
+                && !component.flag.unscaled_component_offset()
+            {
+                Ok(transform.apply(offset))
+            } else {
+                Ok(offset)
+            }
+        }
+        (&ComponentGlyphArgument::Point(parent), &ComponentGlyphArgument::Point(child)) => {
+            let anchor = placed
+                .iter()
+                .flatten()
+                .nth(parent as usize)
+                .ok_or_else(|| anyhow!("Anchor point {} is out of bounds.", parent))?;
+            let point = component_contours
+                .iter()
+                .flatten()
+                .nth(child as usize)
+                .ok_or_else(|| anyhow!("Component point {} is out of bounds.", child))?;
+
+            Ok((anchor.x - point.x, anchor.y - point.y))
+        }
+        _ => unreachable!("Component arguments are either both points or both offsets."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::TrueTypeFontParser;
+    use std::fs;
+
+    #[test]
+    fn test_accented_glyphs_are_flattened() -> Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        let e = ttf.cmap_table.glyph_index('e');
+        let e_acute = ttf.cmap_table.glyph_index('é');
+        assert!(!ttf.glyph_table.glyphs[e_acute as usize].is_simple());
+
+        let plain = ttf.outline(e)?;
+        let accented = ttf.outline(e_acute)?;
+
+        assert!(accented.contours.len() > plain.contours.len());
+        assert_eq!(accented.advance_width, plain.advance_width);
+
+        // The base letter comes through unchanged, with the accent above it.
+        let max_y =
+            |contours: &GlyphContours| contours.points().map(|p| p.y).fold(f32::MIN, f32::max);
+        assert!(accented.contours.contains(&plain.contours[0]));
+        assert!(max_y(&accented) > max_y(&plain));
+
+        let space = ttf.outline(ttf.cmap_table.glyph_index(' '))?;
+        assert!(space.contours.is_empty());
+        assert!(space.advance_width > 0);
+
+        for glyph_index in 0..ttf.glyph_table.glyphs.len() {
+            ttf.outline(glyph_index as u16)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_components_are_bounded() -> Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let mut ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        let e = ttf.cmap_table.glyph_index('e');
+        let compound = |glyph_index, count| {
+            let component = ComponentGlyph {
+                flag: crate::font::grammar::ComponentGlyphFlag(0b10),
+                glyph_index,
+                arg_1: ComponentGlyphArgument::Coord(0),
+                arg_2: ComponentGlyphArgument::Coord(0),
+                transformation: ComponentGlyphTransformation::Uniform(1 << 14),
+            };
+            GlyphData::Compound(crate::font::grammar::CompoundGlyph {
+                components: vec![component; count],
+            })
+        };
+
+        // Glyphs without data in loca are never looked at.
+        let glyphs: Vec<u16> = (0..ttf.maxp_table.num_glyphs)
+            .filter(|&glyph_index| !ttf.is_empty_glyph(glyph_index) && glyph_index != e)
+            .take(21)
+            .collect();
+
+        // A font claiming deep nesting still stops at 16 levels.
+        ttf.maxp_table.max_component_depth = u16::MAX;
+        for pair in glyphs.windows(2) {
+            ttf.glyph_table.glyphs[pair[0] as usize].data = compound(pair[1], 1);
+        }
+        assert!(ttf.outline(glyphs[10]).is_ok());
+        assert!(ttf.outline(glyphs[0]).is_err());
+
+        // 300 copies of a glyph of 300 copies of an e is too many points to hold.
+        ttf.glyph_table.glyphs[glyphs[0] as usize].data = compound(glyphs[1], 300);
+        ttf.glyph_table.glyphs[glyphs[1] as usize].data = compound(e, 300);
+        assert!(ttf.outline(glyphs[1]).is_ok());
+        assert!(ttf.outline(glyphs[0]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_component_transforms() {
+        let component = |transformation, arg_1, arg_2| ComponentGlyph {
+            flag: crate::font::grammar::ComponentGlyphFlag(0b10),
+            glyph_index: 0,
+            arg_1,
+            arg_2,
+            transformation,
+        };
+        let point = |x, y| OutlinePoint {
+            x,
+            y,
+            on_curve: true,
+        };
+
+        let rotated = component(
+            ComponentGlyphTransformation::Affine {
+                x_scale: 0,
+                scale_01: 1 << 14,
+                scale_10: -(1 << 14),
+                y_scale: 0,
+            },
+            ComponentGlyphArgument::Coord(10),
+            ComponentGlyphArgument::Coord(20),
+        );
+        let transform = component_transform(&rotated);
+        assert_eq!(transform.apply((1.0, 0.0)), (0.0, 1.0));
+        assert_eq!(
+            component_offset(&rotated, &transform, &[], &[]).unwrap(),
+            (10.0, 20.0)
+        );
+
+        let halved = component(
+            ComponentGlyphTransformation::Uniform(1 << 13),
+            ComponentGlyphArgument::Point(1),
+            ComponentGlyphArgument::Point(0),
+        );
+        let placed = [vec![point(0.0, 0.0), point(100.0, 50.0)]];
+        let contours = [vec![point(5.0, 5.0)]];
+        assert_eq!(
+            component_offset(&halved, &component_transform(&halved), &placed, &contours).unwrap(),
+            (95.0, 45.0)
+        );
+        assert!(component_offset(&halved, &Affine::IDENTITY, &[], &contours).is_err());
+    }
+}
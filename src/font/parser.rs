@@ -1,28 +1,54 @@
 use std::collections::BTreeMap;
 
 use super::grammar::{
-    CMapFormat0, CMapFormat12, CMapFormat4, CMapIndividualGroup, CMapSubtable, CMapTable,
+    CMapFormat0, CMapFormat10, CMapFormat12, CMapFormat13, CMapFormat14, CMapFormat2, CMapFormat4,
+    CMapFormat6, CMapFormat8, CMapIndividualGroup, CMapSubHeader, CMapSubtable, CMapTable,
     ComponentGlyph, ComponentGlyphArgument, ComponentGlyphFlag, ComponentGlyphTransformation,
-    CompoundGlyph, F2Dot14, FWord, Fixed, FontDirectory, Glyph, GlyphData, GlyphDescription,
-    GlyphTable, HHeaTable, HMtxTable, HeadTable, LongDateTime, LongHorizontalMetric, MaxPTable,
-    OffsetSubTable, ScalarType, SimpleGlyph, SimpleGlyphFlag, TableRecord, TableTag,
-    TrueTypeFontFile, UnsignedFWord,
+    CompoundGlyph, DefaultUVSTable, F2Dot14, FWord, Fixed, FontDirectory, Glyph, GlyphData,
+    GlyphDescription, GlyphTable, HHeaTable, HMtxTable, HeadTable, LongDateTime,
+    LongHorizontalMetric, MaxPTable, NonDefaultUVSTable, OffsetSubTable, ScalarType, SimpleGlyph,
+    SimpleGlyphFlag, TableRecord, TableTag, TrueTypeFontFile, UnicodeValueMap, UnicodeValueRange,
+    UnsignedFWord, VariationSelectorRecord,
 };
 
-use crate::font::grammar::{IndexToLocFormat, Platform, PlatformDouble};
+use crate::font::grammar::{
+    IndexToLocFormat, KernClassTable, KernData, KernPair, KernSubtable, KernTable, Platform,
+    PlatformDouble,
+};
+use crate::font::layout::{
+    Anchor, ClassDef, ClassRangeRecord, Coverage, CursiveAttachment, EntryExitRecord,
+    FeatureRecord, GdefTable, GposSubtable, GsubSubtable, LangSys, LangSysRecord, LayoutTable,
+    Ligature, Lookup, MarkAttachment, MarkRecord, MarkToLigature, PairAdjustment, PairValueRecord,
+    RangeRecord, Script, ScriptRecord, SingleSubstitution, ValueRecord, USE_MARK_FILTERING_SET,
+};
 use crate::util::read_bytes::{U16_BYTES, U32_BYTES, U64_BYTES, U8_BYTES};
 use crate::{eof, read};
 use anyhow::{bail, ensure, Result};
 
+/// The lookup types of GPOS and GSUB subtables that point to a subtable of another type.
+const GPOS_EXTENSION: u16 = 9;
+const GSUB_EXTENSION: u16 = 7;
+
+/// How many items an optional table may read for each of its bytes. Layout tables reach shared
+/// subtables through offsets, so a table can read more items than it has bytes, but a crafted
+/// one could otherwise fan out to billions.
+const ITEMS_PER_TABLE_BYTE: usize = 8;
+
 #[derive(Debug)]
 pub struct TrueTypeFontParser<'a> {
     cursor: usize,
     data: &'a [u8],
+    /// Items `read_vec` may still read before parsing fails.
+    items_left: usize,
 }
 
 impl<'a> TrueTypeFontParser<'a> {
     pub const fn new(data: &'a [u8]) -> Self {
-        Self { cursor: 0, data }
+        Self {
+            cursor: 0,
+            data,
+            items_left: usize::MAX,
+        }
     }
 
     pub fn parse(&mut self) -> Result<TrueTypeFontFile<'a>> {
@@ -102,6 +128,21 @@ impl<'a> TrueTypeFontParser<'a> {
             self.parse_glyph_table(&loca_table)?
         };
 
+        let table_directory = &font_directory.table_directory;
+
+        let kern_table =
+            self.parse_optional_table(table_directory.get(&TableTag::Kern), Self::parse_kern_table);
+        let gpos_table = self
+            .parse_optional_table(table_directory.get(&TableTag::GPos), |parser| {
+                parser.parse_layout_table(GPOS_EXTENSION, Self::parse_gpos_subtable)
+            });
+        let gdef_table =
+            self.parse_optional_table(table_directory.get(&TableTag::GDef), Self::parse_gdef_table);
+        let gsub_table = self
+            .parse_optional_table(table_directory.get(&TableTag::GSub), |parser| {
+                parser.parse_layout_table(GSUB_EXTENSION, Self::parse_gsub_subtable)
+            });
+
         Ok(TrueTypeFontFile {
             font_directory,
             head_table,
@@ -111,9 +152,34 @@ impl<'a> TrueTypeFontParser<'a> {
             hmtx_table,
             cmap_table,
             glyph_table,
+            kern_table,
+            gpos_table,
+            gsub_table,
+            gdef_table,
         })
     }
 
+    /// Parses a table the font can do without. One that fails to parse is left out, so fonts
+    /// with tables in versions or formats this parser doesn't know still load. The items it
+    /// reads are bounded by its length.
+    fn parse_optional_table<T>(
+        &mut self,
+        table_record: Option<&TableRecord>,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Option<T> {
+        let table_record = table_record?;
+        self.items_left = (table_record.length as usize).saturating_mul(ITEMS_PER_TABLE_BYTE);
+
+        let parsed = self
+            .jump_to_table_record(table_record)
+            .and_then(|()| parse(self));
+        self.items_left = usize::MAX;
+
+        parsed
+            .map_err(|e| log::warn!("Skipping an optional font table: {e}"))
+            .ok()
+    }
+
     fn parse_font_directory(&mut self) -> Result<FontDirectory<'a>> {
         let offset_sub_table = OffsetSubTable {
             scalar_type: ScalarType::try_from(self.read_slice::<U32_BYTES>()?)?,
@@ -303,13 +369,11 @@ impl<'a> TrueTypeFontParser<'a> {
             for (offset, platform_doubles) in unique_offsets {
                 self.jump(cmap_offset + offset as usize, 0)?;
 
-                if let Some(subtable) = self.parse_cmap_subtable()? {
-                    if subtable_map.contains_key(&subtable) {
-                        panic!("Can cmap subtables be duplicated?");
-                    }
-
-                    subtable_map.insert(subtable, platform_doubles);
-                }
+                // Identical subtables at different offsets share their encodings.
+                subtable_map
+                    .entry(self.parse_cmap_subtable()?)
+                    .or_insert_with(Vec::new)
+                    .extend(platform_doubles);
             }
 
             subtable_map
@@ -321,24 +385,39 @@ impl<'a> TrueTypeFontParser<'a> {
     // A note about the CMap table formats:
     // Many of the cmap formats are either obsolete or were designed to meet anticipated needs which never materialized.
     // Modern font generation tools might not need to be able to write general-purpose cmaps in formats other than 4 and 12.
-    //
-    // Returns Ok(None) if that cmap subtable format is unsupported.
-    fn parse_cmap_subtable(&mut self) -> Result<Option<CMapSubtable>> {
+    fn parse_cmap_subtable(&mut self) -> Result<CMapSubtable> {
         let offset = self.cursor;
 
         let (format, length) = self.parse_cmap_subtable_format_and_length()?;
+        self.eof(length.saturating_sub(self.cursor - offset))?;
 
         let subtable = match format {
             0 => CMapSubtable::Zero(self.parse_cmap_subtable_format_0()?),
-            4 => CMapSubtable::Four(self.parse_cmap_subtable_format_4()?),
+            2 => CMapSubtable::Two(self.parse_cmap_subtable_format_2(offset + length)?),
+            4 => CMapSubtable::Four(self.parse_cmap_subtable_format_4(offset + length)?),
+            6 => CMapSubtable::Six(self.parse_cmap_subtable_format_6()?),
+            8 => CMapSubtable::Eight(self.parse_cmap_subtable_format_8()?),
+            10 => CMapSubtable::Ten(self.parse_cmap_subtable_format_10()?),
             12 => CMapSubtable::Twelve(self.parse_cmap_subtable_format_12()?),
-            2 | 6 | 8 | 10 | 13 | 14 => return Ok(None),
+            13 => CMapSubtable::Thirteen(self.parse_cmap_subtable_format_13()?),
+            14 => {
+                let subtable = CMapSubtable::Fourteen(self.parse_cmap_subtable_format_14(offset)?);
+                // The UVS tables are read through offsets, so the cursor ends up anywhere.
+                self.cursor = offset + length;
+                subtable
+            }
             foreign => bail!("Received unrecognized cmap table format: {foreign}."),
         };
 
-        ensure!(self.cursor - offset == length);
+        ensure!(
+            self.cursor - offset == length,
+            "Expected a cmap format {} subtable of {} bytes, read {}.",
+            format,
+            length,
+            self.cursor - offset
+        );
 
-        Ok(Some(subtable))
+        Ok(subtable)
     }
 
     fn parse_cmap_subtable_format_and_length(&mut self) -> Result<(u16, usize)> {
@@ -366,7 +445,39 @@ impl<'a> TrueTypeFontParser<'a> {
         })
     }
 
-    fn parse_cmap_subtable_format_4(&mut self) -> Result<CMapFormat4> {
+    fn parse_cmap_subtable_format_2(&mut self, end: usize) -> Result<CMapFormat2> {
+        let language = self.read_u16()?;
+
+        let mut subheader_keys = [0; 256];
+        for key in &mut subheader_keys {
+            *key = self.read_u16()?;
+        }
+
+        let num_subheaders = subheader_keys.iter().max().copied().unwrap_or(0) as usize / 8 + 1;
+        let subheaders = self.read_vec(num_subheaders, |parser| {
+            Ok(CMapSubHeader {
+                first_code: parser.read_u16()?,
+                entry_count: parser.read_u16()?,
+                id_delta: parser.read_i16()?,
+                id_range_offset: parser.read_u16()?,
+            })
+        })?;
+
+        ensure!(
+            end >= self.cursor,
+            "Format 2 subheaders overrun the subtable."
+        );
+        let glyph_index_array = self.read_vec((end - self.cursor) / U16_BYTES, Self::read_u16)?;
+
+        Ok(CMapFormat2 {
+            language,
+            subheader_keys,
+            subheaders,
+            glyph_index_array,
+        })
+    }
+
+    fn parse_cmap_subtable_format_4(&mut self, end: usize) -> Result<CMapFormat4> {
         let language = self.read_u16()?;
         let seg_count_x2 = self.read_u16()?;
         let search_range = self.read_u16()?;
@@ -388,11 +499,12 @@ impl<'a> TrueTypeFontParser<'a> {
 
         let id_range_offset = self.read_vec(seg_count, Self::read_u16)?;
 
-        if !id_range_offset.iter().all(|&id| id == 0) {
-            todo!("How do glyph index arrays look like when id range offsets are not zero?");
-        }
-
-        let glyph_index_array = vec![];
+        // Segments with a non-zero id range offset index into the rest of the subtable.
+        ensure!(
+            end >= self.cursor,
+            "Format 4 segments overrun the subtable."
+        );
+        let glyph_index_array = self.read_vec((end - self.cursor) / U16_BYTES, Self::read_u16)?;
 
         Ok(CMapFormat4 {
             language,
@@ -408,6 +520,43 @@ impl<'a> TrueTypeFontParser<'a> {
         })
     }
 
+    fn parse_cmap_subtable_format_6(&mut self) -> Result<CMapFormat6> {
+        let language = self.read_u16()?;
+        let first_code = self.read_u16()?;
+        let entry_count = self.read_u16()?;
+
+        Ok(CMapFormat6 {
+            language,
+            first_code,
+            glyph_index_array: self.read_vec(entry_count as usize, Self::read_u16)?,
+        })
+    }
+
+    fn parse_cmap_subtable_format_8(&mut self) -> Result<CMapFormat8> {
+        let language = self.read_u32()?;
+        let is_32 = self.read_vec(8192, Self::read_u8)?;
+        let n_groups = self.read_u32()?;
+
+        Ok(CMapFormat8 {
+            language,
+            is_32,
+            groups: self.read_vec(n_groups as usize, Self::parse_cmap_individual_group)?,
+        })
+    }
+
+    fn parse_cmap_subtable_format_10(&mut self) -> Result<CMapFormat10> {
+        let language = self.read_u32()?;
+        let start_char_code = self.read_u32()?;
+        let num_chars = self.read_u32()?;
+
+        Ok(CMapFormat10 {
+            language,
+            start_char_code,
+            num_chars,
+            glyphs: self.read_vec(num_chars as usize, Self::read_u16)?,
+        })
+    }
+
     fn parse_cmap_subtable_format_12(&mut self) -> Result<CMapFormat12> {
         let language = self.read_u32()?;
         let n_groups = self.read_u32()?;
@@ -418,6 +567,79 @@ impl<'a> TrueTypeFontParser<'a> {
         })
     }
 
+    fn parse_cmap_subtable_format_13(&mut self) -> Result<CMapFormat13> {
+        let language = self.read_u32()?;
+        let n_groups = self.read_u32()?;
+
+        Ok(CMapFormat13 {
+            language,
+            groups: self.read_vec(n_groups as usize, Self::parse_cmap_individual_group)?,
+        })
+    }
+
+    /// `offset` is where the subtable starts, which the UVS table offsets are relative to.
+    fn parse_cmap_subtable_format_14(&mut self, offset: usize) -> Result<CMapFormat14> {
+        let num_records = self.read_u32()?;
+
+        let records = self.read_vec(num_records as usize, |parser| {
+            Ok((parser.read_u24()?, parser.read_u32()?, parser.read_u32()?))
+        })?;
+
+        let records = records
+            .into_iter()
+            .map(
+                |(variation_selector, default_uvs_offset, non_default_uvs_offset)| {
+                    let default_uvs = match default_uvs_offset {
+                        0 => None,
+                        uvs_offset => {
+                            self.jump(offset + uvs_offset as usize, U32_BYTES)?;
+                            let num_ranges = self.read_u32()?;
+
+                            Some(DefaultUVSTable {
+                                unicode_value_ranges: self.read_vec(
+                                    num_ranges as usize,
+                                    |parser| {
+                                        Ok(UnicodeValueRange {
+                                            start_unicode_value: parser.read_u24()?,
+                                            additional_count: parser.read_u8()?,
+                                        })
+                                    },
+                                )?,
+                            })
+                        }
+                    };
+
+                    let non_default_uvs = match non_default_uvs_offset {
+                        0 => None,
+                        uvs_offset => {
+                            self.jump(offset + uvs_offset as usize, U32_BYTES)?;
+                            let num_mappings = self.read_u32()?;
+
+                            Some(NonDefaultUVSTable {
+                                uvs_mappings: self.read_vec(num_mappings as usize, |parser| {
+                                    Ok(UnicodeValueMap {
+                                        unicode_value: parser.read_u24()?,
+                                        glyph_id: parser.read_u16()?,
+                                    })
+                                })?,
+                            })
+                        }
+                    };
+
+                    Ok(VariationSelectorRecord {
+                        variation_selector,
+                        default_uvs_offset,
+                        non_default_uvs_offset,
+                        default_uvs,
+                        non_default_uvs,
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(CMapFormat14 { records })
+    }
+
     fn parse_cmap_individual_group(&mut self) -> Result<CMapIndividualGroup> {
         Ok(CMapIndividualGroup {
             start_char_code: self.read_u32()?,
@@ -438,7 +660,7 @@ impl<'a> TrueTypeFontParser<'a> {
 
             // https://github.com/khaledhosny/ots/issues/120
             if glyph_length == 0 {
-                glyphs.push(glyphs[0].clone());
+                glyphs.push(Glyph::empty());
                 continue;
             }
 
@@ -447,7 +669,7 @@ impl<'a> TrueTypeFontParser<'a> {
             let description = self.parse_glyph_description()?;
 
             if description.number_of_contours == 0 {
-                glyphs.push(glyphs[0].clone());
+                glyphs.push(Glyph::empty());
                 continue;
             }
 
@@ -611,6 +833,777 @@ impl<'a> TrueTypeFontParser<'a> {
         Ok(CompoundGlyph { components })
     }
 
+    fn parse_kern_table(&mut self) -> Result<KernTable> {
+        let version = self.read_u16()?;
+
+        let subtables = if version == 0 {
+            let num_tables = self.read_u16()?;
+
+            self.read_vec(num_tables as usize, |parser| {
+                let start = parser.cursor;
+                let _version = parser.read_u16()?;
+                // Large format 0 subtables overflow this, so it's only used to skip the others.
+                let length = parser.read_u16()? as usize;
+                let coverage = parser.read_u16()?;
+
+                let data = parser.parse_kern_data(coverage >> 8, start)?;
+                if coverage >> 8 != 0 {
+                    ensure!(length >= 6, "Kern subtable length {} is too short.", length);
+                    parser.cursor = start + length;
+                }
+
+                Ok(data.map(|data| KernSubtable {
+                    horizontal: coverage & 0x1 != 0,
+                    minimum: coverage & 0x2 != 0,
+                    cross_stream: coverage & 0x4 != 0,
+                    override_accumulator: coverage & 0x8 != 0,
+                    data,
+                }))
+            })?
+        } else {
+            // Apple's version 1.0, a fixed where Microsoft's has two shorts.
+            ensure!(
+                version == 1 && self.read_u16()? == 0,
+                "Unknown kern table version {}.",
+                version
+            );
+            let num_tables = self.read_u32()?;
+
+            self.read_vec(num_tables as usize, |parser| {
+                let start = parser.cursor;
+                let length = parser.read_u32()? as usize;
+                let coverage = parser.read_u16()?;
+                let _tuple_index = parser.read_u16()?;
+                ensure!(length >= 8, "Kern subtable length {} is too short.", length);
+
+                let data = parser.parse_kern_data(coverage & 0xFF, start)?;
+                parser.cursor = start + length;
+
+                Ok(data.map(|data| KernSubtable {
+                    horizontal: coverage & 0x8000 == 0,
+                    minimum: false,
+                    // Variation subtables need a variation axis to apply.
+                    cross_stream: coverage & 0x6000 != 0,
+                    override_accumulator: false,
+                    data,
+                }))
+            })?
+        };
+
+        Ok(KernTable {
+            subtables: subtables.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Parses the body of a kern subtable starting at `start`, after its header. Formats other
+    /// than 0 and 2, like Apple's state tables, give `None` and are skipped.
+    fn parse_kern_data(&mut self, format: u16, start: usize) -> Result<Option<KernData>> {
+        Ok(Some(match format {
+            0 => {
+                let num_pairs = self.read_u16()?;
+                let _search_range = self.read_u16()?;
+                let _entry_selector = self.read_u16()?;
+                let _range_shift = self.read_u16()?;
+
+                let mut pairs = self.read_vec(num_pairs as usize, |parser| {
+                    Ok(KernPair {
+                        left: parser.read_u16()?,
+                        right: parser.read_u16()?,
+                        value: parser.read_fword()?,
+                    })
+                })?;
+                pairs.sort_unstable_by_key(|pair| (pair.left, pair.right));
+
+                KernData::Pairs(pairs)
+            }
+            2 => {
+                let _row_width = self.read_u16()?;
+                let left_offset = self.read_u16()? as usize;
+                let right_offset = self.read_u16()? as usize;
+                let _array_offset = self.read_u16()?;
+
+                let mut left = self.parse_at(start + left_offset, Self::parse_kern_class_table)?;
+                let mut right =
+                    self.parse_at(start + right_offset, Self::parse_kern_class_table)?;
+
+                // Left classes are offsets to a row and right classes offsets within it, both
+                // from the start of the subtable. Number the distinct ones instead.
+                let rows = Self::number_kern_classes(&mut left);
+                let columns = Self::number_kern_classes(&mut right);
+                ensure!(
+                    rows.len() * columns.len() * U16_BYTES <= self.data.len() - start,
+                    "Kern class array of {}x{} values is out of bounds.",
+                    rows.len(),
+                    columns.len()
+                );
+
+                let mut values = Vec::with_capacity(rows.len() * columns.len());
+                for row in &rows {
+                    for column in &columns {
+                        let offset = start + *row as usize + *column as usize;
+                        values.push(self.parse_at(offset, Self::read_i16)?);
+                    }
+                }
+
+                KernData::Classes {
+                    left,
+                    right,
+                    values,
+                }
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    fn parse_kern_class_table(&mut self) -> Result<KernClassTable> {
+        let first_glyph = self.read_u16()?;
+        let num_glyphs = self.read_u16()?;
+
+        Ok(KernClassTable {
+            first_glyph,
+            classes: self.read_vec(num_glyphs as usize, Self::read_u16)?,
+            class_count: 0,
+        })
+    }
+
+    /// Replaces the offsets in `table` by their rank and returns the offsets in order.
+    fn number_kern_classes(table: &mut KernClassTable) -> Vec<u16> {
+        let mut offsets = table.classes.clone();
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        for class in &mut table.classes {
+            *class = offsets.partition_point(|&offset| offset < *class) as u16;
+        }
+        table.class_count = offsets.len() as u16;
+
+        offsets
+    }
+
+    /// Parses GPOS or GSUB, with `parse_subtable` parsing a subtable of the given lookup type.
+    /// Subtables of `extension_type` point to the actual subtable and are resolved here.
+    fn parse_layout_table<S>(
+        &mut self,
+        extension_type: u16,
+        parse_subtable: impl Fn(&mut Self, u16) -> Result<S> + Copy,
+    ) -> Result<LayoutTable<S>> {
+        let start = self.cursor;
+
+        let major_version = self.read_u16()?;
+        let _minor_version = self.read_u16()?;
+        ensure!(
+            major_version == 1,
+            "Unknown layout table version {}.",
+            major_version
+        );
+
+        let script_list_offset = self.read_u16()? as usize;
+        let feature_list_offset = self.read_u16()? as usize;
+        let lookup_list_offset = self.read_u16()? as usize;
+
+        Ok(LayoutTable {
+            scripts: self.parse_at(start + script_list_offset, Self::parse_script_list)?,
+            features: self.parse_at(start + feature_list_offset, Self::parse_feature_list)?,
+            lookups: self.parse_at(start + lookup_list_offset, |parser| {
+                let start = parser.cursor;
+                let lookup_count = parser.read_u16()?;
+                let offsets = parser.read_vec(lookup_count as usize, Self::read_u16)?;
+
+                offsets
+                    .into_iter()
+                    .map(|offset| {
+                        parser.parse_at(start + offset as usize, |parser| {
+                            parser.parse_lookup(extension_type, parse_subtable)
+                        })
+                    })
+                    .collect()
+            })?,
+        })
+    }
+
+    fn parse_script_list(&mut self) -> Result<Vec<ScriptRecord>> {
+        let start = self.cursor;
+        let script_count = self.read_u16()?;
+
+        self.read_vec(script_count as usize, |parser| {
+            let tag = *parser.read_slice::<U32_BYTES>()?;
+            let offset = parser.read_u16()? as usize;
+
+            Ok(ScriptRecord {
+                tag,
+                script: parser.parse_at(start + offset, Self::parse_script)?,
+            })
+        })
+    }
+
+    fn parse_script(&mut self) -> Result<Script> {
+        let start = self.cursor;
+        let default_lang_sys_offset = self.read_u16()? as usize;
+        let lang_sys_count = self.read_u16()?;
+
+        let lang_sys_records = self.read_vec(lang_sys_count as usize, |parser| {
+            let tag = *parser.read_slice::<U32_BYTES>()?;
+            let offset = parser.read_u16()? as usize;
+
+            Ok(LangSysRecord {
+                tag,
+                lang_sys: parser.parse_at(start + offset, Self::parse_lang_sys)?,
+            })
+        })?;
+
+        let default_lang_sys = match default_lang_sys_offset {
+            0 => None,
+            offset => Some(self.parse_at(start + offset, Self::parse_lang_sys)?),
+        };
+
+        Ok(Script {
+            default_lang_sys,
+            lang_sys_records,
+        })
+    }
+
+    fn parse_lang_sys(&mut self) -> Result<LangSys> {
+        let _lookup_order_offset = self.read_u16()?;
+        let required_feature_index = self.read_u16()?;
+        let feature_index_count = self.read_u16()?;
+
+        Ok(LangSys {
+            required_feature_index: (required_feature_index != 0xFFFF)
+                .then_some(required_feature_index),
+            feature_indices: self.read_vec(feature_index_count as usize, Self::read_u16)?,
+        })
+    }
+
+    fn parse_feature_list(&mut self) -> Result<Vec<FeatureRecord>> {
+        let start = self.cursor;
+        let feature_count = self.read_u16()?;
+
+        self.read_vec(feature_count as usize, |parser| {
+            let tag = *parser.read_slice::<U32_BYTES>()?;
+            let offset = parser.read_u16()? as usize;
+
+            let lookup_indices = parser.parse_at(start + offset, |parser| {
+                let _feature_params_offset = parser.read_u16()?;
+                let lookup_index_count = parser.read_u16()?;
+                parser.read_vec(lookup_index_count as usize, Self::read_u16)
+            })?;
+
+            Ok(FeatureRecord {
+                tag,
+                lookup_indices,
+            })
+        })
+    }
+
+    fn parse_lookup<S>(
+        &mut self,
+        extension_type: u16,
+        parse_subtable: impl Fn(&mut Self, u16) -> Result<S>,
+    ) -> Result<Lookup<S>> {
+        let start = self.cursor;
+        let lookup_type = self.read_u16()?;
+        let lookup_flag = self.read_u16()?;
+        let subtable_count = self.read_u16()?;
+        let offsets = self.read_vec(subtable_count as usize, Self::read_u16)?;
+
+        let mark_filtering_set = if lookup_flag & USE_MARK_FILTERING_SET != 0 {
+            Some(self.read_u16()?)
+        } else {
+            None
+        };
+
+        let subtables = offsets
+            .into_iter()
+            .map(|offset| {
+                self.parse_at(start + offset as usize, |parser| {
+                    if lookup_type != extension_type {
+                        return parse_subtable(parser, lookup_type);
+                    }
+
+                    let start = parser.cursor;
+                    let _format = parser.read_u16()?;
+                    let extension_lookup_type = parser.read_u16()?;
+                    let offset = parser.read_u32()? as usize;
+
+                    parser.parse_at(start + offset, |parser| {
+                        parse_subtable(parser, extension_lookup_type)
+                    })
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Lookup {
+            lookup_flag,
+            subtables,
+            mark_filtering_set,
+        })
+    }
+
+    fn parse_coverage(&mut self) -> Result<Coverage> {
+        let format = self.read_u16()?;
+        let count = self.read_u16()? as usize;
+
+        match format {
+            1 => Ok(Coverage::Glyphs(self.read_vec(count, Self::read_u16)?)),
+            2 => Ok(Coverage::Ranges(self.read_vec(count, |parser| {
+                Ok(RangeRecord {
+                    start_glyph: parser.read_u16()?,
+                    end_glyph: parser.read_u16()?,
+                    start_coverage_index: parser.read_u16()?,
+                })
+            })?)),
+            _ => bail!("Unknown coverage format {}.", format),
+        }
+    }
+
+    fn parse_class_def(&mut self) -> Result<ClassDef> {
+        match self.read_u16()? {
+            1 => {
+                let start_glyph = self.read_u16()?;
+                let glyph_count = self.read_u16()?;
+
+                Ok(ClassDef::Glyphs {
+                    start_glyph,
+                    classes: self.read_vec(glyph_count as usize, Self::read_u16)?,
+                })
+            }
+            2 => {
+                let class_range_count = self.read_u16()?;
+
+                Ok(ClassDef::Ranges(self.read_vec(
+                    class_range_count as usize,
+                    |parser| {
+                        Ok(ClassRangeRecord {
+                            start_glyph: parser.read_u16()?,
+                            end_glyph: parser.read_u16()?,
+                            class: parser.read_u16()?,
+                        })
+                    },
+                )?))
+            }
+            format => bail!("Unknown class definition format {}.", format),
+        }
+    }
+
+    fn parse_gdef_table(&mut self) -> Result<GdefTable> {
+        let start = self.cursor;
+
+        let major_version = self.read_u16()?;
+        let minor_version = self.read_u16()?;
+        ensure!(
+            major_version == 1,
+            "Unknown GDEF table version {}.",
+            major_version
+        );
+
+        let glyph_class_def_offset = self.read_u16()? as usize;
+        let _attach_list_offset = self.read_u16()?;
+        let _lig_caret_list_offset = self.read_u16()?;
+        let mark_attach_class_def_offset = self.read_u16()? as usize;
+        let mark_glyph_sets_def_offset = if minor_version >= 2 {
+            self.read_u16()? as usize
+        } else {
+            0
+        };
+
+        let mark_glyph_sets =
+            self.parse_optional_at(start, mark_glyph_sets_def_offset, |parser| {
+                let start = parser.cursor;
+                let _format = parser.read_u16()?;
+                let mark_glyph_set_count = parser.read_u16()?;
+                let offsets = parser.read_vec(mark_glyph_set_count as usize, Self::read_u32)?;
+
+                offsets
+                    .into_iter()
+                    .map(|offset| parser.parse_at(start + offset as usize, Self::parse_coverage))
+                    .collect::<Result<Vec<_>>>()
+            })?;
+
+        Ok(GdefTable {
+            glyph_class_def: self.parse_optional_at(
+                start,
+                glyph_class_def_offset,
+                Self::parse_class_def,
+            )?,
+            mark_attach_class_def: self.parse_optional_at(
+                start,
+                mark_attach_class_def_offset,
+                Self::parse_class_def,
+            )?,
+            mark_glyph_sets: mark_glyph_sets.unwrap_or_default(),
+        })
+    }
+
+    fn parse_gpos_subtable(&mut self, lookup_type: u16) -> Result<GposSubtable> {
+        let format = self.parse_at(self.cursor, Self::read_u16)?;
+
+        Ok(match (lookup_type, format) {
+            (2, 1 | 2) => GposSubtable::PairAdjustment(self.parse_pair_adjustment()?),
+            (3, 1) => GposSubtable::Cursive(self.parse_cursive_attachment()?),
+            (4, 1) => GposSubtable::MarkToBase(self.parse_mark_attachment()?),
+            (5, 1) => GposSubtable::MarkToLigature(self.parse_mark_to_ligature()?),
+            (6, 1) => GposSubtable::MarkToMark(self.parse_mark_attachment()?),
+            _ => GposSubtable::Unsupported { lookup_type },
+        })
+    }
+
+    /// Only the coordinates of any anchor format are read. Contour points and device tables
+    /// refine them for hinting and specific sizes.
+    fn parse_anchor(&mut self) -> Result<Anchor> {
+        let format = self.read_u16()?;
+        ensure!(
+            (1..=3).contains(&format),
+            "Unknown anchor format {}.",
+            format
+        );
+
+        Ok(Anchor {
+            x: self.read_i16()?,
+            y: self.read_i16()?,
+        })
+    }
+
+    fn parse_mark_array(&mut self) -> Result<Vec<MarkRecord>> {
+        let start = self.cursor;
+        let mark_count = self.read_u16()?;
+
+        self.read_vec(mark_count as usize, |parser| {
+            let mark_class = parser.read_u16()?;
+            let anchor_offset = parser.read_u16()? as usize;
+
+            Ok(MarkRecord {
+                mark_class,
+                anchor: parser.parse_at(start + anchor_offset, Self::parse_anchor)?,
+            })
+        })
+    }
+
+    /// Reads `count` rows of `mark_class_count` anchor offsets from `start`, which may be null.
+    fn parse_anchor_matrix(
+        &mut self,
+        start: usize,
+        count: usize,
+        mark_class_count: usize,
+    ) -> Result<Vec<Vec<Option<Anchor>>>> {
+        let offsets = self.read_vec(count * mark_class_count, Self::read_u16)?;
+
+        offsets
+            .chunks(mark_class_count.max(1))
+            .map(|row| {
+                row.iter()
+                    .map(|&offset| {
+                        self.parse_optional_at(start, offset as usize, Self::parse_anchor)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Mark-to-base and mark-to-mark subtables, which share a layout.
+    fn parse_mark_attachment(&mut self) -> Result<MarkAttachment> {
+        let start = self.cursor;
+        let _format = self.read_u16()?;
+
+        let mark_coverage_offset = self.read_u16()? as usize;
+        let base_coverage_offset = self.read_u16()? as usize;
+        let mark_class_count = self.read_u16()? as usize;
+        let mark_array_offset = self.read_u16()? as usize;
+        let base_array_offset = self.read_u16()? as usize;
+
+        Ok(MarkAttachment {
+            mark_coverage: self.parse_at(start + mark_coverage_offset, Self::parse_coverage)?,
+            base_coverage: self.parse_at(start + base_coverage_offset, Self::parse_coverage)?,
+            marks: self.parse_at(start + mark_array_offset, Self::parse_mark_array)?,
+            base_anchors: self.parse_at(start + base_array_offset, |parser| {
+                let start = parser.cursor;
+                let base_count = parser.read_u16()?;
+                parser.parse_anchor_matrix(start, base_count as usize, mark_class_count)
+            })?,
+        })
+    }
+
+    fn parse_mark_to_ligature(&mut self) -> Result<MarkToLigature> {
+        let start = self.cursor;
+        let _format = self.read_u16()?;
+
+        let mark_coverage_offset = self.read_u16()? as usize;
+        let ligature_coverage_offset = self.read_u16()? as usize;
+        let mark_class_count = self.read_u16()? as usize;
+        let mark_array_offset = self.read_u16()? as usize;
+        let ligature_array_offset = self.read_u16()? as usize;
+
+        Ok(MarkToLigature {
+            mark_coverage: self.parse_at(start + mark_coverage_offset, Self::parse_coverage)?,
+            ligature_coverage: self
+                .parse_at(start + ligature_coverage_offset, Self::parse_coverage)?,
+            marks: self.parse_at(start + mark_array_offset, Self::parse_mark_array)?,
+            ligature_anchors: self.parse_at(start + ligature_array_offset, |parser| {
+                let start = parser.cursor;
+                let ligature_count = parser.read_u16()?;
+                let offsets = parser.read_vec(ligature_count as usize, Self::read_u16)?;
+
+                offsets
+                    .into_iter()
+                    .map(|offset| {
+                        parser.parse_at(start + offset as usize, |parser| {
+                            let start = parser.cursor;
+                            let component_count = parser.read_u16()?;
+                            parser.parse_anchor_matrix(
+                                start,
+                                component_count as usize,
+                                mark_class_count,
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })?,
+        })
+    }
+
+    fn parse_cursive_attachment(&mut self) -> Result<CursiveAttachment> {
+        let start = self.cursor;
+        let _format = self.read_u16()?;
+
+        let coverage_offset = self.read_u16()? as usize;
+        let entry_exit_count = self.read_u16()?;
+
+        let entry_exits = self.read_vec(entry_exit_count as usize, |parser| {
+            let entry_offset = parser.read_u16()? as usize;
+            let exit_offset = parser.read_u16()? as usize;
+
+            Ok(EntryExitRecord {
+                entry: parser.parse_optional_at(start, entry_offset, Self::parse_anchor)?,
+                exit: parser.parse_optional_at(start, exit_offset, Self::parse_anchor)?,
+            })
+        })?;
+
+        Ok(CursiveAttachment {
+            coverage: self.parse_at(start + coverage_offset, Self::parse_coverage)?,
+            entry_exits,
+        })
+    }
+
+    fn parse_gsub_subtable(&mut self, lookup_type: u16) -> Result<GsubSubtable> {
+        let start = self.cursor;
+        let format = self.read_u16()?;
+        let coverage_offset = self.read_u16()? as usize;
+
+        if !matches!((lookup_type, format), (1, 1 | 2) | (2..=4, 1)) {
+            return Ok(GsubSubtable::Unsupported { lookup_type });
+        }
+
+        let coverage = self.parse_at(start + coverage_offset, Self::parse_coverage)?;
+
+        if lookup_type == 1 {
+            return Ok(GsubSubtable::Single(if format == 1 {
+                SingleSubstitution::Delta {
+                    coverage,
+                    delta_glyph_id: self.read_i16()?,
+                }
+            } else {
+                let glyph_count = self.read_u16()?;
+                SingleSubstitution::Glyphs {
+                    coverage,
+                    substitutes: self.read_vec(glyph_count as usize, Self::read_u16)?,
+                }
+            }));
+        }
+
+        // Multiple, alternate and ligature substitutions all list a set for every glyph in
+        // the coverage.
+        let set_count = self.read_u16()?;
+        let offsets = self.read_vec(set_count as usize, Self::read_u16)?;
+        let sets = offsets.into_iter().map(|offset| start + offset as usize);
+
+        match lookup_type {
+            2 | 3 => {
+                let sets = sets
+                    .map(|offset| {
+                        self.parse_at(offset, |parser| {
+                            let glyph_count = parser.read_u16()?;
+                            parser.read_vec(glyph_count as usize, Self::read_u16)
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(if lookup_type == 2 {
+                    GsubSubtable::Multiple {
+                        coverage,
+                        sequences: sets,
+                    }
+                } else {
+                    GsubSubtable::Alternate {
+                        coverage,
+                        alternate_sets: sets,
+                    }
+                })
+            }
+            _ => {
+                let ligature_sets = sets
+                    .map(|offset| self.parse_at(offset, Self::parse_ligature_set))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(GsubSubtable::Ligature {
+                    coverage,
+                    ligature_sets,
+                })
+            }
+        }
+    }
+
+    fn parse_ligature_set(&mut self) -> Result<Vec<Ligature>> {
+        let start = self.cursor;
+        let ligature_count = self.read_u16()?;
+        let offsets = self.read_vec(ligature_count as usize, Self::read_u16)?;
+
+        offsets
+            .into_iter()
+            .map(|offset| {
+                self.parse_at(start + offset as usize, |parser| {
+                    let ligature_glyph = parser.read_u16()?;
+                    let component_count = parser.read_u16()?;
+
+                    Ok(Ligature {
+                        ligature_glyph,
+                        components: parser
+                            .read_vec(component_count.saturating_sub(1) as usize, Self::read_u16)?,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    fn parse_pair_adjustment(&mut self) -> Result<PairAdjustment> {
+        let start = self.cursor;
+        let format = self.read_u16()?;
+        let coverage_offset = self.read_u16()? as usize;
+        let value_format_1 = self.read_u16()?;
+        let value_format_2 = self.read_u16()?;
+
+        let coverage = self.parse_at(start + coverage_offset, Self::parse_coverage)?;
+        let adjusts_second = value_format_2 != 0;
+
+        let read_values = |parser: &mut Self| {
+            Ok([
+                parser.parse_value_record(value_format_1)?,
+                parser.parse_value_record(value_format_2)?,
+            ])
+        };
+
+        match format {
+            1 => {
+                let pair_set_count = self.read_u16()?;
+                let offsets = self.read_vec(pair_set_count as usize, Self::read_u16)?;
+
+                let pair_sets = offsets
+                    .into_iter()
+                    .map(|offset| {
+                        self.parse_at(start + offset as usize, |parser| {
+                            let pair_value_count = parser.read_u16()?;
+                            parser.read_vec(pair_value_count as usize, |parser| {
+                                Ok(PairValueRecord {
+                                    second_glyph: parser.read_u16()?,
+                                    values: read_values(parser)?,
+                                })
+                            })
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(PairAdjustment::Glyphs {
+                    coverage,
+                    adjusts_second,
+                    pair_sets,
+                })
+            }
+            2 => {
+                let class_def_1_offset = self.read_u16()? as usize;
+                let class_def_2_offset = self.read_u16()? as usize;
+                let class_1_count = self.read_u16()?;
+                let class_2_count = self.read_u16()?;
+
+                // Value records are empty when neither value format has a bit set.
+                let record_count = class_1_count as usize * class_2_count as usize;
+                let record_size = (value_format_1.count_ones() + value_format_2.count_ones())
+                    as usize
+                    * U16_BYTES;
+                ensure!(
+                    record_count * record_size.max(1) <= self.data.len() - self.cursor,
+                    "Pair adjustment of {}x{} classes is out of bounds.",
+                    class_1_count,
+                    class_2_count
+                );
+
+                let records = self.read_vec(record_count, read_values)?;
+
+                Ok(PairAdjustment::Classes {
+                    coverage,
+                    adjusts_second,
+                    class_def_1: self
+                        .parse_at(start + class_def_1_offset, Self::parse_class_def)?,
+                    class_def_2: self
+                        .parse_at(start + class_def_2_offset, Self::parse_class_def)?,
+                    class_2_count,
+                    records,
+                })
+            }
+            _ => bail!("Unknown pair adjustment format {}.", format),
+        }
+    }
+
+    fn parse_value_record(&mut self, value_format: u16) -> Result<ValueRecord> {
+        let mut read = |bit: u16| {
+            if value_format & bit != 0 {
+                self.read_i16()
+            } else {
+                Ok(0)
+            }
+        };
+
+        let value_record = ValueRecord {
+            x_placement: read(0x1)?,
+            y_placement: read(0x2)?,
+            x_advance: read(0x4)?,
+            y_advance: read(0x8)?,
+        };
+
+        // Offsets to device and variation tables follow.
+        for _ in 0..(value_format & 0xF0).count_ones() {
+            self.read_u16()?;
+        }
+
+        Ok(value_record)
+    }
+
+    /// Like `parse_at` for the table `offset` past `start`, where an offset of 0 means there is
+    /// none.
+    fn parse_optional_at<T>(
+        &mut self,
+        start: usize,
+        offset: usize,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<Option<T>> {
+        match offset {
+            0 => Ok(None),
+            offset => self.parse_at(start + offset, parse).map(Some),
+        }
+    }
+
+    /// Runs `parse` at `offset` and returns to where the cursor was.
+    fn parse_at<T>(
+        &mut self,
+        offset: usize,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        ensure!(offset < self.data.len(), "Offset is out of bounds.");
+
+        let cursor = std::mem::replace(&mut self.cursor, offset);
+        let parsed = parse(self);
+        self.cursor = cursor;
+
+        parsed
+    }
+
     eof!();
 
     fn jump_to_table_record(&mut self, table_record: &TableRecord) -> Result<()> {
@@ -626,14 +1619,22 @@ impl<'a> TrueTypeFontParser<'a> {
         Ok(())
     }
 
+    /// Reads `count` items. Counts come from the file, so no more room is reserved than the
+    /// bytes left could fill. Items that read nothing need their count checked by the caller.
     fn read_vec<T>(
         &mut self,
-        capacity: usize,
+        count: usize,
         read_fn: impl Fn(&mut Self) -> Result<T>,
     ) -> Result<Vec<T>> {
-        let mut list = Vec::with_capacity(capacity);
+        ensure!(
+            count <= self.items_left,
+            "Table reads more items than its length allows."
+        );
+        self.items_left -= count;
+
+        let mut list = Vec::with_capacity(count.min(self.data.len().saturating_sub(self.cursor)));
 
-        for _ in 0..capacity {
+        for _ in 0..count {
             list.push(read_fn(self)?)
         }
 
@@ -648,6 +1649,12 @@ impl<'a> TrueTypeFontParser<'a> {
         Ok(bs.try_into()?)
     }
 
+    fn read_u24(&mut self) -> Result<u32> {
+        let [a, b, c] = *self.read_slice::<3>()?;
+
+        Ok(u32::from_be_bytes([0, a, b, c]))
+    }
+
     // read!(read_short_frac, ShortFrac, U16_BYTES);
     read!(read_fixed, Fixed, U32_BYTES);
     read!(read_fword, FWord, U16_BYTES);
@@ -665,12 +1672,398 @@ impl<'a> TrueTypeFontParser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::font::grammar::Variation;
     use std::fs;
 
     #[test]
     fn test_parse_lato() -> Result<()> {
         let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
-        let _ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        assert!(matches!(
+            ttf.cmap_table.best_subtable(),
+            Some(CMapSubtable::Four(_))
+        ));
+        assert_ne!(ttf.cmap_table.glyph_index('A'), 0);
+        assert_eq!(ttf.cmap_table.glyph_index('\u{1F600}'), 0);
+
+        Ok(())
+    }
+
+    fn words(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_format_4_id_range_offset() -> Result<()> {
+        #[rustfmt::skip]
+        let subtable = words(&[
+            4, 38, 0, // format, length, language
+            4, 4, 1, 0, // seg count x2, search range, entry selector, range shift
+            0x43, 0xFFFF, // end codes
+            0, // reserved
+            0x41, 0xFFFF, // start codes
+            5, 1, // id deltas
+            4, 0, // id range offsets, the first pointing past the second
+            10, 0, 12, // glyph index array
+        ]);
+
+        let CMapSubtable::Four(cmap) = TrueTypeFontParser::new(&subtable).parse_cmap_subtable()?
+        else {
+            panic!("Expected a format 4 subtable.");
+        };
+
+        assert_eq!(cmap.glyph_index_array, [10, 0, 12]);
+        // The delta only applies to glyphs the array maps.
+        assert_eq!(cmap.find_glyph_index('A' as u32), 15);
+        assert_eq!(cmap.find_glyph_index('B' as u32), 0);
+        assert_eq!(cmap.find_glyph_index('C' as u32), 17);
+        assert_eq!(cmap.find_glyph_index('D' as u32), 0);
+        assert_eq!(cmap.find_glyph_index(0x1F600), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_14_variation_sequences() -> Result<()> {
+        let mut subtable = vec![];
+        subtable.extend(14u16.to_be_bytes());
+        subtable.extend(38u32.to_be_bytes());
+        subtable.extend(1u32.to_be_bytes());
+        // U+FE0F with a default UVS table at 21 and a non-default one at 29.
+        subtable.extend([0x00, 0xFE, 0x0F]);
+        subtable.extend(21u32.to_be_bytes());
+        subtable.extend(29u32.to_be_bytes());
+        // U+2764 alone.
+        subtable.extend(1u32.to_be_bytes());
+        subtable.extend([0x00, 0x27, 0x64, 0]);
+        // U+263A to glyph 7.
+        subtable.extend(1u32.to_be_bytes());
+        subtable.extend([0x00, 0x26, 0x3A, 0, 7]);
+
+        let mut parser = TrueTypeFontParser::new(&subtable);
+        let CMapSubtable::Fourteen(cmap) = parser.parse_cmap_subtable()? else {
+            panic!("Expected a format 14 subtable.");
+        };
+        assert_eq!(parser.cursor, subtable.len());
+
+        assert_eq!(
+            cmap.find_variation(0x2764, 0xFE0F),
+            Some(Variation::Default)
+        );
+        assert_eq!(
+            cmap.find_variation(0x263A, 0xFE0F),
+            Some(Variation::Glyph(7))
+        );
+        assert_eq!(cmap.find_variation(0x41, 0xFE0F), None);
+        assert_eq!(cmap.find_variation(0x263A, 0xFE0E), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kern_formats_0_and_2() -> Result<()> {
+        #[rustfmt::skip]
+        let format_0 = words(&[
+            0, 1, // version, number of tables
+            0, 26, 0x0001, // version, length, coverage
+            2, 12, 1, 0, // pairs, search range, entry selector, range shift
+            3, 4, -50i16 as u16,
+            3, 5, 20,
+        ]);
+
+        let kern = TrueTypeFontParser::new(&format_0).parse_kern_table()?;
+        assert_eq!(kern.kerning(3, 4), -50);
+        assert_eq!(kern.kerning(3, 5), 20);
+        assert_eq!(kern.kerning(4, 3), 0);
+
+        #[rustfmt::skip]
+        let format_2 = words(&[
+            0, 1,
+            0, 38, 0x0201,
+            4, 14, 22, 30, // row width, left and right class tables, array
+            10, 2, 30, 34, // left classes, as offsets to a row
+            20, 2, 0, 2, // right classes, as offsets within one
+            0, -30i16 as u16,
+            15, 40,
+        ]);
+
+        let kern = TrueTypeFontParser::new(&format_2).parse_kern_table()?;
+        assert_eq!(kern.kerning(10, 20), 0);
+        assert_eq!(kern.kerning(10, 21), -30);
+        assert_eq!(kern.kerning(11, 20), 15);
+        assert_eq!(kern.kerning(11, 21), 40);
+        assert_eq!(kern.kerning(12, 20), 0);
+        assert_eq!(kern.kerning(10, 19), 0);
+
+        // Apple's format 1 state table is skipped over by its length.
+        #[rustfmt::skip]
+        let apple = words(&[
+            1, 0, 0, 2, // version 1.0, number of tables
+            0, 12, 0x0001, 0, // length, coverage, tuple index
+            0xFFFF, 0xFFFF,
+            0, 22, 0x0000, 0,
+            1, 6, 0, 0,
+            3, 4, -50i16 as u16,
+        ]);
+
+        let kern = TrueTypeFontParser::new(&apple).parse_kern_table()?;
+        assert_eq!(kern.subtables.len(), 1);
+        assert_eq!(kern.kerning(3, 4), -50);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_past_the_end_fail() {
+        // A format 13 cmap subtable claiming four billion groups.
+        let cmap = words(&[0, 0, 0xFFFF, 0xFFFF]);
+        assert!(TrueTypeFontParser::new(&cmap)
+            .parse_cmap_subtable_format_13()
+            .is_err());
+
+        // Pair adjustment classes with empty value records.
+        let pair_adjustment = words(&[2, 16, 0, 0, 0, 0, 0xFFFF, 0xFFFF, 1, 0]);
+        assert!(TrueTypeFontParser::new(&pair_adjustment)
+            .parse_gpos_subtable(2)
+            .is_err());
+
+        // A mark to base subtable with as many bases and mark classes as a u16 holds.
+        let mark_attachment = words(&[1, 12, 12, 0xFFFF, 16, 18, 1, 0, 0, 0xFFFF]);
+        assert!(TrueTypeFontParser::new(&mark_attachment)
+            .parse_gpos_subtable(4)
+            .is_err());
+    }
+
+    #[test]
+    fn test_layout_offsets_fanning_out_fail() -> Result<()> {
+        // Raising the script count in DejaVu's GPOS reads script records out of the rest of
+        // the table, whose offsets lead to more lists read out of unrelated data.
+        let mut ttf_file = fs::read("./tests/fixtures/DejaVuSans-ExtraLight.ttf")?;
+        ttf_file[506] = 0x08;
+
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        assert!(ttf.gpos_table.is_none());
+        assert!(ttf.gsub_table.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_formats_are_skipped() -> Result<()> {
+        // A mark to base subtable in format 2 and a single substitution in format 3.
+        let subtable = words(&[2, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            TrueTypeFontParser::new(&subtable).parse_gpos_subtable(4)?,
+            GposSubtable::Unsupported { lookup_type: 4 }
+        );
+
+        let subtable = words(&[3, 0, 0]);
+        assert_eq!(
+            TrueTypeFontParser::new(&subtable).parse_gsub_subtable(1)?,
+            GsubSubtable::Unsupported { lookup_type: 1 }
+        );
+
+        // A GPOS table of an unknown version leaves the font without one.
+        let mut ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let gpos_offset = TrueTypeFontParser::new(&ttf_file)
+            .parse()?
+            .font_directory
+            .get_table_record(&TableTag::GPos)?
+            .offset as usize;
+        ttf_file[gpos_offset + 1] = 9;
+
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        assert!(ttf.gpos_table.is_none());
+        assert!(ttf.gsub_table.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gpos_pair_adjustment() -> Result<()> {
+        #[rustfmt::skip]
+        let gpos = words(&[
+            1, 0, 10, 30, 44, // version, script, feature and lookup lists
+            // Script list at 10, with the default script's default language using feature 0.
+            1, 0x4446, 0x4C54, 8,
+            4, 0,
+            0, 0xFFFF, 1, 0,
+            // Feature list at 30, with kern using lookup 0.
+            1, 0x6B65, 0x726E, 8,
+            0, 1, 0,
+            // Lookup list at 44, with one pair adjustment lookup of two subtables.
+            1, 4,
+            2, 0, 2, 10, 34,
+            // Format 1 at 58: glyph 5 then 7 moves closer.
+            1, 18, 0x4, 0, 1, 12,
+            1, 7, -80i16 as u16,
+            1, 1, 5,
+            // Format 2 at 82: glyphs 5 and 6 then class 1, glyphs 8 and 9, which moves right.
+            2, 24, 0x4, 0x1, 32, 42, 1, 2,
+            0, 0, -40i16 as u16, 10,
+            1, 2, 5, 6,
+            1, 5, 2, 0, 0,
+            2, 1, 8, 9, 1,
+        ]);
+
+        let gpos = TrueTypeFontParser::new(&gpos)
+            .parse_layout_table(GPOS_EXTENSION, TrueTypeFontParser::parse_gpos_subtable)?;
+
+        let lookups = gpos.lookups(b"latn", &[*b"kern"]);
+        assert_eq!(lookups.len(), 1);
+
+        let find = |first, second| {
+            lookups[0]
+                .subtables
+                .iter()
+                .find_map(|subtable| match subtable {
+                    GposSubtable::PairAdjustment(pair) => pair.find(first, second),
+                    _ => None,
+                })
+        };
+        let advance = |x_advance| ValueRecord {
+            x_advance,
+            ..Default::default()
+        };
+        let placement = |x_placement| ValueRecord {
+            x_placement,
+            ..Default::default()
+        };
+
+        assert_eq!(find(5, 7), Some([advance(-80), ValueRecord::default()]));
+        assert_eq!(find(5, 8), Some([advance(-40), placement(10)]));
+        assert_eq!(find(6, 3), Some([advance(0), placement(0)]));
+        assert_eq!(find(4, 8), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gdef_glyph_classes() -> Result<()> {
+        #[rustfmt::skip]
+        let gdef = words(&[
+            1, 2, 14, 0, 0, 30, 38, // version 1.2, then the class definition, mark class and set offsets
+            // Glyphs 3 and 4 are bases and glyph 7 a mark.
+            2, 2, 3, 4, 1, 7, 7, 3,
+            // Glyph 7 attaches as class 2.
+            1, 7, 1, 2,
+            // One mark glyph set at 46, holding glyph 7.
+            1, 1, 0, 8,
+            1, 1, 7,
+        ]);
+
+        let gdef = TrueTypeFontParser::new(&gdef).parse_gdef_table()?;
+
+        assert_eq!(gdef.glyph_class(3), GdefTable::BASE_GLYPH);
+        assert_eq!(gdef.glyph_class(5), 0);
+        assert!(gdef.is_mark(7));
+        assert_eq!(gdef.mark_attachment_class(7), 2);
+        assert_eq!(gdef.mark_glyph_sets.len(), 1);
+        assert_eq!(gdef.mark_glyph_sets[0].index(7), Some(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gpos_attachment_types() -> Result<()> {
+        let parse = |lookup_type, subtable: &[u16]| {
+            TrueTypeFontParser::new(&words(subtable)).parse_gpos_subtable(lookup_type)
+        };
+        let anchor = |x, y| Anchor { x, y };
+
+        // Glyph 3 exits at 400, 100 into glyph 4's entry, a format 2 anchor on point 7.
+        #[rustfmt::skip]
+        let cursive = [
+            1, 28, 2, 0, 14, 20, 0,
+            1, 400, 100,
+            2, 50, -20i16 as u16, 7,
+            1, 2, 3, 4,
+        ];
+        let GposSubtable::Cursive(cursive) = parse(3, &cursive)? else {
+            panic!("Not a cursive attachment");
+        };
+        assert_eq!(
+            cursive.anchors(3, 4),
+            Some((anchor(400, 100), anchor(50, -20)))
+        );
+        assert_eq!(cursive.anchors(4, 3), None);
+
+        // Mark glyph 7 on base glyph 3.
+        #[rustfmt::skip]
+        let mark_attachment = [
+            1, 34, 40, 1, 12, 24,
+            1, 0, 6, 1, 100, 0,
+            1, 4, 1, 500, 1200,
+            1, 1, 7,
+            1, 1, 3,
+        ];
+        let GposSubtable::MarkToBase(mark_to_base) = parse(4, &mark_attachment)? else {
+            panic!("Not a mark to base attachment");
+        };
+        assert_eq!(
+            mark_to_base.anchors(7, 3),
+            Some((anchor(100, 0), anchor(500, 1200)))
+        );
+        assert_eq!(mark_to_base.anchors(3, 7), None);
+        assert_eq!(
+            parse(6, &mark_attachment)?,
+            GposSubtable::MarkToMark(mark_to_base)
+        );
+
+        // Mark glyph 7 on the last of ligature glyph 9's two components.
+        #[rustfmt::skip]
+        let mark_to_ligature = [
+            1, 46, 52, 1, 12, 24,
+            1, 0, 6, 1, 100, 0,
+            1, 4,
+            2, 6, 12, 1, 200, 900, 1, 700, 900,
+            1, 1, 7,
+            1, 1, 9,
+        ];
+        let GposSubtable::MarkToLigature(mark_to_ligature) = parse(5, &mark_to_ligature)? else {
+            panic!("Not a mark to ligature attachment");
+        };
+        assert_eq!(
+            mark_to_ligature.anchors(7, 9),
+            Some((anchor(100, 0), anchor(700, 900)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gsub_lookup_types() -> Result<()> {
+        let substitute = |lookup_type, subtable: &[u16], glyphs: &[u16]| {
+            TrueTypeFontParser::new(&words(subtable))
+                .parse_gsub_subtable(lookup_type)
+                .map(|subtable| subtable.substitute(glyphs))
+        };
+
+        // Each covers glyph 5 only.
+        assert_eq!(
+            substitute(1, &[1, 6, -3i16 as u16, 1, 1, 5], &[5])?,
+            Some((1, vec![2]))
+        );
+        assert_eq!(
+            substitute(1, &[2, 8, 1, 9, 1, 1, 5], &[5])?,
+            Some((1, vec![9]))
+        );
+        assert_eq!(
+            substitute(2, &[1, 14, 1, 8, 2, 20, 21, 1, 1, 5], &[5])?,
+            Some((1, vec![20, 21]))
+        );
+        assert_eq!(
+            substitute(3, &[1, 14, 1, 8, 2, 30, 31, 1, 1, 5], &[5])?,
+            Some((1, vec![30]))
+        );
+
+        let ligature = [1, 20, 1, 8, 1, 4, 40, 3, 6, 7, 1, 1, 5];
+        assert_eq!(substitute(4, &ligature, &[5, 6, 7])?, Some((3, vec![40])));
+        assert_eq!(substitute(4, &ligature, &[5, 6])?, None);
+
+        // Chained contexts aren't applied.
+        assert_eq!(substitute(6, &[3, 0], &[5])?, None);
 
         Ok(())
     }
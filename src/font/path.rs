@@ -0,0 +1,369 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Glyph outlines as drawing commands.
+//!
+//! TrueType contours leave on-curve points between two off-curve points implied, so turning them
+//! into explicit lines and quadratic Béziers is the first thing anything drawing a glyph needs.
+
+use crate::font::grammar::TrueTypeFontFile;
+use crate::font::outline::{GlyphContours, OutlinePoint};
+use crate::util::affine::Affine;
+use anyhow::Result;
+use std::fmt::Write;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathCommand {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    /// A quadratic Bézier through a control point to an end point.
+    QuadTo(f32, f32, f32, f32),
+    /// Closes the contour with a straight line back to the last `MoveTo`.
+    Close,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+}
+
+impl BoundingBox {
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
+
+    const fn from_point((x, y): (f32, f32)) -> Self {
+        Self {
+            x_min: x,
+            y_min: y,
+            x_max: x,
+            y_max: y,
+        }
+    }
+
+    const fn include(&mut self, (x, y): (f32, f32)) {
+        self.x_min = self.x_min.min(x);
+        self.y_min = self.y_min.min(y);
+        self.x_max = self.x_max.max(x);
+        self.y_max = self.y_max.max(y);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyphOutline {
+    pub commands: Vec<PathCommand>,
+}
+
+impl From<&GlyphContours> for GlyphOutline {
+    fn from(glyph: &GlyphContours) -> Self {
+        let mut commands = vec![];
+
+        for contour in &glyph.contours {
+            append_contour(&mut commands, contour);
+        }
+
+        Self { commands }
+    }
+}
+
+impl TrueTypeFontFile<'_> {
+    /// The outline of any glyph, simple or compound, in font units with y pointing up.
+    pub fn glyph_outline(&self, glyph_index: u16) -> Result<GlyphOutline> {
+        Ok(GlyphOutline::from(&self.outline(glyph_index)?))
+    }
+}
+
+impl GlyphOutline {
+    pub const fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// The tight bounds of the drawn shape, counting curve extrema rather than control points.
+    /// `None` for outlines without any points, like the space.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut bounds: Option<BoundingBox> = None;
+        let mut include = |point| match bounds.as_mut() {
+            Some(bounds) => bounds.include(point),
+            None => bounds = Some(BoundingBox::from_point(point)),
+        };
+
+        let mut current = (0.0, 0.0);
+
+        for &command in &self.commands {
+            match command {
+                PathCommand::MoveTo(x, y) | PathCommand::LineTo(x, y) => {
+                    include((x, y));
+                    current = (x, y);
+                }
+                PathCommand::QuadTo(cx, cy, x, y) => {
+                    let (x0, y0) = current;
+
+                    for t in [quad_extremum(x0, cx, x), quad_extremum(y0, cy, y)]
+                        .into_iter()
+                        .flatten()
+                    {
+                        include((quad_at(x0, cx, x, t), quad_at(y0, cy, y, t)));
+                    }
+
+                    include((x, y));
+                    current = (x, y);
+                }
+                PathCommand::Close => {}
+            }
+        }
+
+        bounds
+    }
+
+    /// The outline with every point mapped through `transform`. Affine maps take Béziers to
+    /// Béziers, so transforming control points is exact.
+    pub fn transform(&self, transform: &Affine) -> Self {
+        let apply = |x, y| transform.apply((x, y));
+
+        let commands = self
+            .commands
+            .iter()
+            .map(|&command| match command {
+                PathCommand::MoveTo(x, y) => {
+                    let (x, y) = apply(x, y);
+                    PathCommand::MoveTo(x, y)
+                }
+                PathCommand::LineTo(x, y) => {
+                    let (x, y) = apply(x, y);
+                    PathCommand::LineTo(x, y)
+                }
+                PathCommand::QuadTo(cx, cy, x, y) => {
+                    let (cx, cy) = apply(cx, cy);
+                    let (x, y) = apply(x, y);
+                    PathCommand::QuadTo(cx, cy, x, y)
+                }
+                PathCommand::Close => PathCommand::Close,
+            })
+            .collect();
+
+        Self { commands }
+    }
+
+    /// SVG path data, the `d` attribute of a `<path>`, in the outline's own coordinates.
+    pub fn to_svg_path(&self) -> String {
+        let mut out = String::new();
+
+        for &command in &self.commands {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+
+            // Writing to a String can't fail.
+            let _ = match command {
+                PathCommand::MoveTo(x, y) => write!(out, "M{} {}", x, y),
+                PathCommand::LineTo(x, y) => write!(out, "L{} {}", x, y),
+                PathCommand::QuadTo(cx, cy, x, y) => write!(out, "Q{} {} {} {}", cx, cy, x, y),
+                PathCommand::Close => write!(out, "Z"),
+            };
+        }
+
+        out
+    }
+
+    /// A standalone SVG document of the outline, flipped so y points down as SVG expects and
+    /// framed by its bounding box.
+    pub fn to_svg(&self) -> String {
+        let flipped = self.transform(&Affine::scale(1.0, -1.0));
+        let bounds = flipped
+            .bounding_box()
+            .unwrap_or(BoundingBox::from_point((0.0, 0.0)));
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\
+             <path d=\"{}\"/></svg>\n",
+            bounds.x_min,
+            bounds.y_min,
+            bounds.width(),
+            bounds.height(),
+            flipped.to_svg_path()
+        )
+    }
+}
+
+fn midpoint(a: &OutlinePoint, b: &OutlinePoint) -> (f32, f32) {
+    ((a.x + b.x) / 2.0, (a.y + b.y) / 2.0)
+}
+
+/// Turns one closed contour into commands, starting from an on-curve point. A contour made only
+/// of control points starts halfway between its last and first point.
+fn append_contour(commands: &mut Vec<PathCommand>, contour: &[OutlinePoint]) {
+    let Some(last) = contour.last() else {
+        return;
+    };
+
+    let (start, rest) = contour.iter().position(|point| point.on_curve).map_or_else(
+        || (midpoint(last, &contour[0]), contour.iter().collect()),
+        |i| {
+            let rest = contour[i + 1..].iter().chain(&contour[..i]);
+            ((contour[i].x, contour[i].y), rest.collect::<Vec<_>>())
+        },
+    );
+
+    commands.push(PathCommand::MoveTo(start.0, start.1));
+
+    let mut control: Option<&OutlinePoint> = None;
+
+    for point in rest {
+        match (control, point.on_curve) {
+            (None, true) => commands.push(PathCommand::LineTo(point.x, point.y)),
+            (Some(c), true) => commands.push(PathCommand::QuadTo(c.x, c.y, point.x, point.y)),
+            (None, false) => {}
+            (Some(c), false) => {
+                let (x, y) = midpoint(c, point);
+                commands.push(PathCommand::QuadTo(c.x, c.y, x, y));
+            }
+        }
+
+        control = (!point.on_curve).then_some(point);
+    }
+
+    if let Some(c) = control {
+        commands.push(PathCommand::QuadTo(c.x, c.y, start.0, start.1));
+    }
+
+    commands.push(PathCommand::Close);
+}
+
+fn quad_at(p0: f32, p1: f32, p2: f32, t: f32) -> f32 {
+    let mt = 1.0 - t;
+    mt * mt * p0 + 2.0 * mt * t * p1 + t * t * p2
+}
+
+/// Where a quadratic's derivative is zero, when that falls strictly inside the curve.
+fn quad_extremum(p0: f32, p1: f32, p2: f32) -> Option<f32> {
+    let denominator = p0 - 2.0 * p1 + p2;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let t = (p0 - p1) / denominator;
+    (t > 0.0 && t < 1.0).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::TrueTypeFontParser;
+    use std::fs;
+
+    fn contours(contours: &[&[(f32, f32, bool)]]) -> GlyphContours {
+        GlyphContours {
+            contours: contours
+                .iter()
+                .map(|contour| {
+                    contour
+                        .iter()
+                        .map(|&(x, y, on_curve)| OutlinePoint { x, y, on_curve })
+                        .collect()
+                })
+                .collect(),
+            advance_width: 0,
+            left_side_bearing: 0,
+        }
+    }
+
+    #[test]
+    fn test_implied_on_curve_points() {
+        let outline = GlyphOutline::from(&contours(&[&[
+            (0.0, 0.0, false),
+            (10.0, 0.0, true),
+            (10.0, 10.0, false),
+            (0.0, 10.0, false),
+        ]]));
+
+        assert_eq!(
+            outline.commands,
+            [
+                PathCommand::MoveTo(10.0, 0.0),
+                PathCommand::QuadTo(10.0, 10.0, 5.0, 10.0),
+                PathCommand::QuadTo(0.0, 10.0, 0.0, 5.0),
+                PathCommand::QuadTo(0.0, 0.0, 10.0, 0.0),
+                PathCommand::Close,
+            ]
+        );
+        assert_eq!(
+            outline.to_svg_path(),
+            "M10 0 Q10 10 5 10 Q0 10 0 5 Q0 0 10 0 Z"
+        );
+
+        // Without any on-curve point, a contour starts between its last and first points.
+        let circle = GlyphOutline::from(&contours(&[&[
+            (0.0, 0.0, false),
+            (2.0, 0.0, false),
+            (2.0, 2.0, false),
+            (0.0, 2.0, false),
+        ]]));
+        assert_eq!(circle.commands[0], PathCommand::MoveTo(0.0, 1.0));
+        assert_eq!(circle.commands.len(), 6);
+    }
+
+    #[test]
+    fn test_bounding_box_and_transform() {
+        let outline = GlyphOutline::from(&contours(&[&[
+            (0.0, 0.0, true),
+            (5.0, 10.0, false),
+            (10.0, 0.0, true),
+        ]]));
+
+        // The curve peaks halfway to its control point.
+        assert_eq!(
+            outline.bounding_box(),
+            Some(BoundingBox {
+                x_min: 0.0,
+                y_min: 0.0,
+                x_max: 10.0,
+                y_max: 5.0
+            })
+        );
+
+        let moved = outline.transform(&Affine::scale(2.0, -1.0).then(&Affine::translate(1.0, 0.0)));
+        assert_eq!(
+            moved.commands[1],
+            PathCommand::QuadTo(11.0, -10.0, 21.0, 0.0)
+        );
+        assert_eq!(moved.bounding_box().unwrap().y_min, -5.0);
+
+        assert_eq!(GlyphOutline::default().bounding_box(), None);
+    }
+
+    #[test]
+    fn test_lato_outlines() -> Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        let o = ttf.cmap_table.glyph_index('o');
+        let outline = ttf.glyph_outline(o)?;
+        let bounds = outline.bounding_box().unwrap();
+        let description = &ttf.glyph_table.glyphs[o as usize].description;
+
+        // The exact bounds agree with the ones stored in the font, up to rounding.
+        assert!((bounds.x_min - description.x_min as f32).abs() <= 1.0);
+        assert!((bounds.y_max - description.y_max as f32).abs() <= 1.0);
+        assert_eq!(
+            outline
+                .commands
+                .iter()
+                .filter(|&&command| command == PathCommand::Close)
+                .count(),
+            2
+        );
+
+        assert!(ttf
+            .glyph_outline(ttf.cmap_table.glyph_index(' '))?
+            .is_empty());
+        assert!(outline.to_svg().starts_with("<svg"));
+
+        Ok(())
+    }
+}
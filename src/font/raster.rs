@@ -0,0 +1,352 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Anti-aliased glyph rasterization on the CPU.
+//!
+//! Follows font-rs: every edge adds the exact signed area it covers to each pixel of an
+//! accumulation buffer, then a running sum along each row turns those into coverage. Clamping
+//! the absolute sum to 1 fills by the non-zero winding rule, so overlapping components stay
+//! opaque while counter-wound holes stay empty.
+
+use crate::font::grammar::TrueTypeFontFile;
+use crate::font::path::{GlyphOutline, PathCommand};
+use crate::png::grammar::{ColorType, Png};
+use crate::util::affine::Affine;
+use anyhow::Result;
+
+/// How finely quadratics are split into lines, the same tolerance font-rs uses.
+const FLATTENING_TOLERANCE: f32 = 3.0;
+
+type Point = (f32, f32);
+
+#[derive(Debug, Clone)]
+pub struct Rasterizer {
+    width: usize,
+    height: usize,
+    /// Signed area contributions in row-major order. Edges on the right border spill into the
+    /// first pixels of the next row, which is what the padding at the end is for.
+    accumulation: Vec<f32>,
+}
+
+impl Rasterizer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            accumulation: vec![0.0; width * height + 2],
+        }
+    }
+
+    pub const fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Draws an outline mapped into pixel space, where y points down, by `transform`.
+    pub fn draw_outline(&mut self, outline: &GlyphOutline, transform: &Affine) {
+        let mut start = (0.0, 0.0);
+        let mut current = (0.0, 0.0);
+
+        for &command in &outline.commands {
+            match command {
+                PathCommand::MoveTo(x, y) => {
+                    self.draw_line(current, start);
+                    start = transform.apply((x, y));
+                    current = start;
+                }
+                PathCommand::LineTo(x, y) => {
+                    let next = transform.apply((x, y));
+                    self.draw_line(current, next);
+                    current = next;
+                }
+                PathCommand::QuadTo(cx, cy, x, y) => {
+                    let next = transform.apply((x, y));
+                    self.draw_quad(current, transform.apply((cx, cy)), next);
+                    current = next;
+                }
+                PathCommand::Close => {
+                    self.draw_line(current, start);
+                    current = start;
+                }
+            }
+        }
+
+        self.draw_line(current, start);
+    }
+
+    /// Adds the signed area to the right of a line, positive for lines going down the bitmap and
+    /// negative for lines going up.
+    pub fn draw_line(&mut self, p0: Point, p1: Point) {
+        if (p0.1 - p1.1).abs() <= f32::EPSILON {
+            return;
+        }
+
+        let (direction, p0, p1) = if p0.1 < p1.1 {
+            (1.0, p0, p1)
+        } else {
+            (-1.0, p1, p0)
+        };
+
+        let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+        let mut x = p0.0;
+        if p0.1 < 0.0 {
+            x -= p0.1 * dxdy;
+        }
+
+        // Area left of the bitmap still counts for the pixels on its left edge, and area right
+        // of it is never accumulated.
+        let clamp_x = |x: f32| x.clamp(0.0, self.width as f32);
+
+        let first_row = p0.1.max(0.0) as usize;
+        let last_row = self.height.min(p1.1.ceil().max(0.0) as usize);
+
+        for y in first_row..last_row {
+            let line_start = y * self.width;
+            let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+            let x_next = x + dxdy * dy;
+            let d = dy * direction;
+
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let (x0, x1) = (clamp_x(x0), clamp_x(x1));
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+
+            if x1i <= x0i + 1 {
+                // Within a single pixel, split by how far across it the line runs on average.
+                let x_mid = 0.5 * (x0 + x1) - x0_floor;
+                self.accumulation[line_start + x0i] += d - d * x_mid;
+                self.accumulation[line_start + x0i + 1] += d * x_mid;
+            } else {
+                // Across several pixels the covered area grows linearly, with triangles at
+                // both ends.
+                let s = (x1 - x0).recip();
+                let x0_fraction = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1_fraction * x1_fraction;
+
+                self.accumulation[line_start + x0i] += d * a0;
+
+                if x1i == x0i + 2 {
+                    self.accumulation[line_start + x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0_fraction);
+                    self.accumulation[line_start + x0i + 1] += d * (a1 - a0);
+
+                    for xi in x0i + 2..x1i - 1 {
+                        self.accumulation[line_start + xi] += d * s;
+                    }
+
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    self.accumulation[line_start + x1i - 1] += d * (1.0 - a2 - am);
+                }
+
+                self.accumulation[line_start + x1i] += d * am;
+            }
+
+            x = x_next;
+        }
+    }
+
+    /// Draws a quadratic Bézier as lines, more of them the more it bends.
+    pub fn draw_quad(&mut self, p0: Point, p1: Point, p2: Point) {
+        let deviation_x = p0.0 - 2.0 * p1.0 + p2.0;
+        let deviation_y = p0.1 - 2.0 * p1.1 + p2.1;
+        let deviation = deviation_x * deviation_x + deviation_y * deviation_y;
+
+        if deviation < 1.0 / 3.0 {
+            self.draw_line(p0, p2);
+            return;
+        }
+
+        let segments = 1 + (FLATTENING_TOLERANCE * deviation).sqrt().sqrt().floor() as usize;
+        let lerp = |t: f32, a: Point, b: Point| (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1));
+
+        let mut p = p0;
+        for i in 1..segments {
+            let t = i as f32 / segments as f32;
+            let next = lerp(t, lerp(t, p0, p1), lerp(t, p1, p2));
+            self.draw_line(p, next);
+            p = next;
+        }
+
+        self.draw_line(p, p2);
+    }
+
+    /// The coverage of every pixel from 0 to 255, row by row.
+    pub fn coverage(&self) -> Vec<u8> {
+        let mut sum = 0.0f32;
+
+        self.accumulation[..self.width * self.height]
+            .iter()
+            .map(|&area| {
+                sum += area;
+                (sum.abs().min(1.0) * 255.0).round() as u8
+            })
+            .collect()
+    }
+}
+
+/// A rasterized glyph and where it goes relative to the pen position on the baseline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels from the pen position to the left edge of the bitmap.
+    pub left: i32,
+    /// Pixels from the baseline up to the top edge of the bitmap.
+    pub top: i32,
+    pub coverage: Vec<u8>,
+}
+
+impl GlyphBitmap {
+    /// Black ink on a transparent background.
+    pub fn to_png(&self) -> Png {
+        Png {
+            width: self.width,
+            height: self.height,
+            gamma: 0,
+            color_type: ColorType::GrayscaleAlpha,
+            pixel_buffer: self.coverage.iter().flat_map(|&alpha| [0, alpha]).collect(),
+            exif: None,
+        }
+    }
+}
+
+impl GlyphOutline {
+    /// Renders the outline with font units multiplied by `scale`, into a bitmap just large
+    /// enough for it.
+    pub fn rasterize(&self, scale: f32) -> GlyphBitmap {
+        let Some(bounds) = self.bounding_box() else {
+            return GlyphBitmap {
+                width: 0,
+                height: 0,
+                left: 0,
+                top: 0,
+                coverage: vec![],
+            };
+        };
+
+        let left = (bounds.x_min * scale).floor() as i32;
+        let top = (bounds.y_max * scale).ceil() as i32;
+        let width = ((bounds.x_max * scale).ceil() as i32 - left) as u32;
+        let height = (top - (bounds.y_min * scale).floor() as i32) as u32;
+
+        let transform =
+            Affine::scale(scale, -scale).then(&Affine::translate(-left as f32, top as f32));
+
+        let mut rasterizer = Rasterizer::new(width as usize, height as usize);
+        rasterizer.draw_outline(self, &transform);
+
+        GlyphBitmap {
+            width,
+            height,
+            left,
+            top,
+            coverage: rasterizer.coverage(),
+        }
+    }
+}
+
+impl TrueTypeFontFile<'_> {
+    /// How many pixels a font unit covers when the em square is `pixel_size` pixels tall.
+    pub fn pixel_scale(&self, pixel_size: f32) -> f32 {
+        pixel_size / self.head_table.units_per_em as f32
+    }
+
+    pub fn rasterize_glyph(&self, glyph_index: u16, pixel_size: f32) -> Result<GlyphBitmap> {
+        Ok(self
+            .glyph_outline(glyph_index)?
+            .rasterize(self.pixel_scale(pixel_size)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::TrueTypeFontParser;
+    use std::fs;
+
+    fn rectangle(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<PathCommand> {
+        vec![
+            PathCommand::MoveTo(x0, y0),
+            PathCommand::LineTo(x1, y0),
+            PathCommand::LineTo(x1, y1),
+            PathCommand::LineTo(x0, y1),
+            PathCommand::Close,
+        ]
+    }
+
+    fn render(commands: Vec<PathCommand>, width: usize, height: usize) -> Vec<u8> {
+        let mut rasterizer = Rasterizer::new(width, height);
+        rasterizer.draw_outline(&GlyphOutline { commands }, &Affine::IDENTITY);
+        rasterizer.coverage()
+    }
+
+    #[test]
+    fn test_exact_area_coverage() {
+        #[rustfmt::skip]
+        assert_eq!(render(rectangle(1.0, 1.0, 3.0, 2.0), 4, 3), [
+            0, 0, 0, 0,
+            0, 255, 255, 0,
+            0, 0, 0, 0,
+        ]);
+
+        // Half covered pixels are half opaque, a quarter covered corner a quarter.
+        #[rustfmt::skip]
+        assert_eq!(render(rectangle(0.5, 0.5, 2.5, 2.0), 3, 2), [
+            64, 128, 64,
+            128, 255, 128,
+        ]);
+
+        // A diagonal through a pixel covers half of it.
+        let triangle = vec![
+            PathCommand::MoveTo(0.0, 0.0),
+            PathCommand::LineTo(1.0, 1.0),
+            PathCommand::LineTo(0.0, 1.0),
+            PathCommand::Close,
+        ];
+        assert_eq!(render(triangle, 1, 1), [128]);
+    }
+
+    #[test]
+    fn test_non_zero_winding() {
+        // The same direction twice stays opaque where the squares overlap.
+        let mut overlapping = rectangle(0.0, 0.0, 2.0, 1.0);
+        overlapping.extend(rectangle(1.0, 0.0, 3.0, 1.0));
+        assert_eq!(render(overlapping, 3, 1), [255, 255, 255]);
+
+        // The opposite direction cuts a hole.
+        let mut hole = rectangle(0.0, 0.0, 3.0, 1.0);
+        hole.extend(rectangle(2.0, 0.0, 1.0, 1.0));
+        assert_eq!(render(hole, 3, 1), [255, 0, 255]);
+
+        // Whatever sticks out of the bitmap is clipped away.
+        assert_eq!(render(rectangle(-5.0, -5.0, 1.5, 9.0), 2, 1), [255, 128]);
+    }
+
+    #[test]
+    fn test_rasterize_lato() -> Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        let o = ttf.cmap_table.glyph_index('o');
+        let bitmap = ttf.rasterize_glyph(o, 64.0)?;
+        let (width, height) = (bitmap.width as usize, bitmap.height as usize);
+
+        assert!(width > 20 && height > 20);
+        assert!(bitmap.top > 0 && bitmap.left >= 0);
+        assert_eq!(bitmap.coverage.len(), width * height);
+
+        // The counter of the o is empty, its stroke solid and its edges anti-aliased.
+        let row = &bitmap.coverage[height / 2 * width..][..width];
+        assert_eq!(row[width / 2], 0);
+        assert!(row.contains(&255));
+        assert!(row.iter().any(|&c| c > 0 && c < 255));
+
+        let space = ttf.rasterize_glyph(ttf.cmap_table.glyph_index(' '), 64.0)?;
+        assert_eq!((space.width, space.height), (0, 0));
+
+        Ok(())
+    }
+}
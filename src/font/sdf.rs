@@ -0,0 +1,720 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Signed distance field glyph atlases, for text that stays crisp at any zoom.
+//!
+//! Every texel stores the distance to the outline, 0.5 on the edge and above it inside, so a
+//! shader can threshold bilinear samples instead of blurring a coverage bitmap. Single channel
+//! fields round off corners when magnified. Multi-channel fields follow Chlumsky's msdfgen:
+//! edges meeting at a corner get different channels, so the median of the three channels keeps
+//! the corner sharp.
+
+use crate::font::grammar::TrueTypeFontFile;
+use crate::font::path::{GlyphOutline, PathCommand};
+use crate::png::grammar::{ColorType, Png};
+use crate::util::affine::Affine;
+use anyhow::{ensure, Result};
+use std::fmt::Write;
+
+/// Samples atlases built here. Prepend it to a shader and call `sample_sdf` or `sample_msdf`.
+pub const SDF_WGSL: &str = include_str!("sdf.wgsl");
+
+type Point = (f32, f32);
+
+const RED: u8 = 0b001;
+const GREEN: u8 = 0b010;
+const BLUE: u8 = 0b100;
+const WHITE: u8 = RED | GREEN | BLUE;
+/// The colors edges cycle through between corners. Any two of them share exactly one channel.
+const CORNER_COLORS: [u8; 3] = [GREEN | BLUE, RED | BLUE, RED | GREEN];
+
+/// Directions closer than this, in radians, join smoothly rather than at a corner. The same
+/// threshold msdfgen uses.
+const CORNER_ANGLE: f32 = 3.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SdfOptions {
+    /// The em size glyphs are rendered at into the atlas.
+    pub pixel_size: f32,
+    /// How far from the outline, in atlas pixels, distances are still told apart. Glyphs get
+    /// this much padding on every side.
+    pub range: f32,
+    /// Three channels with sharp corners, or a single one.
+    pub multi_channel: bool,
+    pub atlas_width: u32,
+}
+
+impl Default for SdfOptions {
+    fn default() -> Self {
+        Self {
+            pixel_size: 48.0,
+            range: 4.0,
+            multi_channel: true,
+            atlas_width: 512,
+        }
+    }
+}
+
+/// The distance field of one glyph, placed relative to the pen position on the baseline like
+/// `GlyphBitmap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceField {
+    pub width: u32,
+    pub height: u32,
+    pub left: i32,
+    pub top: i32,
+    /// 1 or 3.
+    pub channels: usize,
+    pub values: Vec<u8>,
+}
+
+/// A colored edge, flattened into a polyline.
+#[derive(Debug, Clone)]
+struct Edge {
+    points: Vec<Point>,
+    color: u8,
+}
+
+impl Edge {
+    fn start_direction(&self) -> Point {
+        normalize(sub(self.points[1], self.points[0]))
+    }
+
+    fn end_direction(&self) -> Point {
+        let n = self.points.len();
+        normalize(sub(self.points[n - 1], self.points[n - 2]))
+    }
+
+    /// The true distance to the edge, and the signed pseudo-distance that extends the edge past
+    /// its ends along its end directions. The orthogonality of the nearest point breaks ties.
+    fn distance(&self, p: Point) -> (f32, f32, f32) {
+        let segments = self.points.len() - 1;
+        let mut best = (f32::INFINITY, 0.0, 0.0);
+
+        for (i, window) in self.points.windows(2).enumerate() {
+            let (a, b) = (window[0], window[1]);
+            let d = sub(b, a);
+            let length_squared = dot(d, d);
+            if length_squared == 0.0 {
+                continue;
+            }
+
+            let t = dot(sub(p, a), d) / length_squared;
+            let nearest = add(a, scale(d, t.clamp(0.0, 1.0)));
+            let distance = length(sub(p, nearest));
+
+            if distance > best.0 {
+                continue;
+            }
+
+            let direction = normalize(d);
+            let side = cross(direction, sub(p, a));
+            let orthogonality = if distance > 0.0 {
+                (side / distance).abs()
+            } else {
+                1.0
+            };
+
+            if distance == best.0 && orthogonality <= best.2 {
+                continue;
+            }
+
+            let extends_start = i == 0 && t < 0.0;
+            let extends_end = i == segments - 1 && t > 1.0;
+            let signed = if extends_start || extends_end {
+                side
+            } else {
+                distance.copysign(side)
+            };
+
+            best = (distance, signed, orthogonality);
+        }
+
+        best
+    }
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn scale(a: Point, s: f32) -> Point {
+    (a.0 * s, a.1 * s)
+}
+
+fn dot(a: Point, b: Point) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: Point, b: Point) -> f32 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn length(a: Point) -> f32 {
+    a.0.hypot(a.1)
+}
+
+fn normalize(a: Point) -> Point {
+    let l = length(a);
+    if l == 0.0 {
+        (0.0, 0.0)
+    } else {
+        scale(a, 1.0 / l)
+    }
+}
+
+/// Splits an outline into contours of edges, one per line or curve, mapped by `transform`.
+/// Curves are flattened finely enough that the polyline stays well within a pixel.
+fn contours(outline: &GlyphOutline, transform: &Affine) -> Vec<Vec<Edge>> {
+    let mut contours: Vec<Vec<Edge>> = vec![];
+    let mut start = (0.0, 0.0);
+    let mut current = (0.0, 0.0);
+
+    let push_edge = |contours: &mut Vec<Vec<Edge>>, points: Vec<Point>| {
+        if let Some(contour) = contours.last_mut() {
+            if points.windows(2).any(|w| w[0] != w[1]) {
+                contour.push(Edge {
+                    points,
+                    color: WHITE,
+                });
+            }
+        }
+    };
+
+    for &command in &outline.commands {
+        match command {
+            PathCommand::MoveTo(x, y) => {
+                start = transform.apply((x, y));
+                current = start;
+                contours.push(vec![]);
+            }
+            PathCommand::LineTo(x, y) => {
+                let next = transform.apply((x, y));
+                push_edge(&mut contours, vec![current, next]);
+                current = next;
+            }
+            PathCommand::QuadTo(cx, cy, x, y) => {
+                let control = transform.apply((cx, cy));
+                let next = transform.apply((x, y));
+
+                let deviation = length(add(sub(current, scale(control, 2.0)), next));
+                let segments = 2 + (deviation * 2.0).sqrt().ceil() as usize;
+                let points = (0..=segments)
+                    .map(|i| {
+                        let t = i as f32 / segments as f32;
+                        let mt = 1.0 - t;
+                        add(
+                            add(scale(current, mt * mt), scale(control, 2.0 * mt * t)),
+                            scale(next, t * t),
+                        )
+                    })
+                    .collect();
+
+                push_edge(&mut contours, points);
+                current = next;
+            }
+            PathCommand::Close => {
+                push_edge(&mut contours, vec![current, start]);
+                current = start;
+            }
+        }
+    }
+
+    contours.retain(|contour| !contour.is_empty());
+    contours
+}
+
+/// Gives edges on either side of every corner different colors. Smooth contours stay white,
+/// and a contour with a single corner is split into thirds so that corner stays sharp too.
+fn color_edges(contour: &mut [Edge]) {
+    let n = contour.len();
+    let threshold = CORNER_ANGLE.sin();
+    let corners = (0..n)
+        .filter(|&i| {
+            let a = contour[(i + n - 1) % n].end_direction();
+            let b = contour[i].start_direction();
+            dot(a, b) <= 0.0 || cross(a, b).abs() > threshold
+        })
+        .collect::<Vec<_>>();
+
+    match corners.as_slice() {
+        [] => contour.iter_mut().for_each(|edge| edge.color = WHITE),
+        &[corner] => {
+            let colors = [CORNER_COLORS[0], WHITE, CORNER_COLORS[2]];
+            for i in 0..n {
+                let third = (3 * i / n.max(1)).min(2);
+                contour[(corner + i) % n].color = colors[third];
+            }
+        }
+        corners => {
+            let mut colors = (0..corners.len())
+                .map(|group| CORNER_COLORS[group % 3])
+                .collect::<Vec<_>>();
+
+            // The last group wraps around to the first, so it needs a third color when the
+            // cycle would give them the same one.
+            let last = colors.len() - 1;
+            if colors[last] == colors[0] {
+                colors[last] = CORNER_COLORS
+                    .into_iter()
+                    .find(|&c| c != colors[0] && c != colors[last - 1])
+                    .unwrap_or(WHITE);
+            }
+
+            let mut group = 0;
+            for i in 0..n {
+                let index = (corners[0] + i) % n;
+                if group + 1 < corners.len() && index == corners[group + 1] {
+                    group += 1;
+                }
+
+                contour[index].color = colors[group];
+            }
+        }
+    }
+}
+
+/// Whether `p` is inside by the non-zero winding rule.
+fn is_inside(contours: &[Vec<Edge>], p: Point) -> bool {
+    let mut winding = 0;
+
+    for (a, b) in contours
+        .iter()
+        .flatten()
+        .flat_map(|edge| edge.points.windows(2).map(|w| (w[0], w[1])))
+    {
+        if (a.1 <= p.1) != (b.1 <= p.1) {
+            let x = a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+            if x > p.0 {
+                winding += if b.1 > a.1 { 1 } else { -1 };
+            }
+        }
+    }
+
+    winding != 0
+}
+
+/// Twice the signed area, to find which side of the edges is inside whatever direction the
+/// font winds its outer contours.
+fn signed_area(contours: &[Vec<Edge>]) -> f32 {
+    contours
+        .iter()
+        .flatten()
+        .flat_map(|edge| edge.points.windows(2).map(|w| cross(w[0], w[1])))
+        .sum()
+}
+
+const fn median(a: f32, b: f32, c: f32) -> f32 {
+    a.min(b).max(a.max(b).min(c))
+}
+
+impl GlyphOutline {
+    /// The distance field of the outline with font units multiplied by `scale`, padded by
+    /// `range` pixels on every side.
+    pub fn distance_field(&self, scale: f32, range: f32, multi_channel: bool) -> DistanceField {
+        let channels = if multi_channel { 3 } else { 1 };
+
+        let Some(bounds) = self.bounding_box() else {
+            return DistanceField {
+                width: 0,
+                height: 0,
+                left: 0,
+                top: 0,
+                channels,
+                values: vec![],
+            };
+        };
+
+        let padding = range.ceil() as i32;
+        let left = (bounds.x_min * scale).floor() as i32 - padding;
+        let top = (bounds.y_max * scale).ceil() as i32 + padding;
+        let width = ((bounds.x_max * scale).ceil() as i32 + padding - left) as u32;
+        let height = (top - (bounds.y_min * scale).floor() as i32 + padding) as u32;
+
+        let transform =
+            Affine::scale(scale, -scale).then(&Affine::translate(-left as f32, top as f32));
+        let mut contours = contours(self, &transform);
+        contours.iter_mut().for_each(|contour| color_edges(contour));
+
+        // Edge distances come out positive on the side that is inside.
+        let orientation = if signed_area(&contours) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let encode = |distance: f32| {
+            ((0.5 + distance / (2.0 * range)).clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+
+        let mut values = Vec::with_capacity(width as usize * height as usize * channels);
+
+        for y in 0..height {
+            for x in 0..width {
+                let p = (x as f32 + 0.5, y as f32 + 0.5);
+
+                // The nearest edge overall, and the nearest edge of each channel.
+                let mut nearest = [(f32::INFINITY, 0.0, 0.0); 4];
+                for edge in contours.iter().flatten() {
+                    let candidate = edge.distance(p);
+                    for (slot, channel) in [WHITE, RED, GREEN, BLUE].into_iter().enumerate() {
+                        let best = nearest[slot];
+                        let closer =
+                            candidate.0 < best.0 || (candidate.0 == best.0 && candidate.2 > best.2);
+                        if edge.color & channel != 0 && closer {
+                            nearest[slot] = candidate;
+                        }
+                    }
+                }
+
+                let inside = is_inside(&contours, p);
+                let true_distance = nearest[0].0.copysign(if inside { 1.0 } else { -1.0 });
+
+                if !multi_channel {
+                    values.push(encode(true_distance));
+                    continue;
+                }
+
+                let [_, r, g, b] = nearest.map(|(_, signed, _)| signed * orientation);
+
+                // Where the channels disagree with the winding rule, usually close to where
+                // edges of the same color nearly meet, fall back to the plain distance.
+                if (median(r, g, b) > 0.0) != inside {
+                    values.extend([encode(true_distance); 3]);
+                } else {
+                    values.extend([encode(r), encode(g), encode(b)]);
+                }
+            }
+        }
+
+        DistanceField {
+            width,
+            height,
+            left,
+            top,
+            channels,
+            values,
+        }
+    }
+}
+
+/// Where a glyph sits in an atlas and how to place it, in pixels at the atlas' em size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfGlyph {
+    pub glyph_index: u16,
+    pub character: char,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The normalized `[left, top, right, bottom]` texture coordinates of the glyph.
+    pub uv: [f32; 4],
+    /// From the pen position to the left edge of the glyph's cell, padding included.
+    pub left: i32,
+    /// From the baseline up to the top edge of the glyph's cell, padding included.
+    pub top: i32,
+    pub advance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub channels: usize,
+    pub pixels: Vec<u8>,
+    pub glyphs: Vec<SdfGlyph>,
+    pub pixel_size: f32,
+    pub range: f32,
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl SdfAtlas {
+    /// Grayscale for single channel atlases, RGB otherwise.
+    pub fn to_png(&self) -> Png {
+        Png {
+            width: self.width,
+            height: self.height,
+            gamma: 0,
+            color_type: if self.channels == 3 {
+                ColorType::RGB
+            } else {
+                ColorType::Grayscale
+            },
+            pixel_buffer: self.pixels.clone(),
+            exif: None,
+        }
+    }
+
+    /// The metrics as JSON. Characters are given as code points so nothing needs escaping.
+    pub fn to_json(&self) -> String {
+        let glyphs = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                format!(
+                    "{{\"unicode\": {}, \"glyph_index\": {}, \"x\": {}, \"y\": {}, \
+                     \"width\": {}, \"height\": {}, \"uv\": [{}, {}, {}, {}], \"left\": {}, \
+                     \"top\": {}, \"advance\": {}}}",
+                    glyph.character as u32,
+                    glyph.glyph_index,
+                    glyph.x,
+                    glyph.y,
+                    glyph.width,
+                    glyph.height,
+                    glyph.uv[0],
+                    glyph.uv[1],
+                    glyph.uv[2],
+                    glyph.uv[3],
+                    glyph.left,
+                    glyph.top,
+                    glyph.advance
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut out = String::new();
+        // Writing to a String can't fail.
+        let _ = write!(
+            out,
+            "{{\"width\": {}, \"height\": {}, \"channels\": {}, \"pixel_size\": {}, \
+             \"distance_range\": {}, \"ascent\": {}, \"descent\": {}, \"line_gap\": {}, \
+             \"glyphs\": [\n  {}\n]}}\n",
+            self.width,
+            self.height,
+            self.channels,
+            self.pixel_size,
+            self.range,
+            self.ascent,
+            self.descent,
+            self.line_gap,
+            glyphs.join(",\n  ")
+        );
+
+        out
+    }
+}
+
+impl TrueTypeFontFile<'_> {
+    /// Distance fields of every distinct character in `characters`, packed in rows from the
+    /// tallest down into an atlas `options.atlas_width` wide and as tall as it needs.
+    pub fn sdf_atlas(&self, characters: &str, options: &SdfOptions) -> Result<SdfAtlas> {
+        let scale = self.pixel_scale(options.pixel_size);
+        let channels = if options.multi_channel { 3 } else { 1 };
+
+        let mut characters = characters.chars().collect::<Vec<_>>();
+        characters.sort_unstable();
+        characters.dedup();
+
+        let mut fields = characters
+            .into_iter()
+            .map(|character| {
+                let glyph_index = self.cmap_table.glyph_index(character);
+                let field = self.glyph_outline(glyph_index)?.distance_field(
+                    scale,
+                    options.range,
+                    options.multi_channel,
+                );
+
+                Ok((character, glyph_index, field))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        fields.sort_by_key(|(_, _, field)| std::cmp::Reverse(field.height));
+
+        let mut placements = vec![];
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+        for (_, _, field) in &fields {
+            ensure!(
+                field.width <= options.atlas_width,
+                "A glyph {} pixels wide doesn't fit an atlas {} wide.",
+                field.width,
+                options.atlas_width
+            );
+
+            if x + field.width > options.atlas_width {
+                (x, y, row_height) = (0, y + row_height, 0);
+            }
+
+            placements.push((x, y));
+            x += field.width;
+            row_height = row_height.max(field.height);
+        }
+
+        let (width, height) = (options.atlas_width, y + row_height);
+        let stride = width as usize * channels;
+        let mut pixels = vec![0; stride * height as usize];
+        let mut glyphs = vec![];
+
+        for ((character, glyph_index, field), (x, y)) in fields.into_iter().zip(placements) {
+            let row_length = field.width as usize * channels;
+
+            for (row, values) in field.values.chunks_exact(row_length.max(1)).enumerate() {
+                let start = (y as usize + row) * stride + x as usize * channels;
+                pixels[start..start + row_length].copy_from_slice(values);
+            }
+
+            glyphs.push(SdfGlyph {
+                glyph_index,
+                character,
+                x,
+                y,
+                width: field.width,
+                height: field.height,
+                uv: [
+                    x as f32 / width as f32,
+                    y as f32 / height.max(1) as f32,
+                    (x + field.width) as f32 / width as f32,
+                    (y + field.height) as f32 / height.max(1) as f32,
+                ],
+                left: field.left,
+                top: field.top,
+                advance: self.horizontal_metrics(glyph_index).0 as f32 * scale,
+            });
+        }
+
+        glyphs.sort_by_key(|glyph| glyph.character);
+
+        Ok(SdfAtlas {
+            width,
+            height,
+            channels,
+            pixels,
+            glyphs,
+            pixel_size: options.pixel_size,
+            range: options.range,
+            ascent: self.hhea_table.ascent as f32 * scale,
+            descent: self.hhea_table.descent as f32 * scale,
+            line_gap: self.hhea_table.line_gap as f32 * scale,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::TrueTypeFontParser;
+    use std::fs;
+
+    fn square() -> GlyphOutline {
+        GlyphOutline {
+            commands: vec![
+                PathCommand::MoveTo(0.0, 0.0),
+                PathCommand::LineTo(0.0, 16.0),
+                PathCommand::LineTo(16.0, 16.0),
+                PathCommand::LineTo(16.0, 0.0),
+                PathCommand::Close,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_square_distances() {
+        let field = square().distance_field(1.0, 4.0, false);
+        assert_eq!((field.width, field.height), (24, 24));
+        assert_eq!((field.left, field.top), (-4, 20));
+
+        let at = |x: usize, y: usize| field.values[y * 24 + x];
+
+        // Half a pixel in from the edge, half a pixel out, and well clear of it on both sides.
+        assert_eq!(at(4, 12), 143);
+        assert_eq!(at(3, 12), 112);
+        assert_eq!(at(12, 12), 255);
+        assert_eq!(at(0, 12), 16);
+
+        // The sign doesn't depend on which way the contour winds.
+        let mut reversed = square();
+        reversed.commands[1..4].reverse();
+        assert_eq!(reversed.distance_field(1.0, 4.0, false), field);
+    }
+
+    #[test]
+    fn test_multi_channel_corners() {
+        let field = square().distance_field(1.0, 4.0, true);
+        let texel = |x: usize, y: usize| {
+            let i = (y * 24 + x) * 3;
+            let [r, g, b] = [0, 1, 2].map(|c| field.values[i + c] as f32);
+            median(r, g, b)
+        };
+
+        // Every corner of the square is a corner, so no edge is left white.
+        let mut contours = contours(&square(), &Affine::IDENTITY);
+        color_edges(&mut contours[0]);
+        assert!(contours[0].iter().all(|edge| edge.color != WHITE));
+
+        // Diagonally off a corner the median is the distance to the extended edges, so the
+        // contours around it stay square, where a single channel field rounds them off.
+        let single = square().distance_field(1.0, 4.0, false);
+        assert_eq!(texel(3, 3), 112.0);
+        assert_eq!(texel(1, 1), 48.0);
+        assert_eq!(single.values[3 * 24 + 3], 105);
+        assert_eq!(texel(4, 4), 143.0);
+    }
+
+    #[test]
+    fn test_lato_atlas_matches_rasterizer() -> Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        let options = SdfOptions {
+            pixel_size: 32.0,
+            ..Default::default()
+        };
+        let atlas = ttf.sdf_atlas("aAé@ a", &options)?;
+
+        assert_eq!(atlas.glyphs.len(), 5);
+        assert_eq!(
+            atlas.to_png().pixel_buffer.len(),
+            (512 * atlas.height * 3) as usize
+        );
+        assert!(atlas.to_json().contains("\"unicode\": 233"));
+
+        // No two glyphs overlap.
+        for (i, a) in atlas.glyphs.iter().enumerate() {
+            for b in &atlas.glyphs[i + 1..] {
+                let apart = a.x + a.width <= b.x
+                    || b.x + b.width <= a.x
+                    || a.y + a.height <= b.y
+                    || b.y + b.height <= a.y;
+                assert!(apart, "{a:?} overlaps {b:?}");
+            }
+        }
+
+        // Inside and outside agree with the rasterizer wherever a pixel is fully one or the
+        // other.
+        let scale = ttf.pixel_scale(32.0);
+        for c in ['A', '@'] {
+            let outline = ttf.glyph_outline(ttf.cmap_table.glyph_index(c))?;
+            let bitmap = outline.rasterize(scale);
+
+            for multi_channel in [false, true] {
+                let field = outline.distance_field(scale, 4.0, multi_channel);
+                let channels = field.channels;
+                assert_eq!(field.left, bitmap.left - 4);
+
+                for (i, &coverage) in bitmap.coverage.iter().enumerate() {
+                    let (x, y) = (i % bitmap.width as usize + 4, i / bitmap.width as usize + 4);
+                    let at = (y * field.width as usize + x) * channels;
+                    let value = if multi_channel {
+                        let [r, g, b] = [0, 1, 2].map(|c| field.values[at + c] as f32);
+                        median(r, g, b)
+                    } else {
+                        field.values[at] as f32
+                    };
+
+                    match coverage {
+                        0 => assert!(value < 128.0, "{c} outside at {x}, {y}"),
+                        255 => assert!(value > 127.0, "{c} inside at {x}, {y}"),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+// Samples signed distance field atlases from `font::sdf`. Prepend to a shader and pass the
+// atlas' `distance_range` along with a linear sampler.
+
+fn median3(v: vec3<f32>) -> f32 {
+    return max(min(v.r, v.g), min(max(v.r, v.g), v.b));
+}
+
+// How many screen pixels the distance range spans here, so edges stay about a pixel wide at
+// any zoom.
+fn sdf_screen_range(atlas: texture_2d<f32>, uv: vec2<f32>, distance_range: f32) -> f32 {
+    let unit_range = vec2(distance_range) / vec2<f32>(textureDimensions(atlas, 0));
+    let screen_size = vec2(1.0) / fwidth(uv);
+
+    return max(0.5 * dot(unit_range, screen_size), 1.0);
+}
+
+fn sdf_coverage(distance: f32, screen_range: f32) -> f32 {
+    return clamp(screen_range * (distance - 0.5) + 0.5, 0.0, 1.0);
+}
+
+// Coverage from a single channel atlas.
+fn sample_sdf(atlas: texture_2d<f32>, s: sampler, uv: vec2<f32>, distance_range: f32) -> f32 {
+    let distance = textureSample(atlas, s, uv).r;
+
+    return sdf_coverage(distance, sdf_screen_range(atlas, uv, distance_range));
+}
+
+// Coverage from a multi-channel atlas, where the median of the channels keeps corners sharp.
+fn sample_msdf(atlas: texture_2d<f32>, s: sampler, uv: vec2<f32>, distance_range: f32) -> f32 {
+    let distance = median3(textureSample(atlas, s, uv).rgb);
+
+    return sdf_coverage(distance, sdf_screen_range(atlas, uv, distance_range));
+}
@@ -1,54 +1,774 @@
-use crate::font::grammar::{Glyph, TrueTypeFontFile};
+use crate::font::grammar::TrueTypeFontFile;
+use crate::font::layout::{
+    Anchor, Coverage, GdefTable, GposSubtable, Lookup, Tag, ValueRecord, RIGHT_TO_LEFT,
+};
+use std::iter;
+
+/// Tabs advance as far as this many spaces.
+const TAB_WIDTH: f32 = 4.0;
+
+/// A glyph placed by the shaper.
+///
+/// Following HarfBuzz, the pen moves by the advance after the glyph is drawn offset from it.
+/// Distances are in pixels, or font units when no pixel size was given, with y pointing up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PositionedGlyph {
+    pub glyph_index: u16,
+    /// The byte offset in the phrase of the first character the glyph was shaped from.
+    pub cluster: usize,
+    pub x_advance: f32,
+    pub y_advance: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+}
+
+/// The substitution features applied unless others are asked for, as in HarfBuzz: glyph
+/// composition, localized forms, ligatures and contextual alternates.
+pub const DEFAULT_FEATURES: [Tag; 6] = [*b"ccmp", *b"locl", *b"rlig", *b"liga", *b"clig", *b"calt"];
+
+/// The positioning features applied, as in HarfBuzz: marks above and below, cursive attachment,
+/// distances, kerning and marks on bases and on other marks.
+const POSITIONING_FEATURES: [Tag; 7] = [
+    *b"abvm", *b"blwm", *b"curs", *b"dist", *b"kern", *b"mark", *b"mkmk",
+];
 
 #[derive(Debug)]
 pub struct TrueTypeFontShaper<'a> {
     file: &'a TrueTypeFontFile<'a>,
+    scale: f32,
+    script: Tag,
+    features: Vec<Tag>,
 }
 
 impl<'a> TrueTypeFontShaper<'a> {
-    pub const fn from(file: &'a TrueTypeFontFile<'a>) -> Self {
-        Self { file }
+    pub fn from(file: &'a TrueTypeFontFile<'a>) -> Self {
+        Self {
+            file,
+            scale: 1.0,
+            script: *b"latn",
+            features: DEFAULT_FEATURES.to_vec(),
+        }
     }
 
-    pub fn shape(&self, phrase: &str) -> Vec<&Glyph> {
-        let cmap_4 = self
-            .file
-            .cmap_table
-            .format_4()
-            .expect("How do you handle other formats?");
+    /// Picks the OpenType script whose lookups apply, Latin unless told otherwise.
+    pub const fn with_script(mut self, script: Tag) -> Self {
+        self.script = script;
+        self
+    }
 
-        let words = phrase.split_whitespace();
+    /// Replaces the substitution features to apply, like `smcp` for small capitals or `onum`
+    /// for old style figures. Kerning always applies.
+    pub fn with_features(mut self, features: &[Tag]) -> Self {
+        self.features = features.to_vec();
+        self
+    }
 
-        words
-            .flat_map(|word| {
-                word.chars()
-                    .map(|c| {
-                        let j = cmap_4.find_glyph_index(c);
+    /// Scales positions from font units to pixels at `pixel_size` pixels per em.
+    pub fn with_pixel_size(mut self, pixel_size: f32) -> Self {
+        self.scale = self.file.pixel_scale(pixel_size);
+        self
+    }
 
-                        &self.file.glyph_table.glyphs[j]
-                    })
-                    .collect::<Vec<&Glyph>>()
+    /// Maps every character to its glyph, variation selectors included, substitutes glyphs by
+    /// the features and places them one after another by their horizontal metrics and kerning.
+    pub fn shape(&self, phrase: &str) -> Vec<PositionedGlyph> {
+        let (mut glyph_indices, mut clusters) = self.map_characters(phrase);
+        self.substitute(&mut glyph_indices, &mut clusters);
+
+        let space = self.file.cmap_table.glyph_index(' ');
+        let mut glyphs = glyph_indices
+            .into_iter()
+            .zip(clusters)
+            .map(|(glyph_index, cluster)| {
+                let (advance_width, left_side_bearing) = self.file.horizontal_metrics(glyph_index);
+                let width = if glyph_index == space && phrase[cluster..].starts_with('\t') {
+                    TAB_WIDTH
+                } else {
+                    1.0
+                };
+
+                PositionedGlyph {
+                    glyph_index,
+                    cluster,
+                    x_advance: advance_width as f32 * width,
+                    y_advance: 0.0,
+                    x_offset: self.bearing_offset(glyph_index, left_side_bearing),
+                    y_offset: 0.0,
+                }
             })
-            .collect::<Vec<&Glyph>>()
+            .collect::<Vec<_>>();
+
+        self.position(&mut glyphs);
+
+        for glyph in &mut glyphs {
+            glyph.x_advance *= self.scale;
+            glyph.y_advance *= self.scale;
+            glyph.x_offset *= self.scale;
+            glyph.y_offset *= self.scale;
+        }
+
+        glyphs
     }
+
+    /// The glyph of every character and the byte offset of the character.
+    fn map_characters(&self, phrase: &str) -> (Vec<u16>, Vec<usize>) {
+        let cmap = &self.file.cmap_table;
+        let space = cmap.glyph_index(' ');
+
+        let mut chars = phrase.char_indices().peekable();
+        let (mut glyph_indices, mut clusters) = (vec![], vec![]);
+
+        while let Some((cluster, c)) = chars.next() {
+            let glyph_index = chars
+                .next_if(|&(_, next)| is_variation_selector(next))
+                .map_or_else(
+                    || cmap.glyph_index(c),
+                    |(_, selector)| cmap.variation_glyph_index(c, selector),
+                );
+
+            // Whitespace the font has no glyph for, like tabs, is drawn as spaces rather than
+            // the missing glyph box.
+            glyph_indices.push(match glyph_index {
+                0 if c.is_whitespace() => space,
+                j => j,
+            });
+            clusters.push(cluster);
+        }
+
+        (glyph_indices, clusters)
+    }
+
+    /// Applies the GSUB lookups of the features in order, passing over the glyphs each lookup
+    /// ignores. Glyphs a substitution makes take the cluster of the first glyph it replaces, and
+    /// ignored glyphs between the ones it replaces, like marks inside a ligature, follow them.
+    fn substitute(&self, glyph_indices: &mut Vec<u16>, clusters: &mut Vec<usize>) {
+        let Some(gsub) = &self.file.gsub_table else {
+            return;
+        };
+        let gdef = self.file.gdef_table.as_ref();
+
+        for lookup in gsub.lookups(&self.script, &self.features) {
+            let ignored = |glyph_index: u16| lookup.ignores(gdef, glyph_index);
+            // Where the glyphs the lookup sees are, and which glyphs they are.
+            let visible = |glyph_indices: &[u16]| -> (Vec<usize>, Vec<u16>) {
+                glyph_indices
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &glyph_index)| !ignored(glyph_index))
+                    .unzip()
+            };
+
+            let (mut positions, mut glyphs) = visible(glyph_indices);
+            let mut k = 0;
+
+            while k < glyphs.len() {
+                let substitution = lookup
+                    .subtables
+                    .iter()
+                    .find_map(|subtable| subtable.substitute(&glyphs[k..]));
+
+                let Some((replaced, substitutes)) = substitution else {
+                    k += 1;
+                    continue;
+                };
+
+                let (start, end) = (positions[k], positions[k + replaced - 1] + 1);
+                let count = substitutes.len();
+                let skipped = (start..end)
+                    .filter(|&j| ignored(glyph_indices[j]))
+                    .collect::<Vec<_>>();
+
+                let cluster = clusters[start];
+                let skipped_clusters = skipped.iter().map(|&j| clusters[j]).collect::<Vec<_>>();
+                let skipped_glyphs = skipped
+                    .iter()
+                    .map(|&j| glyph_indices[j])
+                    .collect::<Vec<_>>();
+
+                glyph_indices.splice(start..end, substitutes.into_iter().chain(skipped_glyphs));
+                clusters.splice(
+                    start..end,
+                    iter::repeat(cluster).take(count).chain(skipped_clusters),
+                );
+
+                (positions, glyphs) = visible(glyph_indices);
+                k = positions.partition_point(|&j| j < start + count);
+            }
+        }
+    }
+
+    /// Applies the GPOS positioning features, and the kern table in fonts without GPOS
+    /// kerning. Marks take no room of their own.
+    fn position(&self, glyphs: &mut [PositionedGlyph]) {
+        let gdef = self.file.gdef_table.as_ref();
+
+        if let Some(gdef) = gdef {
+            for glyph in glyphs.iter_mut() {
+                if gdef.is_mark(glyph.glyph_index) {
+                    glyph.x_advance = 0.0;
+                    glyph.y_advance = 0.0;
+                }
+            }
+        }
+
+        let (lookups, has_kerning) = self.file.gpos_table.as_ref().map_or_else(
+            || (vec![], false),
+            |gpos| {
+                (
+                    gpos.lookups(&self.script, &POSITIONING_FEATURES),
+                    !gpos.lookups(&self.script, &[*b"kern"]).is_empty(),
+                )
+            },
+        );
+
+        if let (false, Some(kern_table)) = (has_kerning, &self.file.kern_table) {
+            for i in 1..glyphs.len() {
+                let kerning = kern_table.kerning(glyphs[i - 1].glyph_index, glyphs[i].glyph_index);
+                glyphs[i - 1].x_advance += kerning as f32;
+            }
+        }
+
+        for lookup in lookups {
+            apply_positioning(lookup, gdef, glyphs);
+        }
+    }
+
+    /// How far the outline has to move for its left edge to sit at the left side bearing, as
+    /// TrueType places it, when glyf and hmtx disagree.
+    fn bearing_offset(&self, glyph_index: u16, left_side_bearing: i16) -> f32 {
+        self.file
+            .glyph_table
+            .glyphs
+            .get(glyph_index as usize)
+            .filter(|glyph| glyph.description.number_of_contours != 0)
+            .map_or(0.0, |glyph| {
+                (left_side_bearing as i32 - glyph.description.x_min as i32) as f32
+            })
+    }
+}
+
+/// Applies the first subtable of the lookup that fits at each glyph in turn, passing over the
+/// glyphs the lookup ignores.
+fn apply_positioning(
+    lookup: &Lookup<GposSubtable>,
+    gdef: Option<&GdefTable>,
+    glyphs: &mut [PositionedGlyph],
+) {
+    let ignored = |glyph: &PositionedGlyph| lookup.ignores(gdef, glyph.glyph_index);
+    let mut i = 0;
+
+    while i < glyphs.len() {
+        if ignored(&glyphs[i]) {
+            i += 1;
+            continue;
+        }
+
+        let glyph_index = glyphs[i].glyph_index;
+        let previous = (0..i).rev().find(|&j| !ignored(&glyphs[j]));
+        let next = (i + 1..glyphs.len()).find(|&j| !ignored(&glyphs[j]));
+        let mut step = 1;
+
+        for subtable in &lookup.subtables {
+            let applied = match subtable {
+                GposSubtable::PairAdjustment(pair_adjustment) => next
+                    .and_then(|j| {
+                        let [first, second] =
+                            pair_adjustment.find(glyph_index, glyphs[j].glyph_index)?;
+                        adjust(&mut glyphs[i], &first);
+                        adjust(&mut glyphs[j], &second);
+
+                        if pair_adjustment.adjusts_second() {
+                            step = j - i + 1;
+                        }
+
+                        Some(())
+                    })
+                    .is_some(),
+                GposSubtable::Cursive(cursive_attachment) => previous
+                    .and_then(|j| {
+                        let (exit, entry) =
+                            cursive_attachment.anchors(glyphs[j].glyph_index, glyph_index)?;
+                        let right_to_left = lookup.lookup_flag & RIGHT_TO_LEFT != 0;
+                        join_cursive(glyphs, j, i, exit, entry, right_to_left);
+
+                        Some(())
+                    })
+                    .is_some(),
+                GposSubtable::MarkToBase(mark_attachment) => {
+                    find_base(glyphs, i, gdef, &mark_attachment.mark_coverage, &ignored)
+                        .and_then(|j| {
+                            let (mark, base) =
+                                mark_attachment.anchors(glyph_index, glyphs[j].glyph_index)?;
+                            attach(glyphs, j, base, i, mark);
+
+                            Some(())
+                        })
+                        .is_some()
+                }
+                GposSubtable::MarkToLigature(mark_to_ligature) => {
+                    find_base(glyphs, i, gdef, &mark_to_ligature.mark_coverage, &ignored)
+                        .and_then(|j| {
+                            let (mark, ligature) =
+                                mark_to_ligature.anchors(glyph_index, glyphs[j].glyph_index)?;
+                            attach(glyphs, j, ligature, i, mark);
+
+                            Some(())
+                        })
+                        .is_some()
+                }
+                GposSubtable::MarkToMark(mark_attachment) => previous
+                    .and_then(|j| {
+                        let (mark, base) =
+                            mark_attachment.anchors(glyph_index, glyphs[j].glyph_index)?;
+                        attach(glyphs, j, base, i, mark);
+
+                        Some(())
+                    })
+                    .is_some(),
+                GposSubtable::Unsupported { .. } => false,
+            };
+
+            if applied {
+                break;
+            }
+        }
+
+        i += step;
+    }
+}
+
+/// The glyph before `mark` that isn't a mark itself. Without GDEF, marks are the glyphs the
+/// subtable covers as marks.
+fn find_base(
+    glyphs: &[PositionedGlyph],
+    mark: usize,
+    gdef: Option<&GdefTable>,
+    mark_coverage: &Coverage,
+    ignored: impl Fn(&PositionedGlyph) -> bool,
+) -> Option<usize> {
+    let is_mark = |glyph_index| {
+        gdef.map_or_else(
+            || mark_coverage.index(glyph_index).is_some(),
+            |gdef| gdef.is_mark(glyph_index),
+        )
+    };
+
+    (0..mark)
+        .rev()
+        .find(|&j| !ignored(&glyphs[j]) && !is_mark(glyphs[j].glyph_index))
+}
+
+/// Moves the mark so its anchor lands on the anchor of the glyph it attaches to.
+fn attach(
+    glyphs: &mut [PositionedGlyph],
+    base: usize,
+    base_anchor: Anchor,
+    mark: usize,
+    mark_anchor: Anchor,
+) {
+    let (pen_x, pen_y) = glyphs[base..mark].iter().fold((0.0, 0.0), |(x, y), glyph| {
+        (x + glyph.x_advance, y + glyph.y_advance)
+    });
+
+    glyphs[mark].x_offset =
+        glyphs[base].x_offset + base_anchor.x as f32 - mark_anchor.x as f32 - pen_x;
+    glyphs[mark].y_offset =
+        glyphs[base].y_offset + base_anchor.y as f32 - mark_anchor.y as f32 - pen_y;
+}
+
+/// Joins the exit anchor of `first` to the entry anchor of `second`, as HarfBuzz does in left
+/// to right text. The second glyph moves up or down to meet the first, or the first to meet
+/// the second in right to left lookups.
+fn join_cursive(
+    glyphs: &mut [PositionedGlyph],
+    first: usize,
+    second: usize,
+    exit: Anchor,
+    entry: Anchor,
+    right_to_left: bool,
+) {
+    glyphs[first].x_advance = exit.x as f32 + glyphs[first].x_offset;
+
+    let entry_x = entry.x as f32 + glyphs[second].x_offset;
+    glyphs[second].x_advance -= entry_x;
+    glyphs[second].x_offset -= entry_x;
+
+    let rise = exit.y as f32 - entry.y as f32;
+    if right_to_left {
+        glyphs[first].y_offset = glyphs[second].y_offset - rise;
+    } else {
+        glyphs[second].y_offset = glyphs[first].y_offset + rise;
+    }
+}
+
+fn adjust(glyph: &mut PositionedGlyph, value_record: &ValueRecord) {
+    glyph.x_offset += value_record.x_placement as f32;
+    glyph.y_offset += value_record.y_placement as f32;
+    glyph.x_advance += value_record.x_advance as f32;
+    glyph.y_advance += value_record.y_advance as f32;
+}
+
+/// Variation selectors pick a glyph for the character before them and have none of their own.
+const fn is_variation_selector(c: char) -> bool {
+    matches!(c, '\u{180B}'..='\u{180D}' | '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::font::layout::{
+        ClassDef, ClassRangeRecord, CursiveAttachment, EntryExitRecord, FeatureRecord, LangSys,
+        LayoutTable, MarkAttachment, MarkRecord, PairAdjustment, PairValueRecord, Script,
+        ScriptRecord, DEFAULT_SCRIPT, IGNORE_MARKS,
+    };
     use crate::font::TrueTypeFontParser;
     use std::fs;
 
     #[test]
-    fn test_parse_lato() -> anyhow::Result<()> {
+    fn test_shape_lato() -> anyhow::Result<()> {
         let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
         let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
 
+        let glyphs = TrueTypeFontShaper::from(&ttf).shape("Good Lord");
+        let space = ttf.cmap_table.glyph_index(' ');
+
+        // The space is kept, with its own advance.
+        assert_eq!(glyphs.len(), 9);
+        assert_eq!(glyphs[4].glyph_index, space);
+        assert_eq!(glyphs[4].x_advance, ttf.horizontal_metrics(space).0 as f32);
+        assert!(glyphs.iter().all(|glyph| glyph.x_offset == 0.0));
+
+        let clusters = glyphs.iter().map(|glyph| glyph.cluster).collect::<Vec<_>>();
+        assert_eq!(clusters, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Scaled to pixels by the em size.
+        let pixels = TrueTypeFontShaper::from(&ttf)
+            .with_pixel_size(20.0)
+            .shape("Good Lord");
+        let scale = 20.0 / ttf.head_table.units_per_em as f32;
+        for (font_units, pixels) in glyphs.iter().zip(&pixels) {
+            assert_eq!(pixels.x_advance, font_units.x_advance * scale);
+        }
+
+        // Tabs without a glyph of their own are four spaces wide.
+        let tab = TrueTypeFontShaper::from(&ttf).shape("a\tb");
+        assert_eq!(tab[1].glyph_index, space);
+        assert_eq!(tab[1].x_advance, 4.0 * glyphs[4].x_advance);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bearing_offset_of_extreme_metrics() -> anyhow::Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let mut ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+
+        // A bearing and left edge at opposite ends of an i16 are further apart than one holds.
+        let o = ttf.cmap_table.glyph_index('o');
+        ttf.glyph_table.glyphs[o as usize].description.x_min = i16::MIN;
+        let offset = TrueTypeFontShaper::from(&ttf).bearing_offset(o, i16::MAX);
+        assert_eq!(offset, 65535.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gpos_kerning() -> anyhow::Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        let shaper = TrueTypeFontShaper::from(&ttf);
+
+        // Lato kerns through GPOS and has no kern table.
+        assert!(ttf.kern_table.is_none());
+
+        for pair in ["AV", "To", "LT"] {
+            let glyphs = shaper.shape(pair);
+            let advance = ttf.horizontal_metrics(glyphs[0].glyph_index).0 as f32;
+            assert!(glyphs[0].x_advance < advance, "{pair} isn't kerned");
+        }
+
+        let glyphs = shaper.shape("HH");
+        assert_eq!(
+            glyphs[0].x_advance,
+            ttf.horizontal_metrics(glyphs[0].glyph_index).0 as f32
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mark_and_cursive_attachment() -> anyhow::Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let mut ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        let [o, grave, a, b] = ['o', '`', 'a', 'b'].map(|c| ttf.cmap_table.glyph_index(c));
+        assert!(a < b);
+
+        let anchor = |x, y| Anchor { x, y };
+        let lookup = |lookup_flag, subtable| Lookup {
+            lookup_flag,
+            subtables: vec![subtable],
+            mark_filtering_set: None,
+        };
+        let feature = |tag: &[u8; 4], lookup_index| FeatureRecord {
+            tag: *tag,
+            lookup_indices: vec![lookup_index],
+        };
+        let mark_attachment = |base_coverage, base_anchor| MarkAttachment {
+            mark_coverage: Coverage::Glyphs(vec![grave]),
+            base_coverage: Coverage::Glyphs(vec![base_coverage]),
+            marks: vec![MarkRecord {
+                mark_class: 0,
+                anchor: anchor(100, 0),
+            }],
+            base_anchors: vec![vec![Some(base_anchor)]],
+        };
+
+        // Lato has neither GDEF nor marks, so the grave accent stands in for one.
+        let mut classes = vec![(o, 1), (grave, 3), (a, 1), (b, 1)];
+        classes.sort();
+        ttf.gdef_table = Some(GdefTable {
+            glyph_class_def: Some(ClassDef::Ranges(
+                classes
+                    .into_iter()
+                    .map(|(glyph, class)| ClassRangeRecord {
+                        start_glyph: glyph,
+                        end_glyph: glyph,
+                        class,
+                    })
+                    .collect(),
+            )),
+            mark_attach_class_def: None,
+            mark_glyph_sets: vec![],
+        });
+        ttf.gpos_table = Some(LayoutTable {
+            scripts: vec![ScriptRecord {
+                tag: DEFAULT_SCRIPT,
+                script: Script {
+                    default_lang_sys: Some(LangSys {
+                        required_feature_index: None,
+                        feature_indices: vec![0, 1, 2, 3],
+                    }),
+                    lang_sys_records: vec![],
+                },
+            }],
+            features: vec![
+                feature(b"kern", 0),
+                feature(b"mark", 1),
+                feature(b"mkmk", 2),
+                feature(b"curs", 3),
+            ],
+            lookups: vec![
+                lookup(
+                    IGNORE_MARKS,
+                    GposSubtable::PairAdjustment(PairAdjustment::Glyphs {
+                        coverage: Coverage::Glyphs(vec![o]),
+                        adjusts_second: false,
+                        pair_sets: vec![vec![PairValueRecord {
+                            second_glyph: o,
+                            values: [
+                                ValueRecord {
+                                    x_advance: -100,
+                                    ..Default::default()
+                                },
+                                ValueRecord::default(),
+                            ],
+                        }]],
+                    }),
+                ),
+                lookup(
+                    0,
+                    GposSubtable::MarkToBase(mark_attachment(o, anchor(500, 1200))),
+                ),
+                lookup(
+                    0,
+                    GposSubtable::MarkToMark(mark_attachment(grave, anchor(100, 300))),
+                ),
+                lookup(
+                    0,
+                    GposSubtable::Cursive(CursiveAttachment {
+                        coverage: Coverage::Glyphs(vec![a, b]),
+                        entry_exits: vec![
+                            EntryExitRecord {
+                                entry: None,
+                                exit: Some(anchor(400, 100)),
+                            },
+                            EntryExitRecord {
+                                entry: Some(anchor(50, -20)),
+                                exit: None,
+                            },
+                        ],
+                    }),
+                ),
+            ],
+        });
+
+        let shaper = TrueTypeFontShaper::from(&ttf);
+        let advance = |glyph| ttf.horizontal_metrics(glyph).0 as f32;
+
+        // The marks take no room. The first sits on the base's anchor, the second on the first.
+        let glyphs = shaper.shape("o``o");
+        assert_eq!(glyphs[0].x_advance, advance(o) - 100.0);
+        assert_eq!(glyphs[1].x_advance, 0.0);
+        assert_eq!(glyphs[1].x_offset, 400.0 - glyphs[0].x_advance);
+        assert_eq!(glyphs[1].y_offset, 1200.0);
+        assert_eq!(glyphs[2].x_offset, 400.0 - glyphs[0].x_advance);
+        assert_eq!(glyphs[2].y_offset, 1500.0);
+
+        // The entry of b meets the exit of a.
+        let glyphs = shaper.shape("ab");
+        assert_eq!(glyphs[0].x_advance, 400.0);
+        assert_eq!(glyphs[1].x_advance, advance(b) - 50.0);
+        assert_eq!(glyphs[1].x_offset, -50.0);
+        assert_eq!(glyphs[1].y_offset, 120.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gsub_features() -> anyhow::Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        let glyph_indices = |glyphs: &[PositionedGlyph]| {
+            glyphs
+                .iter()
+                .map(|glyph| glyph.glyph_index)
+                .collect::<Vec<_>>()
+        };
+
+        // Ligatures are on by default and keep the cluster of their first character.
+        let glyphs = TrueTypeFontShaper::from(&ttf).shape("fish flat");
+        assert_eq!(glyphs.len(), 7);
+        let clusters = glyphs.iter().map(|glyph| glyph.cluster).collect::<Vec<_>>();
+        assert_eq!(clusters, [0, 2, 3, 4, 5, 7, 8]);
+        assert_ne!(glyphs[0].glyph_index, ttf.cmap_table.glyph_index('f'));
+
+        let unligated = TrueTypeFontShaper::from(&ttf)
+            .with_features(&[])
+            .shape("fish flat");
+        assert_eq!(unligated.len(), 9);
+        assert_eq!(unligated[0].glyph_index, ttf.cmap_table.glyph_index('f'));
+
+        // Lato has superscript figures, and only substitutes them when asked to.
+        let figures = TrueTypeFontShaper::from(&ttf).shape("x2");
+        let superscript = TrueTypeFontShaper::from(&ttf)
+            .with_features(&[*b"sups"])
+            .shape("x2");
+        assert_eq!(glyph_indices(&figures)[0], glyph_indices(&superscript)[0]);
+        assert_ne!(glyph_indices(&figures)[1], glyph_indices(&superscript)[1]);
+
+        // Features the font doesn't have change nothing.
+        let small_caps = TrueTypeFontShaper::from(&ttf)
+            .with_features(&[*b"smcp", *b"onum"])
+            .shape("Good 1984");
+        assert_eq!(
+            glyph_indices(&small_caps),
+            glyph_indices(&TrueTypeFontShaper::from(&ttf).shape("Good 1984"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gsub_skips_ignored_glyphs() -> anyhow::Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let mut ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
+        let [f, grave, i] = ['f', '`', 'i'].map(|c| ttf.cmap_table.glyph_index(c));
+        let fi = TrueTypeFontShaper::from(&ttf).shape("fi")[0].glyph_index;
+
+        let mut classes = vec![(f, 1), (grave, 3), (i, 1)];
+        classes.sort();
+        ttf.gdef_table = Some(GdefTable {
+            glyph_class_def: Some(ClassDef::Ranges(
+                classes
+                    .into_iter()
+                    .map(|(glyph, class)| ClassRangeRecord {
+                        start_glyph: glyph,
+                        end_glyph: glyph,
+                        class,
+                    })
+                    .collect(),
+            )),
+            mark_attach_class_def: None,
+            mark_glyph_sets: vec![],
+        });
+
+        // Lato's ligatures don't skip marks, so the grave accent keeps f and i apart.
+        let glyphs = TrueTypeFontShaper::from(&ttf).shape("f`i");
+        assert_eq!(glyphs.len(), 3);
+
+        if let Some(gsub) = &mut ttf.gsub_table {
+            for lookup in &mut gsub.lookups {
+                lookup.lookup_flag |= IGNORE_MARKS;
+            }
+        }
+
+        // Once they do, the mark follows the ligature with its own cluster.
+        let glyphs = TrueTypeFontShaper::from(&ttf).shape("f`i");
+        let shaped = glyphs
+            .iter()
+            .map(|glyph| (glyph.glyph_index, glyph.cluster))
+            .collect::<Vec<_>>();
+        assert_eq!(shaped, [(fi, 0), (grave, 1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dejavu_marks() -> anyhow::Result<()> {
+        // DejaVu Sans has GDEF classes and mark to base, ligature and mark lookups. The anchors
+        // below are the font's, as ttf-parser reads them.
+        let ttf_file = fs::read("./tests/fixtures/DejaVuSans-ExtraLight.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
         let shaper = TrueTypeFontShaper::from(&ttf);
+        let advance = |c| ttf.horizontal_metrics(ttf.cmap_table.glyph_index(c)).0 as f32;
+
+        let subtables = ttf
+            .gpos_table
+            .iter()
+            .flat_map(|gpos| &gpos.lookups)
+            .flat_map(|lookup| &lookup.subtables)
+            .collect::<Vec<_>>();
+        assert!(subtables
+            .iter()
+            .any(|subtable| matches!(subtable, GposSubtable::MarkToBase(_))));
+        assert!(subtables
+            .iter()
+            .any(|subtable| matches!(subtable, GposSubtable::MarkToLigature(_))));
+        assert!(subtables
+            .iter()
+            .any(|subtable| matches!(subtable, GposSubtable::MarkToMark(_))));
+
+        // The acute's anchor at (-512, 1147) meets the one of a at (596, 1147).
+        let glyphs = shaper.shape("a\u{301}");
+        assert_eq!(glyphs[1].x_advance, 0.0);
+        assert_eq!(glyphs[1].x_offset, 596.0 + 512.0 - advance('a'));
+        assert_eq!(glyphs[1].y_offset, 0.0);
+
+        // Without a base, only the mark to mark lookup applies, and the acute's anchor meets
+        // the circumflex's at (-512, 1640).
+        let glyphs = shaper.shape("\u{302}\u{301}");
+        assert_eq!(glyphs[1].x_offset, glyphs[0].x_offset);
+        assert_eq!(glyphs[1].y_offset, glyphs[0].y_offset + 1640.0 - 1147.0);
+
+        // DejaVu classes ç as a base but lists it as a ligature of one component, with the dot
+        // below anchored at (678, -430) and the dot's own anchor at (-512, -1).
+        let glyphs = shaper.shape("ç\u{323}");
+        assert_eq!(glyphs[1].x_offset, 678.0 + 512.0 - advance('ç'));
+        assert_eq!(glyphs[1].y_offset, -429.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variation_selector_shares_cluster() -> anyhow::Result<()> {
+        let ttf_file = fs::read("./src/font/Lato-Regular.ttf")?;
+        let ttf = TrueTypeFontParser::new(&ttf_file).parse()?;
 
-        let glyphs = shaper.shape("hello world!");
+        let glyphs = TrueTypeFontShaper::from(&ttf).shape("é\u{FE00}x");
 
-        dbg!(glyphs);
+        assert_eq!(glyphs.len(), 2);
+        assert_eq!(glyphs[1].cluster, "é\u{FE00}".len());
+        assert_eq!(glyphs[0].glyph_index, ttf.cmap_table.glyph_index('é'));
 
         Ok(())
     }
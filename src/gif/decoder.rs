@@ -0,0 +1,311 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::gif::grammar::{
+    ColorTable, DisposalMethod, Frame, Gif, APPLICATION_LABEL, EXTENSION_INTRODUCER,
+    GRAPHIC_CONTROL_LABEL, IMAGE_SEPARATOR, TRAILER,
+};
+use crate::gif::lzw;
+use crate::util::read_bytes::{U16_BYTES, U8_BYTES};
+use crate::{eof, read_le};
+
+#[derive(Debug, Default)]
+struct GraphicControl {
+    disposal_method: DisposalMethod,
+    delay: u16,
+    transparent_index: Option<u8>,
+}
+
+#[derive(Debug)]
+pub struct GifDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> GifDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Gif> {
+        let signature = self.read_slice(6)?;
+        ensure!(
+            signature == b"GIF87a" || signature == b"GIF89a",
+            "Invalid GIF file: incorrect signature."
+        );
+
+        let width = self.read_u16()?;
+        let height = self.read_u16()?;
+        let packed = self.read_u8()?;
+        let background_color_index = self.read_u8()?;
+        let _pixel_aspect_ratio = self.read_u8()?;
+
+        let global_color_table = if packed & 0b1000_0000 != 0 {
+            Some(self.read_color_table(packed & 0b111)?)
+        } else {
+            None
+        };
+
+        let mut loop_count = None;
+        let mut graphic_control = None;
+        let mut frames = Vec::new();
+
+        loop {
+            match self.read_u8()? {
+                EXTENSION_INTRODUCER => match self.read_u8()? {
+                    GRAPHIC_CONTROL_LABEL => {
+                        graphic_control = Some(self.parse_graphic_control()?);
+                    }
+                    APPLICATION_LABEL => {
+                        if let Some(count) = self.parse_application_extension()? {
+                            loop_count = Some(count);
+                        }
+                    }
+                    _foreign => {
+                        // Comment and plain text extensions carry nothing we render.
+                        self.read_sub_blocks()?;
+                    }
+                },
+                IMAGE_SEPARATOR => {
+                    let frame = self.parse_frame(graphic_control.take().unwrap_or_default())?;
+                    frames.push(frame);
+                }
+                TRAILER => break,
+                foreign => bail!("Unrecognized block introducer: {:#x}", foreign),
+            }
+        }
+
+        Ok(Gif {
+            width,
+            height,
+            global_color_table,
+            background_color_index,
+            loop_count,
+            frames,
+        })
+    }
+
+    fn read_color_table(&mut self, size: u8) -> Result<ColorTable> {
+        let len = 1 << (size + 1);
+        let table = self
+            .read_slice(3 * len)?
+            .chunks_exact(3)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect();
+
+        Ok(table)
+    }
+
+    fn parse_graphic_control(&mut self) -> Result<GraphicControl> {
+        let block_size = self.read_u8()?;
+        ensure!(block_size == 4, "Graphic control block size should be 4.");
+
+        let packed = self.read_u8()?;
+        let delay = self.read_u16()?;
+        let transparent_index = self.read_u8()?;
+
+        // Some encoders append data here, the terminator is found by skipping remaining sub-blocks.
+        self.read_sub_blocks()?;
+
+        Ok(GraphicControl {
+            disposal_method: DisposalMethod::try_from((packed >> 2) & 0b111)?,
+            delay,
+            transparent_index: (packed & 0b1 != 0).then_some(transparent_index),
+        })
+    }
+
+    /// Returns the loop count if this is a NETSCAPE2.0 (or ANIMEXTS1.0) looping extension.
+    fn parse_application_extension(&mut self) -> Result<Option<u16>> {
+        let block_size = self.read_u8()? as usize;
+        let identifier = self.read_slice(block_size)?;
+
+        let data = self.read_sub_blocks()?;
+
+        let is_looping = identifier == b"NETSCAPE2.0" || identifier == b"ANIMEXTS1.0";
+
+        if is_looping && data.len() >= 3 && data[0] == 1 {
+            return Ok(Some(u16::from_le_bytes([data[1], data[2]])));
+        }
+
+        Ok(None)
+    }
+
+    fn parse_frame(&mut self, graphic_control: GraphicControl) -> Result<Frame> {
+        let left = self.read_u16()?;
+        let top = self.read_u16()?;
+        let width = self.read_u16()?;
+        let height = self.read_u16()?;
+        let packed = self.read_u8()?;
+
+        let local_color_table = if packed & 0b1000_0000 != 0 {
+            Some(self.read_color_table(packed & 0b111)?)
+        } else {
+            None
+        };
+
+        let interlaced = packed & 0b0100_0000 != 0;
+
+        let min_code_size = self.read_u8()?;
+        let compressed = self.read_sub_blocks()?;
+
+        let len = width as usize * height as usize;
+        let mut indices = lzw::decode(min_code_size, &compressed, len)?;
+        // Truncated streams are padded with the first color, as most decoders do.
+        indices.resize(len, 0);
+
+        if interlaced {
+            indices = Self::deinterlace(&indices, width as usize, height as usize);
+        }
+
+        Ok(Frame {
+            left,
+            top,
+            width,
+            height,
+            delay: graphic_control.delay,
+            disposal_method: graphic_control.disposal_method,
+            transparent_index: graphic_control.transparent_index,
+            local_color_table,
+            indices,
+        })
+    }
+
+    /// Interlaced images store every 8th row starting at 0, every 8th starting at 4, every 4th starting at 2 and
+    /// finally every other row starting at 1.
+    fn deinterlace(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let mut out = vec![0; indices.len()];
+        let mut rows = indices.chunks_exact(width.max(1));
+
+        for (start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)] {
+            for y in (start..height).step_by(step) {
+                if let Some(row) = rows.next() {
+                    out[y * width..(y + 1) * width].copy_from_slice(row);
+                }
+            }
+        }
+
+        out
+    }
+
+    fn read_sub_blocks(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+
+        loop {
+            let len = self.read_u8()? as usize;
+
+            if len == 0 {
+                break;
+            }
+
+            data.extend_from_slice(self.read_slice(len)?);
+        }
+
+        Ok(data)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        self.eof(len)?;
+
+        let slice = &self.data[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        Ok(slice)
+    }
+
+    eof!();
+    read_le!(read_u8, u8, U8_BYTES);
+    read_le!(read_u16, u16, U16_BYTES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gif::grammar::AnimationFrame;
+    use crate::png::grammar::{ColorType, Png};
+    use image::codecs::gif::{GifEncoder as ReferenceEncoder, Repeat};
+    use image::{Delay, Frame as ReferenceFrame, RgbaImage};
+
+    #[test]
+    fn test_decode_reference_animation() -> Result<()> {
+        let frames = (0..3u8)
+            .map(|i| {
+                let image = RgbaImage::from_fn(5, 4, |x, y| {
+                    image::Rgba([x as u8 * 50, y as u8 * 60, i * 100, 255])
+                });
+
+                ReferenceFrame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(70, 1))
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = Vec::new();
+        {
+            let mut encoder = ReferenceEncoder::new(&mut encoded);
+            encoder.set_repeat(Repeat::Infinite)?;
+            encoder.encode_frames(frames.clone())?;
+        }
+
+        let gif = GifDecoder::new(&encoded).decode()?;
+
+        assert_eq!(gif.dimensions(), (5, 4));
+        assert_eq!(gif.loop_count(), Some(0));
+        assert_eq!(gif.frames().len(), 3);
+
+        for (AnimationFrame { image, delay }, expected) in gif.animation_frames().zip(&frames) {
+            assert_eq!(delay.as_millis(), 70);
+            assert_eq!(image.pixel_buffer, expected.buffer().as_raw().as_slice());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deinterlace() {
+        let rows = [0u8, 4, 2, 6, 1, 3, 5, 7];
+        let out = GifDecoder::deinterlace(&rows, 1, 8);
+
+        assert_eq!(out, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_disposal() -> Result<()> {
+        let frame = |left, disposal_method, transparent_index, indices: Vec<u8>| Frame {
+            left,
+            top: 0,
+            width: 1,
+            height: 1,
+            delay: 10,
+            disposal_method,
+            transparent_index,
+            local_color_table: None,
+            indices,
+        };
+
+        let gif = Gif {
+            width: 2,
+            height: 1,
+            global_color_table: Some(vec![[255, 0, 0], [0, 255, 0]]),
+            background_color_index: 0,
+            loop_count: None,
+            frames: vec![
+                frame(0, DisposalMethod::DoNotDispose, None, vec![0]),
+                frame(1, DisposalMethod::RestoreToBackground, None, vec![1]),
+                frame(1, DisposalMethod::RestoreToPrevious, None, vec![0]),
+                frame(0, DisposalMethod::Unspecified, Some(0), vec![0]),
+            ],
+        };
+
+        let canvases = gif
+            .animation_frames()
+            .map(|f| f.image)
+            .collect::<Vec<Png>>();
+
+        assert!(canvases.iter().all(|c| c.color_type == ColorType::RGBA));
+        assert_eq!(canvases[0].pixel_buffer, [255, 0, 0, 255, 0, 0, 0, 0]);
+        assert_eq!(canvases[1].pixel_buffer, [255, 0, 0, 255, 0, 255, 0, 255]);
+        assert_eq!(canvases[2].pixel_buffer, [255, 0, 0, 255, 255, 0, 0, 255]);
+        // The previous frame restored to the state before it was drawn, and a fully transparent frame
+        // leaves the canvas unchanged.
+        assert_eq!(canvases[3].pixel_buffer, [255, 0, 0, 255, 0, 0, 0, 0]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{ensure, Result};
+
+use crate::gif::grammar::{
+    AnimationFrame, DisposalMethod, APPLICATION_LABEL, EXTENSION_INTRODUCER,
+    GRAPHIC_CONTROL_LABEL, IMAGE_SEPARATOR, TRAILER,
+};
+use crate::gif::lzw;
+use crate::gif::quantize::{quantize, QuantizedImage};
+
+/// Encodes full-canvas frames as a GIF89a. Every frame gets its own quantized local color table.
+#[derive(Debug)]
+pub struct GifEncoder<'a> {
+    frames: &'a [AnimationFrame],
+    loop_count: Option<u16>,
+}
+
+impl<'a> GifEncoder<'a> {
+    /// Animations loop forever by default.
+    pub const fn new(frames: &'a [AnimationFrame]) -> Self {
+        Self {
+            frames,
+            loop_count: Some(0),
+        }
+    }
+
+    /// `Some(0)` loops forever, `None` plays the animation once.
+    pub const fn with_loop_count(mut self, loop_count: Option<u16>) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        ensure!(!self.frames.is_empty(), "A GIF needs at least one frame.");
+
+        let (width, height) = self.frames[0].image.dimensions();
+        ensure!(
+            width <= u16::MAX as u32 && height <= u16::MAX as u32,
+            "GIF dimensions must fit in 16 bits, got {}x{}.",
+            width,
+            height
+        );
+        ensure!(
+            self.frames
+                .iter()
+                .all(|f| f.image.dimensions() == (width, height)),
+            "Every frame must share the same dimensions."
+        );
+
+        let mut out = b"GIF89a".to_vec();
+
+        out.extend_from_slice(&(width as u16).to_le_bytes());
+        out.extend_from_slice(&(height as u16).to_le_bytes());
+        // No global color table, 8 bits of color resolution.
+        out.push(0b0111_0000);
+        out.push(0); // background color index
+        out.push(0); // pixel aspect ratio
+
+        if self.frames.len() > 1 {
+            if let Some(loop_count) = self.loop_count {
+                out.extend_from_slice(&[EXTENSION_INTRODUCER, APPLICATION_LABEL, 11]);
+                out.extend_from_slice(b"NETSCAPE2.0");
+                out.extend_from_slice(&[3, 1]);
+                out.extend_from_slice(&loop_count.to_le_bytes());
+                out.push(0);
+            }
+        }
+
+        for frame in self.frames {
+            let quantized = quantize(&frame.image);
+            self.write_graphic_control(&mut out, frame, &quantized);
+            self.write_image(&mut out, width as u16, height as u16, &quantized);
+        }
+
+        out.push(TRAILER);
+
+        Ok(out)
+    }
+
+    fn write_graphic_control(
+        &self,
+        out: &mut Vec<u8>,
+        frame: &AnimationFrame,
+        quantized: &QuantizedImage,
+    ) {
+        // Frames cover the whole canvas, so clearing keeps transparent pixels from showing the previous frame.
+        let disposal_method = DisposalMethod::RestoreToBackground as u8;
+        let transparent_flag = quantized.transparent_index.is_some() as u8;
+        let delay = (frame.delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+
+        out.extend_from_slice(&[EXTENSION_INTRODUCER, GRAPHIC_CONTROL_LABEL, 4]);
+        out.push((disposal_method << 2) | transparent_flag);
+        out.extend_from_slice(&delay.to_le_bytes());
+        out.push(quantized.transparent_index.unwrap_or(0));
+        out.push(0);
+    }
+
+    fn write_image(&self, out: &mut Vec<u8>, width: u16, height: u16, quantized: &QuantizedImage) {
+        // Color tables hold 2^(size + 1) entries.
+        let bits = (usize::BITS - (quantized.color_table.len().max(2) - 1).leading_zeros()) as u8;
+        let table_size = bits - 1;
+
+        out.push(IMAGE_SEPARATOR);
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.push(0b1000_0000 | table_size);
+
+        for i in 0..1 << bits {
+            let color = quantized.color_table.get(i).copied().unwrap_or_default();
+            out.extend_from_slice(&color);
+        }
+
+        let min_code_size = bits.max(2);
+        out.push(min_code_size);
+
+        let compressed = lzw::encode(min_code_size, &quantized.indices);
+        for block in compressed.chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gif::GifDecoder;
+    use crate::png::grammar::{ColorType, Png};
+    use crate::png::PngDecoder;
+    use image::AnimationDecoder;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    fn frame(pixel_buffer: Vec<u8>, width: u32, height: u32) -> AnimationFrame {
+        AnimationFrame {
+            image: Png {
+                width,
+                height,
+                gamma: 0,
+                color_type: ColorType::RGBA,
+                pixel_buffer,
+                exif: None,
+            },
+            delay: Duration::from_millis(120),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_animation() -> Result<()> {
+        let frames = (0..4u8)
+            .map(|i| {
+                let pixels = (0..6 * 3)
+                    .flat_map(|p| {
+                        if p == i as usize {
+                            [0, 0, 0, 0]
+                        } else {
+                            [p as u8 * 10, i * 40, 200, 255]
+                        }
+                    })
+                    .collect();
+
+                frame(pixels, 6, 3)
+            })
+            .collect::<Vec<_>>();
+
+        let encoded = GifEncoder::new(&frames).encode()?;
+        let gif = GifDecoder::new(&encoded).decode()?;
+
+        assert_eq!(gif.loop_count(), Some(0));
+
+        let decoded = gif.animation_frames().collect::<Vec<_>>();
+        assert_eq!(decoded.len(), frames.len());
+
+        for (expected, actual) in frames.iter().zip(&decoded) {
+            assert_eq!(actual.delay, expected.delay);
+            assert_eq!(actual.image.pixel_buffer, expected.image.pixel_buffer);
+        }
+
+        // Cross check against an independent decoder.
+        let reference = image::codecs::gif::GifDecoder::new(Cursor::new(&encoded))?
+            .into_frames()
+            .collect_frames()?;
+
+        for (expected, actual) in frames.iter().zip(reference) {
+            assert_eq!(actual.buffer().as_raw(), &expected.image.pixel_buffer);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_photo() -> Result<()> {
+        let content = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+        let (width, height) = png.dimensions();
+
+        let frames = [frame(png.to_rgba8().to_vec(), width, height)];
+        let encoded = GifEncoder::new(&frames).encode()?;
+
+        let gif = GifDecoder::new(&encoded).decode()?;
+        let decoded = gif.animation_frames().next().unwrap().image;
+
+        assert_eq!(decoded.dimensions(), png.dimensions());
+        assert!(decoded.compute_sim(&png)? > 0.9);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use crate::png::grammar::{ColorType, Png};
+
+pub const EXTENSION_INTRODUCER: u8 = 0x21;
+pub const IMAGE_SEPARATOR: u8 = 0x2C;
+pub const TRAILER: u8 = 0x3B;
+
+pub const GRAPHIC_CONTROL_LABEL: u8 = 0xF9;
+pub const APPLICATION_LABEL: u8 = 0xFF;
+
+pub type ColorTable = Vec<[u8; 3]>;
+
+#[derive(Debug)]
+pub struct Gif {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) global_color_table: Option<ColorTable>,
+    pub(crate) background_color_index: u8,
+    /// The NETSCAPE2.0 loop count. `Some(0)` loops forever, `None` plays once.
+    pub(crate) loop_count: Option<u16>,
+    pub(crate) frames: Vec<Frame>,
+}
+
+impl Gif {
+    pub const fn dimensions(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// The background color from the global color table. Most viewers ignore it and show transparency instead.
+    pub fn background_color(&self) -> Option<[u8; 3]> {
+        self.global_color_table
+            .as_ref()?
+            .get(self.background_color_index as usize)
+            .copied()
+    }
+
+    pub const fn loop_count(&self) -> Option<u16> {
+        self.loop_count
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Composites every frame onto the logical screen, honoring offsets, transparency and disposal methods.
+    pub fn animation_frames(&self) -> AnimationFrames<'_> {
+        AnimationFrames {
+            gif: self,
+            index: 0,
+            canvas: vec![0; self.width as usize * self.height as usize * 4],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub(crate) left: u16,
+    pub(crate) top: u16,
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    /// Delay before the next frame, in hundredths of a second.
+    pub(crate) delay: u16,
+    pub(crate) disposal_method: DisposalMethod,
+    pub(crate) transparent_index: Option<u8>,
+    pub(crate) local_color_table: Option<ColorTable>,
+    /// Color indices in row-major order, already de-interlaced.
+    pub(crate) indices: Vec<u8>,
+}
+
+impl Frame {
+    pub const fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay as u64 * 10)
+    }
+
+    pub const fn disposal_method(&self) -> DisposalMethod {
+        self.disposal_method
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum DisposalMethod {
+    #[default]
+    Unspecified = 0,
+    DoNotDispose = 1,
+    RestoreToBackground = 2,
+    RestoreToPrevious = 3,
+}
+
+impl TryFrom<u8> for DisposalMethod {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let method = match value {
+            0 => Self::Unspecified,
+            1 => Self::DoNotDispose,
+            2 => Self::RestoreToBackground,
+            3 => Self::RestoreToPrevious,
+            // Values 4-7 are reserved; decoders treat them as no disposal.
+            4..=7 => Self::Unspecified,
+            foreign => bail!("Unrecognized disposal method: {}", foreign),
+        };
+
+        Ok(method)
+    }
+}
+
+/// A fully composited frame of an animation.
+#[derive(Debug)]
+pub struct AnimationFrame {
+    pub image: Png,
+    pub delay: Duration,
+}
+
+pub struct AnimationFrames<'a> {
+    gif: &'a Gif,
+    index: usize,
+    /// RGBA logical screen, carried over between frames.
+    canvas: Vec<u8>,
+}
+
+impl AnimationFrames<'_> {
+    fn draw(&mut self, frame: &Frame) {
+        let color_table = frame
+            .local_color_table
+            .as_ref()
+            .or(self.gif.global_color_table.as_ref());
+
+        let Some(color_table) = color_table else {
+            return;
+        };
+
+        let screen_width = self.gif.width as usize;
+        let screen_height = self.gif.height as usize;
+
+        for (i, &index) in frame.indices.iter().enumerate() {
+            if Some(index) == frame.transparent_index {
+                continue;
+            }
+
+            let x = frame.left as usize + i % frame.width as usize;
+            let y = frame.top as usize + i / frame.width as usize;
+
+            if x >= screen_width || y >= screen_height {
+                continue;
+            }
+
+            // Out of range indices are left as whatever was already on the canvas.
+            let Some(&[r, g, b]) = color_table.get(index as usize) else {
+                continue;
+            };
+
+            let offset = (y * screen_width + x) * 4;
+            self.canvas[offset..offset + 4].copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+
+    fn clear(&mut self, frame: &Frame) {
+        let screen_width = self.gif.width as usize;
+        let screen_height = self.gif.height as usize;
+
+        for y in frame.top as usize..(frame.top as usize + frame.height as usize).min(screen_height)
+        {
+            for x in
+                frame.left as usize..(frame.left as usize + frame.width as usize).min(screen_width)
+            {
+                let offset = (y * screen_width + x) * 4;
+                self.canvas[offset..offset + 4].fill(0);
+            }
+        }
+    }
+}
+
+impl Iterator for AnimationFrames<'_> {
+    type Item = AnimationFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.gif.frames.get(self.index)?;
+        self.index += 1;
+
+        let previous = (frame.disposal_method == DisposalMethod::RestoreToPrevious)
+            .then(|| self.canvas.clone());
+
+        self.draw(frame);
+
+        let image = Png {
+            width: self.gif.width as u32,
+            height: self.gif.height as u32,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: self.canvas.clone(),
+            exif: None,
+        };
+
+        match frame.disposal_method {
+            DisposalMethod::RestoreToBackground => self.clear(frame),
+            DisposalMethod::RestoreToPrevious => {
+                if let Some(previous) = previous {
+                    self.canvas = previous;
+                }
+            }
+            DisposalMethod::Unspecified | DisposalMethod::DoNotDispose => {}
+        }
+
+        Some(AnimationFrame {
+            image,
+            delay: frame.delay(),
+        })
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Result};
+
+const MAX_CODE_SIZE: u8 = 12;
+const MAX_CODES: usize = 1 << MAX_CODE_SIZE;
+
+/// Reads variable width codes least significant bit first.
+struct CodeReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    accumulator: u32,
+    len: u8,
+}
+
+impl<'a> CodeReader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            accumulator: 0,
+            len: 0,
+        }
+    }
+
+    fn read(&mut self, width: u8) -> Option<u16> {
+        while self.len < width {
+            let &byte = self.data.get(self.cursor)?;
+            self.cursor += 1;
+
+            self.accumulator |= (byte as u32) << self.len;
+            self.len += 8;
+        }
+
+        let code = (self.accumulator & ((1 << width) - 1)) as u16;
+        self.accumulator >>= width;
+        self.len -= width;
+
+        Some(code)
+    }
+}
+
+/// Decompresses GIF flavored LZW data (the concatenated sub-blocks of an image) into color indices.
+pub fn decode(min_code_size: u8, data: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    ensure!(
+        (1..MAX_CODE_SIZE).contains(&min_code_size),
+        "Invalid LZW minimum code size: {}",
+        min_code_size
+    );
+
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Every entry is stored as (prefix code, last byte, length).
+    let mut prefixes = vec![0u16; MAX_CODES];
+    let mut suffixes = vec![0u8; MAX_CODES];
+    let mut lengths = vec![0usize; MAX_CODES];
+
+    for code in 0..clear_code {
+        suffixes[code as usize] = code as u8;
+        lengths[code as usize] = 1;
+    }
+
+    let mut reader = CodeReader::new(data);
+    let mut out = Vec::with_capacity(expected_len);
+
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+    let mut previous: Option<u16> = None;
+
+    while let Some(code) = reader.read(code_size) {
+        if code == clear_code {
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+            previous = None;
+            continue;
+        }
+
+        if code == end_code {
+            break;
+        }
+
+        let Some(prev) = previous else {
+            ensure!(code < clear_code, "First code after a clear must be a literal.");
+            out.push(code as u8);
+            previous = Some(code);
+            continue;
+        };
+
+        let start = out.len();
+
+        let first_byte = if code < next_code {
+            write_entry(&mut out, code, &prefixes, &suffixes, &lengths);
+            out[start]
+        } else if code == next_code {
+            // The KwKwK case: the code being defined is the previous string plus its own first byte.
+            write_entry(&mut out, prev, &prefixes, &suffixes, &lengths);
+            let first = out[start];
+            out.push(first);
+            first
+        } else {
+            bail!("Invalid LZW code {} (next code is {}).", code, next_code);
+        };
+
+        if (next_code as usize) < MAX_CODES {
+            prefixes[next_code as usize] = prev;
+            suffixes[next_code as usize] = first_byte;
+            lengths[next_code as usize] = lengths[prev as usize] + 1;
+            next_code += 1;
+
+            if next_code as usize == 1 << code_size && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+
+        previous = Some(code);
+
+        if out.len() >= expected_len {
+            break;
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_entry(out: &mut Vec<u8>, code: u16, prefixes: &[u16], suffixes: &[u8], lengths: &[usize]) {
+    let len = lengths[code as usize];
+    let start = out.len();
+    out.resize(start + len, 0);
+
+    let mut code = code as usize;
+    for i in (0..len).rev() {
+        out[start + i] = suffixes[code];
+        code = prefixes[code] as usize;
+    }
+}
+
+/// Packs variable width codes least significant bit first.
+#[derive(Default)]
+struct CodeWriter {
+    out: Vec<u8>,
+    accumulator: u32,
+    len: u8,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.accumulator |= (code as u32) << self.len;
+        self.len += width;
+
+        while self.len >= 8 {
+            self.out.push(self.accumulator as u8);
+            self.accumulator >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.accumulator as u8);
+        }
+
+        self.out
+    }
+}
+
+/// Compresses color indices. Every index must fit into `min_code_size` bits.
+pub fn encode(min_code_size: u8, indices: &[u8]) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut writer = CodeWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    writer.write(clear_code, code_size);
+
+    let mut indices = indices.iter();
+    let Some(&first) = indices.next() else {
+        writer.write(end_code, code_size);
+        return writer.finish();
+    };
+
+    let mut current = first as u16;
+
+    for &index in indices {
+        if let Some(&code) = table.get(&(current, index)) {
+            current = code;
+            continue;
+        }
+
+        writer.write(current, code_size);
+
+        table.insert((current, index), next_code);
+        next_code += 1;
+
+        if next_code as usize > 1 << code_size && code_size < MAX_CODE_SIZE {
+            code_size += 1;
+        }
+
+        if next_code as usize == MAX_CODES {
+            writer.write(clear_code, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+        }
+
+        current = index as u16;
+    }
+
+    writer.write(current, code_size);
+    writer.write(end_code, code_size);
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(min_code_size: u8, indices: &[u8]) -> Result<()> {
+        let encoded = encode(min_code_size, indices);
+        let decoded = decode(min_code_size, &encoded, indices.len())?;
+
+        assert_eq!(decoded, indices);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_small() -> Result<()> {
+        round_trip(2, &[1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2])?;
+        round_trip(2, &[0])?;
+        round_trip(2, &[])
+    }
+
+    #[test]
+    fn test_round_trip_fills_table() -> Result<()> {
+        // Pseudo random data overflows the 4096 entry table several times.
+        let mut state = 0x1234_5678u32;
+        let indices = (0..200_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+
+        round_trip(8, &indices)
+    }
+
+    #[test]
+    fn test_kwkwk() -> Result<()> {
+        round_trip(2, &[3; 64])
+    }
+}
@@ -0,0 +1,8 @@
+pub use decoder::*;
+pub use encoder::*;
+pub mod grammar;
+pub mod quantize;
+
+mod decoder;
+mod encoder;
+mod lzw;
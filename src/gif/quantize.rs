@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::gif::grammar::ColorTable;
+use crate::png::grammar::Png;
+
+/// Pixels with an alpha below this value are mapped to the transparent index.
+const ALPHA_THRESHOLD: u8 = 128;
+
+#[derive(Debug)]
+pub struct QuantizedImage {
+    pub color_table: ColorTable,
+    pub indices: Vec<u8>,
+    pub transparent_index: Option<u8>,
+}
+
+/// Reduces an image to at most 256 colors using median cut. Images that already use few enough colors keep
+/// them exactly. When any pixel is transparent, one palette slot is reserved for transparency.
+pub fn quantize(png: &Png) -> QuantizedImage {
+    let rgba = png.to_rgba8();
+    let is_transparent = |pixel: &[u8]| pixel[3] < ALPHA_THRESHOLD;
+
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    let mut any_transparent = false;
+
+    for pixel in rgba.chunks_exact(4) {
+        if is_transparent(pixel) {
+            any_transparent = true;
+            continue;
+        }
+
+        *histogram.entry([pixel[0], pixel[1], pixel[2]]).or_default() += 1;
+    }
+
+    let max_colors = if any_transparent { 255 } else { 256 };
+
+    let mut colors = histogram.into_iter().collect::<Vec<_>>();
+    // Deterministic output regardless of hash map ordering.
+    colors.sort_unstable();
+
+    let boxes = if colors.len() <= max_colors {
+        colors.into_iter().map(|color| vec![color]).collect()
+    } else {
+        median_cut(colors, max_colors)
+    };
+
+    // Every color maps to the average of the box it ended up in.
+    let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut color_table = ColorTable::with_capacity(boxes.len() + 1);
+
+    for (i, b) in boxes.iter().enumerate() {
+        color_table.push(average(b));
+        lookup.extend(b.iter().map(|&(color, _)| (color, i as u8)));
+    }
+
+    let transparent_index = any_transparent.then(|| {
+        color_table.push([0, 0, 0]);
+        (color_table.len() - 1) as u8
+    });
+
+    let indices = rgba
+        .chunks_exact(4)
+        .map(|pixel| {
+            if is_transparent(pixel) {
+                return transparent_index.unwrap_or(0);
+            }
+
+            lookup[&[pixel[0], pixel[1], pixel[2]]]
+        })
+        .collect();
+
+    QuantizedImage {
+        color_table,
+        indices,
+        transparent_index,
+    }
+}
+
+type ColorCount = ([u8; 3], u32);
+
+/// Splits the color space into at most `max_colors` boxes of colors.
+fn median_cut(colors: Vec<ColorCount>, max_colors: usize) -> Vec<Vec<ColorCount>> {
+    let mut boxes = vec![colors];
+
+    while boxes.len() < max_colors {
+        // Split the box whose widest channel spans the most, weighted by how many pixels it holds.
+        let Some((i, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                let population = b.iter().map(|&(_, n)| n as u64).sum::<u64>();
+                (i, channel, range as u64 * population)
+            })
+            .max_by_key(|&(_, _, score)| score)
+            .map(|(i, channel, _)| (i, channel))
+        else {
+            break;
+        };
+
+        let mut b = boxes.swap_remove(i);
+        b.sort_unstable_by_key(|&(color, _)| color[channel]);
+
+        let population = b.iter().map(|&(_, n)| n as u64).sum::<u64>();
+        let mut acc = 0;
+        let mut split = 1;
+
+        for (j, &(_, n)) in b.iter().enumerate() {
+            acc += n as u64;
+            if acc * 2 >= population {
+                split = (j + 1).clamp(1, b.len() - 1);
+                break;
+            }
+        }
+
+        let upper = b.split_off(split);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+
+    boxes
+}
+
+/// The population weighted mean color of a box.
+fn average(colors: &[ColorCount]) -> [u8; 3] {
+    let population = colors.iter().map(|&(_, n)| n as u64).sum::<u64>().max(1);
+    let mut sum = [0u64; 3];
+
+    for &(color, n) in colors {
+        for (s, c) in sum.iter_mut().zip(color) {
+            *s += c as u64 * n as u64;
+        }
+    }
+
+    sum.map(|s| ((s + population / 2) / population) as u8)
+}
+
+fn widest_channel(colors: &[ColorCount]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = colors.iter().map(|(c, _)| c[channel]).min().unwrap_or(0);
+            let max = colors.iter().map(|(c, _)| c[channel]).max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::grammar::ColorType;
+
+    #[test]
+    fn test_exact_palette() {
+        let png = Png {
+            width: 2,
+            height: 2,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: vec![255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255],
+            exif: None,
+        };
+
+        let quantized = quantize(&png);
+
+        assert_eq!(quantized.color_table.len(), 3);
+        assert_eq!(quantized.transparent_index, Some(2));
+        assert_eq!(quantized.indices[0], quantized.indices[3]);
+        assert_eq!(quantized.indices[2], 2);
+    }
+
+    #[test]
+    fn test_reduces_to_256_colors() {
+        let pixel_buffer = (0..64 * 64)
+            .flat_map(|i: u32| [(i % 64 * 4) as u8, (i / 64 * 4) as u8, 128])
+            .collect::<Vec<_>>();
+
+        let png = Png {
+            width: 64,
+            height: 64,
+            gamma: 0,
+            color_type: ColorType::RGB,
+            pixel_buffer,
+            exif: None,
+        };
+
+        let quantized = quantize(&png);
+
+        assert_eq!(quantized.color_table.len(), 256);
+        assert_eq!(quantized.transparent_index, None);
+
+        let errors = png
+            .pixel_buffer
+            .chunks_exact(3)
+            .zip(&quantized.indices)
+            .map(|(pixel, &i)| {
+                let c = quantized.color_table[i as usize];
+                (0..3)
+                    .map(|k| (pixel[k] as i32 - c[k] as i32).abs())
+                    .sum::<i32>()
+            })
+            .collect::<Vec<_>>();
+
+        let mean_error = errors.iter().sum::<i32>() as f32 / errors.len() as f32;
+        assert!(mean_error < 16.0, "mean error: {}", mean_error);
+    }
+}
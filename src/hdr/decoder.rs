@@ -0,0 +1,263 @@
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::hdr::grammar::HdrImage;
+
+/// Decodes Radiance RGBE (.hdr) images, flat or run-length encoded.
+#[derive(Debug)]
+pub struct HdrDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> HdrDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<HdrImage> {
+        let signature = self.read_line()?;
+        ensure!(
+            signature.starts_with(b"#?"),
+            "Invalid Radiance HDR file: incorrect signature."
+        );
+
+        // Header variables run until the first blank line.
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                break;
+            }
+
+            if let Some(format) = line.strip_prefix(b"FORMAT=") {
+                ensure!(
+                    format == b"32-bit_rle_rgbe",
+                    "Unsupported HDR pixel format: {}",
+                    String::from_utf8_lossy(format)
+                );
+            }
+        }
+
+        let resolution = String::from_utf8_lossy(self.read_line()?).into_owned();
+        let (flip_y, height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            [y @ ("-Y" | "+Y"), height, "+X", width] => (y == "+Y", height, width),
+            _ => bail!("Unsupported HDR orientation: {}", resolution),
+        };
+
+        let width = width.parse::<u32>()?;
+        let height = height.parse::<u32>()?;
+        ensure!(
+            width > 0 && height > 0 && (width as u64 * height as u64) < (1 << 28),
+            "Invalid HDR dimensions: {}x{}.",
+            width,
+            height
+        );
+
+        let mut rgbe = Vec::with_capacity(width as usize * height as usize * 4);
+        for _ in 0..height {
+            self.read_scanline(width as usize, &mut rgbe)?;
+        }
+
+        let mut pixel_buffer = rgbe
+            .chunks_exact(4)
+            .map(|p| Self::rgbe_to_rgb([p[0], p[1], p[2], p[3]]))
+            .collect::<Vec<_>>();
+
+        // +Y stores the bottom row first.
+        if flip_y {
+            pixel_buffer = pixel_buffer
+                .chunks_exact(width as usize)
+                .rev()
+                .flatten()
+                .copied()
+                .collect();
+        }
+
+        Ok(HdrImage {
+            width,
+            height,
+            pixel_buffer,
+        })
+    }
+
+    fn read_scanline(&mut self, width: usize, out: &mut Vec<u8>) -> Result<()> {
+        let start = out.len();
+
+        // New style run-length encoding stores each channel separately.
+        if (8..0x8000).contains(&width) && self.peek(2) == Some(&[2, 2]) {
+            let header = self.read_slice(4)?;
+            ensure!(
+                ((header[2] as usize) << 8 | header[3] as usize) == width,
+                "HDR scanline width does not match the image width."
+            );
+
+            out.resize(start + width * 4, 0);
+            let scanline = &mut out[start..];
+
+            for channel in 0..4 {
+                let mut x = 0;
+
+                while x < width {
+                    let count = self.read_u8()? as usize;
+
+                    if count > 128 {
+                        let count = count - 128;
+                        ensure!(x + count <= width, "HDR run overflows the scanline.");
+
+                        let value = self.read_u8()?;
+                        for _ in 0..count {
+                            scanline[x * 4 + channel] = value;
+                            x += 1;
+                        }
+                    } else {
+                        ensure!(
+                            count > 0 && x + count <= width,
+                            "Invalid HDR literal run of {} bytes.",
+                            count
+                        );
+
+                        for &value in self.read_slice(count)? {
+                            scanline[x * 4 + channel] = value;
+                            x += 1;
+                        }
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        // Flat pixels, where (1, 1, 1, n) repeats the previous pixel using the old encoding.
+        let mut shift = 0;
+        while out.len() < start + width * 4 {
+            let pixel = self.read_slice(4)?;
+
+            if pixel[..3] == [1, 1, 1] {
+                let previous = out
+                    .len()
+                    .checked_sub(4)
+                    .filter(|&i| i >= start)
+                    .ok_or_else(|| anyhow!("HDR run without a previous pixel."))?;
+
+                let count = (pixel[3] as usize) << shift;
+                ensure!(
+                    out.len() + count * 4 <= start + width * 4,
+                    "HDR run overflows the scanline."
+                );
+
+                let previous: [u8; 4] = out[previous..previous + 4].try_into()?;
+                for _ in 0..count {
+                    out.extend_from_slice(&previous);
+                }
+
+                shift += 8;
+            } else {
+                out.extend_from_slice(pixel);
+                shift = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shared exponent to float, `channel * 2^(exponent - 136)`.
+    fn rgbe_to_rgb([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+        if e == 0 {
+            return [0.0; 3];
+        }
+
+        let scale = (e as f32 - 136.0).exp2();
+        [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+    }
+
+    fn read_line(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.cursor..];
+        let len = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| anyhow!("Unexpected EOF in HDR header."))?;
+
+        self.cursor += len + 1;
+
+        Ok(&rest[..len])
+    }
+
+    fn peek(&self, len: usize) -> Option<&'a [u8]> {
+        self.data.get(self.cursor..self.cursor + len)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .peek(len)
+            .ok_or_else(|| anyhow!("Unexpected EOF in HDR pixel data."))?;
+        self.cursor += len;
+
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_slice(1)?[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::hdr::HdrEncoder;
+    use image::Rgb;
+
+    #[test]
+    fn test_reference_encoder() -> Result<()> {
+        // Wide enough for run-length encoded scanlines, with runs and literals in every channel.
+        let (width, height) = (40, 3);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let x = (i % width) as f32;
+                Rgb([x / 4.0, (i / 10) as f32 * 8.0, 0.25])
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = Vec::new();
+        HdrEncoder::new(&mut encoded).encode(&pixels, width, height)?;
+
+        let reference = image::load_from_memory(&encoded)?.into_rgb32f();
+        let image = HdrDecoder::new(&encoded).decode()?;
+
+        assert_eq!(image.dimensions(), (width as u32, height as u32));
+        for (actual, expected) in image.pixels().iter().zip(reference.pixels()) {
+            assert_eq!(actual, &expected.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flat_bottom_up() -> Result<()> {
+        let mut file = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n+Y 2 +X 2\n".to_vec();
+        // The bottom row: 1.0 in red, then 0.5 in green and blue.
+        file.extend_from_slice(&[128, 0, 0, 129, 0, 128, 128, 128]);
+        // The top row: black, then the old style run repeating it once.
+        file.extend_from_slice(&[0, 0, 0, 0, 1, 1, 1, 1]);
+
+        let image = HdrDecoder::new(&file).decode()?;
+        assert_eq!(
+            image.pixels(),
+            [[0.0; 3], [0.0; 3], [1.0, 0.0, 0.0], [0.0, 0.5, 0.5]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tone_map_matches_clipping() -> Result<()> {
+        let image = HdrImage {
+            width: 2,
+            height: 1,
+            pixel_buffer: vec![[0.5, 2.0, 0.0], [1.0, 0.25, 0.0]],
+        };
+
+        let png = image.tone_map(-1.0, crate::hdr::tone_map::ToneMap::None);
+        assert_eq!(png.pixel_buffer, [137, 255, 0, 188, 99, 0]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use crate::hdr::tone_map::ToneMap;
+use crate::png::grammar::{ColorType, Png};
+use crate::util::color::{f32_to_f16, linear_to_srgb, unit_to_u8};
+
+/// A high dynamic range image with linear, unbounded RGB samples.
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixel_buffer: Vec<[f32; 3]>,
+}
+
+impl HdrImage {
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[[f32; 3]] {
+        &self.pixel_buffer
+    }
+
+    /// RGBA half floats, the layout of an `Rgba16Float` texture.
+    pub fn to_rgba_f16(&self) -> Vec<u16> {
+        self.pixel_buffer
+            .iter()
+            .flat_map(|&[r, g, b]| [f32_to_f16(r), f32_to_f16(g), f32_to_f16(b), 0x3C00])
+            .collect()
+    }
+
+    /// Applies the exposure (in stops) and tone mapping operator the renderer does, then encodes
+    /// the result as 8-bit sRGB.
+    pub fn tone_map(&self, exposure: f32, tone_map: ToneMap) -> Png {
+        let scale = exposure.exp2();
+
+        let pixel_buffer = self
+            .pixel_buffer
+            .iter()
+            .flat_map(|rgb| rgb.map(|c| unit_to_u8(linear_to_srgb(tone_map.apply(c * scale)))))
+            .collect();
+
+        Png {
+            width: self.width,
+            height: self.height,
+            gamma: 0,
+            color_type: ColorType::RGB,
+            pixel_buffer,
+            exif: None,
+        }
+    }
+}
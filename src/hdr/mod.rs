@@ -0,0 +1,5 @@
+pub use decoder::*;
+pub mod grammar;
+pub mod tone_map;
+
+mod decoder;
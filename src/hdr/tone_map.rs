@@ -0,0 +1,78 @@
+#![allow(clippy::suboptimal_flops)]
+
+use anyhow::{bail, Result};
+
+/// Operators compressing unbounded linear values into [0, 1]. The discriminants match the
+/// renderer's `tone_map` uniform.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Values above 1 are clipped.
+    #[default]
+    None = 0,
+    /// `c / (1 + c)`.
+    Reinhard = 1,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces = 2,
+}
+
+impl ToneMap {
+    pub fn apply(self, c: f32) -> f32 {
+        let c = c.max(0.0);
+
+        match self {
+            Self::None => c.min(1.0),
+            Self::Reinhard => c / (1.0 + c),
+            Self::Aces => {
+                let (a, b, c2, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((c * (a * c + b)) / (c * (c2 * c + d) + e)).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// The next operator, wrapping around to `None`.
+    pub const fn next(self) -> Self {
+        match self {
+            Self::None => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::None,
+        }
+    }
+}
+
+impl TryFrom<u32> for ToneMap {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        let tone_map = match value {
+            0 => Self::None,
+            1 => Self::Reinhard,
+            2 => Self::Aces,
+            foreign => bail!("Unknown tone mapping operator: {}", foreign),
+        };
+
+        Ok(tone_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(ToneMap::None.apply(4.0), 1.0);
+        assert_eq!(ToneMap::Reinhard.apply(1.0), 0.5);
+        assert_eq!(ToneMap::Reinhard.apply(-1.0), 0.0);
+
+        // ACES maps 0 to 0, saturates highlights, and is monotonic in between.
+        assert_eq!(ToneMap::Aces.apply(0.0), 0.0);
+        assert_eq!(ToneMap::Aces.apply(100.0), 1.0);
+
+        let samples = (0..100).map(|i| ToneMap::Aces.apply(i as f32 * 0.1));
+        let mut previous = 0.0;
+        for value in samples {
+            assert!(value >= previous);
+            previous = value;
+        }
+    }
+}
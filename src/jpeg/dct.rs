@@ -0,0 +1,60 @@
+#![allow(clippy::suboptimal_flops)]
+
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// Separable 8x8 forward DCT-II.
+#[derive(Debug)]
+pub struct ForwardDct {
+    /// `c(u) * cos((2x + 1) * u * pi / 16) / 2`, indexed by `[u][x]`.
+    table: [[f32; 8]; 8],
+}
+
+impl ForwardDct {
+    pub(crate) fn new() -> Self {
+        let mut table = [[0.0; 8]; 8];
+
+        for (u, row) in table.iter_mut().enumerate() {
+            let c = if u == 0 { FRAC_1_SQRT_2 } else { 1.0 };
+
+            for (x, entry) in row.iter_mut().enumerate() {
+                *entry = c * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos() / 2.0;
+            }
+        }
+
+        Self { table }
+    }
+
+    /// Transforms level-shifted samples in natural order into coefficients in natural order.
+    pub(crate) fn transform(&self, block: &[f32; 64]) -> [f32; 64] {
+        let mut rows = [0.0; 64];
+
+        for y in 0..8 {
+            for u in 0..8 {
+                rows[y * 8 + u] = (0..8).map(|x| self.table[u][x] * block[y * 8 + x]).sum();
+            }
+        }
+
+        let mut out = [0.0; 64];
+
+        for u in 0..8 {
+            for v in 0..8 {
+                out[v * 8 + u] = (0..8).map(|y| self.table[v][y] * rows[y * 8 + u]).sum();
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_block_has_only_dc() {
+        let coefficients = ForwardDct::new().transform(&[10.0; 64]);
+
+        assert!((coefficients[0] - 80.0).abs() < 1e-3);
+        assert!(coefficients[1..].iter().all(|c| c.abs() < 1e-3));
+    }
+}
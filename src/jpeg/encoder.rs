@@ -0,0 +1,523 @@
+#![allow(clippy::suboptimal_flops)]
+
+use anyhow::{ensure, Result};
+
+use crate::jpeg::dct::ForwardDct;
+use crate::jpeg::grammar::{
+    ChromaSubsampling, QuantizationTable, APP0, APP1, DHT, DQT, EOI, SOF0, SOI, SOS, ZIGZAG,
+};
+use crate::jpeg::huffman::{BitWriter, HuffmanTable};
+use crate::png::grammar::Png;
+
+/// A baseline, sequential JPEG encoder with per-image optimized Huffman tables.
+#[derive(Debug)]
+pub struct JpegEncoder<'a> {
+    png: &'a Png,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+    exif: Option<&'a [u8]>,
+}
+
+impl<'a> JpegEncoder<'a> {
+    pub const DEFAULT_QUALITY: u8 = 90;
+
+    /// Creates an encoder that carries over the EXIF payload of `png`, if it has one.
+    pub fn new(png: &'a Png) -> Self {
+        Self {
+            png,
+            quality: Self::DEFAULT_QUALITY,
+            subsampling: ChromaSubsampling::Yuv420,
+            exif: png.exif(),
+        }
+    }
+
+    /// Quality from 1 (smallest) to 100 (best).
+    pub const fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub const fn with_subsampling(mut self, subsampling: ChromaSubsampling) -> Self {
+        self.subsampling = subsampling;
+        self
+    }
+
+    /// Overrides the EXIF payload written to the APP1 segment. `None` strips it.
+    pub const fn with_exif(mut self, exif: Option<&'a [u8]>) -> Self {
+        self.exif = exif;
+        self
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.png.dimensions();
+        ensure!(
+            width > 0 && height > 0 && width <= u16::MAX as u32 && height <= u16::MAX as u32,
+            "JPEG dimensions must be between 1 and 65535, got {}x{}.",
+            width,
+            height
+        );
+        ensure!(
+            (1..=100).contains(&self.quality),
+            "Quality must be between 1 and 100, got {}.",
+            self.quality
+        );
+
+        let planes = YCbCrPlanes::from_png(self.png);
+
+        let luma_table = QuantizationTable::luminance(self.quality);
+        let chroma_table = QuantizationTable::chrominance(self.quality);
+
+        let symbols = self.collect_symbols(&planes, &luma_table, &chroma_table);
+
+        let mut frequencies = [[0u32; 256]; 4];
+        for symbol in &symbols {
+            frequencies[symbol.table as usize][symbol.symbol as usize] += 1;
+        }
+
+        let tables = frequencies.map(|f| HuffmanTable::from_frequencies(&f));
+
+        let mut writer = BitWriter::default();
+        for symbol in &symbols {
+            let (code, len) = tables[symbol.table as usize].code(symbol.symbol);
+            writer.write(code, len);
+            writer.write(symbol.extra_bits, symbol.extra_len);
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&SOI.to_be_bytes());
+        self.write_jfif(&mut out);
+        self.write_exif(&mut out)?;
+        self.write_quantization_tables(&mut out, &luma_table, &chroma_table);
+        self.write_frame_header(&mut out, width as u16, height as u16);
+        self.write_huffman_tables(&mut out, &tables);
+        self.write_scan_header(&mut out);
+        out.extend_from_slice(&writer.finish());
+        out.extend_from_slice(&EOI.to_be_bytes());
+
+        Ok(out)
+    }
+
+    /// Runs the DCT, quantization and run-length coding over every MCU, returning the symbol stream so the
+    /// Huffman tables can be optimized before anything is written.
+    fn collect_symbols(
+        &self,
+        planes: &YCbCrPlanes,
+        luma_table: &QuantizationTable,
+        chroma_table: &QuantizationTable,
+    ) -> Vec<CodedSymbol> {
+        let dct = ForwardDct::new();
+        let (h, v) = self.subsampling.luma_sampling_factors();
+
+        let mcu_width = 8 * h;
+        let mcu_height = 8 * v;
+        let mcus_x = planes.width.div_ceil(mcu_width);
+        let mcus_y = planes.height.div_ceil(mcu_height);
+
+        let mut symbols = Vec::new();
+        let mut predictions = [0i32; 3];
+
+        for mcu_y in 0..mcus_y {
+            for mcu_x in 0..mcus_x {
+                let (x0, y0) = (mcu_x * mcu_width, mcu_y * mcu_height);
+
+                for by in 0..v {
+                    for bx in 0..h {
+                        let block = planes.block(0, x0 + bx * 8, y0 + by * 8, 1);
+                        Self::encode_block(
+                            &dct,
+                            &block,
+                            luma_table,
+                            &mut predictions[0],
+                            ComponentTables::LUMA,
+                            &mut symbols,
+                        );
+                    }
+                }
+
+                for (component, prediction) in predictions.iter_mut().enumerate().skip(1) {
+                    let block = planes.block(component, x0, y0, h);
+                    Self::encode_block(
+                        &dct,
+                        &block,
+                        chroma_table,
+                        prediction,
+                        ComponentTables::CHROMA,
+                        &mut symbols,
+                    );
+                }
+            }
+        }
+
+        symbols
+    }
+
+    fn encode_block(
+        dct: &ForwardDct,
+        block: &[f32; 64],
+        quantization_table: &QuantizationTable,
+        prediction: &mut i32,
+        tables: ComponentTables,
+        symbols: &mut Vec<CodedSymbol>,
+    ) {
+        let coefficients = dct.transform(block);
+
+        let mut quantized = [0i32; 64];
+        for (k, &natural) in ZIGZAG.iter().enumerate() {
+            quantized[k] = (coefficients[natural] / quantization_table.steps[natural] as f32)
+                .round() as i32;
+        }
+
+        let diff = quantized[0] - *prediction;
+        *prediction = quantized[0];
+
+        let (extra_bits, extra_len) = magnitude(diff);
+        symbols.push(CodedSymbol {
+            table: tables.dc,
+            symbol: extra_len,
+            extra_bits,
+            extra_len,
+        });
+
+        let mut run = 0;
+        for &coefficient in &quantized[1..] {
+            if coefficient == 0 {
+                run += 1;
+                continue;
+            }
+
+            while run > 15 {
+                symbols.push(CodedSymbol::run_length(tables.ac, 0xF0));
+                run -= 16;
+            }
+
+            let (extra_bits, extra_len) = magnitude(coefficient);
+            symbols.push(CodedSymbol {
+                table: tables.ac,
+                symbol: (run << 4) | extra_len,
+                extra_bits,
+                extra_len,
+            });
+
+            run = 0;
+        }
+
+        if run > 0 {
+            symbols.push(CodedSymbol::run_length(tables.ac, 0x00));
+        }
+    }
+
+    fn write_jfif(&self, out: &mut Vec<u8>) {
+        write_marker_segment(
+            out,
+            APP0,
+            &[
+                b'J', b'F', b'I', b'F', 0, // identifier
+                1, 1, // version 1.01
+                0, // no density units, aspect ratio only
+                0, 1, 0, 1, // 1:1 pixel aspect ratio
+                0, 0, // no thumbnail
+            ],
+        );
+    }
+
+    fn write_exif(&self, out: &mut Vec<u8>) -> Result<()> {
+        let Some(exif) = self.exif else {
+            return Ok(());
+        };
+
+        let mut payload = b"Exif\0\0".to_vec();
+        payload.extend_from_slice(exif);
+
+        ensure!(
+            payload.len() + 2 <= u16::MAX as usize,
+            "EXIF payload does not fit into a single APP1 segment."
+        );
+
+        write_marker_segment(out, APP1, &payload);
+
+        Ok(())
+    }
+
+    fn write_quantization_tables(
+        &self,
+        out: &mut Vec<u8>,
+        luma_table: &QuantizationTable,
+        chroma_table: &QuantizationTable,
+    ) {
+        let mut payload = Vec::with_capacity(2 * 65);
+
+        for (id, table) in [luma_table, chroma_table].into_iter().enumerate() {
+            // 8-bit precision in the high nibble, destination in the low nibble.
+            payload.push(id as u8);
+            payload.extend(ZIGZAG.iter().map(|&natural| table.steps[natural]));
+        }
+
+        write_marker_segment(out, DQT, &payload);
+    }
+
+    fn write_frame_header(&self, out: &mut Vec<u8>, width: u16, height: u16) {
+        let (h, v) = self.subsampling.luma_sampling_factors();
+
+        let mut payload = vec![8];
+        payload.extend_from_slice(&height.to_be_bytes());
+        payload.extend_from_slice(&width.to_be_bytes());
+        payload.push(3);
+
+        // (component id, sampling factors, quantization table)
+        payload.extend_from_slice(&[1, ((h as u8) << 4) | v as u8, 0]);
+        payload.extend_from_slice(&[2, 0x11, 1]);
+        payload.extend_from_slice(&[3, 0x11, 1]);
+
+        write_marker_segment(out, SOF0, &payload);
+    }
+
+    fn write_huffman_tables(&self, out: &mut Vec<u8>, tables: &[HuffmanTable; 4]) {
+        let mut payload = Vec::new();
+
+        for (i, table) in tables.iter().enumerate() {
+            // Table class (0 = DC, 1 = AC) in the high nibble, destination in the low nibble.
+            let class = (i % 2) as u8;
+            let destination = (i / 2) as u8;
+
+            payload.push((class << 4) | destination);
+            payload.extend_from_slice(&table.bits);
+            payload.extend_from_slice(&table.values);
+        }
+
+        write_marker_segment(out, DHT, &payload);
+    }
+
+    fn write_scan_header(&self, out: &mut Vec<u8>) {
+        write_marker_segment(
+            out,
+            SOS,
+            &[
+                3, // components in scan
+                1, 0x00, // Y: DC table 0, AC table 0
+                2, 0x11, // Cb: DC table 1, AC table 1
+                3, 0x11, // Cr: DC table 1, AC table 1
+                0, 63, 0, // spectral selection and successive approximation for baseline
+            ],
+        );
+    }
+}
+
+fn write_marker_segment(out: &mut Vec<u8>, marker: u16, payload: &[u8]) {
+    out.extend_from_slice(&marker.to_be_bytes());
+    out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Returns the additional bits and their count (the magnitude category) used to code `value`.
+const fn magnitude(value: i32) -> (u16, u8) {
+    let abs = value.unsigned_abs();
+    let len = (u32::BITS - abs.leading_zeros()) as u8;
+
+    let bits = if value < 0 {
+        (value - 1) as u32 & ((1 << len) - 1)
+    } else {
+        value as u32
+    };
+
+    (bits as u16, len)
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ComponentTables {
+    dc: u8,
+    ac: u8,
+}
+
+impl ComponentTables {
+    // Indices into the table list, ordered as they are emitted: DC 0, AC 0, DC 1, AC 1.
+    const LUMA: Self = Self { dc: 0, ac: 1 };
+    const CHROMA: Self = Self { dc: 2, ac: 3 };
+}
+
+#[derive(Debug)]
+struct CodedSymbol {
+    table: u8,
+    symbol: u8,
+    extra_bits: u16,
+    extra_len: u8,
+}
+
+impl CodedSymbol {
+    const fn run_length(table: u8, symbol: u8) -> Self {
+        Self {
+            table,
+            symbol,
+            extra_bits: 0,
+            extra_len: 0,
+        }
+    }
+}
+
+/// Full resolution Y, Cb and Cr planes.
+#[derive(Debug)]
+struct YCbCrPlanes {
+    width: usize,
+    height: usize,
+    planes: [Vec<f32>; 3],
+}
+
+impl YCbCrPlanes {
+    fn from_png(png: &Png) -> Self {
+        let (width, height) = png.dimensions();
+        let rgb = png.to_rgb8();
+
+        let mut planes = [
+            Vec::with_capacity(rgb.len() / 3),
+            Vec::with_capacity(rgb.len() / 3),
+            Vec::with_capacity(rgb.len() / 3),
+        ];
+
+        for pixel in rgb.chunks_exact(3) {
+            let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+
+            planes[0].push(0.299 * r + 0.587 * g + 0.114 * b);
+            planes[1].push(-0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0);
+            planes[2].push(0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0);
+        }
+
+        Self {
+            width: width as usize,
+            height: height as usize,
+            planes,
+        }
+    }
+
+    /// Extracts a level-shifted 8x8 block starting at (`x0`, `y0`), where every output sample averages a
+    /// `factor` x `factor` area. Samples past the image edge replicate the last row and column.
+    fn block(&self, component: usize, x0: usize, y0: usize, factor: usize) -> [f32; 64] {
+        let plane = &self.planes[component];
+        let mut block = [0.0; 64];
+
+        for (i, sample) in block.iter_mut().enumerate() {
+            let (bx, by) = (i % 8, i / 8);
+            let mut acc = 0.0;
+
+            for dy in 0..factor {
+                for dx in 0..factor {
+                    let x = (x0 + bx * factor + dx).min(self.width - 1);
+                    let y = (y0 + by * factor + dy).min(self.height - 1);
+                    acc += plane[y * self.width + x];
+                }
+            }
+
+            *sample = acc / (factor * factor) as f32 - 128.0;
+        }
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::{grammar::ColorType, PngDecoder};
+    use image::ImageReader;
+    use std::io::Cursor;
+
+    fn read_png(path: &str) -> Result<Png> {
+        let content = std::fs::read(path)?;
+        PngDecoder::new(&content).decode()
+    }
+
+    fn decode_jpeg(jpeg: &[u8]) -> Result<Png> {
+        let decoded = ImageReader::new(Cursor::new(jpeg))
+            .with_guessed_format()?
+            .decode()?
+            .to_rgb8();
+
+        Ok(Png {
+            width: decoded.width(),
+            height: decoded.height(),
+            gamma: 0,
+            color_type: ColorType::RGB,
+            pixel_buffer: decoded.into_raw(),
+            exif: None,
+        })
+    }
+
+    #[test]
+    fn test_magnitude() {
+        assert_eq!(magnitude(0), (0, 0));
+        assert_eq!(magnitude(1), (1, 1));
+        assert_eq!(magnitude(-1), (0, 1));
+        assert_eq!(magnitude(5), (0b101, 3));
+        assert_eq!(magnitude(-5), (0b010, 3));
+    }
+
+    #[test]
+    fn test_quality_controls_similarity() -> Result<()> {
+        let reference = read_png("./tests/obama.png")?;
+
+        let high = JpegEncoder::new(&reference).with_quality(95).encode()?;
+        let low = JpegEncoder::new(&reference).with_quality(10).encode()?;
+
+        let high_ssim = decode_jpeg(&high)?.compute_sim(&reference)?;
+        let low_ssim = decode_jpeg(&low)?.compute_sim(&reference)?;
+
+        assert!(high_ssim > 0.99, "ssim at quality 95: {}", high_ssim);
+        assert!(low_ssim < high_ssim);
+        assert!(low.len() < high.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subsampling() -> Result<()> {
+        let reference = read_png("./test_suite/basn6a08.png")?;
+
+        let full = JpegEncoder::new(&reference)
+            .with_subsampling(ChromaSubsampling::Yuv444)
+            .encode()?;
+        let subsampled = JpegEncoder::new(&reference)
+            .with_subsampling(ChromaSubsampling::Yuv420)
+            .encode()?;
+
+        assert!(decode_jpeg(&full)?.compute_sim(&reference)? > 0.95);
+        assert!(decode_jpeg(&subsampled)?.compute_sim(&reference)? > 0.95);
+        assert!(subsampled.len() < full.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_odd_dimensions() -> Result<()> {
+        let png = Png {
+            width: 13,
+            height: 7,
+            gamma: 0,
+            color_type: ColorType::Grayscale,
+            pixel_buffer: (0..13 * 7).map(|i| (i * 3) as u8).collect(),
+            exif: None,
+        };
+
+        let decoded = decode_jpeg(&JpegEncoder::new(&png).encode()?)?;
+        assert_eq!(decoded.dimensions(), (13, 7));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exif_passthrough() -> Result<()> {
+        let mut png = read_png("./test_suite/basn6a08.png")?;
+        png.exif = Some(b"MM\0*\0\0\0\x08\0\0".to_vec());
+
+        let jpeg = JpegEncoder::new(&png).encode()?;
+        let app1 = jpeg
+            .windows(2)
+            .position(|w| w == APP1.to_be_bytes())
+            .expect("missing APP1 segment");
+
+        assert_eq!(&jpeg[app1 + 4..app1 + 10], b"Exif\0\0");
+        assert_eq!(&jpeg[app1 + 10..app1 + 20], png.exif().unwrap());
+
+        let stripped = JpegEncoder::new(&png).with_exif(None).encode()?;
+        assert!(!stripped.windows(6).any(|w| w == b"Exif\0\0"));
+
+        Ok(())
+    }
+}
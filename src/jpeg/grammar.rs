@@ -0,0 +1,96 @@
+use anyhow::{bail, Result};
+
+pub const SOI: u16 = 0xFFD8;
+pub const EOI: u16 = 0xFFD9;
+pub const APP0: u16 = 0xFFE0;
+pub const APP1: u16 = 0xFFE1;
+pub const DQT: u16 = 0xFFDB;
+pub const SOF0: u16 = 0xFFC0;
+pub const DHT: u16 = 0xFFC4;
+pub const SOS: u16 = 0xFFDA;
+
+/// Maps the zig-zag scan position to the natural (row-major) position inside an 8x8 block.
+pub const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27,
+    20, 13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58,
+    59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// The example tables from Annex K.1 of the JPEG specification, in natural order.
+const LUMINANCE_QUANTIZATION: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69,
+    56, 14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104,
+    113, 92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+const CHROMINANCE_QUANTIZATION: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99,
+    99, 47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Every component is sampled at full resolution.
+    Yuv444,
+    /// Chroma is sampled at half resolution horizontally and vertically.
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    /// The (horizontal, vertical) sampling factors of the luma component.
+    pub(crate) const fn luma_sampling_factors(&self) -> (usize, usize) {
+        match self {
+            Self::Yuv444 => (1, 1),
+            Self::Yuv420 => (2, 2),
+        }
+    }
+}
+
+impl TryFrom<&str> for ChromaSubsampling {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let subsampling = match value {
+            "444" | "4:4:4" => Self::Yuv444,
+            "420" | "4:2:0" => Self::Yuv420,
+            foreign => bail!("Unrecognized chroma subsampling: {}", foreign),
+        };
+
+        Ok(subsampling)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct QuantizationTable {
+    /// Quantizer steps in natural order.
+    pub(crate) steps: [u8; 64],
+}
+
+impl QuantizationTable {
+    pub fn luminance(quality: u8) -> Self {
+        Self::scaled(&LUMINANCE_QUANTIZATION, quality)
+    }
+
+    pub fn chrominance(quality: u8) -> Self {
+        Self::scaled(&CHROMINANCE_QUANTIZATION, quality)
+    }
+
+    /// Scales a base table the same way the IJG reference encoder does. A quality of 50 yields the base table,
+    /// 100 yields all ones.
+    fn scaled(base: &[u8; 64], quality: u8) -> Self {
+        let quality = quality.clamp(1, 100) as u32;
+        let scale = if quality < 50 {
+            5000 / quality
+        } else {
+            200 - quality * 2
+        };
+
+        let mut steps = [0; 64];
+        for (step, &b) in steps.iter_mut().zip(base) {
+            *step = ((b as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+        }
+
+        Self { steps }
+    }
+}
@@ -0,0 +1,218 @@
+/// A Huffman table built from observed symbol frequencies, following Annex K.2 of the JPEG specification.
+#[derive(Debug)]
+pub struct HuffmanTable {
+    /// Number of codes of each length, from 1 to 16 bits.
+    pub(crate) bits: [u8; 16],
+    /// Symbols ordered by increasing code length.
+    pub(crate) values: Vec<u8>,
+    /// (code, code length) indexed by symbol.
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanTable {
+    pub(crate) fn from_frequencies(frequencies: &[u32; 256]) -> Self {
+        // Symbol 256 is reserved so that no real symbol is assigned the all-ones code.
+        let mut freq = [0u64; 257];
+        freq[..256]
+            .iter_mut()
+            .zip(frequencies)
+            .for_each(|(f, &g)| *f = g as u64);
+        freq[256] = 1;
+
+        let mut code_size = [0usize; 257];
+        let mut others = [None; 257];
+
+        while let Some((v1, v2)) = Self::two_least_frequent(&freq) {
+            freq[v1] += freq[v2];
+            freq[v2] = 0;
+
+            let mut v = v1;
+            code_size[v] += 1;
+            while let Some(next) = others[v] {
+                v = next;
+                code_size[v] += 1;
+            }
+
+            others[v] = Some(v2);
+
+            let mut v = v2;
+            code_size[v] += 1;
+            while let Some(next) = others[v] {
+                v = next;
+                code_size[v] += 1;
+            }
+        }
+
+        // A degenerate distribution can produce code lengths up to the number of symbols.
+        let mut bits = [0u16; 258];
+        for &size in code_size.iter().filter(|&&size| size > 0) {
+            bits[size] += 1;
+        }
+
+        // Limit code lengths to 16 bits (Annex K.3).
+        for i in (17..bits.len()).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+
+        // Remove the reserved code point.
+        let mut i = 16;
+        while bits[i] == 0 {
+            i -= 1;
+        }
+        bits[i] -= 1;
+
+        let mut values = Vec::new();
+        for size in 1..bits.len() {
+            for (symbol, &s) in code_size[..256].iter().enumerate() {
+                if s == size {
+                    values.push(symbol as u8);
+                }
+            }
+        }
+
+        let bits: [u8; 16] = std::array::from_fn(|i| bits[i + 1] as u8);
+
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut k = 0;
+
+        for (length, &count) in bits.iter().enumerate() {
+            for _ in 0..count {
+                codes[values[k] as usize] = (code, length as u8 + 1);
+                code += 1;
+                k += 1;
+            }
+
+            code <<= 1;
+        }
+
+        Self {
+            bits,
+            values,
+            codes,
+        }
+    }
+
+    /// Finds the two symbols with the smallest nonzero frequencies, or `None` once a single tree remains.
+    fn two_least_frequent(freq: &[u64; 257]) -> Option<(usize, usize)> {
+        let v1 = Self::least_frequent(freq, None)?;
+        let v2 = Self::least_frequent(freq, Some(v1))?;
+
+        Some((v1, v2))
+    }
+
+    /// Finds the symbol with the smallest nonzero frequency, preferring the larger symbol on ties.
+    fn least_frequent(freq: &[u64; 257], exclude: Option<usize>) -> Option<usize> {
+        let mut least: Option<usize> = None;
+
+        for (v, &f) in freq.iter().enumerate() {
+            if f == 0 || Some(v) == exclude {
+                continue;
+            }
+
+            if least.is_none_or(|l| f <= freq[l]) {
+                least = Some(v);
+            }
+        }
+
+        least
+    }
+
+    pub(crate) const fn code(&self, symbol: u8) -> (u16, u8) {
+        self.codes[symbol as usize]
+    }
+}
+
+/// Packs variable length codes MSB-first and applies JPEG byte stuffing.
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    out: Vec<u8>,
+    accumulator: u32,
+    len: u8,
+}
+
+impl BitWriter {
+    pub(crate) fn write(&mut self, bits: u16, len: u8) {
+        if len == 0 {
+            return;
+        }
+
+        self.accumulator = (self.accumulator << len) | (bits as u32 & ((1 << len) - 1));
+        self.len += len;
+
+        while self.len >= 8 {
+            let byte = (self.accumulator >> (self.len - 8)) as u8;
+            self.out.push(byte);
+
+            if byte == 0xFF {
+                self.out.push(0x00);
+            }
+
+            self.len -= 8;
+        }
+    }
+
+    /// Pads the final byte with one bits and returns the entropy coded segment.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            let pad = 8 - self.len;
+            self.write((1 << pad) - 1, pad);
+        }
+
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_lengths_follow_frequencies() {
+        let mut frequencies = [0; 256];
+        frequencies[0] = 100;
+        frequencies[1] = 50;
+        frequencies[2] = 1;
+
+        let table = HuffmanTable::from_frequencies(&frequencies);
+
+        assert_eq!(table.values, vec![0, 1, 2]);
+        assert!(table.code(0).1 <= table.code(1).1);
+        assert!(table.code(1).1 <= table.code(2).1);
+        assert_ne!(table.code(2).0, (1 << table.code(2).1) - 1);
+    }
+
+    #[test]
+    fn code_lengths_are_limited_to_16_bits() {
+        let mut frequencies = [0; 256];
+        let mut f = 1u32;
+        for freq in frequencies.iter_mut().take(40) {
+            *freq = f;
+            f = f.saturating_mul(2);
+        }
+
+        let table = HuffmanTable::from_frequencies(&frequencies);
+
+        assert_eq!(table.bits.iter().map(|&b| b as usize).sum::<usize>(), 40);
+        assert!((0..40).all(|s| (1..=16).contains(&table.code(s).1)));
+    }
+
+    #[test]
+    fn stuffs_ff_bytes() {
+        let mut writer = BitWriter::default();
+        writer.write(0xFF, 8);
+        writer.write(0b1, 1);
+
+        assert_eq!(writer.finish(), vec![0xFF, 0x00, 0xFF, 0x00]);
+    }
+}
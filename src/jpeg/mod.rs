@@ -0,0 +1,6 @@
+pub use encoder::*;
+pub mod grammar;
+
+mod dct;
+mod encoder;
+mod huffman;
@@ -3,7 +3,17 @@
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+pub mod bmp;
+pub mod cube;
+pub mod farbfeld;
 pub mod font;
+pub mod gif;
+pub mod hdr;
+pub mod jpeg;
+pub mod ops;
 pub mod png;
+pub mod pnm;
 pub mod renderer;
+pub mod tga;
 pub mod util;
+pub mod webp;
@@ -1,6 +1,11 @@
-use anyhow::{anyhow, Result};
-use iris::{png::PngDecoder, renderer};
+use anyhow::{anyhow, bail, Result};
+use iris::{
+    bmp::BmpDecoder, cube::CubeDecoder, farbfeld::FarbfeldDecoder, gif::GifDecoder,
+    hdr::HdrDecoder, png::PngDecoder, pnm::PnmDecoder, renderer, tga::TgaDecoder,
+    webp::WebpDecoder,
+};
 use pollster::block_on;
+use std::path::Path;
 
 fn main() -> Result<()> {
     let mut args = std::env::args().skip(1);
@@ -8,11 +13,47 @@ fn main() -> Result<()> {
         .next()
         .ok_or_else(|| anyhow!("Failed to read image path"))?;
 
-    let content = std::fs::read(image_path)?;
-    let mut decoder = PngDecoder::new(&content);
-    let png = decoder.decode()?;
+    let content = std::fs::read(&image_path)?;
 
-    let _ = block_on(renderer::run(png));
+    let lut = match (args.next().as_deref(), args.next()) {
+        (Some("--lut"), Some(lut_path)) => {
+            Some(CubeDecoder::new(&std::fs::read(lut_path)?).decode()?)
+        }
+        (None, _) => None,
+        _ => bail!("Usage: iris <image> [--lut <file.cube>]"),
+    };
+
+    let extension = Path::new(&image_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let png = match extension.as_str() {
+        "gif" => {
+            let gif = GifDecoder::new(&content).decode()?;
+            let _ = block_on(renderer::run_animation(
+                gif.animation_frames().collect(),
+                gif.loop_count(),
+                lut,
+            ));
+
+            return Ok(());
+        }
+        "hdr" => {
+            let hdr = HdrDecoder::new(&content).decode()?;
+            let _ = block_on(renderer::run_hdr(hdr, lut));
+
+            return Ok(());
+        }
+        "bmp" => BmpDecoder::new(&content).decode()?,
+        "tga" => TgaDecoder::new(&content).decode()?,
+        "pbm" | "pgm" | "ppm" | "pam" | "pnm" => PnmDecoder::new(&content).decode()?,
+        "ff" => FarbfeldDecoder::new(&content).decode()?,
+        "webp" => WebpDecoder::new(&content).decode()?,
+        _ => PngDecoder::new(&content).decode()?,
+    };
+
+    let _ = block_on(renderer::run(png, lut));
 
     Ok(())
 }
@@ -0,0 +1,468 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Tonal and color adjustments. White balance scales linear light, everything else works on sRGB
+//! encoded values, where the controls behave the way they do in other editors.
+
+use crate::ops::LinearImage;
+use crate::util::color::{linear_to_srgb, srgb_to_linear};
+
+/// Entries in a curve lookup table, one per 8-bit input level.
+pub const CURVE_LUT_SIZE: usize = 256;
+
+/// Remaps `[black, white]` to `[0, 1]`, then applies `gamma` to the midtones.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Levels {
+    pub black: f32,
+    pub white: f32,
+    pub gamma: f32,
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            black: 0.0,
+            white: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Levels {
+    pub fn apply(&self, v: f32) -> f32 {
+        let range = (self.white - self.black).max(1e-4);
+        ((v - self.black) / range)
+            .clamp(0.0, 1.0)
+            .powf(1.0 / self.gamma)
+    }
+}
+
+/// A tone curve through control points in `[0, 1]`, interpolated with a monotone cubic so it
+/// never overshoots between points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    points: Vec<(f32, f32)>,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Curve {
+    pub fn identity() -> Self {
+        Self {
+            points: vec![(0.0, 0.0), (1.0, 1.0)],
+        }
+    }
+
+    /// Points are sorted by input. Fewer than two points make the identity curve.
+    pub fn new(mut points: Vec<(f32, f32)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points.dedup_by(|a, b| a.0 == b.0);
+
+        if points.len() < 2 {
+            return Self::identity();
+        }
+
+        Self { points }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.points == [(0.0, 0.0), (1.0, 1.0)]
+    }
+
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let points = &self.points;
+        let last = points.len() - 1;
+
+        if x <= points[0].0 {
+            return points[0].1.clamp(0.0, 1.0);
+        }
+
+        if x >= points[last].0 {
+            return points[last].1.clamp(0.0, 1.0);
+        }
+
+        let i = points
+            .windows(2)
+            .position(|w| x < w[1].0)
+            .unwrap_or(last - 1);
+        let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+        let h = x1 - x0;
+        let (m0, m1) = (self.tangent(i), self.tangent(i + 1));
+
+        // Cubic Hermite basis.
+        let t = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        let y = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * m0
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * m1;
+
+        y.clamp(0.0, 1.0)
+    }
+
+    fn secant(&self, i: usize) -> f32 {
+        let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+        (y1 - y0) / (x1 - x0)
+    }
+
+    /// Fritsch-Carlson tangents: zero at extrema, otherwise the harmonic mean of the neighbouring
+    /// secants, which keeps monotone data monotone.
+    fn tangent(&self, i: usize) -> f32 {
+        let last = self.points.len() - 1;
+
+        if i == 0 {
+            return self.secant(0);
+        }
+
+        if i == last {
+            return self.secant(last - 1);
+        }
+
+        let (before, after) = (self.secant(i - 1), self.secant(i));
+        if before * after <= 0.0 {
+            return 0.0;
+        }
+
+        2.0 / (1.0 / before + 1.0 / after)
+    }
+}
+
+/// A master curve applied to every channel, followed by one curve per channel.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Curves {
+    pub master: Curve,
+    pub red: Curve,
+    pub green: Curve,
+    pub blue: Curve,
+}
+
+impl Curves {
+    /// Deepens shadows and brightens highlights.
+    pub fn s_curve() -> Self {
+        Self {
+            master: Curve::new(vec![(0.0, 0.0), (0.25, 0.18), (0.75, 0.82), (1.0, 1.0)]),
+            ..Default::default()
+        }
+    }
+
+    /// Lifts the blacks and dims the whites, like faded film.
+    pub fn fade() -> Self {
+        Self {
+            master: Curve::new(vec![(0.0, 0.12), (0.5, 0.52), (1.0, 0.92)]),
+            ..Default::default()
+        }
+    }
+
+    /// Cross processing: contrasty reds and greens, flattened blues.
+    pub fn cross_process() -> Self {
+        Self {
+            red: Curve::new(vec![(0.0, 0.0), (0.3, 0.22), (0.7, 0.82), (1.0, 1.0)]),
+            green: Curve::new(vec![(0.0, 0.0), (0.3, 0.25), (0.7, 0.78), (1.0, 1.0)]),
+            blue: Curve::new(vec![(0.0, 0.15), (1.0, 0.85)]),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        [&self.master, &self.red, &self.green, &self.blue]
+            .iter()
+            .all(|curve| curve.is_identity())
+    }
+
+    /// Samples the composed curves at every 8-bit level, as RGBA with alpha left at 1.
+    pub fn lut(&self) -> Vec<[f32; 4]> {
+        (0..CURVE_LUT_SIZE)
+            .map(|i| {
+                let v = self.master.evaluate(i as f32 / (CURVE_LUT_SIZE - 1) as f32);
+                [
+                    self.red.evaluate(v),
+                    self.green.evaluate(v),
+                    self.blue.evaluate(v),
+                    1.0,
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Every adjustment, neutral by default.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Adjustments {
+    pub levels: Levels,
+    pub curves: Curves,
+    /// Added to every channel, in `[-1, 1]`.
+    pub brightness: f32,
+    /// In `[-1, 1]`, stretching values away from or towards middle gray.
+    pub contrast: f32,
+    /// Rotation around the color wheel, in degrees.
+    pub hue: f32,
+    /// In `[-1, 1]`, where -1 is fully desaturated.
+    pub saturation: f32,
+    /// In `[-1, 1]`, blending towards black or white.
+    pub lightness: f32,
+    /// Warmer when positive, cooler when negative, in `[-1, 1]`.
+    pub temperature: f32,
+    /// Magenta when positive, green when negative, in `[-1, 1]`.
+    pub tint: f32,
+}
+
+impl Adjustments {
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Linear light channel gains for the white balance.
+    pub fn white_balance(&self) -> [f32; 3] {
+        [
+            1.0 + 0.3 * self.temperature,
+            1.0 - 0.3 * self.tint,
+            1.0 - 0.3 * self.temperature,
+        ]
+    }
+
+    /// The slope contrast stretches values around middle gray by.
+    pub fn contrast_factor(&self) -> f32 {
+        let contrast = self.contrast.clamp(-0.99, 0.99);
+        (1.0 + contrast) / (1.0 - contrast)
+    }
+}
+
+/// Applies `adjustments` in the order the renderer does.
+pub fn adjust(image: &LinearImage, adjustments: &Adjustments) -> LinearImage {
+    if adjustments.is_neutral() {
+        return image.clone();
+    }
+
+    let gains = adjustments.white_balance();
+    let lut = (!adjustments.curves.is_identity()).then(|| adjustments.curves.lut());
+    let contrast = adjustments.contrast_factor();
+
+    image.map(|[r, g, b, a]| {
+        let mut rgb = [r, g, b];
+
+        for c in 0..3 {
+            let v = linear_to_srgb((rgb[c] * gains[c]).clamp(0.0, 1.0));
+            let v = adjustments.levels.apply(v);
+            let v = lut.as_ref().map_or(v, |lut| sample_lut(lut, c, v));
+
+            rgb[c] = ((v - 0.5) * contrast + 0.5 + adjustments.brightness).clamp(0.0, 1.0);
+        }
+
+        let [r, g, b] = adjust_hsl(rgb, adjustments);
+
+        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+    })
+}
+
+/// Linear interpolation between table entries, like the renderer's filtered texture lookup.
+fn sample_lut(lut: &[[f32; 4]], channel: usize, v: f32) -> f32 {
+    let x = v.clamp(0.0, 1.0) * (CURVE_LUT_SIZE - 1) as f32;
+    let i = (x.floor() as usize).min(CURVE_LUT_SIZE - 2);
+    let t = x - i as f32;
+
+    lut[i][channel] + (lut[i + 1][channel] - lut[i][channel]) * t
+}
+
+fn adjust_hsl(rgb: [f32; 3], adjustments: &Adjustments) -> [f32; 3] {
+    if adjustments.hue == 0.0 && adjustments.saturation == 0.0 && adjustments.lightness == 0.0 {
+        return rgb;
+    }
+
+    let [h, s, l] = rgb_to_hsl(rgb);
+
+    let h = (h + adjustments.hue / 360.0).rem_euclid(1.0);
+    let s = (s * (1.0 + adjustments.saturation)).clamp(0.0, 1.0);
+    let l = if adjustments.lightness > 0.0 {
+        l + (1.0 - l) * adjustments.lightness
+    } else {
+        l * (1.0 + adjustments.lightness)
+    };
+
+    hsl_to_rgb([h, s, l.clamp(0.0, 1.0)])
+}
+
+/// Hue, saturation and lightness, each in `[0, 1]`.
+pub fn rgb_to_hsl([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return [0.0, 0.0, l];
+    }
+
+    let s = delta / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+
+    [h / 6.0, s, l]
+}
+
+pub fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = h * 6.0;
+    let x = chroma * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let m = l - chroma / 2.0;
+
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    [r + m, g + m, b + m]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgb: [f32; 3]) -> LinearImage {
+        LinearImage {
+            width: 1,
+            height: 1,
+            pixels: vec![[
+                srgb_to_linear(rgb[0]),
+                srgb_to_linear(rgb[1]),
+                srgb_to_linear(rgb[2]),
+                1.0,
+            ]],
+        }
+    }
+
+    fn adjusted(rgb: [f32; 3], adjustments: &Adjustments) -> [f32; 3] {
+        let [r, g, b, _] = adjust(&pixel(rgb), adjustments).pixels[0];
+        [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)]
+    }
+
+    fn assert_rgb(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-3, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_hsl_round_trip() {
+        for rgb in [
+            [0.2, 0.4, 0.6],
+            [0.9, 0.1, 0.3],
+            [0.5, 0.5, 0.5],
+            [1.0, 1.0, 0.0],
+        ] {
+            assert_rgb(hsl_to_rgb(rgb_to_hsl(rgb)), rgb);
+        }
+
+        assert_rgb(rgb_to_hsl([1.0, 0.0, 0.0]), [0.0, 1.0, 0.5]);
+        assert_rgb(rgb_to_hsl([0.0, 0.0, 1.0]), [2.0 / 3.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_levels() {
+        let adjustments = Adjustments {
+            levels: Levels {
+                black: 0.2,
+                white: 0.6,
+                gamma: 1.0,
+            },
+            ..Default::default()
+        };
+
+        assert_rgb(adjusted([0.1, 0.4, 0.8], &adjustments), [0.0, 0.5, 1.0]);
+
+        let levels = Levels {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert!((levels.apply(0.25) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_brightness_contrast_hsl() {
+        let brighter = Adjustments {
+            brightness: 0.1,
+            ..Default::default()
+        };
+        assert_rgb(adjusted([0.2, 0.4, 0.6], &brighter), [0.3, 0.5, 0.7]);
+
+        let contrast = Adjustments {
+            contrast: 1.0 / 3.0,
+            ..Default::default()
+        };
+        assert_rgb(adjusted([0.25, 0.5, 0.7], &contrast), [0.0, 0.5, 0.9]);
+
+        let gray = Adjustments {
+            saturation: -1.0,
+            ..Default::default()
+        };
+        assert_rgb(adjusted([1.0, 0.0, 0.0], &gray), [0.5, 0.5, 0.5]);
+
+        let shifted = Adjustments {
+            hue: 120.0,
+            ..Default::default()
+        };
+        assert_rgb(adjusted([1.0, 0.0, 0.0], &shifted), [0.0, 1.0, 0.0]);
+
+        let white = Adjustments {
+            lightness: 1.0,
+            ..Default::default()
+        };
+        assert_rgb(adjusted([0.3, 0.6, 0.1], &white), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_white_balance() {
+        let warm = Adjustments {
+            temperature: 0.5,
+            ..Default::default()
+        };
+        let [r, g, b] = adjusted([0.5, 0.5, 0.5], &warm);
+
+        assert!(r > g && g > b);
+        assert_eq!(g, 0.5);
+    }
+
+    #[test]
+    fn test_curves() {
+        let curve = Curve::new(vec![(1.0, 1.0), (0.0, 0.0), (0.5, 0.8)]);
+
+        assert_eq!(curve.evaluate(0.0), 0.0);
+        assert!((curve.evaluate(0.5) - 0.8).abs() < 1e-6);
+        assert_eq!(curve.evaluate(1.0), 1.0);
+
+        // Monotone input stays monotone, without overshooting the control points.
+        let samples = (0..=100)
+            .map(|i| curve.evaluate(i as f32 / 100.0))
+            .collect::<Vec<_>>();
+        assert!(samples.windows(2).all(|w| w[0] <= w[1]));
+
+        assert!(Curves::default().is_identity());
+        let lut = Curves::default().lut();
+        assert_eq!(lut.len(), CURVE_LUT_SIZE);
+        let [r, g, b, _] = lut[128];
+        assert_rgb([r, g, b], [128.0 / 255.0; 3]);
+
+        let faded = Adjustments {
+            curves: Curves::fade(),
+            ..Default::default()
+        };
+        assert_rgb(adjusted([0.0, 0.5, 1.0], &faded), [0.12, 0.52, 0.92]);
+    }
+
+    #[test]
+    fn test_neutral_is_identity() {
+        let image = pixel([0.1, 0.5, 0.9]);
+        assert_eq!(adjust(&image, &Adjustments::default()), image);
+    }
+}
@@ -0,0 +1,102 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Per-pixel features, in the order `image_shader.wgsl` applies them.
+
+use crate::hdr::tone_map::ToneMap;
+use crate::ops::LinearImage;
+
+/// Scales by `exposure` stops, then compresses with `tone_map`.
+pub fn tone_map(image: &LinearImage, exposure: f32, tone_map: ToneMap) -> LinearImage {
+    let scale = exposure.exp2();
+
+    image.map(|[r, g, b, a]| {
+        [
+            tone_map.apply(r * scale),
+            tone_map.apply(g * scale),
+            tone_map.apply(b * scale),
+            a,
+        ]
+    })
+}
+
+/// Corrects for a `gAMA` chunk, stored as gamma * 100,000.
+///
+/// Sampling decodes every texel as sRGB, roughly a 2.2 exponent, where the file says it was
+/// encoded with `gamma`. Zero means the chunk was absent and leaves the image alone.
+/// See https://www.w3.org/TR/2003/REC-PNG-20031110/#13Decoder-gamma-handling
+pub fn gamma(image: &LinearImage, gamma: u32) -> LinearImage {
+    if gamma == 0 {
+        return image.clone();
+    }
+
+    let exponent = 1.0 / (2.2 * gamma as f32 / 100_000.0);
+
+    image.map(|[r, g, b, a]| [r.powf(exponent), g.powf(exponent), b.powf(exponent), a])
+}
+
+/// Luma with the renderer's weights. Like the shader, this drops alpha.
+pub fn grayscale(image: &LinearImage) -> LinearImage {
+    image.map(|[r, g, b, _]| {
+        let y = r * 0.29891 + g * 0.58661 + b * 0.11448;
+        [y, y, y, 1.0]
+    })
+}
+
+pub fn invert(image: &LinearImage) -> LinearImage {
+    image.map(|[r, g, b, a]| [1.0 - r, 1.0 - g, 1.0 - b, a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: Vec<[f32; 4]>) -> LinearImage {
+        LinearImage {
+            width: pixels.len() as u32,
+            height: 1,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn test_grayscale_and_invert() {
+        let source = image(vec![[1.0, 0.0, 0.0, 0.5], [0.25, 0.5, 0.75, 1.0]]);
+
+        let gray = grayscale(&source);
+        assert_eq!(gray.pixels[0], [0.29891, 0.29891, 0.29891, 1.0]);
+
+        let inverted = invert(&source);
+        assert_eq!(inverted.pixels[0], [0.0, 1.0, 1.0, 0.5]);
+        assert_eq!(invert(&inverted), source);
+    }
+
+    #[test]
+    fn test_gamma() {
+        let source = image(vec![[0.25, 0.5, 1.0, 0.5]]);
+
+        assert_eq!(gamma(&source, 0), source);
+        // A file gamma of 1 / 2.2 is what sampling already assumed.
+        for (a, b) in gamma(&source, 45_455).pixels[0]
+            .iter()
+            .zip(source.pixels[0])
+        {
+            assert!((a - b).abs() < 1e-4);
+        }
+        // Linear data was over-darkened by the sRGB decode.
+        assert!(gamma(&source, 100_000).pixels[0][0] > 0.25);
+    }
+
+    #[test]
+    fn test_tone_map() {
+        let source = image(vec![[0.5, 2.0, 0.0, 0.25]]);
+
+        assert_eq!(
+            tone_map(&source, 1.0, ToneMap::None).pixels[0],
+            [1.0, 1.0, 0.0, 0.25]
+        );
+        assert_eq!(
+            tone_map(&source, 0.0, ToneMap::Reinhard).pixels[0],
+            [0.5 / 1.5, 2.0 / 3.0, 0.0, 0.25]
+        );
+    }
+}
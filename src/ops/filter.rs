@@ -0,0 +1,252 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Neighbourhood filters, sampled the same way as `image_shader.wgsl`.
+
+use crate::ops::LinearImage;
+
+impl LinearImage {
+    fn filter(&self, f: impl Fn(u32, u32) -> [f32; 4]) -> Self {
+        let pixels = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+/// Gaussian blur over a `radius` x `radius` window with sigma `radius / 4`.
+///
+/// The shader walks offsets from `-radius / 2` in whole pixel steps, so odd radii land halfway
+/// between texels and linear filtering splits each tap over two of them. Both the kernel and the
+/// filtering are separable, which keeps this affordable on the CPU.
+pub fn gaussian_blur(image: &LinearImage, radius: u32) -> LinearImage {
+    let half = radius as f32 / 2.0;
+    let sigma = radius as f32 * 0.25;
+
+    let mut taps = Vec::<(i64, f32)>::new();
+    let mut add_tap = |offset: i64, weight: f32| match taps.last_mut() {
+        Some((last, w)) if *last == offset => *w += weight,
+        _ => taps.push((offset, weight)),
+    };
+
+    // Offsets `-half + i` for every whole step still below `half`.
+    for i in 0..radius {
+        let x = i as f32 - half;
+        let weight = (-(x * x) / (2.0 * sigma * sigma)).exp();
+        let floor = x.floor();
+        let fraction = x - floor;
+
+        add_tap(floor as i64, weight * (1.0 - fraction));
+        if fraction > 0.0 {
+            add_tap(floor as i64 + 1, weight * fraction);
+        }
+    }
+
+    let total = taps.iter().map(|&(_, w)| w).sum::<f32>();
+    taps.iter_mut().for_each(|(_, w)| *w /= total);
+
+    let horizontal = image.filter(|x, y| convolve(&taps, |d| image.texel(x as i64 + d, y as i64)));
+    horizontal.filter(|x, y| convolve(&taps, |d| horizontal.texel(x as i64, y as i64 + d)))
+}
+
+fn convolve(taps: &[(i64, f32)], texel: impl Fn(i64) -> [f32; 4]) -> [f32; 4] {
+    let mut color = [0.0; 4];
+
+    for &(offset, weight) in taps {
+        for (c, s) in color.iter_mut().zip(texel(offset)) {
+            *c += s * weight;
+        }
+    }
+
+    color
+}
+
+/// Unsharp mask with a 4-neighbour Laplacian scaled by `factor`.
+pub fn sharpen(image: &LinearImage, factor: u32) -> LinearImage {
+    let k = factor as f32;
+
+    image.filter(|x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let center = image.texel(x, y);
+        let neighbours =
+            [(0, 1), (-1, 0), (1, 0), (0, -1)].map(|(dx, dy)| image.texel(x + dx, y + dy));
+
+        std::array::from_fn(|i| {
+            (1.0 + 4.0 * k) * center[i] - k * neighbours.iter().map(|n| n[i]).sum::<f32>()
+        })
+    })
+}
+
+/// Sobel gradient magnitude of the RGB vector length, as an opaque gray image.
+pub fn detect_edges(image: &LinearImage) -> LinearImage {
+    let intensity = |[r, g, b, _]: [f32; 4]| (r * r + g * g + b * b).sqrt();
+
+    image.filter(|x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let at = |dx: i64, dy: i64| intensity(image.texel(x + dx, y + dy));
+
+        // Texture space `y` grows downwards, so the shader's "top" row is the one below.
+        let (top_left, top, top_right) = (at(-1, 1), at(0, 1), at(1, 1));
+        let (left, right) = (at(-1, 0), at(1, 0));
+        let (bottom_left, bottom, bottom_right) = (at(-1, -1), at(0, -1), at(1, -1));
+
+        let gx = top_left + 2.0 * left + bottom_left - top_right - 2.0 * right - bottom_right;
+        // The shader reads `left` where Sobel has `top_left`; kept so both paths agree.
+        let gy = -left - 2.0 * top - top_right + bottom_left + 2.0 * bottom + bottom_right;
+
+        let c = gx.hypot(gy);
+        [c, c, c, 1.0]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::tests::{read_png, renderer, ssim_against};
+    use crate::renderer::RenderSettings;
+    use anyhow::Result;
+
+    fn constant(width: u32, height: u32, color: [f32; 4]) -> LinearImage {
+        LinearImage {
+            width,
+            height,
+            pixels: vec![color; (width * height) as usize],
+        }
+    }
+
+    #[test]
+    fn test_filters_keep_flat_images() {
+        let image = constant(9, 7, [0.25, 0.5, 0.75, 1.0]);
+
+        for blurred in [gaussian_blur(&image, 3), gaussian_blur(&image, 4)] {
+            for (a, b) in blurred
+                .pixels
+                .iter()
+                .flatten()
+                .zip(image.pixels.iter().flatten())
+            {
+                assert!((a - b).abs() < 1e-5);
+            }
+        }
+
+        assert_eq!(sharpen(&image, 16), image);
+        for edge in detect_edges(&image).pixels {
+            assert!(edge[0] < 1e-5 && edge[3] == 1.0);
+        }
+    }
+
+    #[test]
+    fn test_gaussian_blur_reference() -> Result<()> {
+        let image = LinearImage::from_png(&read_png("./tests/vangogh-normal.png")?);
+        let blurred = gaussian_blur(&image, 21).to_png();
+
+        let ssim = ssim_against(&blurred, "./tests/vangogh-gaussian-blur.png")?;
+        assert!(ssim > 0.98, "ssim {ssim}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sharpen_reference() -> Result<()> {
+        let image = LinearImage::from_png(&read_png("./tests/vangogh-normal.png")?);
+        let sharpened = sharpen(&image, 16).to_png();
+
+        let ssim = ssim_against(&sharpened, "./tests/vangogh-sharpen.png")?;
+        assert!(ssim > 0.9, "ssim {ssim}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edge_detect_reference() -> Result<()> {
+        let image = LinearImage::from_png(&read_png("./tests/vangogh-normal.png")?);
+        let edges = detect_edges(&image).to_png();
+
+        let ssim = ssim_against(&edges, "./tests/vangogh-edge-detect.png")?;
+        assert!(ssim > 0.85, "ssim {ssim}");
+
+        Ok(())
+    }
+
+    /// Renders `settings` on the GPU and checks every sample against `expected` in linear light.
+    fn assert_matches_shader(
+        settings: RenderSettings,
+        expected: &LinearImage,
+        tolerance: f32,
+    ) -> Result<()> {
+        let renderer = renderer()?;
+
+        let png = read_png("./tests/vangogh-normal.png")?;
+        let rendered = LinearImage::from_png(&renderer.render(&png, &settings)?);
+        // Both sides went through 8-bit sRGB, so identical results can still be a step apart.
+        let expected = LinearImage::from_png(&expected.to_png());
+
+        assert_eq!(
+            (rendered.width, rendered.height),
+            (expected.width, expected.height)
+        );
+        for (i, (a, b)) in rendered.pixels.iter().zip(&expected.pixels).enumerate() {
+            for (x, y) in a.iter().zip(b) {
+                assert!(
+                    (x - y).abs() <= tolerance,
+                    "{settings:?} differs at pixel {i}: {a:?} against {b:?}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_gaussian_blur_matches_shader() -> Result<()> {
+        let image = LinearImage::from_png(&read_png("./tests/vangogh-normal.png")?);
+
+        // Odd radii sample halfway between texels, even ones on texel centers.
+        for radius in [3, 4, 21] {
+            let settings = RenderSettings {
+                blur_radius: Some(radius),
+                ..Default::default()
+            };
+            assert_matches_shader(settings, &gaussian_blur(&image, radius), 0.01)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_sharpen_matches_shader() -> Result<()> {
+        let image = LinearImage::from_png(&read_png("./tests/vangogh-normal.png")?);
+
+        // The kernel's weights add up to `1 + 8 * factor` in magnitude, and the GPU's texture
+        // filtering carries about a thousandth of error into each tap.
+        for factor in [1, 4, 16] {
+            let settings = RenderSettings {
+                sharpen_factor: Some(factor),
+                ..Default::default()
+            };
+            let tolerance = 0.01 + 0.001 * (1 + 8 * factor) as f32;
+            assert_matches_shader(settings, &sharpen(&image, factor), tolerance)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_edge_detect_matches_shader() -> Result<()> {
+        let image = LinearImage::from_png(&read_png("./tests/vangogh-normal.png")?);
+        let settings = RenderSettings {
+            edge_detect: true,
+            ..Default::default()
+        };
+
+        assert_matches_shader(settings, &detect_edges(&image), 0.02)
+    }
+}
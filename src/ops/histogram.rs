@@ -0,0 +1,205 @@
+//! Contrast enhancement by histogram equalization. The mapping is built from the luma of sRGB
+//! encoded values and applied to each channel alike, so hues are kept.
+
+use crate::ops::LinearImage;
+use crate::png::stats::{luma, Histogram, BINS};
+use crate::util::color::{linear_to_srgb, srgb_to_linear, unit_to_u8};
+
+type Mapping = [f32; BINS];
+
+/// Spreads the luma histogram over the full range.
+pub fn equalize(image: &LinearImage) -> LinearImage {
+    let encoded = encode(image);
+    let histogram = Histogram::from_samples(encoded.iter().map(|&(_, l)| l));
+    let mapping = equalization_mapping(&histogram);
+
+    apply(image, &encoded, |_, _, v| mapping[v as usize])
+}
+
+/// Contrast limited adaptive histogram equalization.
+///
+/// Each tile of a `tiles_x` x `tiles_y` grid is equalized on its own, with bins clipped at `clip_limit` times the average bin count so
+/// noise in flat regions isn't amplified. Pixels blend the mappings of the nearest four tiles.
+pub fn clahe(image: &LinearImage, (tiles_x, tiles_y): (u32, u32), clip_limit: f32) -> LinearImage {
+    let (width, height) = image.dimensions();
+    let tiles_x = tiles_x.clamp(1, width.max(1));
+    let tiles_y = tiles_y.clamp(1, height.max(1));
+
+    let encoded = encode(image);
+    let bounds = |i: u32, tiles: u32, size: u32| {
+        (i * size / tiles) as usize..((i + 1) * size / tiles) as usize
+    };
+
+    let mappings = (0..tiles_y)
+        .flat_map(|ty| (0..tiles_x).map(move |tx| (tx, ty)))
+        .map(|(tx, ty)| {
+            let rows = bounds(ty, tiles_y, height);
+            let columns = bounds(tx, tiles_x, width);
+
+            let mut histogram = Histogram::from_samples(rows.flat_map(|y| {
+                let row = y * width as usize;
+                encoded[row + columns.start..row + columns.end]
+                    .iter()
+                    .map(|&(_, l)| l)
+            }));
+            clip(&mut histogram, clip_limit);
+
+            equalization_mapping(&histogram)
+        })
+        .collect::<Vec<_>>();
+
+    // Tile centers, in tile units, for interpolating between neighbouring mappings.
+    let neighbours = |position: u32, tiles: u32, size: u32| {
+        let center = ((position as f32 + 0.5) * tiles as f32 / size as f32 - 0.5)
+            .clamp(0.0, (tiles - 1) as f32);
+        let first = center.floor() as usize;
+
+        (
+            first,
+            (first + 1).min(tiles as usize - 1),
+            center - first as f32,
+        )
+    };
+
+    apply(image, &encoded, |x, y, v| {
+        let (x0, x1, tx) = neighbours(x, tiles_x, width);
+        let (y0, y1, ty) = neighbours(y, tiles_y, height);
+        let mapping = |tx: usize, ty: usize| mappings[ty * tiles_x as usize + tx][v as usize];
+
+        let top = (mapping(x1, y0) - mapping(x0, y0)).mul_add(tx, mapping(x0, y0));
+        let bottom = (mapping(x1, y1) - mapping(x0, y1)).mul_add(tx, mapping(x0, y1));
+
+        (bottom - top).mul_add(ty, top)
+    })
+}
+
+/// Maps each value to its rank, the classic equalization transfer function.
+fn equalization_mapping(histogram: &Histogram) -> Mapping {
+    let cumulative = histogram.cumulative();
+    let total = cumulative[BINS - 1];
+    let first = cumulative.iter().copied().find(|&n| n > 0).unwrap_or(0);
+
+    if total == first {
+        return std::array::from_fn(|v| v as f32 / (BINS - 1) as f32);
+    }
+
+    cumulative.map(|n| (n.saturating_sub(first)) as f32 / (total - first) as f32)
+}
+
+/// Caps every bin at `clip_limit` times the average, handing the excess out evenly.
+fn clip(histogram: &mut Histogram, clip_limit: f32) {
+    let limit = ((clip_limit * histogram.count() as f32 / BINS as f32) as u32).max(1);
+    let bins = histogram.bins();
+
+    let excess = bins.iter().map(|&n| n.saturating_sub(limit)).sum::<u32>();
+    let (share, remainder) = (excess / BINS as u32, excess % BINS as u32);
+
+    let clipped =
+        std::array::from_fn(|v| bins[v].min(limit) + share + (v < remainder as usize) as u32);
+
+    *histogram = Histogram::from_bins(clipped);
+}
+
+/// The sRGB encoded 8-bit channels and luma of each pixel.
+fn encode(image: &LinearImage) -> Vec<([u8; 3], u8)> {
+    image
+        .pixels()
+        .iter()
+        .map(|&[r, g, b, _]| {
+            let rgb = [r, g, b].map(|c| linear_to_srgb(c.clamp(0.0, 1.0)));
+            (rgb.map(unit_to_u8), unit_to_u8(luma(rgb)))
+        })
+        .collect()
+}
+
+/// Remaps every channel with `mapping(x, y, value)`, which returns an encoded value in [0, 1].
+fn apply(
+    image: &LinearImage,
+    encoded: &[([u8; 3], u8)],
+    mapping: impl Fn(u32, u32, u8) -> f32,
+) -> LinearImage {
+    let (width, _) = image.dimensions();
+
+    let pixels = image
+        .pixels()
+        .iter()
+        .zip(encoded)
+        .enumerate()
+        .map(|(i, (&[_, _, _, a], &(rgb, _)))| {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            let [r, g, b] = rgb.map(|v| srgb_to_linear(mapping(x, y, v)));
+
+            [r, g, b, a]
+        })
+        .collect();
+
+    LinearImage { pixels, ..*image }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::grammar::{ColorType, Png};
+
+    /// A horizontal gradient squeezed into the gray levels [96, 160).
+    fn low_contrast(width: u32, height: u32) -> LinearImage {
+        let pixel_buffer = (0..height)
+            .flat_map(|_| (0..width).map(|x| (96 + x * 64 / width) as u8))
+            .collect();
+
+        LinearImage::from_png(&Png {
+            width,
+            height,
+            gamma: 0,
+            color_type: ColorType::Grayscale,
+            pixel_buffer,
+            exif: None,
+        })
+    }
+
+    fn luma_histogram(image: &LinearImage) -> Histogram {
+        image.to_png().histograms().luma
+    }
+
+    #[test]
+    fn test_equalize_stretches_range() {
+        let image = low_contrast(64, 8);
+        let equalized = luma_histogram(&equalize(&image));
+
+        assert_eq!(equalized.min(), Some(0));
+        assert_eq!(equalized.max(), Some(255));
+        assert!(equalized.std_dev().unwrap() > 3.0 * luma_histogram(&image).std_dev().unwrap());
+    }
+
+    #[test]
+    fn test_single_tile_clahe_is_equalization() {
+        let image = low_contrast(64, 8);
+
+        assert_eq!(clahe(&image, (1, 1), f32::MAX), equalize(&image));
+    }
+
+    #[test]
+    fn test_clip_limit_bounds_contrast() {
+        let image = low_contrast(64, 64);
+
+        let limited = luma_histogram(&clahe(&image, (4, 4), 2.0));
+        let unlimited = luma_histogram(&clahe(&image, (4, 4), f32::MAX));
+
+        assert!(limited.std_dev() < unlimited.std_dev());
+        assert!(limited.std_dev() > luma_histogram(&image).std_dev());
+    }
+
+    #[test]
+    fn test_flat_image_is_unchanged() {
+        let image = LinearImage {
+            width: 4,
+            height: 4,
+            pixels: vec![[0.2, 0.2, 0.2, 1.0]; 16],
+        };
+
+        assert_eq!(
+            equalize(&image).to_png().pixel_buffer,
+            image.to_png().pixel_buffer
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::cube::grammar::{CubeLut, LutInterpolation};
+use crate::ops::LinearImage;
+use crate::util::color::{linear_to_srgb, srgb_to_linear};
+
+/// Grades `image` with a 3D LUT. Like most LUTs made for stills and Rec. 709 footage, the table is
+/// looked up with sRGB encoded values in `[0, 1]`.
+pub fn apply_lut(
+    image: &LinearImage,
+    lut: &CubeLut,
+    interpolation: LutInterpolation,
+) -> LinearImage {
+    image.map(|[r, g, b, a]| {
+        let encoded = [r, g, b].map(|c| linear_to_srgb(c.clamp(0.0, 1.0)));
+        let [r, g, b] = lut
+            .apply(encoded, interpolation)
+            .map(|c| srgb_to_linear(c.clamp(0.0, 1.0)));
+
+        [r, g, b, a]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::CubeDecoder;
+    use crate::ops::tests::read_png;
+    use anyhow::Result;
+
+    #[test]
+    fn test_identity_lut() -> Result<()> {
+        let png = read_png("./tests/obama.png")?;
+        let image = LinearImage::from_png(&png);
+
+        let graded = apply_lut(
+            &image,
+            &CubeLut::identity(33),
+            LutInterpolation::Tetrahedral,
+        );
+        assert_eq!(graded.to_png().pixel_buffer, image.to_png().pixel_buffer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inverting_lut() -> Result<()> {
+        let cube = "LUT_3D_SIZE 2\n1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = CubeDecoder::new(cube.as_bytes()).decode()?;
+
+        let image = LinearImage {
+            width: 2,
+            height: 1,
+            pixels: vec![[0.0, 0.25, 1.0, 0.5], [1.0, 1.0, 0.0, 1.0]],
+        };
+        // The LUT inverts sRGB encoded values, so it matches inverting the 8-bit output.
+        let graded = apply_lut(&image, &lut, LutInterpolation::Trilinear).to_png();
+        let original = image.to_png();
+
+        for (graded, original) in graded
+            .pixel_buffer
+            .chunks(4)
+            .zip(original.pixel_buffer.chunks(4))
+        {
+            for c in 0..3 {
+                assert!(graded[c].abs_diff(255 - original[c]) <= 1);
+            }
+            assert_eq!(graded[3], original[3]);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! CPU implementations of the renderer's image features.
+//!
+//! Every op works on [`LinearImage`], which holds what the fragment shader sees when it samples
+//! the `Rgba8UnormSrgb` texture: linear light RGBA in floats. Converting back to a [`Png`] encodes
+//! with sRGB, like the surface does, so results line up with what the GPU draws at a 1:1 scale.
+
+pub use adjust::*;
+pub use color::*;
+pub use filter::*;
+pub use histogram::*;
+pub use lut::*;
+pub use resize::*;
+pub use transform::*;
+
+mod adjust;
+mod color;
+mod filter;
+mod histogram;
+mod lut;
+mod resize;
+mod transform;
+
+use crate::hdr::grammar::HdrImage;
+use crate::png::grammar::{ColorType, Png};
+use crate::util::color::{linear_to_srgb, srgb_to_linear, unit_to_u8};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearImage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<[f32; 4]>,
+}
+
+impl LinearImage {
+    pub fn from_png(png: &Png) -> Self {
+        // Decoding through a table is much cheaper than calling powf for every sample.
+        let table: [f32; 256] = std::array::from_fn(|i| srgb_to_linear(i as f32 / 255.0));
+
+        let pixels = png
+            .to_rgba8()
+            .chunks_exact(4)
+            .map(|p| {
+                [
+                    table[p[0] as usize],
+                    table[p[1] as usize],
+                    table[p[2] as usize],
+                    p[3] as f32 / 255.0,
+                ]
+            })
+            .collect();
+
+        Self {
+            width: png.width(),
+            height: png.height(),
+            pixels,
+        }
+    }
+
+    pub fn to_png(&self) -> Png {
+        let pixel_buffer = self
+            .pixels
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                [
+                    unit_to_u8(linear_to_srgb(r.clamp(0.0, 1.0))),
+                    unit_to_u8(linear_to_srgb(g.clamp(0.0, 1.0))),
+                    unit_to_u8(linear_to_srgb(b.clamp(0.0, 1.0))),
+                    unit_to_u8(a),
+                ]
+            })
+            .collect();
+
+        Png {
+            width: self.width,
+            height: self.height,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer,
+            exif: None,
+        }
+    }
+
+    /// Opaque pixels from a float image, without clamping its range.
+    pub fn from_hdr(image: &HdrImage) -> Self {
+        Self {
+            width: image.width,
+            height: image.height,
+            pixels: image
+                .pixel_buffer
+                .iter()
+                .map(|&[r, g, b]| [r, g, b, 1.0])
+                .collect(),
+        }
+    }
+
+    /// Drops alpha and clamps negative overshoot, keeping values above 1.
+    pub fn to_hdr(&self) -> HdrImage {
+        HdrImage {
+            width: self.width,
+            height: self.height,
+            pixel_buffer: self
+                .pixels
+                .iter()
+                .map(|&[r, g, b, _]| [r.max(0.0), g.max(0.0), b.max(0.0)])
+                .collect(),
+        }
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+
+    /// The texel at (x, y), clamped to the edge like the renderer's sampler.
+    pub(crate) fn texel(&self, x: i64, y: i64) -> [f32; 4] {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+
+        self.pixels[y * self.width as usize + x]
+    }
+
+    pub(crate) fn map(&self, f: impl Fn([f32; 4]) -> [f32; 4]) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|&p| f(p)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::png::PngDecoder;
+    use crate::renderer::OffscreenRenderer;
+    use anyhow::Result;
+
+    pub fn read_png(path: &str) -> Result<Png> {
+        let content = std::fs::read(path)?;
+        PngDecoder::new(&content).decode()
+    }
+
+    /// The software fallback adapter, like lavapipe, so results don't depend on the GPU. Tests
+    /// that need it are ignored by default and run in CI with `cargo test -- --ignored`.
+    pub fn renderer() -> Result<OffscreenRenderer> {
+        pollster::block_on(OffscreenRenderer::new(true))
+    }
+
+    /// The reference renders are window screenshots of a larger source image, so pixels don't line
+    /// up one to one. Compare the window contents with fine detail blurred away on both sides.
+    pub fn ssim_against(actual: &Png, reference_path: &str) -> Result<f32> {
+        let reference = read_png(reference_path)?;
+        let low_pass = |png: &Png| {
+            let content = crop(png, 8, 64, 820, 1020);
+            gaussian_blur(&LinearImage::from_png(&content), 9).to_png()
+        };
+
+        low_pass(actual).compute_sim(&low_pass(&reference))
+    }
+
+    fn crop(png: &Png, x: u32, y: u32, width: u32, height: u32) -> Png {
+        let channels = png.pixel_buffer.len() / (png.width * png.height) as usize;
+
+        let pixel_buffer = png
+            .pixel_buffer
+            .chunks_exact(png.width as usize * channels)
+            .skip(y as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[x as usize * channels..(x + width) as usize * channels])
+            .copied()
+            .collect();
+
+        Png {
+            width,
+            height,
+            pixel_buffer,
+            ..png.clone()
+        }
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let png = read_png("./tests/vangogh-normal.png")?;
+        let image = LinearImage::from_png(&png);
+
+        assert_eq!(image.to_png().pixel_buffer, png.to_rgba8().as_ref());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Separable resampling. Filtering happens on premultiplied, linear light samples, so edges
+//! against transparency don't pick up the color of invisible pixels and averages don't darken.
+
+use crate::ops::LinearImage;
+use std::f32::consts::PI;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// The closest source pixel, without any smoothing.
+    Nearest,
+    /// Triangle filter.
+    Bilinear,
+    /// Catmull-Rom spline, which keeps edges sharper than bilinear without ringing much.
+    Bicubic,
+    /// Windowed sinc with three lobes.
+    #[default]
+    Lanczos3,
+    /// Averages the source pixels each destination pixel covers, weighted by overlap.
+    Area,
+}
+
+impl ResizeFilter {
+    /// Distance from the center where the kernel reaches zero, in source pixels at 1:1.
+    const fn support(self) -> f32 {
+        match self {
+            Self::Nearest | Self::Area => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn kernel(self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            Self::Nearest | Self::Area => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                // Keys' cubic with a = -0.5.
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos3 => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let pi_x = PI * x;
+                    3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// The source pixels, and their normalized weights, making up one destination pixel.
+#[derive(Debug)]
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Computes the contributions along one axis going from `source` to `destination` pixels.
+///
+/// When shrinking, the kernel is stretched by the scale factor so every source pixel is covered,
+/// which is what keeps downscales from aliasing.
+fn contributions(source: u32, destination: u32, filter: ResizeFilter) -> Vec<Contribution> {
+    let scale = source as f32 / destination as f32;
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+    let last = source as i64 - 1;
+
+    (0..destination)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+
+            if filter == ResizeFilter::Nearest {
+                let nearest = (center.floor() as i64).clamp(0, last) as usize;
+
+                return Contribution {
+                    start: nearest,
+                    weights: vec![1.0],
+                };
+            }
+
+            let left = ((center - support).floor() as i64).clamp(0, last);
+            let right = ((center + support).ceil() as i64).clamp(0, last);
+
+            let mut weights = (left..=right)
+                .map(|j| {
+                    if filter == ResizeFilter::Area {
+                        // Overlap of the source pixel with the destination pixel's footprint.
+                        let (from, to) = (center - scale / 2.0, center + scale / 2.0);
+                        (to.min(j as f32 + 1.0) - from.max(j as f32)).max(0.0)
+                    } else {
+                        filter.kernel((j as f32 + 0.5 - center) / filter_scale)
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let total = weights.iter().sum::<f32>();
+            if total != 0.0 {
+                weights.iter_mut().for_each(|w| *w /= total);
+            }
+
+            Contribution {
+                start: left as usize,
+                weights,
+            }
+        })
+        .collect()
+}
+
+/// Resamples `image` to `width` x `height`.
+pub fn resize(image: &LinearImage, width: u32, height: u32, filter: ResizeFilter) -> LinearImage {
+    if width == 0 || height == 0 || image.width == 0 || image.height == 0 {
+        return LinearImage {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width as usize * height as usize],
+        };
+    }
+
+    let premultiplied = image
+        .pixels
+        .iter()
+        .map(|&[r, g, b, a]| [r * a, g * a, b * a, a])
+        .collect::<Vec<_>>();
+
+    // Horizontal first, into a `width` x `image.height` buffer.
+    let columns = contributions(image.width, width, filter);
+    let source_width = image.width as usize;
+
+    let mut horizontal = Vec::with_capacity(width as usize * image.height as usize);
+    for row in premultiplied.chunks_exact(source_width) {
+        for column in &columns {
+            horizontal.push(accumulate(column, |j| row[j]));
+        }
+    }
+
+    let rows = contributions(image.height, height, filter);
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for row in &rows {
+        for x in 0..width as usize {
+            let [r, g, b, a] = accumulate(row, |j| horizontal[j * width as usize + x]);
+
+            // Kernels with negative lobes can overshoot.
+            let a = a.clamp(0.0, 1.0);
+            pixels.push(if a > 0.0 {
+                [r / a, g / a, b / a, a]
+            } else {
+                [0.0; 4]
+            });
+        }
+    }
+
+    LinearImage {
+        width,
+        height,
+        pixels,
+    }
+}
+
+fn accumulate(contribution: &Contribution, sample: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+    let mut color = [0.0; 4];
+
+    for (i, &weight) in contribution.weights.iter().enumerate() {
+        let s = sample(contribution.start + i);
+
+        for c in 0..4 {
+            color[c] += s[c] * weight;
+        }
+    }
+
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::tests::read_png;
+    use anyhow::Result;
+
+    const FILTERS: [ResizeFilter; 5] = [
+        ResizeFilter::Nearest,
+        ResizeFilter::Bilinear,
+        ResizeFilter::Bicubic,
+        ResizeFilter::Lanczos3,
+        ResizeFilter::Area,
+    ];
+
+    fn image(width: u32, height: u32, f: impl Fn(u32, u32) -> [f32; 4]) -> LinearImage {
+        LinearImage {
+            width,
+            height,
+            pixels: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| f(x, y))
+                .collect(),
+        }
+    }
+
+    fn assert_close(actual: &LinearImage, expected: &LinearImage) {
+        assert_eq!(actual.dimensions(), expected.dimensions());
+
+        for (a, e) in actual
+            .pixels
+            .iter()
+            .flatten()
+            .zip(expected.pixels.iter().flatten())
+        {
+            assert!((a - e).abs() < 1e-4, "{a} != {e}");
+        }
+    }
+
+    #[test]
+    fn test_same_size_is_identity() {
+        let source = image(7, 5, |x, y| [x as f32 / 7.0, y as f32 / 5.0, 0.5, 1.0]);
+
+        for filter in FILTERS {
+            assert_close(&resize(&source, 7, 5, filter), &source);
+        }
+    }
+
+    #[test]
+    fn test_flat_images_stay_flat() {
+        let source = image(9, 6, |_, _| [0.2, 0.4, 0.6, 0.8]);
+
+        for filter in FILTERS {
+            for (width, height) in [(3, 2), (4, 5), (20, 13)] {
+                let expected = image(width, height, |_, _| [0.2, 0.4, 0.6, 0.8]);
+                assert_close(&resize(&source, width, height, filter), &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_downscale_antialiases() {
+        // A one pixel checkerboard averages out instead of aliasing to either color.
+        let checkerboard = image(16, 16, |x, y| {
+            let c = ((x + y) % 2) as f32;
+            [c, c, c, 1.0]
+        });
+
+        for filter in [
+            ResizeFilter::Bilinear,
+            ResizeFilter::Area,
+            ResizeFilter::Lanczos3,
+        ] {
+            let resized = resize(&checkerboard, 4, 4, filter);
+
+            for &[r, g, b, _] in &resized.pixels {
+                assert!([r, g, b].iter().all(|c| (c - 0.5).abs() < 0.01));
+            }
+        }
+    }
+
+    #[test]
+    fn test_premultiplied_alpha() {
+        // Transparent pixels don't bleed their color into the opaque ones.
+        let source = image(2, 1, |x, _| match x {
+            0 => [1.0, 0.0, 0.0, 1.0],
+            _ => [0.0, 1.0, 0.0, 0.0],
+        });
+
+        let resized = resize(&source, 1, 1, ResizeFilter::Area);
+        assert_close(&resized, &image(1, 1, |_, _| [1.0, 0.0, 0.0, 0.5]));
+    }
+
+    #[test]
+    fn test_nearest_upscale() {
+        let source = image(2, 1, |x, _| [x as f32, 0.0, 0.0, 1.0]);
+        let resized = resize(&source, 4, 2, ResizeFilter::Nearest);
+
+        let expected = image(4, 2, |x, _| [(x / 2) as f32, 0.0, 0.0, 1.0]);
+        assert_eq!(resized, expected);
+    }
+
+    #[test]
+    fn test_against_reference_resizer() -> Result<()> {
+        let png = read_png("./tests/vangogh-normal.png")?;
+        let (width, height) = (png.width() / 4, png.height() / 4);
+
+        let resized = resize(
+            &LinearImage::from_png(&png),
+            width,
+            height,
+            ResizeFilter::Lanczos3,
+        )
+        .to_png();
+
+        let reference =
+            image::RgbaImage::from_raw(png.width(), png.height(), png.to_rgba8().to_vec()).unwrap();
+        let reference = image::imageops::resize(
+            &reference,
+            width,
+            height,
+            image::imageops::FilterType::Lanczos3,
+        );
+
+        let mut expected = resized.clone();
+        expected.pixel_buffer = reference.into_raw();
+
+        assert!(resized.compute_sim(&expected)? > 0.98);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Geometric transforms. Actions are composed in image space: pixel units, y pointing down and the
+//! origin at the center of the image, so rotations and flips pivot around the middle.
+
+use crate::ops::LinearImage;
+use crate::util::affine::Affine;
+use anyhow::{ensure, Result};
+
+/// The most pixels a bake allocates. Scales can ask for far larger canvases than fit in memory.
+const MAX_PIXELS: u64 = 1 << 28;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransformAction {
+    FlipX,
+    FlipY,
+    /// Quarter turns clockwise, kept exact so baking them doesn't resample.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Clockwise, in degrees.
+    Rotate(f32),
+    Scale(f32, f32),
+    /// Horizontal and vertical shear angles, in degrees.
+    Skew(f32, f32),
+    /// In pixels. The canvas is sized without translations, so this moves the image within it.
+    Translate(f32, f32),
+}
+
+impl TransformAction {
+    pub fn matrix(self) -> Affine {
+        match self {
+            Self::FlipX => Affine::scale(-1.0, 1.0),
+            Self::FlipY => Affine::scale(1.0, -1.0),
+            Self::Rotate90 => Affine::new(0.0, 1.0, -1.0, 0.0, 0.0, 0.0),
+            Self::Rotate180 => Affine::scale(-1.0, -1.0),
+            Self::Rotate270 => Affine::new(0.0, -1.0, 1.0, 0.0, 0.0, 0.0),
+            // With y pointing down, a positive angle turns clockwise on screen.
+            Self::Rotate(degrees) => Affine::rotate(degrees.to_radians()),
+            Self::Scale(x, y) => Affine::scale(x, y),
+            Self::Skew(x, y) => Affine::skew(x.to_radians(), y.to_radians()),
+            Self::Translate(x, y) => Affine::translate(x, y),
+        }
+    }
+}
+
+/// A rectangle of the transformed image to keep, in its pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The transform actions applied so far, in order, and an optional crop of the result.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TransformStack {
+    actions: Vec<TransformAction>,
+    crop: Option<Crop>,
+}
+
+impl TransformStack {
+    pub const fn new() -> Self {
+        Self {
+            actions: vec![],
+            crop: None,
+        }
+    }
+
+    pub fn push(&mut self, action: TransformAction) {
+        self.actions.push(action);
+    }
+
+    /// Undoes the last action.
+    pub fn pop(&mut self) -> Option<TransformAction> {
+        self.actions.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.actions.clear();
+        self.crop = None;
+    }
+
+    pub fn actions(&self) -> &[TransformAction] {
+        &self.actions
+    }
+
+    pub const fn crop(&self) -> Option<Crop> {
+        self.crop
+    }
+
+    pub const fn set_crop(&mut self, crop: Option<Crop>) {
+        self.crop = crop;
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.crop.is_none() && self.matrix() == Affine::IDENTITY
+    }
+
+    /// Every action multiplied together, the first one applied first.
+    pub fn matrix(&self) -> Affine {
+        self.actions
+            .iter()
+            .fold(Affine::IDENTITY, |matrix, action| {
+                matrix.then(&action.matrix())
+            })
+    }
+
+    /// Top left corner and size of the canvas, the box around the image transformed without its
+    /// translation, in image space.
+    fn bounds(&self, width: u32, height: u32) -> ((f32, f32), (u32, u32)) {
+        let matrix = Affine {
+            e: 0.0,
+            f: 0.0,
+            ..self.matrix()
+        };
+        let (w, h) = (width as f32 / 2.0, height as f32 / 2.0);
+
+        let corners = [(-w, -h), (w, -h), (-w, h), (w, h)].map(|corner| matrix.apply(corner));
+
+        let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min);
+        let max_x = corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+        let max_y = corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        // Rounding absorbs float error, like a 90 degree turn landing a hair off a whole pixel.
+        let size = (
+            ((max_x - min_x).round() as u32).max(1),
+            ((max_y - min_y).round() as u32).max(1),
+        );
+
+        ((min_x, min_y), size)
+    }
+
+    /// Dimensions of the output for a `width` x `height` image.
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let (_, size) = self.bounds(width, height);
+
+        self.crop.map_or(size, |crop| {
+            (
+                crop.width.min(size.0.saturating_sub(crop.x)).max(1),
+                crop.height.min(size.1.saturating_sub(crop.y)).max(1),
+            )
+        })
+    }
+
+    /// Maps pixel coordinates of the source, origin at the top left, to those of the output.
+    pub fn output_from_source(&self, width: u32, height: u32) -> Affine {
+        let ((min_x, min_y), _) = self.bounds(width, height);
+        let (crop_x, crop_y) = self.crop.map_or((0.0, 0.0), |c| (c.x as f32, c.y as f32));
+
+        Affine::translate(-(width as f32) / 2.0, -(height as f32) / 2.0)
+            .then(&self.matrix())
+            .then(&Affine::translate(-min_x - crop_x, -min_y - crop_y))
+    }
+}
+
+/// Bakes `stack` into the pixels. Each output pixel samples the source bilinearly like the
+/// renderer's texture sampler, and pixels the transformed image doesn't cover are transparent.
+pub fn transform(image: &LinearImage, stack: &TransformStack) -> Result<LinearImage> {
+    let (width, height) = stack.output_size(image.width, image.height);
+    ensure!(
+        width as u64 * height as u64 <= MAX_PIXELS,
+        "Transformed image of {}x{} is too large.",
+        width,
+        height
+    );
+
+    let Some(source_from_output) = stack
+        .output_from_source(image.width, image.height)
+        .inverse()
+    else {
+        return Ok(LinearImage {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width as usize * height as usize],
+        });
+    };
+
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (u, v) = source_from_output.apply((x as f32 + 0.5, y as f32 + 0.5));
+
+            if u < 0.0 || v < 0.0 || u > image.width as f32 || v > image.height as f32 {
+                return [0.0; 4];
+            }
+
+            sample_bilinear(image, u - 0.5, v - 0.5)
+        })
+        .collect();
+
+    Ok(LinearImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Samples at texel coordinates, where whole numbers are texel centers, clamping to the edge.
+fn sample_bilinear(image: &LinearImage, x: f32, y: f32) -> [f32; 4] {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let lerp = |a: [f32; 4], b: [f32; 4], t: f32| -> [f32; 4] {
+        std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+    };
+
+    let top = lerp(image.texel(x0, y0), image.texel(x0 + 1, y0), fx);
+    let bottom = lerp(image.texel(x0, y0 + 1), image.texel(x0 + 1, y0 + 1), fx);
+
+    lerp(top, bottom, fy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn numbered(width: u32, height: u32) -> LinearImage {
+        LinearImage {
+            width,
+            height,
+            pixels: (0..width * height)
+                .map(|i| [i as f32 / 100.0, 0.0, 0.0, 1.0])
+                .collect(),
+        }
+    }
+
+    fn reds(image: &LinearImage) -> Vec<u32> {
+        image
+            .pixels
+            .iter()
+            .map(|p| (p[0] * 100.0).round() as u32)
+            .collect()
+    }
+
+    fn stack(actions: &[TransformAction]) -> TransformStack {
+        let mut stack = TransformStack::new();
+        actions.iter().for_each(|&action| stack.push(action));
+        stack
+    }
+
+    #[test]
+    fn test_quarter_turns() -> Result<()> {
+        // 0 1 2
+        // 3 4 5
+        let image = numbered(3, 2);
+
+        let rotated = transform(&image, &stack(&[TransformAction::Rotate90]))?;
+        assert_eq!(rotated.dimensions(), (2, 3));
+        assert_eq!(reds(&rotated), [3, 0, 4, 1, 5, 2]);
+
+        let rotated = transform(&image, &stack(&[TransformAction::Rotate180]))?;
+        assert_eq!(reds(&rotated), [5, 4, 3, 2, 1, 0]);
+
+        let rotated = transform(&image, &stack(&[TransformAction::Rotate270]))?;
+        assert_eq!(reds(&rotated), [2, 5, 1, 4, 0, 3]);
+
+        // Flips compose with rotations through the matrix product.
+        let transposed = transform(
+            &image,
+            &stack(&[TransformAction::Rotate90, TransformAction::FlipX]),
+        )?;
+        assert_eq!(reds(&transposed), [0, 3, 1, 4, 2, 5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_arbitrary_rotation_grows_canvas() -> Result<()> {
+        let image = numbered(10, 10);
+        let rotated = transform(&image, &stack(&[TransformAction::Rotate(45.0)]))?;
+
+        assert_eq!(rotated.dimensions(), (14, 14));
+        // The corners of the canvas aren't covered.
+        assert_eq!(rotated.pixels[0], [0.0; 4]);
+        // Four quarter turns worth of arbitrary rotation come back around.
+        let full_turn = stack(&[TransformAction::Rotate(90.0); 4]);
+        assert_eq!(transform(&image, &full_turn)?.dimensions(), (10, 10));
+        assert_eq!(reds(&transform(&image, &full_turn)?), reds(&image));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scale_and_skew() -> Result<()> {
+        let image = numbered(4, 2);
+
+        let scaled = transform(&image, &stack(&[TransformAction::Scale(2.0, 0.5)]))?;
+        assert_eq!(scaled.dimensions(), (8, 1));
+
+        let skewed = transform(&image, &stack(&[TransformAction::Skew(45.0, 0.0)]))?;
+        assert_eq!(skewed.dimensions(), (6, 2));
+
+        // Canvases too large to allocate are refused instead of overflowing.
+        let huge = stack(&[TransformAction::Scale(1e6, 1e6)]);
+        assert!(transform(&image, &huge).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crop_and_translate() -> Result<()> {
+        let image = numbered(4, 3);
+        let mut stack = stack(&[TransformAction::Translate(-1.0, 0.0)]);
+
+        let translated = transform(&image, &stack)?;
+        assert_eq!(translated.dimensions(), (4, 3));
+        assert_eq!(reds(&translated)[..4], [1, 2, 3, 0]);
+        assert_eq!(translated.pixels[3], [0.0; 4]);
+
+        stack.set_crop(Some(Crop {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 5,
+        }));
+        let cropped = transform(&image, &stack)?;
+
+        // The crop is clamped to the canvas.
+        assert_eq!(cropped.dimensions(), (2, 2));
+        assert_eq!(reds(&cropped), [6, 7, 10, 11]);
+
+        Ok(())
+    }
+}
@@ -65,6 +65,7 @@ impl<'a> PngDecoder<'a> {
         let mut compressed_stream = Vec::new();
 
         let mut gamma = 0;
+        let mut exif = None;
 
         while let Some(chunk) = chunks.peek() {
             // todo, how would you collect palettes if ColorType::Palette?
@@ -73,6 +74,10 @@ impl<'a> PngDecoder<'a> {
                 gamma = g;
             }
 
+            if let &Chunk::Exif(data) = chunk {
+                exif = Some(data.to_vec());
+            }
+
             if let &Chunk::ImageData(sub_data) = chunk {
                 compressed_stream.extend_from_slice(sub_data);
             }
@@ -116,6 +121,7 @@ impl<'a> PngDecoder<'a> {
             gamma,
             color_type: image_header.color_type,
             pixel_buffer,
+            exif,
         })
     }
 
@@ -123,7 +129,7 @@ impl<'a> PngDecoder<'a> {
         expected_crc == compute_crc(chunk_type, chunk_data)
     }
 
-    fn parse_chunks(&mut self) -> Result<Vec<Chunk>> {
+    fn parse_chunks(&mut self) -> Result<Vec<Chunk<'a>>> {
         let mut chunks = Vec::new();
 
         let mut text_map = BTreeMap::new();
@@ -191,6 +197,7 @@ impl<'a> PngDecoder<'a> {
                 b"IDAT" => Chunk::ImageData(self.read_slice(length)?),
                 b"IEND" => break,
                 b"gAMA" => Chunk::Gamma(self.read_u32()?),
+                b"eXIf" => Chunk::Exif(self.read_slice(length)?),
                 // b"sRGB" => todo!("Parse srgb chunks"),
                 b"tEXt" => {
                     let cursor_start = self.cursor;
@@ -260,6 +267,8 @@ impl<'a> PngDecoder<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::png::diff::{side_by_side, DiffOptions};
+    use crate::png::PngEncoder;
     use crate::util::test_file_parser::parse_test_file;
     use anyhow::anyhow;
     use image::ImageReader;
@@ -275,6 +284,43 @@ mod tests {
         Ok(())
     }
 
+    /// Saves the reference, the decoded image and their differences side by side, returning
+    /// where to, instead of dumping both pixel buffers.
+    fn write_diff(
+        image_title: &str,
+        generated_png: &Png,
+        reference_rgbs: Vec<u8>,
+        generated_rgbs: Vec<u8>,
+    ) -> Result<String> {
+        let rgb_png = |pixel_buffer| Png {
+            gamma: 0,
+            color_type: ColorType::RGB,
+            pixel_buffer,
+            exif: None,
+            ..*generated_png
+        };
+        let (reference_png, generated_png) = (rgb_png(reference_rgbs), rgb_png(generated_rgbs));
+
+        let exact = DiffOptions {
+            threshold: 0.0,
+            ..Default::default()
+        };
+        let diff = generated_png.diff(&reference_png, exact)?;
+        let combined = side_by_side(&[
+            &reference_png,
+            &generated_png,
+            &diff.highlight(&reference_png),
+        ]);
+
+        let diff_path = format!("./target/diffs/{}.png", image_title);
+        std::fs::create_dir_all("./target/diffs")?;
+        std::fs::write(&diff_path, PngEncoder::new(&combined).encode()?)?;
+
+        eprintln!("{}: {} pixels mismatched", image_title, diff.mismatched());
+
+        Ok(diff_path)
+    }
+
     fn compare_png(image_title: &str) -> Result<()> {
         let expected_png =
             Png::read_from_binary_blob(&format!("./test_suite/{}", image_title).into())
@@ -287,12 +333,16 @@ mod tests {
         let generated_png = PngDecoder::new(&content).decode()?;
         let generated_rgbs = generated_png.to_rgb8().to_vec();
 
-        assert_eq!(
-            reference_rgbs,
-            generated_rgbs,
-            "Failed test: {:?}",
-            parse_test_file(&path.into())?.test_desc
-        );
+        if reference_rgbs != generated_rgbs {
+            let diff_path =
+                write_diff(image_title, &generated_png, reference_rgbs, generated_rgbs)?;
+
+            panic!(
+                "Failed test: {:?}, see {}",
+                parse_test_file(&path.into())?.test_desc,
+                diff_path
+            );
+        }
 
         if expected_png != generated_png {
             assert_eq!(
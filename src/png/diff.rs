@@ -0,0 +1,326 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Pixel by pixel differences for figuring out why a comparison failed.
+//!
+//! Pixels are compared like pixelmatch does, by their distance in YIQ space after blending them over white, so the
+//! threshold is perceptual and fully transparent pixels compare equal whatever their color.
+
+use crate::gif::grammar::AnimationFrame;
+use crate::png::grammar::{ColorType, Png};
+use anyhow::{ensure, Result};
+use std::time::Duration;
+
+/// The largest squared YIQ distance between two colors.
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+/// The color mismatched pixels are drawn in by `Diff::highlight`.
+const MISMATCH_COLOR: [u8; 4] = [255, 0, 0, 255];
+
+/// Heatmap colors from no difference to the largest one, roughly following inferno.
+const HEATMAP_STOPS: [[f32; 3]; 5] = [
+    [0.0, 0.0, 4.0],
+    [87.0, 16.0, 110.0],
+    [188.0, 55.0, 84.0],
+    [249.0, 142.0, 9.0],
+    [252.0, 255.0, 164.0],
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DiffOptions {
+    /// Pixels further apart than this, from 0 for any change to 1 for the most distant colors,
+    /// count as mismatched. Pixelmatch defaults to 0.1 as well.
+    pub threshold: f32,
+    /// How much `Diff::amplified` scales channel differences by.
+    pub amplification: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.1,
+            amplification: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    width: u32,
+    height: u32,
+    options: DiffOptions,
+    /// The perceptual distance of each pixel in [0, 1].
+    distances: Vec<f32>,
+    /// The absolute difference of each channel, after blending over white.
+    channel_differences: Vec<[u8; 3]>,
+}
+
+impl Png {
+    pub fn diff(&self, reference_image: &Self, options: DiffOptions) -> Result<Diff> {
+        ensure!(
+            self.dimensions() == reference_image.dimensions(),
+            "Expect reference and test images to have identical dimensions."
+        );
+
+        let (distances, channel_differences) = self
+            .to_rgba8()
+            .chunks_exact(4)
+            .zip(reference_image.to_rgba8().chunks_exact(4))
+            .map(|(test, reference)| {
+                let (test, reference) = (blend_over_white(test), blend_over_white(reference));
+                let delta = yiq_delta(test, reference);
+                let differences = std::array::from_fn(|c| (test[c] - reference[c]).abs() as u8);
+
+                ((delta / MAX_YIQ_DELTA).clamp(0.0, 1.0).sqrt(), differences)
+            })
+            .unzip();
+
+        Ok(Diff {
+            width: self.width,
+            height: self.height,
+            options,
+            distances,
+            channel_differences,
+        })
+    }
+}
+
+impl Diff {
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn distances(&self) -> &[f32] {
+        &self.distances
+    }
+
+    /// The number of pixels further apart than the threshold.
+    pub fn mismatched(&self) -> usize {
+        self.distances
+            .iter()
+            .filter(|&&d| self.is_mismatch(d))
+            .count()
+    }
+
+    pub fn mismatch_ratio(&self) -> f64 {
+        self.mismatched() as f64 / self.distances.len().max(1) as f64
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.distances.iter().copied().fold(0.0, f32::max)
+    }
+
+    fn is_mismatch(&self, distance: f32) -> bool {
+        distance > self.options.threshold
+    }
+
+    /// The absolute difference of every channel scaled up, black where the images agree.
+    pub fn amplified(&self) -> Png {
+        let amplify = |d: u8| (d as f32 * self.options.amplification).min(255.0) as u8;
+
+        let pixel_buffer = self
+            .channel_differences
+            .iter()
+            .flat_map(|differences| differences.map(amplify))
+            .collect();
+
+        self.image(ColorType::RGB, pixel_buffer)
+    }
+
+    /// The distance of every pixel through a color map, relative to the largest distance so
+    /// small differences still stand out.
+    pub fn heatmap(&self) -> Png {
+        let max = self.max_distance();
+
+        let pixel_buffer = self
+            .distances
+            .iter()
+            .flat_map(|&d| heatmap_color(if max > 0.0 { d / max } else { 0.0 }))
+            .collect();
+
+        self.image(ColorType::RGB, pixel_buffer)
+    }
+
+    /// The reference faded to light gray with mismatched pixels drawn over it in red, the way
+    /// pixelmatch draws its output.
+    pub fn highlight(&self, reference_image: &Png) -> Png {
+        let pixel_buffer = reference_image
+            .to_rgba8()
+            .chunks_exact(4)
+            .zip(&self.distances)
+            .flat_map(|(rgba, &distance)| {
+                if self.is_mismatch(distance) {
+                    return MISMATCH_COLOR;
+                }
+
+                let [r, g, b] = blend_over_white(rgba);
+                let faded = (255.0 + (luma(r, g, b) - 255.0) * 0.1) as u8;
+                [faded, faded, faded, 255]
+            })
+            .collect();
+
+        self.image(ColorType::RGBA, pixel_buffer)
+    }
+
+    const fn image(&self, color_type: ColorType, pixel_buffer: Vec<u8>) -> Png {
+        Png {
+            width: self.width,
+            height: self.height,
+            gamma: 0,
+            color_type,
+            pixel_buffer,
+            exif: None,
+        }
+    }
+}
+
+/// Places images next to each other from left to right, top aligned on a transparent canvas.
+pub fn side_by_side(images: &[&Png]) -> Png {
+    let width = images.iter().map(|image| image.width).sum::<u32>();
+    let height = images.iter().map(|image| image.height).max().unwrap_or(0);
+    let stride = width as usize * 4;
+
+    let mut pixel_buffer = vec![0; stride * height as usize];
+    let mut left = 0;
+
+    for image in images {
+        let row_length = image.width as usize * 4;
+
+        for (y, row) in image.to_rgba8().chunks_exact(row_length).enumerate() {
+            let start = y * stride + left;
+            pixel_buffer[start..start + row_length].copy_from_slice(row);
+        }
+
+        left += row_length;
+    }
+
+    Png {
+        width,
+        height,
+        gamma: 0,
+        color_type: ColorType::RGBA,
+        pixel_buffer,
+        exif: None,
+    }
+}
+
+/// Two frames alternating between the reference and the test image, for `GifEncoder`.
+pub fn blink(reference_image: &Png, test_image: &Png, interval: Duration) -> Vec<AnimationFrame> {
+    [reference_image, test_image]
+        .map(|image| AnimationFrame {
+            image: image.clone(),
+            delay: interval,
+        })
+        .into()
+}
+
+fn blend_over_white(rgba: &[u8]) -> [f32; 3] {
+    let alpha = rgba[3] as f32 / 255.0;
+    [0, 1, 2].map(|c| 255.0 + (rgba[c] as f32 - 255.0) * alpha)
+}
+
+fn luma(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23
+}
+
+/// The squared YIQ distance with the weights from Kotsarenko and Ramos (2010), which pixelmatch
+/// uses too.
+fn yiq_delta([r1, g1, b1]: [f32; 3], [r2, g2, b2]: [f32; 3]) -> f32 {
+    let (r, g, b) = (r1 - r2, g1 - g2, b1 - b2);
+
+    let y = luma(r, g, b);
+    let i = r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_9;
+    let q = r * 0.211_470_17 - g * 0.522_617_2 + b * 0.311_147_07;
+
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn heatmap_color(t: f32) -> [u8; 3] {
+    let position = t.clamp(0.0, 1.0) * (HEATMAP_STOPS.len() - 1) as f32;
+    let index = (position as usize).min(HEATMAP_STOPS.len() - 2);
+    let fraction = position - index as f32;
+
+    let (from, to) = (HEATMAP_STOPS[index], HEATMAP_STOPS[index + 1]);
+    std::array::from_fn(|c| (from[c] + (to[c] - from[c]) * fraction).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(width: u32, pixels: &[[u8; 4]]) -> Png {
+        Png {
+            width,
+            height: pixels.len() as u32 / width,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: pixels.concat(),
+            exif: None,
+        }
+    }
+
+    #[test]
+    fn test_mismatch_threshold() -> Result<()> {
+        let reference = rgba(4, &[[0, 0, 0, 255]; 4]);
+        let test = rgba(
+            4,
+            &[
+                [0, 0, 0, 255],
+                [4, 4, 4, 255],
+                [128, 128, 128, 255],
+                [255, 255, 255, 255],
+            ],
+        );
+
+        let diff = test.diff(&reference, DiffOptions::default())?;
+        assert_eq!(diff.mismatched(), 2);
+        assert_eq!(diff.mismatch_ratio(), 0.5);
+        assert!(diff.max_distance() > 0.95);
+
+        let exact = DiffOptions {
+            threshold: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(test.diff(&reference, exact)?.mismatched(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transparent_pixels_match() -> Result<()> {
+        let reference = rgba(2, &[[255, 0, 0, 0], [0, 0, 255, 255]]);
+        let test = rgba(2, &[[0, 255, 0, 0], [0, 0, 255, 0]]);
+
+        let diff = test.diff(&reference, DiffOptions::default())?;
+        assert_eq!(diff.distances()[0], 0.0);
+        assert_eq!(diff.mismatched(), 1);
+
+        // Coverage changes show up as color changes against the white background.
+        assert_eq!(diff.amplified().pixel_buffer, [0, 0, 0, 255, 255, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_images() -> Result<()> {
+        let reference = rgba(2, &[[255, 255, 255, 255], [255, 255, 255, 255]]);
+        let test = rgba(2, &[[255, 255, 255, 255], [250, 255, 255, 255]]);
+        let diff = test.diff(&reference, DiffOptions::default())?;
+
+        assert_eq!(diff.amplified().pixel_buffer, [0, 0, 0, 50, 0, 0]);
+        assert_eq!(diff.heatmap().pixel_buffer, [0, 0, 4, 252, 255, 164]);
+        assert_eq!(
+            diff.highlight(&reference).pixel_buffer,
+            [255, 255, 255, 255, 255, 255, 255, 255]
+        );
+
+        let combined = side_by_side(&[&reference, &diff.heatmap()]);
+        assert_eq!(combined.dimensions(), (4, 1));
+        assert_eq!(&combined.pixel_buffer[8..12], [0, 0, 4, 255]);
+
+        assert!(test
+            .diff(&rgba(1, &[[0, 0, 0, 0]]), DiffOptions::default())
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{bail, ensure, Result};
+use flate2::{write::ZlibEncoder, Compression};
+use std::io::Write;
+
+use crate::png::{
+    crc32::compute_crc,
+    grammar::{ColorType, Filter, Png},
+};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\n";
+
+/// Writes non-interlaced 8-bit PNGs, choosing a row filter per scanline.
+#[derive(Debug)]
+pub struct PngEncoder<'a> {
+    png: &'a Png,
+}
+
+impl<'a> PngEncoder<'a> {
+    pub const fn new(png: &'a Png) -> Self {
+        Self { png }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let png = self.png;
+        let (width, height) = png.dimensions();
+
+        ensure!(
+            width > 0 && height > 0 && width <= i32::MAX as u32 && height <= i32::MAX as u32,
+            "Invalid PNG dimensions: {}x{}.",
+            width,
+            height
+        );
+
+        if png.color_type == ColorType::Palette {
+            bail!("Encoding palette images is not supported.");
+        }
+
+        let bytes_per_pixel = png.color_type.num_channels() as usize;
+        let row_size = width as usize * bytes_per_pixel;
+
+        ensure!(
+            png.pixel_buffer.len() == row_size * height as usize,
+            "Pixel buffer doesn't match a {}x{} {:?} image.",
+            width,
+            height,
+            png.color_type
+        );
+
+        let mut out = SIGNATURE.to_vec();
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // Bit depth, color type, compression, filter and interlace methods.
+        header.extend_from_slice(&[8, png.color_type as u8, 0, 0, 0]);
+        write_chunk(&mut out, b"IHDR", &header);
+
+        if png.gamma != 0 {
+            write_chunk(&mut out, b"gAMA", &png.gamma.to_be_bytes());
+        }
+
+        if let Some(exif) = &png.exif {
+            write_chunk(&mut out, b"eXIf", exif);
+        }
+
+        let mut zlib_encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        let mut filtered = vec![0; row_size];
+        let previous_rows =
+            std::iter::once(None).chain(png.pixel_buffer.chunks_exact(row_size).map(Some));
+
+        for (row, previous) in png.pixel_buffer.chunks_exact(row_size).zip(previous_rows) {
+            let filter = choose_filter(row, previous, bytes_per_pixel, &mut filtered);
+
+            zlib_encoder.write_all(&[filter as u8])?;
+            zlib_encoder.write_all(&filtered)?;
+        }
+
+        write_chunk(&mut out, b"IDAT", &zlib_encoder.finish()?);
+        write_chunk(&mut out, b"IEND", &[]);
+
+        Ok(out)
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&compute_crc(chunk_type, data).to_be_bytes());
+}
+
+/// Picks the filter with the smallest sum of absolute signed residuals, the heuristic recommended
+/// by the specification, leaving the filtered row in `out`.
+fn choose_filter(
+    row: &[u8],
+    previous: Option<&[u8]>,
+    bytes_per_pixel: usize,
+    out: &mut [u8],
+) -> Filter {
+    let mut best = (Filter::None, u64::MAX);
+    let mut candidate = vec![0; row.len()];
+
+    for filter in [
+        Filter::None,
+        Filter::Sub,
+        Filter::Up,
+        Filter::Average,
+        Filter::Paeth,
+    ] {
+        apply_filter(filter, row, previous, bytes_per_pixel, &mut candidate);
+
+        let cost = candidate
+            .iter()
+            .map(|&b| (b as i8).unsigned_abs() as u64)
+            .sum::<u64>();
+
+        if cost < best.1 {
+            best = (filter, cost);
+            out.copy_from_slice(&candidate);
+        }
+    }
+
+    best.0
+}
+
+fn apply_filter(
+    filter: Filter,
+    row: &[u8],
+    previous: Option<&[u8]>,
+    bytes_per_pixel: usize,
+    out: &mut [u8],
+) {
+    for i in 0..row.len() {
+        let left = if i >= bytes_per_pixel {
+            row[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let up = previous.map_or(0, |previous| previous[i]);
+        let up_left = match previous {
+            Some(previous) if i >= bytes_per_pixel => previous[i - bytes_per_pixel],
+            _ => 0,
+        };
+
+        let predictor = match filter {
+            Filter::None => 0,
+            Filter::Sub => left,
+            Filter::Up => up,
+            Filter::Average => ((left as u16 + up as u16) / 2) as u8,
+            Filter::Paeth => paeth(left, up, up_left),
+        };
+
+        out[i] = row[i].wrapping_sub(predictor);
+    }
+}
+
+const fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let a = left as i16;
+    let b = up as i16;
+    let c = up_left as i16;
+
+    let p = a + b - c;
+
+    let pa = (p - a).abs();
+    let pb = (p - b).abs();
+    let pc = (p - c).abs();
+
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::PngDecoder;
+
+    fn round_trip(png: &Png) -> Result<Png> {
+        let encoded = PngEncoder::new(png).encode()?;
+        PngDecoder::new(&encoded).decode()
+    }
+
+    #[test]
+    fn test_round_trip_color_types() -> Result<()> {
+        let (width, height) = (13, 7);
+
+        for color_type in [
+            ColorType::Grayscale,
+            ColorType::GrayscaleAlpha,
+            ColorType::RGB,
+            ColorType::RGBA,
+        ] {
+            let len = (width * height) as usize * color_type.num_channels() as usize;
+            let pixel_buffer = (0..len).map(|i| (i * 37 % 251) as u8).collect();
+
+            let png = Png {
+                width,
+                height,
+                gamma: 45455,
+                color_type,
+                pixel_buffer,
+                exif: Some(b"MM\0\x2a\0\0\0\x08\0\0".to_vec()),
+            };
+
+            assert_eq!(round_trip(&png)?, png);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_photo() -> Result<()> {
+        let content = std::fs::read("./tests/vangogh-normal.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        assert_eq!(round_trip(&png)?, png);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_decoder() -> Result<()> {
+        let png = Png {
+            width: 3,
+            height: 2,
+            gamma: 0,
+            color_type: ColorType::RGB,
+            pixel_buffer: vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+            exif: None,
+        };
+
+        let encoded = PngEncoder::new(&png).encode()?;
+        let decoded = image::load_from_memory(&encoded)?.to_rgb8();
+
+        assert_eq!(decoded.as_raw(), &png.pixel_buffer);
+
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@ pub enum Chunk<'a> {
     ImageData(&'a [u8]),
     TextData(BTreeMap<Cow<'a, [u8]>, Cow<'a, [u8]>>),
     Gamma(u32),
+    Exif(&'a [u8]),
 }
 
 #[derive(Debug)]
@@ -30,7 +31,7 @@ impl ImageHeader {
     pub(crate) const fn num_bytes_per_pixel(&self) -> usize {
         let bits_per_pixel = self.color_type.num_channels() * self.bit_depth;
 
-        ((bits_per_pixel + 7) / 8) as usize
+        bits_per_pixel.div_ceil(8) as usize
     }
 }
 
@@ -72,7 +73,7 @@ impl TryFrom<u8> for ColorType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Filter {
     None = 0,
     Sub = 1,
@@ -98,7 +99,7 @@ impl TryFrom<u8> for Filter {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Png {
     pub(crate) width: u32,
     pub(crate) height: u32,
@@ -106,6 +107,8 @@ pub struct Png {
     pub(crate) gamma: u32,
     pub(crate) color_type: ColorType,
     pub(crate) pixel_buffer: Vec<u8>,
+    /// Raw TIFF-structured EXIF payload from the `eXIf` chunk, if any.
+    pub(crate) exif: Option<Vec<u8>>,
 }
 
 impl Png {
@@ -130,6 +133,10 @@ impl Png {
         self.color_type
     }
 
+    pub fn exif(&self) -> Option<&[u8]> {
+        self.exif.as_deref()
+    }
+
     pub fn to_rgb8(&self) -> Cow<'_, [u8]> {
         match self.color_type {
             ColorType::RGB => Cow::from(&self.pixel_buffer),
@@ -171,7 +178,7 @@ impl Png {
                 let b = self
                     .pixel_buffer
                     .chunks_exact(3)
-                    .flat_map(|b| [b[0], b[1], b[2], 0])
+                    .flat_map(|b| [b[0], b[1], b[2], 255])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
@@ -180,7 +187,7 @@ impl Png {
                 let b = self
                     .pixel_buffer
                     .iter()
-                    .flat_map(|&y| [y, y, y, 0])
+                    .flat_map(|&y| [y, y, y, 255])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
@@ -247,7 +254,7 @@ impl Png {
 
         file.write_all(&self.width.to_be_bytes())?;
         file.write_all(&self.height.to_be_bytes())?;
-        file.write(&self.gamma.to_be_bytes())?;
+        file.write_all(&self.gamma.to_be_bytes())?;
         file.write_all(&[self.color_type as u8])?;
         file.write_all(&self.pixel_buffer)?;
 
@@ -278,6 +285,7 @@ impl Png {
             gamma: u32::from_be_bytes(gamma),
             color_type: color_type[0].try_into()?,
             pixel_buffer,
+            exif: None,
         })
     }
 }
@@ -0,0 +1,292 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Full-reference comparisons beyond SSIM. Everything compares 8-bit RGBA, so any color type and
+//! bit depth can be compared with any other of the same dimensions.
+
+use crate::png::grammar::{ColorType, Png};
+use crate::util::color::srgb_to_lab;
+use anyhow::{ensure, Result};
+
+/// A value for each channel of an RGBA image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChannelValues {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+impl ChannelValues {
+    /// The mean over red, green and blue, leaving alpha out.
+    pub fn rgb(&self) -> f64 {
+        (self.red + self.green + self.blue) / 3.0
+    }
+
+    fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        Self {
+            red: f(self.red),
+            green: f(self.green),
+            blue: f(self.blue),
+            alpha: f(self.alpha),
+        }
+    }
+}
+
+/// CIEDE2000 color differences over every pixel. A difference around 1 is just noticeable.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DeltaE {
+    pub mean: f64,
+    pub max: f64,
+}
+
+/// The peak signal-to-noise ratio in decibels of 8-bit samples with the given mean squared
+/// error. Identical samples are infinitely far above the noise.
+pub fn psnr(mse: f64) -> f64 {
+    if mse == 0.0 {
+        return f64::INFINITY;
+    }
+
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+impl Png {
+    /// The mean squared error of each channel, in 8-bit units.
+    pub fn compute_mse(&self, reference_image: &Self) -> Result<ChannelValues> {
+        ensure_same_dimensions(self, reference_image)?;
+
+        let mut sums = [0u64; 4];
+        self.to_rgba8()
+            .chunks_exact(4)
+            .zip(reference_image.to_rgba8().chunks_exact(4))
+            .for_each(|(test, reference)| {
+                for channel in 0..4 {
+                    let d = test[channel].abs_diff(reference[channel]) as u64;
+                    sums[channel] += d * d;
+                }
+            });
+
+        let pixels = (self.width as u64 * self.height as u64).max(1) as f64;
+        let [red, green, blue, alpha] = sums.map(|sum| sum as f64 / pixels);
+
+        Ok(ChannelValues {
+            red,
+            green,
+            blue,
+            alpha,
+        })
+    }
+
+    /// The PSNR of each channel. Use `psnr(mse.rgb())` for a single color figure.
+    pub fn compute_psnr(&self, reference_image: &Self) -> Result<ChannelValues> {
+        Ok(self.compute_mse(reference_image)?.map(psnr))
+    }
+
+    /// The structural dissimilarity `(1 - SSIM) / 2` from the windowed SSIM, 0 for identical
+    /// images.
+    pub fn compute_dssim(&self, reference_image: &Self) -> Result<f32> {
+        Ok((1.0 - self.compute_windowed_ssim(reference_image)?) / 2.0)
+    }
+
+    /// Perceptual color differences, comparing pixels as sRGB in CIE L*a*b*.
+    pub fn compute_delta_e(&self, reference_image: &Self) -> Result<DeltaE> {
+        ensure_same_dimensions(self, reference_image)?;
+
+        let lab = |rgba: &[u8]| srgb_to_lab([0, 1, 2].map(|c| rgba[c] as f32 / 255.0));
+
+        let (sum, max) = self
+            .to_rgba8()
+            .chunks_exact(4)
+            .zip(reference_image.to_rgba8().chunks_exact(4))
+            .map(|(test, reference)| ciede2000(lab(test), lab(reference)))
+            .fold((0.0, 0.0f64), |(sum, max), delta| {
+                (sum + delta, max.max(delta))
+            });
+
+        let pixels = (self.width as u64 * self.height as u64).max(1) as f64;
+
+        Ok(DeltaE {
+            mean: sum / pixels,
+            max,
+        })
+    }
+
+    /// An RGBA copy with color multiplied by alpha, as if composited over black. Comparing
+    /// premultiplied images makes every metric alpha-aware: color under transparent pixels no
+    /// longer counts, and a difference in coverage shows up in the color channels too.
+    pub fn premultiplied(&self) -> Self {
+        let pixel_buffer = self
+            .to_rgba8()
+            .chunks_exact(4)
+            .flat_map(|rgba| {
+                let alpha = rgba[3] as u32;
+                let premultiply = |c: u8| ((c as u32 * alpha + 127) / 255) as u8;
+
+                [
+                    premultiply(rgba[0]),
+                    premultiply(rgba[1]),
+                    premultiply(rgba[2]),
+                    rgba[3],
+                ]
+            })
+            .collect();
+
+        Self {
+            width: self.width,
+            height: self.height,
+            gamma: self.gamma,
+            color_type: ColorType::RGBA,
+            pixel_buffer,
+            exif: None,
+        }
+    }
+}
+
+fn ensure_same_dimensions(test_image: &Png, reference_image: &Png) -> Result<()> {
+    ensure!(
+        test_image.dimensions() == reference_image.dimensions(),
+        "Expect reference and test images to have identical dimensions."
+    );
+
+    Ok(())
+}
+
+/// The CIEDE2000 color difference of two L*a*b* colors, following Sharma, Wu and Dalal (2005).
+pub fn ciede2000(lab1: [f32; 3], lab2: [f32; 3]) -> f64 {
+    let [l1, a1, b1] = lab1.map(|v| v as f64);
+    let [l2, a2, b2] = lab2.map(|v| v as f64);
+
+    let pow7 = |v: f64| v.powi(7);
+    let twenty_five_pow7 = pow7(25.0);
+
+    // Stretch a* so neutral colors have hues closer to what we perceive.
+    let c_mean = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (pow7(c_mean) / (pow7(c_mean) + twenty_five_pow7)).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+    let chromatic = c1 * c2 != 0.0;
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = match h2 - h1 {
+        _ if !chromatic => 0.0,
+        d if d > 180.0 => d - 360.0,
+        d if d < -180.0 => d + 360.0,
+        d => d,
+    };
+    let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let c_mean = (c1 + c2) / 2.0;
+    let h_mean = match h1 + h2 {
+        sum if !chromatic => sum,
+        sum if (h1 - h2).abs() <= 180.0 => sum / 2.0,
+        sum if sum < 360.0 => (sum + 360.0) / 2.0,
+        sum => (sum - 360.0) / 2.0,
+    };
+
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let t =
+        1.0 - 0.17 * cos(h_mean - 30.0) + 0.24 * cos(2.0 * h_mean) + 0.32 * cos(3.0 * h_mean + 6.0)
+            - 0.20 * cos(4.0 * h_mean - 63.0);
+
+    let delta_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (pow7(c_mean) / (pow7(c_mean) + twenty_five_pow7)).sqrt();
+    let s_l = 1.0 + 0.015 * (l_mean - 50.0).powi(2) / (20.0 + (l_mean - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_mean;
+    let s_h = 1.0 + 0.015 * c_mean * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+
+    (l * l + c * c + h * h + r_t * c * h).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(pixels: &[[u8; 4]]) -> Png {
+        Png {
+            width: pixels.len() as u32,
+            height: 1,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: pixels.concat(),
+            exif: None,
+        }
+    }
+
+    #[test]
+    fn test_ciede2000_reference_pairs() {
+        // From the test data published with Sharma, Wu and Dalal (2005).
+        let pairs = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+        ];
+
+        for (lab1, lab2, expected) in pairs {
+            assert!((ciede2000(lab1, lab2) - expected).abs() < 1e-4);
+            assert!((ciede2000(lab2, lab1) - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_mse_and_psnr() -> Result<()> {
+        let reference = rgba(&[[0, 0, 0, 255], [100, 100, 100, 255]]);
+        let test = rgba(&[[10, 0, 0, 255], [100, 100, 120, 255]]);
+
+        let mse = test.compute_mse(&reference)?;
+        assert_eq!(
+            mse,
+            ChannelValues {
+                red: 50.0,
+                green: 0.0,
+                blue: 200.0,
+                alpha: 0.0
+            }
+        );
+
+        let psnr = test.compute_psnr(&reference)?;
+        assert!((psnr.red - 31.1411).abs() < 1e-4);
+        assert_eq!(psnr.green, f64::INFINITY);
+
+        assert!(test.compute_mse(&rgba(&[[0, 0, 0, 255]])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_premultiplied_ignores_hidden_color() -> Result<()> {
+        let reference = rgba(&[[255, 0, 0, 0], [200, 100, 50, 128]]);
+        let test = rgba(&[[0, 255, 0, 0], [200, 100, 50, 128]]);
+
+        assert!(test.compute_delta_e(&reference)?.max > 50.0);
+
+        let premultiplied = test.premultiplied();
+        assert_eq!(premultiplied.pixel_buffer, [0, 0, 0, 0, 100, 50, 25, 128]);
+        assert_eq!(
+            premultiplied.compute_delta_e(&reference.premultiplied())?,
+            DeltaE {
+                mean: 0.0,
+                max: 0.0
+            }
+        );
+
+        Ok(())
+    }
+}
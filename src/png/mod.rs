@@ -1,8 +1,13 @@
 pub use decoder::*;
+pub use encoder::*;
+pub mod diff;
 pub mod grammar;
+pub mod metrics;
 pub mod ssim;
+pub mod stats;
 
 mod crc32;
 mod decoder;
+mod encoder;
 mod interlace;
 mod scanline_reader;
@@ -1,6 +1,7 @@
 #![allow(clippy::suboptimal_flops)]
 
 use crate::png::grammar::{ColorType, Png};
+use crate::png::stats::luma;
 use anyhow::ensure;
 
 const K1: f32 = 0.01;
@@ -50,9 +51,7 @@ impl Png {
                     .chunks_exact(3)
                     .enumerate()
                     .for_each(|(i, rgb)| {
-                        let (r, g, b) = (rgb[0] as f32, rgb[1] as f32, rgb[2] as f32);
-
-                        lumas[i] = r * 0.29891 + g * 0.58661 + b * 0.11448;
+                        lumas[i] = luma([rgb[0] as f32, rgb[1] as f32, rgb[2] as f32]);
                         mean_intensity += lumas[i];
                     });
 
@@ -67,9 +66,7 @@ impl Png {
                     .chunks_exact(4)
                     .enumerate()
                     .for_each(|(i, rgb)| {
-                        let (r, g, b) = (rgb[0] as f32, rgb[1] as f32, rgb[2] as f32);
-
-                        lumas[i] = r * 0.29891 + g * 0.58661 + b * 0.11448;
+                        lumas[i] = luma([rgb[0] as f32, rgb[1] as f32, rgb[2] as f32]);
                         mean_intensity += lumas[i];
                     });
 
@@ -180,6 +177,354 @@ impl LumaBuffer {
     }
 }
 
+/// The side of the Gaussian window Wang et al. average local statistics over.
+const WINDOW_SIZE: usize = 11;
+const WINDOW_SIGMA: f32 = 1.5;
+
+/// Exponents of each scale in MS-SSIM, finest first, from Wang, Simoncelli and Bovik (2003).
+const MS_SSIM_WEIGHTS: [f32; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+impl Png {
+    /// The local SSIM of every 11x11 window that fits inside the image, as in Wang et al. (2004).
+    /// The map is 10 pixels narrower and shorter than the images.
+    pub fn ssim_map(&self, reference_image: &Self) -> anyhow::Result<SsimMap> {
+        let (test, reference) = self.planes(reference_image, WINDOW_SIZE)?;
+        let (ssim, _) = local_ssim(&test, &reference);
+
+        Ok(SsimMap {
+            width: ssim.width as u32,
+            height: ssim.height as u32,
+            values: ssim.values,
+        })
+    }
+
+    /// The mean of the SSIM map, the standard metric published results report.
+    ///
+    /// For grayscale images under 384 pixels on the short side this matches Wang's `ssim.m`.
+    /// Color images go through the repo's luma weights rather than `rgb2gray`, and larger images
+    /// are not downsampled by `max(1, round(min(M, N) / 256))` first, so both score differently
+    /// from the reference implementation.
+    pub fn compute_windowed_ssim(&self, reference_image: &Self) -> anyhow::Result<f32> {
+        Ok(self.ssim_map(reference_image)?.mean())
+    }
+
+    /// Multi-scale SSIM over five dyadic scales. Both sides need at least 176 pixels so the
+    /// coarsest scale still fits a window.
+    ///
+    /// This follows Wang's `msssim.m` on grayscale images with even sides at every scale. An odd
+    /// row or column is dropped instead of mirrored, and color images use the same luma weights
+    /// as [`Png::compute_windowed_ssim`], so those inputs differ slightly from the reference.
+    pub fn compute_ms_ssim(&self, reference_image: &Self) -> anyhow::Result<f32> {
+        let scales = MS_SSIM_WEIGHTS.len();
+        let (mut test, mut reference) =
+            self.planes(reference_image, WINDOW_SIZE << (scales - 1))?;
+
+        let mut score = 1.0;
+        for (scale, weight) in MS_SSIM_WEIGHTS.into_iter().enumerate() {
+            let (ssim, contrast_structure) = local_ssim(&test, &reference);
+
+            // Only the coarsest scale compares luminance. Negative means would make the power
+            // undefined, so they count as no similarity at all.
+            if scale == scales - 1 {
+                score *= ssim.mean().max(0.0).powf(weight);
+            } else {
+                score *= contrast_structure.mean().max(0.0).powf(weight);
+                test = test.downsample();
+                reference = reference.downsample();
+            }
+        }
+
+        Ok(score)
+    }
+
+    fn planes(&self, reference_image: &Self, min_size: usize) -> anyhow::Result<(Plane, Plane)> {
+        ensure!(
+            self.dimensions() == reference_image.dimensions(),
+            "Expect reference and test images to have identical dimensions."
+        );
+
+        let (width, height) = self.dimensions();
+        ensure!(
+            width.min(height) as usize >= min_size,
+            "Expect images of at least {min_size}x{min_size} pixels, got {width}x{height}."
+        );
+
+        Ok((Plane::from_png(self), Plane::from_png(reference_image)))
+    }
+}
+
+/// Local SSIM values, one per window position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsimMap {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl SsimMap {
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.values.iter().sum::<f32>() / self.values.len() as f32
+    }
+
+    /// A grayscale image where white is identical and black is SSIM 0 or below.
+    pub fn to_png(&self) -> Png {
+        Png {
+            width: self.width,
+            height: self.height,
+            gamma: 0,
+            color_type: ColorType::Grayscale,
+            pixel_buffer: self
+                .values
+                .iter()
+                .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect(),
+            exif: None,
+        }
+    }
+}
+
+/// Luma samples in [0, 255] laid out row by row.
+#[derive(Debug, Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Plane {
+    fn from_png(png: &Png) -> Self {
+        let (width, height) = png.dimensions();
+
+        Self {
+            width: width as usize,
+            height: height as usize,
+            values: png
+                .to_rgba8()
+                .chunks_exact(4)
+                .map(|rgba| luma([rgba[0] as f32, rgba[1] as f32, rgba[2] as f32]))
+                .collect(),
+        }
+    }
+
+    fn zip_map(&self, other: &Self, f: impl Fn(f32, f32) -> f32) -> Self {
+        Self {
+            values: self
+                .values
+                .iter()
+                .zip(&other.values)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+            ..*self
+        }
+    }
+
+    fn mean(&self) -> f32 {
+        self.values.iter().sum::<f32>() / self.values.len() as f32
+    }
+
+    /// Convolves with the Gaussian window, keeping only positions where it fits entirely.
+    fn filter_valid(&self, kernel: &[f32; WINDOW_SIZE]) -> Self {
+        let width = self.width - WINDOW_SIZE + 1;
+        let height = self.height - WINDOW_SIZE + 1;
+
+        let rows = (0..self.height)
+            .flat_map(|y| {
+                let row = &self.values[y * self.width..(y + 1) * self.width];
+                (0..width).map(move |x| dot(kernel, &row[x..]))
+            })
+            .collect::<Vec<_>>();
+
+        let values = (0..height)
+            .flat_map(|y| {
+                let rows = &rows;
+                (0..width).map(move |x| {
+                    kernel
+                        .iter()
+                        .enumerate()
+                        .map(|(i, k)| k * rows[(y + i) * width + x])
+                        .sum()
+                })
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Averages 2x2 blocks, dropping an odd last row or column.
+    fn downsample(&self) -> Self {
+        let width = self.width / 2;
+        let height = self.height / 2;
+
+        let values = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    let at =
+                        |dx: usize, dy: usize| self.values[(2 * y + dy) * self.width + 2 * x + dx];
+                    (at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) / 4.0
+                })
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+}
+
+fn dot(kernel: &[f32; WINDOW_SIZE], samples: &[f32]) -> f32 {
+    kernel.iter().zip(samples).map(|(k, s)| k * s).sum()
+}
+
+fn gaussian_window() -> [f32; WINDOW_SIZE] {
+    let center = (WINDOW_SIZE / 2) as f32;
+    let weights: [f32; WINDOW_SIZE] = std::array::from_fn(|i| {
+        let d = i as f32 - center;
+        (-d * d / (2.0 * WINDOW_SIGMA * WINDOW_SIGMA)).exp()
+    });
+    let total = weights.iter().sum::<f32>();
+
+    weights.map(|w| w / total)
+}
+
+/// The SSIM and the contrast-structure term alone at every window position.
+fn local_ssim(x: &Plane, y: &Plane) -> (Plane, Plane) {
+    let window = gaussian_window();
+
+    let mu_x = x.filter_valid(&window);
+    let mu_y = y.filter_valid(&window);
+    let xx = x.zip_map(x, |a, b| a * b).filter_valid(&window);
+    let yy = y.zip_map(y, |a, b| a * b).filter_valid(&window);
+    let xy = x.zip_map(y, |a, b| a * b).filter_valid(&window);
+
+    let mut contrast_structure = mu_x.clone();
+    let mut ssim = mu_x.clone();
+
+    for i in 0..mu_x.values.len() {
+        let (m, n) = (mu_x.values[i], mu_y.values[i]);
+        let variance_x = xx.values[i] - m * m;
+        let variance_y = yy.values[i] - n * n;
+        let covariance = xy.values[i] - m * n;
+
+        let cs = (2.0 * covariance + C2) / (variance_x + variance_y + C2);
+        let luminance = (2.0 * m * n + C1) / (m * m + n * n + C1);
+
+        contrast_structure.values[i] = cs;
+        ssim.values[i] = luminance * cs;
+    }
+
+    (ssim, contrast_structure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width: u32, height: u32, f: impl Fn(u32, u32) -> u8) -> Png {
+        Png {
+            width,
+            height,
+            gamma: 0,
+            color_type: ColorType::Grayscale,
+            pixel_buffer: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| f(x, y))
+                .collect(),
+            exif: None,
+        }
+    }
+
+    /// A deterministic texture with detail at every scale.
+    fn texture(x: u32, y: u32) -> u8 {
+        ((x * 7 + y * 13) % 31 * 4 + (x / 8 + y / 8) % 2 * 100) as u8
+    }
+
+    #[test]
+    fn test_windowed_ssim_of_flat_images() -> anyhow::Result<()> {
+        let (a, b) = (100.0, 140.0);
+        let test = gray(16, 12, |_, _| a as u8);
+        let reference = gray(16, 12, |_, _| b as u8);
+
+        let map = test.ssim_map(&reference)?;
+        assert_eq!(map.dimensions(), (6, 2));
+
+        let expected = (2.0 * a * b + C1) / (a * a + b * b + C1);
+        assert!((map.mean() - expected).abs() < 1e-4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ssim_ranks_degradation() -> anyhow::Result<()> {
+        let reference = gray(192, 192, texture);
+        let noisy = gray(192, 192, |x, y| {
+            texture(x, y).saturating_add((x * y % 5 * 6) as u8)
+        });
+        let flat = gray(192, 192, |_, _| 64);
+
+        assert!((reference.compute_windowed_ssim(&reference)? - 1.0).abs() < 1e-5);
+        assert!((reference.compute_ms_ssim(&reference)? - 1.0).abs() < 1e-5);
+
+        let noisy_ssim = noisy.compute_windowed_ssim(&reference)?;
+        assert!(noisy_ssim < 1.0);
+        assert!(flat.compute_windowed_ssim(&reference)? < noisy_ssim);
+
+        let noisy_ms_ssim = noisy.compute_ms_ssim(&reference)?;
+        assert!(noisy_ms_ssim < 1.0);
+        assert!(flat.compute_ms_ssim(&reference)? < noisy_ms_ssim);
+
+        Ok(())
+    }
+
+    /// `texture` with a ramp of noise and a brighter diagonal band of tiles.
+    fn degraded(x: u32, y: u32) -> u8 {
+        let band = if (x / 32 + y / 32) % 3 == 0 { 17 } else { 0 };
+        (texture(x, y) as i32 + (x * y % 5 * 6) as i32 - 12 + band).clamp(0, 255) as u8
+    }
+
+    #[test]
+    fn test_matches_reference_implementation() -> anyhow::Result<()> {
+        // Computed in float64 by a line-for-line port of Wang's `ssim.m` and `msssim.m` with the
+        // default K, an 11x11 Gaussian window of sigma 1.5 and L = 255. At 256x256 `ssim.m`
+        // does not downsample and every MS-SSIM scale has even sides, so the two should agree
+        // up to f32 rounding.
+        let reference = gray(256, 256, texture);
+        let test = gray(256, 256, degraded);
+
+        let ssim = test.compute_windowed_ssim(&reference)?;
+        assert!((ssim - 0.981_299_4).abs() < 1e-4, "{ssim}");
+
+        let ms_ssim = test.compute_ms_ssim(&reference)?;
+        assert!((ms_ssim - 0.900_827_9).abs() < 1e-4, "{ms_ssim}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_small_images_are_rejected() {
+        let small = gray(100, 100, texture);
+
+        assert!(small.compute_windowed_ssim(&small).is_ok());
+        assert!(small.compute_ms_ssim(&small).is_err());
+        assert!(gray(10, 40, texture)
+            .ssim_map(&gray(10, 40, texture))
+            .is_err());
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
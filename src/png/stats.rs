@@ -0,0 +1,219 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::png::grammar::{ColorType, Png};
+
+/// Bins in a histogram, one per 8-bit value.
+pub const BINS: usize = 256;
+
+/// The luma weights `compute_sim` compares images with.
+pub(crate) fn luma([r, g, b]: [f32; 3]) -> f32 {
+    r * 0.29891 + g * 0.58661 + b * 0.11448
+}
+
+/// Counts of each 8-bit value in a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    bins: [u32; BINS],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self { bins: [0; BINS] }
+    }
+}
+
+impl Histogram {
+    pub fn from_samples(samples: impl IntoIterator<Item = u8>) -> Self {
+        let mut histogram = Self::default();
+        samples.into_iter().for_each(|v| histogram.add(v));
+
+        histogram
+    }
+
+    pub const fn from_bins(bins: [u32; BINS]) -> Self {
+        Self { bins }
+    }
+
+    pub const fn add(&mut self, value: u8) {
+        self.bins[value as usize] += 1;
+    }
+
+    pub const fn bins(&self) -> &[u32; BINS] {
+        &self.bins
+    }
+
+    pub fn count(&self) -> u64 {
+        self.bins.iter().map(|&n| n as u64).sum()
+    }
+
+    /// The number of samples at or below each value.
+    pub fn cumulative(&self) -> [u64; BINS] {
+        let mut total = 0;
+
+        self.bins.map(|n| {
+            total += n as u64;
+            total
+        })
+    }
+
+    pub fn min(&self) -> Option<u8> {
+        self.bins.iter().position(|&n| n > 0).map(|v| v as u8)
+    }
+
+    pub fn max(&self) -> Option<u8> {
+        self.bins.iter().rposition(|&n| n > 0).map(|v| v as u8)
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        let count = self.count();
+        let sum = self
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(v, &n)| v as u64 * n as u64)
+            .sum::<u64>();
+
+        (count > 0).then(|| sum as f32 / count as f32)
+    }
+
+    /// The population standard deviation.
+    pub fn std_dev(&self) -> Option<f32> {
+        let mean = self.mean()? as f64;
+        let variance = self
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(v, &n)| (v as f64 - mean).powi(2) * n as f64)
+            .sum::<f64>()
+            / self.count() as f64;
+
+        Some(variance.sqrt() as f32)
+    }
+
+    /// The smallest value with at least `p` percent of the samples at or below it.
+    pub fn percentile(&self, p: f32) -> Option<u8> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((p.clamp(0.0, 100.0) / 100.0 * count as f32).ceil() as u64).max(1);
+        self.cumulative()
+            .iter()
+            .position(|&total| total >= rank)
+            .map(|v| v as u8)
+    }
+
+    pub fn median(&self) -> Option<u8> {
+        self.percentile(50.0)
+    }
+
+    pub fn stats(&self) -> Option<ChannelStats> {
+        Some(ChannelStats {
+            min: self.min()?,
+            max: self.max()?,
+            mean: self.mean()?,
+            std_dev: self.std_dev()?,
+            median: self.median()?,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChannelStats {
+    pub min: u8,
+    pub max: u8,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub median: u8,
+}
+
+/// A histogram per channel. Grayscale images count their gray value as red, green and blue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histograms {
+    pub red: Histogram,
+    pub green: Histogram,
+    pub blue: Histogram,
+    /// Only for color types with an alpha channel.
+    pub alpha: Option<Histogram>,
+    pub luma: Histogram,
+}
+
+impl Png {
+    pub fn histograms(&self) -> Histograms {
+        let has_alpha = matches!(self.color_type, ColorType::RGBA | ColorType::GrayscaleAlpha);
+
+        let mut histograms = Histograms {
+            red: Histogram::default(),
+            green: Histogram::default(),
+            blue: Histogram::default(),
+            alpha: has_alpha.then(Histogram::default),
+            luma: Histogram::default(),
+        };
+
+        self.to_rgba8().chunks_exact(4).for_each(|rgba| {
+            let (r, g, b) = (rgba[0], rgba[1], rgba[2]);
+
+            histograms.red.add(r);
+            histograms.green.add(g);
+            histograms.blue.add(b);
+            histograms
+                .luma
+                .add(luma([r as f32, g as f32, b as f32]).round() as u8);
+
+            if let Some(alpha) = &mut histograms.alpha {
+                alpha.add(rgba[3]);
+            }
+        });
+
+        histograms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_stats() {
+        let histogram = Histogram::from_samples([10, 20, 20, 30, 40, 40, 40, 200]);
+
+        assert_eq!(histogram.count(), 8);
+        assert_eq!(histogram.bins()[40], 3);
+        assert_eq!(histogram.cumulative()[30], 4);
+
+        let stats = histogram.stats().unwrap();
+        assert_eq!((stats.min, stats.max, stats.median), (10, 200, 30));
+        assert_eq!(stats.mean, 50.0);
+        assert!((stats.std_dev - 57.6628).abs() < 1e-3);
+
+        assert_eq!(histogram.percentile(0.0), Some(10));
+        assert_eq!(histogram.percentile(90.0), Some(200));
+        assert_eq!(histogram.percentile(100.0), Some(200));
+    }
+
+    #[test]
+    fn test_empty_histogram() {
+        let histogram = Histogram::default();
+
+        assert_eq!(histogram.stats(), None);
+        assert_eq!(histogram.percentile(50.0), None);
+    }
+
+    #[test]
+    fn test_png_histograms() {
+        let png = Png {
+            width: 2,
+            height: 1,
+            gamma: 0,
+            color_type: ColorType::GrayscaleAlpha,
+            pixel_buffer: vec![0, 255, 100, 128],
+            exif: None,
+        };
+
+        let histograms = png.histograms();
+        assert_eq!(histograms.red, histograms.blue);
+        assert_eq!(histograms.luma.bins()[100], 1);
+        assert_eq!(histograms.alpha.unwrap().min(), Some(128));
+    }
+}
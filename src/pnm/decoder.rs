@@ -0,0 +1,381 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::png::grammar::{ColorType, Png};
+use crate::pnm::grammar::Magic;
+
+/// Decodes PBM, PGM, PPM and PAM files in both plain and raw encodings.
+#[derive(Debug)]
+pub struct PnmDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+struct PnmHeader {
+    magic: Magic,
+    width: u32,
+    height: u32,
+    channels: usize,
+    max_value: u32,
+}
+
+impl<'a> PnmDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Png> {
+        ensure!(
+            self.data.len() >= 2,
+            "Invalid Netpbm file: missing magic number."
+        );
+        let magic = Magic::try_from(&self.data[..2])?;
+        self.cursor = 2;
+
+        let header = match magic {
+            Magic::ArbitraryMap => self.parse_pam_header()?,
+            _ => self.parse_header(magic)?,
+        };
+
+        ensure!(
+            header.width > 0 && header.height > 0,
+            "Netpbm dimensions must be positive."
+        );
+        ensure!(
+            (1..=u16::MAX as u32).contains(&header.max_value),
+            "Invalid maximum sample value: {}",
+            header.max_value
+        );
+        ensure!(
+            (header.width as u64 * header.height as u64) < (1 << 30),
+            "Netpbm dimensions are too large: {}x{}.",
+            header.width,
+            header.height
+        );
+
+        let color_type = match header.channels {
+            1 => ColorType::Grayscale,
+            2 => ColorType::GrayscaleAlpha,
+            3 => ColorType::RGB,
+            4 => ColorType::RGBA,
+            foreign => bail!("Unsupported PAM depth: {}", foreign),
+        };
+
+        let pixel_buffer = match magic {
+            Magic::Bitmap => self.decode_packed_bitmap(&header)?,
+            _ if magic.is_plain() => self.decode_plain(&header)?,
+            _ => self.decode_raw(&header)?,
+        };
+
+        Ok(Png {
+            width: header.width,
+            height: header.height,
+            gamma: 0,
+            color_type,
+            pixel_buffer,
+            exif: None,
+        })
+    }
+
+    fn parse_header(&mut self, magic: Magic) -> Result<PnmHeader> {
+        let width = self.parse_number()?;
+        let height = self.parse_number()?;
+        let max_value = if magic.is_bitmap() {
+            1
+        } else {
+            self.parse_number()?
+        };
+
+        if !magic.is_plain() {
+            // Exactly one whitespace character separates the header from the raster.
+            ensure!(
+                self.data
+                    .get(self.cursor)
+                    .is_some_and(u8::is_ascii_whitespace),
+                "Expected whitespace before the raster."
+            );
+            self.cursor += 1;
+        }
+
+        Ok(PnmHeader {
+            magic,
+            width,
+            height,
+            channels: magic.channels().unwrap_or(1),
+            max_value,
+        })
+    }
+
+    fn parse_pam_header(&mut self) -> Result<PnmHeader> {
+        let (mut width, mut height, mut depth, mut max_value) = (None, None, None, None);
+
+        loop {
+            let token = self.parse_token()?;
+
+            match token {
+                b"WIDTH" => width = Some(self.parse_number()?),
+                b"HEIGHT" => height = Some(self.parse_number()?),
+                b"DEPTH" => depth = Some(self.parse_number()?),
+                b"MAXVAL" => max_value = Some(self.parse_number()?),
+                // The depth and max value already determine the layout.
+                b"TUPLTYPE" => {
+                    self.parse_token()?;
+                }
+                b"ENDHDR" => break,
+                foreign => bail!(
+                    "Unknown PAM header field: {}",
+                    String::from_utf8_lossy(foreign)
+                ),
+            }
+        }
+
+        // Skip the newline ending the ENDHDR line.
+        self.cursor += 1;
+
+        let (Some(width), Some(height), Some(depth), Some(max_value)) =
+            (width, height, depth, max_value)
+        else {
+            bail!("PAM header is missing WIDTH, HEIGHT, DEPTH or MAXVAL.");
+        };
+
+        Ok(PnmHeader {
+            magic: Magic::ArbitraryMap,
+            width,
+            height,
+            channels: depth as usize,
+            max_value,
+        })
+    }
+
+    fn decode_raw(&mut self, header: &PnmHeader) -> Result<Vec<u8>> {
+        let samples = header.width as usize * header.height as usize * header.channels;
+        let bytes_per_sample = if header.max_value > 255 { 2 } else { 1 };
+
+        let len = samples * bytes_per_sample;
+        ensure!(
+            self.cursor + len <= self.data.len(),
+            "Unexpected EOF. Raster needs {} bytes, {} remaining.",
+            len,
+            self.data.len().saturating_sub(self.cursor)
+        );
+
+        let raster = &self.data[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        let pixels = if bytes_per_sample == 2 {
+            raster
+                .chunks_exact(2)
+                .map(|s| Self::scale(u16::from_be_bytes([s[0], s[1]]) as u32, header.max_value))
+                .collect()
+        } else if header.max_value == 255 {
+            raster.to_vec()
+        } else {
+            raster
+                .iter()
+                .map(|&s| Self::scale(s as u32, header.max_value))
+                .collect()
+        };
+
+        Ok(pixels)
+    }
+
+    fn decode_plain(&mut self, header: &PnmHeader) -> Result<Vec<u8>> {
+        let samples = header.width as usize * header.height as usize * header.channels;
+        let mut pixels = Vec::with_capacity(samples);
+
+        for _ in 0..samples {
+            let sample = if header.magic.is_bitmap() {
+                // Plain PBM samples need not be separated by whitespace.
+                self.skip_whitespace();
+                let Some(&bit) = self.data.get(self.cursor) else {
+                    bail!("Unexpected EOF in PBM raster.");
+                };
+                self.cursor += 1;
+
+                match bit {
+                    b'0' => 255,
+                    b'1' => 0,
+                    foreign => bail!("Invalid PBM sample: {}", foreign as char),
+                }
+            } else {
+                let value = self.parse_number()?;
+                ensure!(
+                    value <= header.max_value,
+                    "Sample {} exceeds the maximum value {}.",
+                    value,
+                    header.max_value
+                );
+
+                Self::scale(value, header.max_value)
+            };
+
+            pixels.push(sample);
+        }
+
+        Ok(pixels)
+    }
+
+    fn decode_packed_bitmap(&mut self, header: &PnmHeader) -> Result<Vec<u8>> {
+        let width = header.width as usize;
+        let row_size = width.div_ceil(8);
+        let len = row_size * header.height as usize;
+
+        ensure!(
+            self.cursor + len <= self.data.len(),
+            "Unexpected EOF in PBM raster."
+        );
+
+        let raster = &self.data[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        let pixels = raster
+            .chunks_exact(row_size)
+            .flat_map(|row| {
+                (0..width).map(move |x| {
+                    // A set bit is black.
+                    if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                        0
+                    } else {
+                        255
+                    }
+                })
+            })
+            .collect();
+
+        Ok(pixels)
+    }
+
+    /// Rescales a sample in `0..=max_value` to 8 bits.
+    fn scale(value: u32, max_value: u32) -> u8 {
+        ((value.min(max_value) * 255 + max_value / 2) / max_value) as u8
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&b) = self.data.get(self.cursor) {
+            match b {
+                b'#' => {
+                    while self.data.get(self.cursor).is_some_and(|&b| b != b'\n') {
+                        self.cursor += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.cursor += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn parse_token(&mut self) -> Result<&'a [u8]> {
+        self.skip_whitespace();
+
+        let start = self.cursor;
+        while self
+            .data
+            .get(self.cursor)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.cursor += 1;
+        }
+
+        ensure!(self.cursor > start, "Unexpected EOF in Netpbm header.");
+
+        Ok(&self.data[start..self.cursor])
+    }
+
+    fn parse_number(&mut self) -> Result<u32> {
+        let token = self.parse_token()?;
+        ensure!(
+            token.iter().all(u8::is_ascii_digit),
+            "Expected a number, found {}",
+            String::from_utf8_lossy(token)
+        );
+
+        Ok(std::str::from_utf8(token)?.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::PngDecoder;
+    use crate::pnm::PnmEncoder;
+    use image::ImageFormat;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        for path in [
+            "./test_suite/basn0g08.png",
+            "./test_suite/basn4a08.png",
+            "./test_suite/basn2c08.png",
+            "./test_suite/basn6a08.png",
+        ] {
+            let content = std::fs::read(path)?;
+            let png = PngDecoder::new(&content).decode()?;
+
+            let encoded = PnmEncoder::new(&png).encode()?;
+            let decoded = PnmDecoder::new(&encoded).decode()?;
+
+            assert_eq!(decoded.dimensions(), png.dimensions());
+            assert_eq!(decoded.color_type, png.color_type, "{path}");
+            assert_eq!(decoded.pixel_buffer, png.pixel_buffer, "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_encoder() -> Result<()> {
+        let reference = image::open("./test_suite/basn2c08.png")?;
+
+        let mut encoded = Vec::new();
+        reference.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Pnm)?;
+
+        let decoded = PnmDecoder::new(&encoded).decode()?;
+        assert_eq!(decoded.pixel_buffer, reference.as_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_formats() -> Result<()> {
+        let pbm = PnmDecoder::new(b"P1\n# comment\n3 2\n0 1 0\n110").decode()?;
+        assert_eq!(pbm.color_type, ColorType::Grayscale);
+        assert_eq!(pbm.pixel_buffer, [255, 0, 255, 0, 0, 255]);
+
+        let pgm = PnmDecoder::new(b"P2 2 1 15 0 15").decode()?;
+        assert_eq!(pgm.pixel_buffer, [0, 255]);
+
+        let ppm = PnmDecoder::new(b"P3\n1 1\n# max\n100\n100 50 0\n").decode()?;
+        assert_eq!(ppm.color_type, ColorType::RGB);
+        assert_eq!(ppm.pixel_buffer, [255, 128, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_bitmap_and_wide_samples() -> Result<()> {
+        let pbm = PnmDecoder::new(b"P4 10 1\n\xA0\x40").decode()?;
+        assert_eq!(
+            pbm.pixel_buffer,
+            [0, 255, 0, 255, 255, 255, 255, 255, 255, 0]
+        );
+
+        let pgm = PnmDecoder::new(b"P5 2 1 65535\n\xFF\xFF\x80\x00").decode()?;
+        assert_eq!(pgm.pixel_buffer, [255, 128]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pam() -> Result<()> {
+        let pam = PnmDecoder::new(
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n\x01\x02\x03\x04",
+        )
+        .decode()?;
+
+        assert_eq!(pam.color_type, ColorType::RGBA);
+        assert_eq!(pam.pixel_buffer, [1, 2, 3, 4]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+
+use crate::png::grammar::{ColorType, Png};
+use crate::png::stats::luma;
+use crate::pnm::grammar::Magic;
+
+/// Writes raw PGM or PPM files, falling back to PAM for images with an alpha channel.
+#[derive(Debug)]
+pub struct PnmEncoder<'a> {
+    png: &'a Png,
+    magic: Option<Magic>,
+}
+
+impl<'a> PnmEncoder<'a> {
+    pub const fn new(png: &'a Png) -> Self {
+        Self { png, magic: None }
+    }
+
+    /// Writes a raw PGM or PPM whatever the color type, dropping alpha and reducing color to
+    /// luma as needed.
+    pub const fn with_magic(mut self, magic: Magic) -> Self {
+        self.magic = Some(magic);
+        self
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.png.dimensions();
+
+        match self.magic {
+            None => {}
+            Some(Magic::Pixmap) => {
+                let mut out = Self::header(Magic::Pixmap, width, height).into_bytes();
+                out.extend_from_slice(&self.png.to_rgb8());
+
+                return Ok(out);
+            }
+            Some(Magic::Graymap) => {
+                let mut out = Self::header(Magic::Graymap, width, height).into_bytes();
+                out.extend(
+                    self.png
+                        .to_rgb8()
+                        .chunks_exact(3)
+                        .map(|rgb| luma([rgb[0], rgb[1], rgb[2]].map(f32::from)).round() as u8),
+                );
+
+                return Ok(out);
+            }
+            Some(magic) => bail!(
+                "Only raw PGM and PPM files can be asked for, not {:?}.",
+                magic
+            ),
+        }
+
+        let header = match self.png.color_type {
+            ColorType::Grayscale => Self::header(Magic::Graymap, width, height),
+            ColorType::RGB => Self::header(Magic::Pixmap, width, height),
+            ColorType::GrayscaleAlpha => Self::pam_header(width, height, 2, "GRAYSCALE_ALPHA"),
+            ColorType::RGBA => Self::pam_header(width, height, 4, "RGB_ALPHA"),
+            ColorType::Palette => bail!("Palette images cannot be written as Netpbm."),
+        };
+
+        let mut out = header.into_bytes();
+        out.extend_from_slice(&self.png.pixel_buffer);
+
+        Ok(out)
+    }
+
+    fn header(magic: Magic, width: u32, height: u32) -> String {
+        let magic = String::from_utf8_lossy(magic.as_bytes());
+        format!("{magic}\n{width} {height}\n255\n")
+    }
+
+    fn pam_header(width: u32, height: u32, depth: u8, tuple_type: &str) -> String {
+        format!(
+            "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH {depth}\nMAXVAL 255\nTUPLTYPE {tuple_type}\nENDHDR\n"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_reference_decoder() -> Result<()> {
+        for (color_type, channels) in [(ColorType::Grayscale, 1), (ColorType::RGB, 3)] {
+            let png = Png {
+                width: 4,
+                height: 3,
+                gamma: 0,
+                color_type,
+                pixel_buffer: (0..4 * 3 * channels).map(|i| (i * 7) as u8).collect(),
+                exif: None,
+            };
+
+            let encoded = PnmEncoder::new(&png).encode()?;
+            let reference = image::load_from_memory_with_format(&encoded, image::ImageFormat::Pnm)?;
+
+            assert_eq!(reference.as_bytes(), png.pixel_buffer);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_magic_drops_alpha() -> Result<()> {
+        let png = Png {
+            width: 2,
+            height: 1,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: vec![255, 0, 0, 128, 40, 40, 40, 255],
+            exif: None,
+        };
+
+        let pixmap = PnmEncoder::new(&png).with_magic(Magic::Pixmap).encode()?;
+        let reference = image::load_from_memory_with_format(&pixmap, image::ImageFormat::Pnm)?;
+        assert_eq!(reference.as_bytes(), [255, 0, 0, 40, 40, 40]);
+
+        let graymap = PnmEncoder::new(&png).with_magic(Magic::Graymap).encode()?;
+        let reference = image::load_from_memory_with_format(&graymap, image::ImageFormat::Pnm)?;
+        assert_eq!(reference.as_bytes(), [76, 40]);
+
+        Ok(())
+    }
+}
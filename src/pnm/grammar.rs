@@ -0,0 +1,73 @@
+use anyhow::{bail, Result};
+
+/// The Netpbm format, identified by the magic number at the start of the file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Magic {
+    /// P1, a plain (ASCII) PBM bitmap.
+    PlainBitmap,
+    /// P2, a plain PGM graymap.
+    PlainGraymap,
+    /// P3, a plain PPM pixmap.
+    PlainPixmap,
+    /// P4, a raw PBM bitmap with rows packed into bits.
+    Bitmap,
+    /// P5, a raw PGM graymap.
+    Graymap,
+    /// P6, a raw PPM pixmap.
+    Pixmap,
+    /// P7, a PAM file with an arbitrary tuple type.
+    ArbitraryMap,
+}
+
+impl Magic {
+    pub const fn is_plain(self) -> bool {
+        matches!(
+            self,
+            Self::PlainBitmap | Self::PlainGraymap | Self::PlainPixmap
+        )
+    }
+
+    pub const fn is_bitmap(self) -> bool {
+        matches!(self, Self::PlainBitmap | Self::Bitmap)
+    }
+
+    /// Number of samples per pixel. Unknown for PAM until its header is parsed.
+    pub const fn channels(self) -> Option<usize> {
+        match self {
+            Self::PlainBitmap | Self::PlainGraymap | Self::Bitmap | Self::Graymap => Some(1),
+            Self::PlainPixmap | Self::Pixmap => Some(3),
+            Self::ArbitraryMap => None,
+        }
+    }
+
+    pub const fn as_bytes(self) -> &'static [u8; 2] {
+        match self {
+            Self::PlainBitmap => b"P1",
+            Self::PlainGraymap => b"P2",
+            Self::PlainPixmap => b"P3",
+            Self::Bitmap => b"P4",
+            Self::Graymap => b"P5",
+            Self::Pixmap => b"P6",
+            Self::ArbitraryMap => b"P7",
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Magic {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let magic = match value {
+            b"P1" => Self::PlainBitmap,
+            b"P2" => Self::PlainGraymap,
+            b"P3" => Self::PlainPixmap,
+            b"P4" => Self::Bitmap,
+            b"P5" => Self::Graymap,
+            b"P6" => Self::Pixmap,
+            b"P7" => Self::ArbitraryMap,
+            foreign => bail!("Invalid Netpbm magic number: {:?}", foreign),
+        };
+
+        Ok(magic)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::ops::{Adjustments, Curves};
+
+/// The adjustment the left and right arrow keys change, picked with the number keys.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum AdjustmentControl {
+    #[default]
+    LevelsBlack,
+    LevelsWhite,
+    LevelsGamma,
+    Brightness,
+    Contrast,
+    Hue,
+    Saturation,
+    Lightness,
+    Temperature,
+    Tint,
+}
+
+impl AdjustmentControl {
+    /// The control for the digit keys `1` through `9`, then `0`.
+    pub(crate) const fn from_digit(digit: u8) -> Option<Self> {
+        Some(match digit {
+            1 => Self::LevelsBlack,
+            2 => Self::LevelsWhite,
+            3 => Self::LevelsGamma,
+            4 => Self::Brightness,
+            5 => Self::Contrast,
+            6 => Self::Hue,
+            7 => Self::Saturation,
+            8 => Self::Lightness,
+            9 => Self::Temperature,
+            0 => Self::Tint,
+            _ => return None,
+        })
+    }
+
+    const fn step(&self) -> f32 {
+        match self {
+            Self::LevelsGamma => 0.1,
+            Self::Hue => 10.0,
+            _ => 0.05,
+        }
+    }
+
+    const fn range(&self) -> (f32, f32) {
+        match self {
+            Self::LevelsBlack | Self::LevelsWhite => (0.0, 1.0),
+            Self::LevelsGamma => (0.1, 10.0),
+            Self::Hue => (-180.0, 180.0),
+            _ => (-1.0, 1.0),
+        }
+    }
+
+    const fn value<'a>(&self, adjustments: &'a mut Adjustments) -> &'a mut f32 {
+        match self {
+            Self::LevelsBlack => &mut adjustments.levels.black,
+            Self::LevelsWhite => &mut adjustments.levels.white,
+            Self::LevelsGamma => &mut adjustments.levels.gamma,
+            Self::Brightness => &mut adjustments.brightness,
+            Self::Contrast => &mut adjustments.contrast,
+            Self::Hue => &mut adjustments.hue,
+            Self::Saturation => &mut adjustments.saturation,
+            Self::Lightness => &mut adjustments.lightness,
+            Self::Temperature => &mut adjustments.temperature,
+            Self::Tint => &mut adjustments.tint,
+        }
+    }
+
+    /// Moves the control `steps` steps, returning its new value.
+    pub(crate) fn nudge(&self, adjustments: &mut Adjustments, steps: f32) -> f32 {
+        let (min, max) = self.range();
+        let value = self.value(adjustments);
+
+        // Rounded so repeated steps land back on the neutral value exactly.
+        *value = (steps.mul_add(self.step(), *value).clamp(min, max) * 1000.0).round() / 1000.0;
+        *value
+    }
+}
+
+/// The tone curves `K` cycles through.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CurvePreset {
+    #[default]
+    None,
+    SCurve,
+    Fade,
+    CrossProcess,
+}
+
+impl CurvePreset {
+    pub(crate) const fn next(&self) -> Self {
+        match self {
+            Self::None => Self::SCurve,
+            Self::SCurve => Self::Fade,
+            Self::Fade => Self::CrossProcess,
+            Self::CrossProcess => Self::None,
+        }
+    }
+
+    pub(crate) fn curves(&self) -> Curves {
+        match self {
+            Self::None => Curves::default(),
+            Self::SCurve => Curves::s_curve(),
+            Self::Fade => Curves::fade(),
+            Self::CrossProcess => Curves::cross_process(),
+        }
+    }
+}
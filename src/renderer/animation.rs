@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+
+use crate::gif::grammar::AnimationFrame;
+use crate::png::grammar::Png;
+
+/// Browsers treat delays this short as "as fast as possible" and slow them down, so we do the same.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub struct Animation {
+    frames: Vec<AnimationFrame>,
+    current: usize,
+    shown_at: Instant,
+    /// The NETSCAPE2.0 loop count, as in [`crate::gif::grammar::Gif`].
+    loop_count: Option<u16>,
+    loops_played: u16,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<AnimationFrame>) -> Self {
+        assert!(!frames.is_empty(), "An animation needs at least one frame.");
+
+        Self {
+            frames,
+            current: 0,
+            shown_at: Instant::now(),
+            loop_count: Some(0),
+            loops_played: 0,
+        }
+    }
+
+    /// Like browsers, a count of `n` plays the animation `n` more times after the first, and
+    /// `None` plays it once. `Some(0)`, the default, loops forever.
+    pub const fn with_loop_count(mut self, loop_count: Option<u16>) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    pub fn current(&self) -> &Png {
+        &self.frames[self.current].image
+    }
+
+    pub(crate) fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// Replaces every frame with `f` applied to it, keeping the delays, and returns the images
+    /// it replaced.
+    pub(crate) fn map_frames(&mut self, f: impl Fn(&Png) -> Png) -> Vec<Png> {
+        self.frames
+            .iter_mut()
+            .map(|frame| {
+                let image = f(&frame.image);
+                std::mem::replace(&mut frame.image, image)
+            })
+            .collect()
+    }
+
+    /// Puts back the images `map_frames` returned, without restarting playback.
+    pub(crate) fn set_images(&mut self, images: Vec<Png>) {
+        for (frame, image) in self.frames.iter_mut().zip(images) {
+            frame.image = image;
+        }
+    }
+
+    /// Moves to the next frame once the current one has been shown long enough, returning the frame to upload.
+    /// Once the loop count is used up, the last frame stays.
+    pub(crate) fn tick(&mut self, now: Instant) -> Option<&Png> {
+        if !self.is_animated() {
+            return None;
+        }
+
+        let mut delay = self.frames[self.current].delay;
+        if delay < MIN_FRAME_DELAY {
+            delay = DEFAULT_FRAME_DELAY;
+        }
+
+        if now.duration_since(self.shown_at) < delay {
+            return None;
+        }
+
+        if self.current + 1 == self.frames.len() {
+            match self.loop_count {
+                Some(0) => {}
+                Some(count) if self.loops_played < count => self.loops_played += 1,
+                _ => return None,
+            }
+        }
+
+        self.current = (self.current + 1) % self.frames.len();
+        self.shown_at = now;
+
+        Some(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::grammar::ColorType;
+
+    fn frame(value: u8, delay: u64) -> AnimationFrame {
+        AnimationFrame {
+            image: Png {
+                width: 1,
+                height: 1,
+                gamma: 0,
+                color_type: ColorType::Grayscale,
+                pixel_buffer: vec![value],
+                exif: None,
+            },
+            delay: Duration::from_millis(delay),
+        }
+    }
+
+    #[test]
+    fn test_tick() {
+        let mut animation = Animation::new(vec![frame(0, 50), frame(1, 0)]);
+        let start = animation.shown_at;
+
+        assert!(animation.tick(start + Duration::from_millis(49)).is_none());
+        assert_eq!(
+            animation
+                .tick(start + Duration::from_millis(50))
+                .map(|f| f.pixel_buffer[0]),
+            Some(1)
+        );
+
+        // A zero delay is played back at the default rate.
+        assert!(animation.tick(start + Duration::from_millis(140)).is_none());
+        assert_eq!(
+            animation
+                .tick(start + Duration::from_millis(150))
+                .map(|f| f.pixel_buffer[0]),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_loop_count() {
+        let played = |loop_count| {
+            let mut animation =
+                Animation::new(vec![frame(0, 50), frame(1, 50)]).with_loop_count(loop_count);
+            let start = animation.shown_at;
+
+            let mut frames = vec![];
+            for i in 1..10 {
+                if let Some(f) = animation.tick(start + Duration::from_millis(50 * i)) {
+                    frames.push(f.pixel_buffer[0]);
+                }
+            }
+
+            (frames, animation.current().pixel_buffer[0])
+        };
+
+        // Playing once only shows the second frame, which then stays.
+        assert_eq!(played(None), (vec![1], 1));
+        assert_eq!(played(Some(1)), (vec![1, 0, 1], 1));
+        assert_eq!(played(Some(0)), (vec![1, 0, 1, 0, 1, 0, 1, 0, 1], 1));
+    }
+
+    #[test]
+    fn test_map_frames_and_back() {
+        let mut animation = Animation::new(vec![frame(10, 50), frame(20, 50)]);
+        let start = animation.shown_at;
+        animation.tick(start + Duration::from_millis(50));
+
+        let originals = animation.map_frames(|png| Png {
+            pixel_buffer: vec![png.pixel_buffer[0] + 1],
+            ..png.clone()
+        });
+        assert_eq!(animation.current().pixel_buffer, [21]);
+
+        // Putting the originals back keeps the frame that is showing.
+        animation.set_images(originals);
+        assert_eq!(animation.current().pixel_buffer, [20]);
+    }
+}
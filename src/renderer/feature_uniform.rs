@@ -1,3 +1,9 @@
+use crate::cube::grammar::{CubeLut, LutInterpolation};
+use crate::hdr::tone_map::ToneMap;
+use crate::ops::{Adjustments, TransformStack};
+use crate::renderer::RenderSettings;
+use crate::util::affine::Affine;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct FeatureUniform {
@@ -12,8 +18,29 @@ pub struct FeatureUniform {
     sharpen: u32,
     sharpen_factor: u32,
     edge_detect: u32,
-    _padding_1: [u8; 8],
+    exposure: f32,
+    tone_map: u32,
     transform: TransformMatrix,
+    adjust: u32,
+    curves: u32,
+    levels_black: f32,
+    levels_white: f32,
+    levels_gamma: f32,
+    brightness: f32,
+    contrast: f32,
+    hue: f32,
+    saturation: f32,
+    lightness: f32,
+    white_balance_r: f32,
+    white_balance_g: f32,
+    white_balance_b: f32,
+    lut: u32,
+    lut_size: u32,
+    _padding0: u32,
+    lut_domain_min: [f32; 3],
+    _padding1: u32,
+    lut_domain_max: [f32; 3],
+    _padding2: u32,
 }
 
 impl FeatureUniform {
@@ -30,9 +57,70 @@ impl FeatureUniform {
             sharpen: 0,
             sharpen_factor: Self::DEFAULT_SHARPEN_FACTOR,
             edge_detect: 0,
-            _padding_1: [0u8; 8],
+            exposure: 0.0,
+            tone_map: ToneMap::None as u32,
             transform: Self::TRANSFORM_IDENTITY,
+            adjust: 0,
+            curves: 0,
+            levels_black: 0.0,
+            levels_white: 1.0,
+            levels_gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            hue: 0.0,
+            saturation: 0.0,
+            lightness: 0.0,
+            white_balance_r: 1.0,
+            white_balance_g: 1.0,
+            white_balance_b: 1.0,
+            lut: 0,
+            lut_size: 2,
+            _padding0: 0,
+            lut_domain_min: [0.0; 3],
+            _padding1: 0,
+            lut_domain_max: [1.0; 3],
+            _padding2: 0,
+        }
+    }
+
+    /// A uniform with the features of `settings`, for a source image of `dimensions`. The output
+    /// is sized to fit its transform.
+    pub(crate) fn from_settings(
+        settings: &RenderSettings,
+        dimensions: (u32, u32),
+        gamma: u32,
+    ) -> Self {
+        let (width, height) = settings.transform.output_size(dimensions.0, dimensions.1);
+        let mut uniform = Self::new(width, height, gamma);
+
+        uniform.grayscale = settings.grayscale as u32;
+        uniform.invert = settings.invert as u32;
+        uniform.edge_detect = settings.edge_detect as u32;
+
+        if let Some(radius) = settings.blur_radius {
+            uniform.blur = 1;
+            uniform.blur_radius = radius.clamp(Self::MIN_BLUR_RADIUS, Self::MAX_BLUR_RADIUS);
+        }
+
+        if let Some(factor) = settings.sharpen_factor {
+            uniform.sharpen = 1;
+            uniform.sharpen_factor =
+                factor.clamp(Self::MIN_SHARPEN_FACTOR, Self::MAX_SHARPEN_FACTOR);
+        }
+
+        uniform.exposure = settings
+            .exposure
+            .clamp(-Self::MAX_EXPOSURE, Self::MAX_EXPOSURE);
+        uniform.set_tone_map(settings.tone_map);
+
+        uniform.set_transform(&settings.transform, dimensions);
+        uniform.set_adjustments(&settings.adjustments);
+
+        if let Some(lut) = &settings.lut {
+            uniform.set_lut(lut, settings.lut_interpolation);
         }
+
+        uniform
     }
 
     pub(crate) fn reset_features(&mut self) {
@@ -42,7 +130,11 @@ impl FeatureUniform {
         self.blur = 0;
         self.sharpen = 0;
         self.edge_detect = 0;
+        self.exposure = 0.0;
+        self.tone_map = ToneMap::None as u32;
         self.transform = Self::TRANSFORM_IDENTITY;
+        self.set_adjustments(&Adjustments::default());
+        self.disable_lut();
     }
 }
 
@@ -59,29 +151,29 @@ impl FeatureUniform {
         self.invert == 1
     }
 
-    pub(crate) fn toggle_grayscale(&mut self) {
+    pub(crate) const fn toggle_grayscale(&mut self) {
         self.grayscale = !self.grayscale() as u32;
     }
 
-    // pub(crate) fn toggle_sepia(&mut self) {
+    // pub(crate) const fn toggle_sepia(&mut self) {
     //     self.sepia = !self.sepia() as u32;
     // }
 
-    pub(crate) fn toggle_invert(&mut self) {
+    pub(crate) const fn toggle_invert(&mut self) {
         self.invert = !self.invert() as u32;
     }
 }
 
 impl FeatureUniform {
     const DEFAULT_BLUR_RADIUS: u32 = 21;
-    const MAX_BLUR_RADIUS: u32 = 39;
-    const MIN_BLUR_RADIUS: u32 = 3;
+    pub(crate) const MAX_BLUR_RADIUS: u32 = 39;
+    pub(crate) const MIN_BLUR_RADIUS: u32 = 3;
 
     pub(crate) const fn blur(&self) -> bool {
         self.blur == 1
     }
 
-    pub(crate) fn toggle_blur(&mut self) {
+    pub(crate) const fn toggle_blur(&mut self) {
         self.blur = !self.blur() as u32;
     }
 
@@ -96,14 +188,14 @@ impl FeatureUniform {
 
 impl FeatureUniform {
     const DEFAULT_SHARPEN_FACTOR: u32 = 16;
-    const MAX_SHARPEN_FACTOR: u32 = 40;
-    const MIN_SHARPEN_FACTOR: u32 = 1;
+    pub(crate) const MAX_SHARPEN_FACTOR: u32 = 40;
+    pub(crate) const MIN_SHARPEN_FACTOR: u32 = 1;
 
     pub(crate) const fn sharpen(&self) -> bool {
         self.sharpen == 1
     }
 
-    pub(crate) fn toggle_sharpen(&mut self) {
+    pub(crate) const fn toggle_sharpen(&mut self) {
         self.sharpen = !self.sharpen() as u32;
     }
 
@@ -117,7 +209,7 @@ impl FeatureUniform {
 }
 
 impl FeatureUniform {
-    pub(crate) fn update_window_dimensions(&mut self, width: u32, height: u32) {
+    pub(crate) const fn update_window_dimensions(&mut self, width: u32, height: u32) {
         self.width = width;
         self.height = height;
     }
@@ -128,59 +220,100 @@ impl FeatureUniform {
         self.edge_detect == 1
     }
 
-    pub(crate) fn toggle_edge_detect(&mut self) {
+    pub(crate) const fn toggle_edge_detect(&mut self) {
         self.edge_detect = !self.edge_detect() as u32;
     }
 }
 
+impl FeatureUniform {
+    const EXPOSURE_STEP: f32 = 0.5;
+    pub(crate) const MAX_EXPOSURE: f32 = 8.0;
+
+    pub(crate) fn tone_map(&self) -> ToneMap {
+        ToneMap::try_from(self.tone_map).unwrap_or_default()
+    }
+
+    pub(crate) const fn set_tone_map(&mut self, tone_map: ToneMap) {
+        self.tone_map = tone_map as u32;
+    }
+
+    pub(crate) fn cycle_tone_map(&mut self) {
+        self.set_tone_map(self.tone_map().next());
+    }
+
+    /// Exposure is measured in stops, each one doubling the brightness.
+    pub(crate) fn increase_exposure(&mut self) {
+        self.exposure = (self.exposure + Self::EXPOSURE_STEP).min(Self::MAX_EXPOSURE);
+    }
+
+    pub(crate) fn decrease_exposure(&mut self) {
+        self.exposure = (self.exposure - Self::EXPOSURE_STEP).max(-Self::MAX_EXPOSURE);
+    }
+}
+
 type TransformMatrix = [[f32; 4]; 4];
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum TransformAction {
-    FlipX,
-    FlipY,
+impl FeatureUniform {
+    const TRANSFORM_IDENTITY: TransformMatrix = Affine::IDENTITY.to_mat4();
+
+    /// Positions the quad so `stack` applied to a `width` x `height` image fills the output.
+    pub(crate) fn set_transform(&mut self, stack: &TransformStack, (width, height): (u32, u32)) {
+        let (output_width, output_height) = stack.output_size(width, height);
+        let (w, h) = (width as f32, height as f32);
+
+        // The quad spans clip space, with the top of the texture at y = 1.
+        let source_from_quad = Affine::new(w / 2.0, 0.0, 0.0, -h / 2.0, w / 2.0, h / 2.0);
+        let clip_from_output = Affine::new(
+            2.0 / output_width as f32,
+            0.0,
+            0.0,
+            -2.0 / output_height as f32,
+            -1.0,
+            1.0,
+        );
+
+        self.transform = source_from_quad
+            .then(&stack.output_from_source(width, height))
+            .then(&clip_from_output)
+            .to_mat4();
+    }
 }
 
-impl TransformAction {
-    const fn matrix(self) -> TransformMatrix {
-        match self {
-            Self::FlipX => FeatureUniform::TRANSFORM_FLIP_X,
-            Self::FlipY => FeatureUniform::TRANSFORM_FLIP_Y,
-        }
+impl FeatureUniform {
+    /// Copies `adjustments` into the uniform. The curves themselves live in the lookup table
+    /// texture, the uniform only records whether to sample it.
+    pub(crate) fn set_adjustments(&mut self, adjustments: &Adjustments) {
+        let [r, g, b] = adjustments.white_balance();
+
+        self.adjust = !adjustments.is_neutral() as u32;
+        self.curves = !adjustments.curves.is_identity() as u32;
+        self.levels_black = adjustments.levels.black;
+        self.levels_white = adjustments.levels.white;
+        self.levels_gamma = adjustments.levels.gamma;
+        self.brightness = adjustments.brightness;
+        self.contrast = adjustments.contrast_factor();
+        self.hue = adjustments.hue;
+        self.saturation = adjustments.saturation;
+        self.lightness = adjustments.lightness;
+        self.white_balance_r = r;
+        self.white_balance_g = g;
+        self.white_balance_b = b;
     }
 }
 
 impl FeatureUniform {
-    const TRANSFORM_IDENTITY: TransformMatrix = [
-        [1.0, 0.0, 0.0, 0.0],
-        [0.0, 1.0, 0.0, 0.0],
-        [0.0, 0.0, 1.0, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ];
-    const TRANSFORM_FLIP_X: TransformMatrix = [
-        [-1.0, 0.0, 0.0, 0.0],
-        [0.0, 1.0, 0.0, 0.0],
-        [0.0, 0.0, 1.0, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ];
-    const TRANSFORM_FLIP_Y: TransformMatrix = [
-        [1.0, 0.0, 0.0, 0.0],
-        [0.0, -1.0, 0.0, 0.0],
-        [0.0, 0.0, 1.0, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ];
-
-    pub(crate) fn apply_transform(&mut self, action: TransformAction) {
-        let transform_mat = action.matrix();
-
-        for (r, row) in transform_mat.iter().enumerate() {
-            for (c, ch) in row.iter().enumerate() {
-                if r == 3 || c == 3 {
-                    continue;
-                }
-
-                self.transform[r][c] *= ch;
-            }
-        }
+    pub(crate) const fn set_lut(&mut self, lut: &CubeLut, interpolation: LutInterpolation) {
+        self.lut = interpolation as u32;
+        self.lut_size = lut.size();
+        self.lut_domain_min = lut.domain_min();
+        self.lut_domain_max = lut.domain_max();
+    }
+
+    pub(crate) fn lut_interpolation(&self) -> Option<LutInterpolation> {
+        LutInterpolation::try_from(self.lut).ok()
+    }
+
+    pub(crate) const fn disable_lut(&mut self) {
+        self.lut = 0;
     }
 }
@@ -0,0 +1,219 @@
+use crate::png::grammar::Png;
+use crate::png::stats::{Histograms, BINS};
+use crate::renderer::draw_uniform::DrawUniform;
+use crate::renderer::feature_uniform::FeatureUniform;
+use crate::renderer::pipeline::ImagePipeline;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, BufferUsages, ColorTargetState,
+    ColorWrites, Device, Extent3d, FragmentState, ImageCopyTexture, ImageDataLayout,
+    MultisampleState, Origin3d, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue,
+    RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+/// A panel with the RGB and luma histograms of what the window shows. The view is captured
+/// offscreen at a reduced size whenever the features change, then read back and counted.
+pub struct HistogramOverlay {
+    visible: bool,
+    /// The features of the last capture, `None` when the image itself changed.
+    captured: Option<FeatureUniform>,
+    render_pipeline: RenderPipeline,
+    histogram_texture: Texture,
+    bind_group: BindGroup,
+    /// Draws the image without the crosshair circle.
+    pub(crate) capture_draw_bind_group: BindGroup,
+}
+
+impl HistogramOverlay {
+    /// The longest side of a capture. Counting a smaller image is plenty for the panel.
+    pub(crate) const CAPTURE_SIZE: u32 = 512;
+
+    pub(crate) fn new(
+        device: &Device,
+        image_pipeline: &ImagePipeline,
+        format: TextureFormat,
+    ) -> Self {
+        let histogram_texture = device.create_texture(&TextureDescriptor {
+            label: Some("histogram_texture"),
+            size: Extent3d {
+                width: BINS as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: TextureViewDimension::D2,
+                    sample_type: TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("histogram_bind_group_layout"),
+        });
+
+        let view = histogram_texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            }],
+            label: Some("histogram_bind_group"),
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Histogram Shader"),
+            source: ShaderSource::Wgsl(include_str!("histogram_shader.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Histogram Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Histogram Render Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let capture_draw_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Capture Draw Buffer"),
+            contents: bytemuck::cast_slice(&[DrawUniform::new()]),
+            usage: BufferUsages::UNIFORM,
+        });
+        let capture_draw_bind_group = image_pipeline.draw_bind_group(device, &capture_draw_buffer);
+
+        Self {
+            visible: false,
+            captured: None,
+            render_pipeline,
+            histogram_texture,
+            bind_group,
+            capture_draw_bind_group,
+        }
+    }
+
+    pub(crate) const fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub(crate) const fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Marks the capture as out of date after the image changed underneath the features.
+    pub(crate) const fn invalidate(&mut self) {
+        self.captured = None;
+    }
+
+    pub(crate) fn is_stale(&self, feature_uniform: &FeatureUniform) -> bool {
+        self.captured.is_none_or(|captured| {
+            bytemuck::bytes_of(&captured) != bytemuck::bytes_of(feature_uniform)
+        })
+    }
+
+    /// Counts a capture of the view rendered with `feature_uniform` and uploads the bar heights.
+    pub(crate) fn update(&mut self, queue: &Queue, capture: &Png, feature_uniform: FeatureUniform) {
+        let histograms = capture.histograms();
+
+        let channels = [
+            &histograms.red,
+            &histograms.green,
+            &histograms.blue,
+            &histograms.luma,
+        ];
+        let tallest = channels
+            .iter()
+            .flat_map(|histogram| histogram.bins())
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let heights = (0..BINS)
+            .flat_map(|v| channels.map(|histogram| histogram.bins()[v]))
+            .map(|n| (n as u64 * 255 / tallest as u64) as u8)
+            .collect::<Vec<_>>();
+
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &self.histogram_texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            &heights,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * BINS as u32),
+                rows_per_image: Some(1),
+            },
+            self.histogram_texture.size(),
+        );
+
+        Self::log_stats(&histograms);
+        self.captured = Some(feature_uniform);
+    }
+
+    fn log_stats(histograms: &Histograms) {
+        for (channel, histogram) in [
+            ("red", &histograms.red),
+            ("green", &histograms.green),
+            ("blue", &histograms.blue),
+            ("luma", &histograms.luma),
+        ] {
+            if let Some(stats) = histogram.stats() {
+                log::debug!("{channel}: {stats:?}");
+            }
+        }
+    }
+
+    pub(crate) fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
@@ -0,0 +1,42 @@
+// Draws the histogram panel in the bottom right corner of the window.
+
+@group(0) @binding(0)
+var t_histogram: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// The panel's corners in clip space.
+const PANEL_MIN = vec2(0.4, -0.95);
+const PANEL_MAX = vec2(0.95, -0.5);
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A triangle strip over the panel, with the uv origin at its bottom left.
+    let uv = vec2(f32(index & 1u), f32(index >> 1u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4(mix(PANEL_MIN, PANEL_MAX, uv), 0.0, 1.0);
+    out.uv = uv;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let bin = min(u32(in.uv.x * 256.0), 255u);
+    // Red, green and blue bar heights, with luma in alpha.
+    let heights = textureLoad(t_histogram, vec2(bin, 0u), 0);
+
+    if abs(in.uv.y - heights.a) < 0.01 {
+        return vec4(1.0);
+    }
+
+    // Overlapping channels add up to white.
+    let covered = step(vec3(in.uv.y), heights.rgb);
+    let coverage = max(covered.r, max(covered.g, covered.b));
+
+    return vec4(covered * 0.8, 0.6 + 0.3 * coverage);
+}
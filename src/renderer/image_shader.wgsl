@@ -11,7 +11,30 @@ struct FeatureUniform {
     sharpen: u32,
     sharpen_factor: u32,
     edge_detect: u32,
+    exposure: f32,
+    tone_map: u32,
     transform: mat4x4<f32>,
+    adjust: u32,
+    curves: u32,
+    levels_black: f32,
+    levels_white: f32,
+    levels_gamma: f32,
+    brightness: f32,
+    // The slope, already derived from the contrast setting.
+    contrast: f32,
+    hue: f32,
+    saturation: f32,
+    lightness: f32,
+    white_balance_r: f32,
+    white_balance_g: f32,
+    white_balance_b: f32,
+    lut: u32,
+    lut_size: u32,
+    _padding0: u32,
+    lut_domain_min: vec3<f32>,
+    _padding1: u32,
+    lut_domain_max: vec3<f32>,
+    _padding2: u32,
 };
 
 struct DrawUniform {
@@ -68,6 +91,15 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+@group(0) @binding(2)
+var t_curve: texture_2d<f32>;
+@group(0) @binding(3)
+var s_curve: sampler;
+@group(0) @binding(4)
+var t_lut: texture_3d<f32>;
+@group(0) @binding(5)
+var s_lut: sampler;
+
 const PI: f32 = 22.0 / 7.0;
 
 fn gaussian(offset: vec2<f32>) -> f32 {
@@ -145,10 +177,215 @@ fn detect_edge(tex_coords: vec2<f32>, viewport_resolution: vec2<f32>) -> vec4<f3
     return vec4(color, color, color, 1.0);
 }
 
+const TONE_MAP_REINHARD: u32 = 1u;
+const TONE_MAP_ACES: u32 = 2u;
+
+// Narkowicz's fit of the ACES filmic curve.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3(0.0), vec3(1.0));
+}
+
+// Mirrors `ToneMap::apply` on the CPU.
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    let exposed = max(color * exp2(feature_uniform.exposure), vec3(0.0));
+
+    switch feature_uniform.tone_map {
+        case TONE_MAP_REINHARD: {
+            return exposed / (1.0 + exposed);
+        }
+        case TONE_MAP_ACES: {
+            return aces(exposed);
+        }
+        default: {
+            return min(exposed, vec3(1.0));
+        }
+    }
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, color <= vec3(0.04045));
+}
+
+// Centers of the 256 lookup table texels, so filtering interpolates between neighbouring entries.
+fn curve_coord(v: f32) -> vec2<f32> {
+    return vec2((v * 255.0 + 0.5) / 256.0, 0.5);
+}
+
+fn rgb_to_hsl(color: vec3<f32>) -> vec3<f32> {
+    let high = max(max(color.r, color.g), color.b);
+    let low = min(min(color.r, color.g), color.b);
+    let l = (high + low) / 2.0;
+    let delta = high - low;
+
+    if delta == 0.0 {
+        return vec3(0.0, 0.0, l);
+    }
+
+    let s = delta / (1.0 - abs(2.0 * l - 1.0));
+    var h: f32;
+
+    if high == color.r {
+        h = (color.g - color.b) / delta;
+        h = h - 6.0 * floor(h / 6.0);
+    } else if high == color.g {
+        h = (color.b - color.r) / delta + 2.0;
+    } else {
+        h = (color.r - color.g) / delta + 4.0;
+    }
+
+    return vec3(h / 6.0, s, l);
+}
+
+fn hsl_to_rgb(hsl: vec3<f32>) -> vec3<f32> {
+    let chroma = (1.0 - abs(2.0 * hsl.z - 1.0)) * hsl.y;
+    let h = hsl.x * 6.0;
+    let x = chroma * (1.0 - abs(h - 2.0 * floor(h / 2.0) - 1.0));
+    let m = hsl.z - chroma / 2.0;
+
+    var rgb: vec3<f32>;
+
+    switch u32(h) {
+        case 0u: { rgb = vec3(chroma, x, 0.0); }
+        case 1u: { rgb = vec3(x, chroma, 0.0); }
+        case 2u: { rgb = vec3(0.0, chroma, x); }
+        case 3u: { rgb = vec3(0.0, x, chroma); }
+        case 4u: { rgb = vec3(x, 0.0, chroma); }
+        default: { rgb = vec3(chroma, 0.0, x); }
+    }
+
+    return rgb + m;
+}
+
+// Mirrors `ops::adjust` on the CPU.
+fn adjust(color: vec3<f32>) -> vec3<f32> {
+    let gains = vec3(feature_uniform.white_balance_r, feature_uniform.white_balance_g, feature_uniform.white_balance_b);
+    var v = linear_to_srgb(clamp(color * gains, vec3(0.0), vec3(1.0)));
+
+    let range = max(feature_uniform.levels_white - feature_uniform.levels_black, 1e-4);
+    v = pow(clamp((v - feature_uniform.levels_black) / range, vec3(0.0), vec3(1.0)), vec3(1.0 / feature_uniform.levels_gamma));
+
+    if feature_uniform.curves == 1u {
+        v = vec3(
+            textureSampleLevel(t_curve, s_curve, curve_coord(v.r), 0.0).r,
+            textureSampleLevel(t_curve, s_curve, curve_coord(v.g), 0.0).g,
+            textureSampleLevel(t_curve, s_curve, curve_coord(v.b), 0.0).b,
+        );
+    }
+
+    v = clamp((v - 0.5) * feature_uniform.contrast + 0.5 + feature_uniform.brightness, vec3(0.0), vec3(1.0));
+
+    if feature_uniform.hue != 0.0 || feature_uniform.saturation != 0.0 || feature_uniform.lightness != 0.0 {
+        var hsl = rgb_to_hsl(v);
+        let hue = hsl.x + feature_uniform.hue / 360.0;
+
+        hsl.x = hue - floor(hue);
+        hsl.y = clamp(hsl.y * (1.0 + feature_uniform.saturation), 0.0, 1.0);
+
+        if feature_uniform.lightness > 0.0 {
+            hsl.z = hsl.z + (1.0 - hsl.z) * feature_uniform.lightness;
+        } else {
+            hsl.z = hsl.z * (1.0 + feature_uniform.lightness);
+        }
+
+        hsl.z = clamp(hsl.z, 0.0, 1.0);
+        v = hsl_to_rgb(hsl);
+    }
+
+    return srgb_to_linear(v);
+}
+
+const LUT_TRILINEAR: u32 = 1u;
+const LUT_TETRAHEDRAL: u32 = 2u;
+
+fn lut_point(index: vec3<f32>) -> vec3<f32> {
+    return textureLoad(t_lut, vec3<i32>(index), 0).rgb;
+}
+
+// Mirrors `CubeLut::apply` on the CPU.
+fn tetrahedral(position: vec3<f32>) -> vec3<f32> {
+    let max_index = f32(feature_uniform.lut_size - 1u);
+    let base = min(floor(position), vec3(max_index - 1.0));
+    let f = position - base;
+
+    // The axis steps from the black to the white corner of the cell, largest fraction first.
+    var first: vec3<f32>;
+    var second: vec3<f32>;
+    var fractions: vec3<f32>;
+
+    if f.r >= f.g {
+        if f.g >= f.b {
+            first = vec3(1.0, 0.0, 0.0);
+            second = vec3(1.0, 1.0, 0.0);
+            fractions = f.rgb;
+        } else if f.r >= f.b {
+            first = vec3(1.0, 0.0, 0.0);
+            second = vec3(1.0, 0.0, 1.0);
+            fractions = f.rbg;
+        } else {
+            first = vec3(0.0, 0.0, 1.0);
+            second = vec3(1.0, 0.0, 1.0);
+            fractions = f.brg;
+        }
+    } else {
+        if f.b >= f.g {
+            first = vec3(0.0, 0.0, 1.0);
+            second = vec3(0.0, 1.0, 1.0);
+            fractions = f.bgr;
+        } else if f.b >= f.r {
+            first = vec3(0.0, 1.0, 0.0);
+            second = vec3(0.0, 1.0, 1.0);
+            fractions = f.gbr;
+        } else {
+            first = vec3(0.0, 1.0, 0.0);
+            second = vec3(1.0, 1.0, 0.0);
+            fractions = f.grb;
+        }
+    }
+
+    return (1.0 - fractions.x) * lut_point(base)
+        + (fractions.x - fractions.y) * lut_point(base + first)
+        + (fractions.y - fractions.z) * lut_point(base + second)
+        + fractions.z * lut_point(base + 1.0);
+}
+
+// LUTs are looked up with sRGB encoded values, like `ops::apply_lut` on the CPU.
+fn apply_lut(color: vec3<f32>) -> vec3<f32> {
+    let encoded = linear_to_srgb(clamp(color, vec3(0.0), vec3(1.0)));
+    let range = feature_uniform.lut_domain_max - feature_uniform.lut_domain_min;
+    let unit = clamp((encoded - feature_uniform.lut_domain_min) / range, vec3(0.0), vec3(1.0));
+    let size = f32(feature_uniform.lut_size);
+
+    var graded: vec3<f32>;
+
+    if feature_uniform.lut == LUT_TETRAHEDRAL {
+        graded = tetrahedral(unit * (size - 1.0));
+    } else {
+        // Texel centers, so the sampler blends between lattice points.
+        graded = textureSampleLevel(t_lut, s_lut, (unit * (size - 1.0) + 0.5) / size, 0.0).rgb;
+    }
+
+    return srgb_to_linear(clamp(graded, vec3(0.0), vec3(1.0)));
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var pixels = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    var viewport_resolution = 1.0 / vec2(f32(feature_uniform.width), f32(feature_uniform.height));
+    // One texel, which stays put however the quad is transformed on screen.
+    var viewport_resolution = 1.0 / vec2<f32>(textureDimensions(t_diffuse));
 
     if feature_uniform.edge_detect == 1u {
         pixels = detect_edge(in.tex_coords, viewport_resolution);
@@ -162,9 +399,21 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         pixels = gaussian_blur(in.tex_coords, f32(feature_uniform.radius), viewport_resolution);
     }
 
+    pixels = vec4(tone_map(pixels.rgb), pixels.a);
+
     if feature_uniform.gamma != 0u {
-        // todo! modify the pixels to account for gamma
+        // Sampling decoded the texels as sRGB, close to a 2.2 exponent, instead of the file's gamma.
         // see https://www.w3.org/TR/2003/REC-PNG-20031110/#13Decoder-gamma-handling
+        var exponent = 1.0 / (2.2 * f32(feature_uniform.gamma) / 100000.0);
+        pixels = vec4(pow(pixels.rgb, vec3(exponent)), pixels.a);
+    }
+
+    if feature_uniform.adjust == 1u {
+        pixels = vec4(adjust(pixels.rgb), pixels.a);
+    }
+
+    if feature_uniform.lut != 0u {
+        pixels = vec4(apply_lut(pixels.rgb), pixels.a);
     }
 
     if feature_uniform.grayscale == 1u {
@@ -1,12 +1,20 @@
-pub use state::run;
+pub use offscreen::{CpuRenderer, OffscreenRenderer, RenderSettings};
+pub use state::{run, run_animation, run_hdr};
+pub use text::TextLabel;
 
 pub(crate) use texture::*;
 pub(crate) use vertex::*;
 
+mod adjustment_control;
+mod animation;
 mod draw_uniform;
 mod feature_uniform;
+mod histogram_overlay;
 mod mouse_state;
+mod offscreen;
+mod pipeline;
 mod shape;
 mod state;
+mod text;
 mod texture;
 mod vertex;
@@ -0,0 +1,638 @@
+use crate::cube::grammar::{CubeLut, LutInterpolation};
+use crate::hdr::grammar::HdrImage;
+use crate::hdr::tone_map::ToneMap;
+use crate::ops::{self, Adjustments, LinearImage, TransformStack};
+use crate::png::grammar::{ColorType, Png};
+use crate::renderer::draw_uniform::DrawUniform;
+use crate::renderer::feature_uniform::FeatureUniform;
+use crate::renderer::pipeline::{ImagePipeline, INDICES, VERTICES};
+use crate::renderer::text::{TextLabel, TextRenderer};
+use crate::renderer::Texture;
+use anyhow::{anyhow, ensure, Result};
+use std::iter;
+use std::sync::mpsc;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Backends, Buffer, BufferDescriptor, BufferUsages, Color, CommandEncoderDescriptor, Device,
+    DeviceDescriptor, Extent3d, Features, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    IndexFormat, Instance, InstanceDescriptor, Limits, LoadOp, Maintain, MapMode, Operations,
+    Origin3d, PowerPreference, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+    RequestAdapterOptions, StoreOp, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureViewDescriptor, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+/// The features to render with, mirroring what the window's keyboard shortcuts toggle.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub grayscale: bool,
+    pub invert: bool,
+    /// Gaussian blur window, in pixels.
+    pub blur_radius: Option<u32>,
+    pub sharpen_factor: Option<u32>,
+    pub edge_detect: bool,
+    /// In stops, each one doubling the brightness.
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    /// Applied after the filters, which work on the untransformed image.
+    pub transform: TransformStack,
+    /// Applied after tone mapping, before grayscale and invert.
+    pub adjustments: Adjustments,
+    /// Applied after the adjustments.
+    pub lut: Option<CubeLut>,
+    pub lut_interpolation: LutInterpolation,
+    /// Drawn over the output after everything else, in output pixels.
+    pub labels: Vec<TextLabel>,
+}
+
+/// Runs the image pipeline without a window, rendering into a texture that is read back into a
+/// [`Png`]. The output is what the window shows at a 1:1 scale.
+pub struct OffscreenRenderer {
+    device: Device,
+    queue: Queue,
+    image_pipeline: ImagePipeline,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+}
+
+impl OffscreenRenderer {
+    /// Like the window, output is written to an sRGB target.
+    const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+    /// Requests a device that doesn't need a surface. `force_fallback_adapter` picks a software
+    /// implementation such as lavapipe or WARP, so rendering works on machines without a GPU.
+    pub async fn new(force_fallback_adapter: bool) -> Result<Self> {
+        // GL is included for machines whose only software rasterizer is llvmpipe.
+        let instance = Instance::new(InstanceDescriptor {
+            backends: Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter,
+            })
+            .await
+            .ok_or_else(|| anyhow!("Failed to get adapter"))?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    label: Some("Offscreen Device"),
+                    required_features: Features::empty(),
+                    required_limits: Limits::downlevel_defaults()
+                        .using_resolution(adapter.limits()),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await?;
+
+        let image_pipeline = ImagePipeline::new(&device, Self::FORMAT);
+
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(VERTICES),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: BufferUsages::INDEX,
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            image_pipeline,
+            vertex_buffer,
+            index_buffer,
+        })
+    }
+
+    /// Renders `png` with `settings`, returning an RGBA image sized by the transform.
+    pub fn render(&self, png: &Png, settings: &RenderSettings) -> Result<Png> {
+        let texture = Texture::from_bytes(&self.device, &self.queue, png)?;
+
+        self.render_texture(&texture, png.dimensions(), png.gamma, settings)
+    }
+
+    /// Renders a float image. Unlike the window, nothing is tone mapped unless `settings` asks.
+    pub fn render_hdr(&self, image: &HdrImage, settings: &RenderSettings) -> Result<Png> {
+        let texture = Texture::from_hdr(&self.device, &self.queue, image, Some("hdr_texture"))?;
+
+        self.render_texture(&texture, image.dimensions(), 0, settings)
+    }
+
+    fn render_texture(
+        &self,
+        texture: &Texture,
+        (width, height): (u32, u32),
+        gamma: u32,
+        settings: &RenderSettings,
+    ) -> Result<Png> {
+        let device = &self.device;
+
+        let feature_uniform = FeatureUniform::from_settings(settings, (width, height), gamma);
+        let (width, height) = settings.transform.output_size(width, height);
+
+        let feature_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Feature Buffer"),
+            contents: bytemuck::cast_slice(&[feature_uniform]),
+            usage: BufferUsages::UNIFORM,
+        });
+        let draw_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Draw Buffer"),
+            contents: bytemuck::cast_slice(&[DrawUniform::new()]),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let curve_lut =
+            Texture::from_curve_lut(device, &self.queue, &settings.adjustments.curves.lut());
+        // Unused without a LUT, but the bind group still needs a texture.
+        let identity = CubeLut::identity(2);
+        let cube_lut = Texture::from_cube_lut(
+            device,
+            &self.queue,
+            settings.lut.as_ref().unwrap_or(&identity),
+        );
+        let diffuse_bind_group = self
+            .image_pipeline
+            .texture_bind_group(device, texture, &curve_lut, &cube_lut);
+        let feature_bind_group = self
+            .image_pipeline
+            .feature_bind_group(device, &feature_buffer);
+        let draw_bind_group = self.image_pipeline.draw_bind_group(device, &draw_buffer);
+
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let target = device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&TextureViewDescriptor::default());
+
+        let text_renderer = if settings.labels.is_empty() {
+            None
+        } else {
+            let mut text_renderer = TextRenderer::new(device, &self.queue, Self::FORMAT)?;
+            text_renderer.prepare(device, &self.queue, &settings.labels, (width, height))?;
+            Some(text_renderer)
+        };
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Offscreen Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Offscreen Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.image_pipeline.render_pipeline);
+            render_pass.set_bind_group(0, &diffuse_bind_group, &[]);
+            render_pass.set_bind_group(1, &feature_bind_group, &[]);
+            render_pass.set_bind_group(2, &draw_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
+            render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
+
+            if let Some(text_renderer) = &text_renderer {
+                text_renderer.draw(&mut render_pass);
+            }
+        }
+
+        self.queue.submit(iter::once(encoder.finish()));
+        let pixel_buffer = read_back(device, &self.queue, &target)?;
+
+        Ok(Png {
+            width,
+            height,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer,
+            exif: None,
+        })
+    }
+}
+
+/// Renders with the [`ops`] mirrors of the image shader, for machines without any adapter. The
+/// output matches [`OffscreenRenderer`] up to sampling and rounding, but labels aren't drawn.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CpuRenderer;
+
+impl CpuRenderer {
+    pub fn render(&self, png: &Png, settings: &RenderSettings) -> Result<Png> {
+        Self::render_image(&LinearImage::from_png(png), png.gamma, settings)
+    }
+
+    pub fn render_hdr(&self, image: &HdrImage, settings: &RenderSettings) -> Result<Png> {
+        Self::render_image(&LinearImage::from_hdr(image), 0, settings)
+    }
+
+    /// Follows `fs_main`, with the transform baked last.
+    fn render_image(image: &LinearImage, gamma: u32, settings: &RenderSettings) -> Result<Png> {
+        ensure!(
+            settings.labels.is_empty(),
+            "Labels can only be drawn with a wgpu adapter."
+        );
+
+        // Every filter samples the source texture, so the last one the shader runs wins.
+        let filtered = match (settings.blur_radius, settings.sharpen_factor) {
+            (Some(radius), _) => ops::gaussian_blur(
+                image,
+                radius.clamp(
+                    FeatureUniform::MIN_BLUR_RADIUS,
+                    FeatureUniform::MAX_BLUR_RADIUS,
+                ),
+            ),
+            (None, Some(factor)) => ops::sharpen(
+                image,
+                factor.clamp(
+                    FeatureUniform::MIN_SHARPEN_FACTOR,
+                    FeatureUniform::MAX_SHARPEN_FACTOR,
+                ),
+            ),
+            (None, None) if settings.edge_detect => ops::detect_edges(image),
+            (None, None) => image.clone(),
+        };
+
+        let exposure = settings
+            .exposure
+            .clamp(-FeatureUniform::MAX_EXPOSURE, FeatureUniform::MAX_EXPOSURE);
+        let mut image = ops::tone_map(&filtered, exposure, settings.tone_map);
+        image = ops::gamma(&image, gamma);
+        image = ops::adjust(&image, &settings.adjustments);
+
+        if let Some(lut) = &settings.lut {
+            image = ops::apply_lut(&image, lut, settings.lut_interpolation);
+        }
+        if settings.grayscale {
+            image = ops::grayscale(&image);
+        }
+        if settings.invert {
+            image = ops::invert(&image);
+        }
+
+        Ok(ops::transform(&image, &settings.transform)?.to_png())
+    }
+}
+
+/// Copies a 4 byte per texel texture, like `Rgba8UnormSrgb`, into memory and waits for it.
+pub fn read_back(device: &Device, queue: &Queue, texture: &wgpu::Texture) -> Result<Vec<u8>> {
+    let size = texture.size();
+    let (width, height) = (size.width, size.height);
+
+    // Buffer copies need rows aligned to 256 bytes.
+    let row_size = 4 * width;
+    let padded_row_size = row_size.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let readback_buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_row_size * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            aspect: TextureAspect::All,
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        },
+        ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size),
+                rows_per_image: Some(height),
+            },
+        },
+        size,
+    );
+
+    queue.submit(iter::once(encoder.finish()));
+
+    let slice = readback_buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(Maintain::Wait);
+    receiver.recv()??;
+
+    let pixels = slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_size as usize)
+        .flat_map(|row| &row[..row_size as usize])
+        .copied()
+        .collect();
+    readback_buffer.unmap();
+
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::tests::renderer;
+    use crate::ops::{self, Crop, Curves, Levels, LinearImage, TransformAction};
+
+    fn pattern(width: u32, height: u32) -> Png {
+        let pixel_buffer = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| [(x * 7) as u8, (y * 5) as u8, ((x ^ y) * 16) as u8, 255])
+            .collect();
+
+        Png {
+            width,
+            height,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer,
+            exif: None,
+        }
+    }
+
+    fn assert_within_a_step(rendered: &Png, expected: &Png) {
+        assert_eq!(rendered.dimensions(), expected.dimensions());
+        for (a, b) in rendered.pixel_buffer.iter().zip(&expected.pixel_buffer) {
+            assert!(a.abs_diff(*b) <= 1, "{a} against {b}");
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_render_matches_cpu_ops() -> Result<()> {
+        let renderer = renderer()?;
+
+        let png = pattern(36, 28);
+        let image = LinearImage::from_png(&png);
+
+        let unchanged = renderer.render(&png, &RenderSettings::default())?;
+        assert_eq!(unchanged.pixel_buffer, png.pixel_buffer);
+
+        let settings = RenderSettings {
+            grayscale: true,
+            invert: true,
+            ..Default::default()
+        };
+        let expected = ops::invert(&ops::grayscale(&image)).to_png();
+        assert_within_a_step(&renderer.render(&png, &settings)?, &expected);
+
+        let settings = RenderSettings {
+            blur_radius: Some(9),
+            ..Default::default()
+        };
+        let expected = ops::gaussian_blur(&image, 9).to_png();
+        assert_within_a_step(&renderer.render(&png, &settings)?, &expected);
+
+        let linear_file = Png {
+            gamma: 100_000,
+            ..png
+        };
+        let expected = ops::gamma(&image, 100_000);
+        let rendered = LinearImage::from_png(&renderer.render(&linear_file, &Default::default())?);
+        // The exponent is steep near black, where an 8-bit step is wider than the difference.
+        for (a, b) in rendered
+            .pixels
+            .iter()
+            .flatten()
+            .zip(expected.pixels.iter().flatten())
+        {
+            assert!((a - b).abs() < 0.01, "{a} against {b}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_transform_matches_cpu_bake() -> Result<()> {
+        let renderer = renderer()?;
+
+        let png = pattern(36, 28);
+        let image = LinearImage::from_png(&png);
+
+        let mut transform = TransformStack::new();
+        transform.push(TransformAction::Rotate90);
+        transform.push(TransformAction::FlipX);
+        transform.set_crop(Some(Crop {
+            x: 3,
+            y: 5,
+            width: 20,
+            height: 16,
+        }));
+
+        let settings = RenderSettings {
+            transform: transform.clone(),
+            ..Default::default()
+        };
+        let rendered = renderer.render(&png, &settings)?;
+        let baked = ops::transform(&image, &transform)?.to_png();
+
+        assert_eq!(rendered.dimensions(), (20, 16));
+        assert_eq!(rendered.pixel_buffer, baked.pixel_buffer);
+
+        transform.push(TransformAction::Rotate(30.0));
+        transform.push(TransformAction::Skew(10.0, 0.0));
+        transform.set_crop(None);
+
+        let settings = RenderSettings {
+            transform: transform.clone(),
+            ..Default::default()
+        };
+        let rendered = renderer.render(&png, &settings)?;
+        let baked = ops::transform(&image, &transform)?.to_png();
+
+        assert_eq!(rendered.dimensions(), baked.dimensions());
+        assert!(rendered.compute_sim(&baked)? > 0.97);
+
+        Ok(())
+    }
+
+    fn cpu_renderer_case() -> (Png, RenderSettings) {
+        let mut transform = TransformStack::new();
+        transform.push(TransformAction::Rotate90);
+        transform.push(TransformAction::Scale(1.5, 1.5));
+        let settings = RenderSettings {
+            blur_radius: Some(5),
+            grayscale: true,
+            exposure: 0.5,
+            tone_map: ToneMap::Reinhard,
+            transform,
+            ..Default::default()
+        };
+
+        (pattern(36, 28), settings)
+    }
+
+    #[test]
+    fn test_cpu_renderer() -> Result<()> {
+        let (png, settings) = cpu_renderer_case();
+
+        let baked = CpuRenderer.render(&png, &settings)?;
+        assert_eq!(baked.dimensions(), (42, 54));
+
+        let labelled = RenderSettings {
+            labels: vec![TextLabel::new("H", (0.0, 20.0), 16.0)],
+            ..Default::default()
+        };
+        assert!(CpuRenderer.render(&png, &labelled).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_cpu_renderer_matches_gpu() -> Result<()> {
+        let (png, settings) = cpu_renderer_case();
+        let baked = CpuRenderer.render(&png, &settings)?;
+        let rendered = renderer()?.render(&png, &settings)?;
+
+        assert_eq!(rendered.dimensions(), baked.dimensions());
+        assert!(rendered.compute_sim(&baked)? > 0.97);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_adjustments_match_cpu_ops() -> Result<()> {
+        let renderer = renderer()?;
+
+        let png = pattern(36, 28);
+        let image = LinearImage::from_png(&png);
+
+        let adjustments = Adjustments {
+            levels: Levels {
+                black: 0.05,
+                white: 0.9,
+                gamma: 1.2,
+            },
+            curves: Curves::s_curve(),
+            brightness: 0.05,
+            contrast: 0.2,
+            hue: 40.0,
+            saturation: -0.3,
+            lightness: 0.1,
+            temperature: 0.4,
+            tint: -0.2,
+        };
+        let settings = RenderSettings {
+            adjustments: adjustments.clone(),
+            ..Default::default()
+        };
+
+        let rendered = renderer.render(&png, &settings)?;
+        let expected = ops::adjust(&image, &adjustments).to_png();
+        assert!(rendered.compute_sim(&expected)? > 0.99);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_lut_matches_cpu_ops() -> Result<()> {
+        let renderer = renderer()?;
+
+        let png = pattern(36, 28);
+        let image = LinearImage::from_png(&png);
+
+        // Crosstalk between channels and a curve, so neither interpolation is exact.
+        let mut lut = CubeLut::identity(9);
+        lut.table.iter_mut().for_each(|[r, g, b]| {
+            (*r, *g, *b) = (r.sqrt(), (*g + *r) / 2.0, *b * *b);
+        });
+
+        for lut_interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+            let settings = RenderSettings {
+                lut: Some(lut.clone()),
+                lut_interpolation,
+                ..Default::default()
+            };
+
+            let rendered = renderer.render(&png, &settings)?;
+            let expected = ops::apply_lut(&image, &lut, lut_interpolation).to_png();
+            assert!(rendered.compute_sim(&expected)? > 0.99);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a fallback wgpu adapter"]
+    fn test_labels_match_cpu_rasterizer() -> Result<()> {
+        let renderer = renderer()?;
+
+        let png = Png {
+            width: 48,
+            height: 40,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: vec![255; 48 * 40 * 4],
+            exif: None,
+        };
+        let (x, baseline) = (8, 32);
+        let settings = RenderSettings {
+            labels: vec![TextLabel {
+                color: [0.0, 0.0, 0.0, 1.0],
+                ..TextLabel::new("H", (x as f32, baseline as f32), 32.0)
+            }],
+            ..Default::default()
+        };
+        let rendered = renderer.render(&png, &settings)?;
+
+        let font = crate::font::TrueTypeFontParser::new(include_bytes!("../font/Lato-Regular.ttf"))
+            .parse()?;
+        let bitmap = font.rasterize_glyph(font.cmap_table.glyph_index('H'), 32.0)?;
+        let coverage_at = |px: i32, py: i32| {
+            let (bx, by) = (px - x - bitmap.left, py - baseline + bitmap.top);
+            let inside =
+                (0..bitmap.width as i32).contains(&bx) && (0..bitmap.height as i32).contains(&by);
+
+            inside.then(|| bitmap.coverage[(by * bitmap.width as i32 + bx) as usize])
+        };
+
+        for (i, rgba) in rendered.pixel_buffer.chunks_exact(4).enumerate() {
+            let (px, py) = ((i % 48) as i32, (i / 48) as i32);
+
+            // Partially covered pixels blend in linear space, so only check the extremes.
+            match coverage_at(px, py).unwrap_or(0) {
+                0 => assert_eq!(rgba, [255, 255, 255, 255], "at {px}, {py}"),
+                255 => assert_eq!(rgba, [0, 0, 0, 255], "at {px}, {py}"),
+                _ => {}
+            }
+        }
+        assert!(bitmap.coverage.contains(&255));
+
+        Ok(())
+    }
+}
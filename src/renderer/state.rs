@@ -1,23 +1,29 @@
-use crate::renderer::feature_uniform::{FeatureUniform, TransformAction};
+use crate::cube::grammar::{CubeLut, LutInterpolation};
+use crate::gif::grammar::AnimationFrame;
+use crate::hdr::grammar::HdrImage;
+use crate::hdr::tone_map::ToneMap;
+use crate::ops::{self, Adjustments, LinearImage, ResizeFilter, TransformAction, TransformStack};
+use crate::renderer::adjustment_control::{AdjustmentControl, CurvePreset};
+use crate::renderer::animation::Animation;
+use crate::renderer::feature_uniform::FeatureUniform;
+use crate::renderer::histogram_overlay::HistogramOverlay;
 use crate::renderer::mouse_state::MouseState;
+use crate::renderer::offscreen::read_back;
+use crate::renderer::text::{TextLabel, TextRenderer};
 use crate::{
-    png::grammar::Png,
-    renderer::{Texture, Vertex},
+    png::grammar::{ColorType, Png},
+    renderer::Texture,
 };
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Result};
 use std::iter;
+use std::time::{Duration, Instant};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent, BlendState, Buffer,
-    BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites,
-    CommandEncoderDescriptor, Device, DeviceDescriptor, Features, FragmentState, FrontFace,
-    IndexFormat, Instance, InstanceDescriptor, Limits, LoadOp, MultisampleState, Operations,
-    PipelineLayoutDescriptor, PolygonMode, PowerPreference, PrimitiveState, PrimitiveTopology,
-    Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
-    RenderPipelineDescriptor, RequestAdapterOptions, SamplerBindingType, ShaderModuleDescriptor,
-    ShaderSource, ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceError,
-    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexState,
+    Backends, BindGroup, Buffer, BufferUsages, Color, CommandEncoderDescriptor, Device,
+    DeviceDescriptor, Extent3d, Features, IndexFormat, Instance, InstanceDescriptor, Limits,
+    LoadOp, Operations, PowerPreference, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+    RequestAdapterOptions, StoreOp, Surface, SurfaceConfiguration, SurfaceError, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
 };
 use winit::window::CursorIcon;
 use winit::{
@@ -29,31 +35,30 @@ use winit::{
 };
 
 use super::draw_uniform::DrawUniform;
+use super::pipeline::{ImagePipeline, INDICES, VERTICES};
 use super::shape::{compute_radius, Shape, ShapeStack};
 
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-1.0, -1.0, 0.0],
-        tex_coords: [0.0, 1.0],
-    },
-    Vertex {
-        position: [-1.0, 1.0, 0.0],
-        tex_coords: [0.0, 0.0],
-    },
-    Vertex {
-        position: [1.0, -1.0, 0.0],
-        tex_coords: [1.0, 1.0],
-    },
-    Vertex {
-        position: [1.0, 1.0, 0.0],
-        tex_coords: [1.0, 0.0],
-    },
-];
-
-const INDICES: &[u16] = &[
-    0, 1, 2, // first triangle
-    2, 1, 3, // second triangle
-];
+/// What the diffuse texture is filled from.
+enum ImageSource {
+    Frames(Animation),
+    /// Linear float samples, uploaded as is and tone mapped in the shader.
+    Hdr(HdrImage),
+}
+
+/// The image as it was loaded, kept while `R` shows it resampled to the window.
+enum Unscaled {
+    Frames(Vec<Png>),
+    Hdr(HdrImage),
+}
+
+impl ImageSource {
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Frames(animation) => animation.current().dimensions(),
+            Self::Hdr(image) => image.dimensions(),
+        }
+    }
+}
 
 struct State<'a> {
     surface: Surface<'a>,
@@ -61,13 +66,14 @@ struct State<'a> {
     queue: Queue,
     config: SurfaceConfiguration,
     size: PhysicalSize<u32>,
-    image_render_pipeline: RenderPipeline,
+    image_pipeline: ImagePipeline,
     vertex_buffer: Buffer,
     index_buffer: Buffer,
     num_indices: u32,
-    #[allow(dead_code)]
     diffuse_texture: Texture,
     diffuse_bind_group: BindGroup,
+    curve_lut: Texture,
+    cube_lut: Texture,
     window: &'a Window,
 
     feature_uniform: FeatureUniform,
@@ -81,10 +87,25 @@ struct State<'a> {
     mouse_state: MouseState,
 
     shape_stack: ShapeStack,
+
+    source: ImageSource,
+    unscaled: Option<Unscaled>,
+    transform: TransformStack,
+    adjustments: Adjustments,
+    adjustment_control: AdjustmentControl,
+    curve_preset: CurvePreset,
+    lut: Option<CubeLut>,
+    histogram_overlay: HistogramOverlay,
+    text_renderer: TextRenderer,
+    show_status: bool,
 }
 
 impl<'a> State<'a> {
-    async fn new(window: &'a Window, png: &'a Png) -> Result<State<'a>> {
+    async fn new(
+        window: &'a Window,
+        source: ImageSource,
+        lut: Option<CubeLut>,
+    ) -> Result<State<'a>> {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -148,77 +169,49 @@ impl<'a> State<'a> {
             desired_maximum_frame_latency: 2,
         };
 
-        let diffuse_texture = Texture::from_bytes(&device, &queue, png)?;
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: TextureViewDimension::D2,
-                            sample_type: TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
+        let diffuse_texture = match &source {
+            ImageSource::Frames(animation) => {
+                Texture::from_bytes(&device, &queue, animation.current())?
+            }
+            ImageSource::Hdr(image) => {
+                Texture::from_hdr(&device, &queue, image, Some("hdr_texture"))?
+            }
+        };
 
-        let diffuse_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&diffuse_texture.view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
+        let adjustments = Adjustments::default();
+        let curve_lut = Texture::from_curve_lut(&device, &queue, &adjustments.curves.lut());
+        // Unused without a LUT, but the bind group still needs a texture.
+        let identity = CubeLut::identity(2);
+        let cube_lut = Texture::from_cube_lut(&device, &queue, lut.as_ref().unwrap_or(&identity));
+
+        let image_pipeline = ImagePipeline::new(&device, config.format);
+        let histogram_overlay = HistogramOverlay::new(&device, &image_pipeline, config.format);
+        let text_renderer = TextRenderer::new(&device, &queue, config.format)?;
+        let diffuse_bind_group =
+            image_pipeline.texture_bind_group(&device, &diffuse_texture, &curve_lut, &cube_lut);
+
+        let mut feature_uniform = match &source {
+            ImageSource::Frames(animation) => {
+                FeatureUniform::new(config.width, config.height, animation.current().gamma)
+            }
+            ImageSource::Hdr(_) => {
+                let mut feature_uniform = FeatureUniform::new(config.width, config.height, 0);
+                // Most of an HDR image would clip without tone mapping.
+                feature_uniform.set_tone_map(ToneMap::Reinhard);
+                feature_uniform
+            }
+        };
 
-        let feature_uniform = FeatureUniform::new(config.width, config.height, png.gamma);
+        if let Some(lut) = &lut {
+            feature_uniform.set_lut(lut, LutInterpolation::default());
+        }
 
         let feature_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Feature Buffer"),
             contents: bytemuck::cast_slice(&[feature_uniform]),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
-
-        let feature_bind_group_layout =
-            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                entries: &[BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::VERTEX_FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("feature_bind_group_layout"),
-            });
-
-        let feature_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &feature_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: feature_buffer.as_entire_binding(),
-            }],
-            label: Some("feature_bind_group"),
-        });
+        let feature_bind_group = image_pipeline.feature_bind_group(&device, &feature_buffer);
 
         let draw_uniform = DrawUniform::new();
 
@@ -227,93 +220,7 @@ impl<'a> State<'a> {
             contents: bytemuck::cast_slice(&[draw_uniform]),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
-
-        let draw_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            entries: &[BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX_FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: Some("draw_bind_group_layout"),
-        });
-
-        let draw_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &draw_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: draw_buffer.as_entire_binding(),
-            }],
-            label: Some("draw_bind_group"),
-        });
-
-        let image_shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: ShaderSource::Wgsl(include_str!("image_shader.wgsl").into()),
-        });
-
-        let image_render_pipeline_layout =
-            device.create_pipeline_layout(&PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    &feature_bind_group_layout,
-                    &draw_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
-
-        let image_render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Image Render Pipeline"),
-            layout: Some(&image_render_pipeline_layout),
-            vertex: VertexState {
-                module: &image_shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &image_shader,
-                entry_point: "fs_main",
-                targets: &[Some(ColorTargetState {
-                    format: config.format,
-                    blend: Some(BlendState {
-                        color: BlendComponent::REPLACE,
-                        alpha: BlendComponent::REPLACE,
-                    }),
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: None,
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            // If the pipeline will be used with a multiview renderer pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-            // Useful for optimizing shader compilation on Android
-            cache: None,
-        });
+        let draw_bind_group = image_pipeline.draw_bind_group(&device, &draw_buffer);
 
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Vertex Buffer"),
@@ -336,12 +243,14 @@ impl<'a> State<'a> {
             queue,
             config,
             size,
-            image_render_pipeline,
+            image_pipeline,
             vertex_buffer,
             index_buffer,
             num_indices,
             diffuse_texture,
             diffuse_bind_group,
+            curve_lut,
+            cube_lut,
             window,
             feature_uniform,
             feature_buffer,
@@ -351,6 +260,16 @@ impl<'a> State<'a> {
             draw_bind_group,
             mouse_state,
             shape_stack,
+            source,
+            unscaled: None,
+            transform: TransformStack::new(),
+            adjustments,
+            adjustment_control: AdjustmentControl::default(),
+            curve_preset: CurvePreset::default(),
+            lut,
+            histogram_overlay,
+            text_renderer,
+            show_status: false,
         })
     }
 
@@ -455,6 +374,10 @@ impl<'a> State<'a> {
                 }
                 (KeyCode::KeyC, ElementState::Pressed) => {
                     feature_uniform.reset_features();
+                    self.transform.clear();
+                    self.update_transform();
+                    self.curve_preset = CurvePreset::default();
+                    self.set_adjustments(Adjustments::default());
                 }
                 (KeyCode::KeyB, ElementState::Pressed) => {
                     feature_uniform.toggle_blur();
@@ -490,10 +413,75 @@ impl<'a> State<'a> {
                     feature_uniform.toggle_edge_detect();
                 }
                 (KeyCode::KeyX, ElementState::Pressed) => {
-                    feature_uniform.apply_transform(TransformAction::FlipX);
+                    self.push_transform(TransformAction::FlipX);
                 }
                 (KeyCode::KeyY, ElementState::Pressed) => {
-                    feature_uniform.apply_transform(TransformAction::FlipY);
+                    self.push_transform(TransformAction::FlipY);
+                }
+                (KeyCode::BracketRight, ElementState::Pressed) => {
+                    self.push_transform(TransformAction::Rotate90);
+                }
+                (KeyCode::BracketLeft, ElementState::Pressed) => {
+                    self.push_transform(TransformAction::Rotate270);
+                }
+                (KeyCode::Period, ElementState::Pressed) => {
+                    self.push_transform(TransformAction::Rotate(Self::ROTATION_STEP));
+                }
+                (KeyCode::Comma, ElementState::Pressed) => {
+                    self.push_transform(TransformAction::Rotate(-Self::ROTATION_STEP));
+                }
+                (KeyCode::Backspace, ElementState::Pressed) => {
+                    if self.transform.pop().is_some() {
+                        self.update_transform();
+                    }
+                }
+                (KeyCode::KeyR, ElementState::Pressed) => {
+                    if let Err(e) = self.resize_image() {
+                        log::error!("Failed to resize the image: {e}");
+                    }
+                }
+                (KeyCode::ArrowRight, ElementState::Pressed) => {
+                    self.nudge_adjustment(1.0);
+                }
+                (KeyCode::ArrowLeft, ElementState::Pressed) => {
+                    self.nudge_adjustment(-1.0);
+                }
+                (KeyCode::KeyK, ElementState::Pressed) => {
+                    self.curve_preset = self.curve_preset.next();
+                    log::info!("curves: {:?}", self.curve_preset);
+
+                    let adjustments = Adjustments {
+                        curves: self.curve_preset.curves(),
+                        ..self.adjustments.clone()
+                    };
+                    self.set_adjustments(adjustments);
+                }
+                (KeyCode::KeyU, ElementState::Pressed) => {
+                    self.cycle_lut();
+                }
+                (KeyCode::KeyH, ElementState::Pressed) => {
+                    self.histogram_overlay.toggle();
+                }
+                (KeyCode::KeyL, ElementState::Pressed) => {
+                    self.show_status = !self.show_status;
+                }
+                (KeyCode::KeyT, ElementState::Pressed) => {
+                    feature_uniform.cycle_tone_map();
+                }
+                (KeyCode::Equal, ElementState::Pressed) => {
+                    feature_uniform.increase_exposure();
+                }
+                (KeyCode::Minus, ElementState::Pressed) => {
+                    feature_uniform.decrease_exposure();
+                }
+                (keycode, ElementState::Pressed) => {
+                    let Some(control) = digit(*keycode).and_then(AdjustmentControl::from_digit)
+                    else {
+                        return false;
+                    };
+
+                    self.adjustment_control = control;
+                    log::info!("adjusting {control:?}");
                 }
                 _ => return false,
             },
@@ -503,7 +491,153 @@ impl<'a> State<'a> {
         true
     }
 
-    fn update(&self) {
+    /// Degrees turned by each press of `,` or `.`.
+    const ROTATION_STEP: f32 = 15.0;
+
+    fn push_transform(&mut self, action: TransformAction) {
+        self.transform.push(action);
+        self.update_transform();
+    }
+
+    fn nudge_adjustment(&mut self, steps: f32) {
+        let mut adjustments = self.adjustments.clone();
+        let value = self.adjustment_control.nudge(&mut adjustments, steps);
+        log::info!("{:?}: {value}", self.adjustment_control);
+
+        self.set_adjustments(adjustments);
+    }
+
+    /// Uploads the adjustments, rewriting the curve lookup table only when the curves changed.
+    fn set_adjustments(&mut self, adjustments: Adjustments) {
+        if adjustments.curves != self.adjustments.curves {
+            self.curve_lut
+                .write_curve_lut(&self.queue, &adjustments.curves.lut());
+            self.histogram_overlay.invalidate();
+        }
+
+        self.feature_uniform.set_adjustments(&adjustments);
+        self.adjustments = adjustments;
+    }
+
+    /// Steps through trilinear, tetrahedral and no interpolation of the LUT given on the
+    /// command line.
+    fn cycle_lut(&mut self) {
+        let Some(lut) = &self.lut else {
+            log::warn!("No LUT loaded, pass one with --lut <file.cube>");
+            return;
+        };
+
+        match self.feature_uniform.lut_interpolation() {
+            Some(LutInterpolation::Tetrahedral) => self.feature_uniform.disable_lut(),
+            Some(interpolation) => self.feature_uniform.set_lut(lut, interpolation.next()),
+            None => self
+                .feature_uniform
+                .set_lut(lut, LutInterpolation::default()),
+        }
+
+        log::info!("LUT: {:?}", self.feature_uniform.lut_interpolation());
+    }
+
+    /// Recomputes the quad's transform and sizes the window to fit the transformed image.
+    fn update_transform(&mut self) {
+        let dimensions = self.source.dimensions();
+        self.feature_uniform
+            .set_transform(&self.transform, dimensions);
+
+        let (width, height) = self.transform.output_size(dimensions.0, dimensions.1);
+        let _ = self
+            .window
+            .request_inner_size(PhysicalSize::new(width, height));
+    }
+
+    /// Resamples the image to the window size, so it's shown 1:1 instead of stretched, or goes
+    /// back to the loaded image if it was resampled already. A transformed image is scaled
+    /// uniformly until it fits. Resampling always starts from the loaded image, so toggling
+    /// loses nothing.
+    fn resize_image(&mut self) -> Result<()> {
+        if let Some(unscaled) = self.unscaled.take() {
+            match (&mut self.source, unscaled) {
+                (ImageSource::Frames(animation), Unscaled::Frames(images)) => {
+                    animation.set_images(images);
+                }
+                (ImageSource::Hdr(image), Unscaled::Hdr(unscaled)) => *image = unscaled,
+                _ => unreachable!("The unscaled image is kept from the same source."),
+            }
+
+            return self.upload_source();
+        }
+
+        let (window_width, window_height) = (self.size.width, self.size.height);
+        let (source_width, source_height) = self.source.dimensions();
+
+        let (width, height) = if self.transform.is_identity() {
+            (window_width, window_height)
+        } else {
+            let (output_width, output_height) =
+                self.transform.output_size(source_width, source_height);
+            let scale = (window_width as f32 / output_width as f32)
+                .min(window_height as f32 / output_height as f32);
+
+            (
+                ((source_width as f32 * scale).round() as u32).max(1),
+                ((source_height as f32 * scale).round() as u32).max(1),
+            )
+        };
+
+        if (source_width, source_height) == (width, height) {
+            return Ok(());
+        }
+
+        let filter = ResizeFilter::default();
+
+        self.unscaled = Some(match &mut self.source {
+            ImageSource::Frames(animation) => Unscaled::Frames(animation.map_frames(|png| {
+                let mut resized =
+                    ops::resize(&LinearImage::from_png(png), width, height, filter).to_png();
+                resized.gamma = png.gamma;
+                resized
+            })),
+            ImageSource::Hdr(image) => {
+                let resized =
+                    ops::resize(&LinearImage::from_hdr(image), width, height, filter).to_hdr();
+                Unscaled::Hdr(std::mem::replace(image, resized))
+            }
+        });
+
+        self.upload_source()
+    }
+
+    /// Replaces the texture with the current image of the source.
+    fn upload_source(&mut self) -> Result<()> {
+        self.diffuse_texture = match &self.source {
+            ImageSource::Frames(animation) => {
+                Texture::from_image(&self.device, &self.queue, animation.current(), None)
+            }
+            ImageSource::Hdr(image) => {
+                Texture::from_hdr(&self.device, &self.queue, image, Some("hdr_texture"))
+            }
+        }?;
+
+        self.diffuse_bind_group = self.image_pipeline.texture_bind_group(
+            &self.device,
+            &self.diffuse_texture,
+            &self.curve_lut,
+            &self.cube_lut,
+        );
+        self.histogram_overlay.invalidate();
+        self.update_transform();
+
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        if let ImageSource::Frames(animation) = &mut self.source {
+            if let Some(frame) = animation.tick(Instant::now()) {
+                self.diffuse_texture.write(&self.queue, frame);
+                self.histogram_overlay.invalidate();
+            }
+        }
+
         self.queue.write_buffer(
             &self.feature_buffer,
             0,
@@ -515,6 +649,129 @@ impl<'a> State<'a> {
             0,
             bytemuck::cast_slice(&[self.draw_uniform]),
         );
+
+        if self.histogram_overlay.visible()
+            && self.histogram_overlay.is_stale(&self.feature_uniform)
+        {
+            match self.capture_view() {
+                Ok(capture) => {
+                    self.histogram_overlay
+                        .update(&self.queue, &capture, self.feature_uniform)
+                }
+                Err(e) => log::error!("Failed to capture the view for its histogram: {e}"),
+            }
+        }
+
+        let labels = self.status_labels();
+        let viewport = (self.config.width, self.config.height);
+        if let Err(e) = self
+            .text_renderer
+            .prepare(&self.device, &self.queue, &labels, viewport)
+        {
+            log::error!("Failed to lay out text: {e}");
+        }
+    }
+
+    /// The status line in the top left corner: the image size after transforms and what the
+    /// arrow keys adjust.
+    fn status_labels(&self) -> Vec<TextLabel> {
+        if !self.show_status {
+            return vec![];
+        }
+
+        let (width, height) = self.source.dimensions();
+        let (width, height) = self.transform.output_size(width, height);
+        let pixel_size = 16.0 * self.window.scale_factor() as f32;
+
+        vec![TextLabel {
+            background: Some([0.0, 0.0, 0.0, 0.6]),
+            ..TextLabel::new(
+                format!(
+                    "{width} × {height}   adjusting {:?}",
+                    self.adjustment_control
+                ),
+                (pixel_size, pixel_size * 1.5),
+                pixel_size,
+            )
+        }]
+    }
+
+    /// Renders what the window shows into a texture no larger than
+    /// `HistogramOverlay::CAPTURE_SIZE` and reads it back.
+    fn capture_view(&self) -> Result<Png> {
+        let (source_width, source_height) = self.source.dimensions();
+        let (width, height) = self.transform.output_size(source_width, source_height);
+        let scale = (HistogramOverlay::CAPTURE_SIZE as f32 / width.max(height) as f32).min(1.0);
+
+        let size = Extent3d {
+            width: ((width as f32 * scale).round() as u32).max(1),
+            height: ((height as f32 * scale).round() as u32).max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let target = self.device.create_texture(&TextureDescriptor {
+            label: Some("Capture Target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Capture Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.image_pipeline.render_pipeline);
+            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.feature_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.histogram_overlay.capture_draw_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
+            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+        }
+
+        self.queue.submit(iter::once(encoder.finish()));
+
+        let mut pixel_buffer = read_back(&self.device, &self.queue, &target)?;
+        if matches!(
+            self.config.format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            pixel_buffer
+                .chunks_exact_mut(4)
+                .for_each(|texel| texel.swap(0, 2));
+        }
+
+        Ok(Png {
+            width: size.width,
+            height: size.height,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer,
+            exif: None,
+        })
     }
 
     fn render(&self) -> Result<(), SurfaceError> {
@@ -550,7 +807,7 @@ impl<'a> State<'a> {
                 timestamp_writes: None,
             });
 
-            render_pass.set_pipeline(&self.image_render_pipeline);
+            render_pass.set_pipeline(&self.image_pipeline.render_pipeline);
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.feature_bind_group, &[]);
             render_pass.set_bind_group(2, &self.draw_bind_group, &[]);
@@ -577,6 +834,9 @@ impl<'a> State<'a> {
             });
 
             render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+
+            self.histogram_overlay.draw(&mut render_pass);
+            self.text_renderer.draw(&mut render_pass);
         }
 
         self.queue.submit(iter::once(encoder.finish()));
@@ -586,9 +846,56 @@ impl<'a> State<'a> {
     }
 }
 
+const fn digit(keycode: KeyCode) -> Option<u8> {
+    Some(match keycode {
+        KeyCode::Digit0 => 0,
+        KeyCode::Digit1 => 1,
+        KeyCode::Digit2 => 2,
+        KeyCode::Digit3 => 3,
+        KeyCode::Digit4 => 4,
+        KeyCode::Digit5 => 5,
+        KeyCode::Digit6 => 6,
+        KeyCode::Digit7 => 7,
+        KeyCode::Digit8 => 8,
+        KeyCode::Digit9 => 9,
+        _ => return None,
+    })
+}
+
 #[allow(clippy::future_not_send)]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
-pub async fn run(png: Png) -> anyhow::Result<()> {
+pub async fn run(png: Png, lut: Option<CubeLut>) -> anyhow::Result<()> {
+    run_source(
+        ImageSource::Frames(Animation::new(vec![AnimationFrame {
+            image: png,
+            delay: Duration::ZERO,
+        }])),
+        lut,
+    )
+    .await
+}
+
+/// Plays back composited animation frames as many times as the GIF loop count asks.
+#[allow(clippy::future_not_send)]
+pub async fn run_animation(
+    frames: Vec<AnimationFrame>,
+    loop_count: Option<u16>,
+    lut: Option<CubeLut>,
+) -> anyhow::Result<()> {
+    ensure!(!frames.is_empty(), "An animation needs at least one frame.");
+
+    let animation = Animation::new(frames).with_loop_count(loop_count);
+    run_source(ImageSource::Frames(animation), lut).await
+}
+
+/// Displays a float image, tone mapped with Reinhard to start with.
+#[allow(clippy::future_not_send)]
+pub async fn run_hdr(image: HdrImage, lut: Option<CubeLut>) -> anyhow::Result<()> {
+    run_source(ImageSource::Hdr(image), lut).await
+}
+
+#[allow(clippy::future_not_send)]
+async fn run_source(source: ImageSource, lut: Option<CubeLut>) -> anyhow::Result<()> {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -600,7 +907,7 @@ pub async fn run(png: Png) -> anyhow::Result<()> {
 
     let event_loop = EventLoop::new()?;
 
-    let (width, height) = png.dimensions();
+    let (width, height) = source.dimensions();
 
     let window = WindowBuilder::new()
         .with_inner_size(PhysicalSize::new(width, height))
@@ -627,7 +934,7 @@ pub async fn run(png: Png) -> anyhow::Result<()> {
     }
 
     // State::new uses async code, so we're going to wait for it to finish
-    let mut state = State::new(&window, &png).await?;
+    let mut state = State::new(&window, source, lut).await?;
     let mut surface_configured = false;
 
     event_loop.run(move |event, control_flow| {
@@ -635,52 +942,50 @@ pub async fn run(png: Png) -> anyhow::Result<()> {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window().id() => {
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            event:
-                                KeyEvent {
-                                    state: ElementState::Pressed,
-                                    physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => control_flow.exit(),
-                        WindowEvent::Resized(physical_size) => {
-                            surface_configured = true;
-                            state.resize(*physical_size);
+            } if window_id == state.window().id() && !state.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                state: ElementState::Pressed,
+                                physical_key: PhysicalKey::Code(KeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } => control_flow.exit(),
+                    WindowEvent::Resized(physical_size) => {
+                        surface_configured = true;
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        // This tells winit that we want another frame after this one
+                        state.window().request_redraw();
+
+                        if !surface_configured {
+                            return;
                         }
-                        WindowEvent::RedrawRequested => {
-                            // This tells winit that we want another frame after this one
-                            state.window().request_redraw();
 
-                            if !surface_configured {
-                                return;
+                        state.update();
+                        match state.render() {
+                            Ok(_) => {}
+                            // Reconfigure the surface if it's lost or outdated
+                            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                                state.resize(state.size)
+                            }
+                            // The system is out of memory, we should probably quit
+                            Err(SurfaceError::OutOfMemory) => {
+                                log::error!("OutOfMemory");
+                                control_flow.exit();
                             }
 
-                            state.update();
-                            match state.render() {
-                                Ok(_) => {}
-                                // Reconfigure the surface if it's lost or outdated
-                                Err(SurfaceError::Lost | SurfaceError::Outdated) => {
-                                    state.resize(state.size)
-                                }
-                                // The system is out of memory, we should probably quit
-                                Err(SurfaceError::OutOfMemory) => {
-                                    log::error!("OutOfMemory");
-                                    control_flow.exit();
-                                }
-
-                                // This happens when a frame takes too long to present
-                                Err(SurfaceError::Timeout) => {
-                                    log::warn!("Surface timeout")
-                                }
+                            // This happens when a frame takes too long to present
+                            Err(SurfaceError::Timeout) => {
+                                log::warn!("Surface timeout")
                             }
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
             _ => {}
@@ -0,0 +1,511 @@
+use crate::font::grammar::TrueTypeFontFile;
+use crate::font::shaper::TrueTypeFontShaper;
+use crate::font::TrueTypeFontParser;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferAddress,
+    BufferBindingType, BufferUsages, ColorTargetState, ColorWrites, Device, Extent3d, FilterMode,
+    FragmentState, ImageCopyTexture, ImageDataLayout, MultisampleState, Origin3d,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension,
+    VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+
+/// The font labels are set in unless another one is given.
+static DEFAULT_FONT: &[u8] = include_bytes!("../font/Lato-Regular.ttf");
+
+/// A line of text drawn over the canvas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLabel {
+    pub text: String,
+    /// Where the baseline starts, in pixels from the top left of the target.
+    pub position: (f32, f32),
+    /// The height of the em square in pixels.
+    pub pixel_size: f32,
+    /// Linear RGBA, like everything else written to the sRGB targets.
+    pub color: [f32; 4],
+    /// Fills the line box behind the text when set.
+    pub background: Option<[f32; 4]>,
+}
+
+impl TextLabel {
+    pub fn new(text: impl Into<String>, position: (f32, f32), pixel_size: f32) -> Self {
+        Self {
+            text: text.into(),
+            position,
+            pixel_size,
+            color: [1.0; 4],
+            background: None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    const fn desc() -> VertexBufferLayout<'static> {
+        use std::mem;
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as BufferAddress,
+                    shader_location: 1,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as BufferAddress,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Where a rasterized glyph sits in the atlas and how it lines up with the pen position.
+#[derive(Debug, Copy, Clone)]
+struct AtlasGlyph {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    left: i32,
+    top: i32,
+}
+
+/// A glyph index and the pen position on the baseline it is drawn at, in pixels.
+type PlacedGlyph = (u16, (f32, f32));
+
+/// Draws labels with glyphs rasterized on the CPU and cached in a coverage atlas, packed in rows
+/// from the top. When the atlas fills up it starts over with only the glyphs still in use.
+pub struct TextRenderer {
+    font: TrueTypeFontFile<'static>,
+    render_pipeline: RenderPipeline,
+    atlas: Texture,
+    viewport_buffer: Buffer,
+    bind_group: BindGroup,
+    /// Keyed by glyph index and pixel size.
+    glyphs: HashMap<(u16, u32), AtlasGlyph>,
+    /// The next free spot, and the tallest glyph in the current row.
+    cursor: (u32, u32),
+    row_height: u32,
+    /// What the vertices were last built from, to skip rebuilding unchanged text.
+    prepared: Option<(Vec<TextLabel>, (u32, u32))>,
+    vertex_buffer: Option<Buffer>,
+    vertex_count: u32,
+}
+
+impl TextRenderer {
+    const ATLAS_SIZE: u32 = 1024;
+    /// Glyphs are kept apart so sampling never bleeds into a neighbour.
+    const PADDING: u32 = 1;
+    /// A fully covered block in the corner, for solid backgrounds.
+    const SOLID_SIZE: u32 = 2;
+
+    pub(crate) fn new(device: &Device, queue: &Queue, format: TextureFormat) -> Result<Self> {
+        let font = TrueTypeFontParser::new(DEFAULT_FONT).parse()?;
+
+        Ok(Self::with_font(device, queue, format, font))
+    }
+
+    pub(crate) fn with_font(
+        device: &Device,
+        queue: &Queue,
+        format: TextureFormat,
+        font: TrueTypeFontFile<'static>,
+    ) -> Self {
+        let atlas = device.create_texture(&TextureDescriptor {
+            label: Some("glyph_atlas"),
+            size: Extent3d {
+                width: Self::ATLAS_SIZE,
+                height: Self::ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Quads are pixel aligned, so nearest sampling reads coverage exactly.
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let viewport_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Text Viewport Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("text_bind_group_layout"),
+        });
+
+        let view = atlas.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: viewport_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("text_bind_group"),
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: ShaderSource::Wgsl(include_str!("text_shader.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Text Render Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[TextVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let mut text_renderer = Self {
+            font,
+            render_pipeline,
+            atlas,
+            viewport_buffer,
+            bind_group,
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+            prepared: None,
+            vertex_buffer: None,
+            vertex_count: 0,
+        };
+        text_renderer.clear_atlas(queue);
+
+        text_renderer
+    }
+
+    /// Forgets every cached glyph, keeping only the solid block.
+    fn clear_atlas(&mut self, queue: &Queue) {
+        self.write_atlas(queue, 0, 0, Self::SOLID_SIZE, &[255; 4]);
+
+        self.glyphs.clear();
+        self.cursor = (Self::SOLID_SIZE + Self::PADDING, 0);
+        self.row_height = Self::SOLID_SIZE;
+    }
+
+    fn write_atlas(&self, queue: &Queue, x: u32, y: u32, width: u32, coverage: &[u8]) {
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &self.atlas,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+            },
+            coverage,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: None,
+            },
+            Extent3d {
+                width,
+                height: coverage.len() as u32 / width,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Rasterizes a glyph into the next free spot, or returns `None` when the atlas is full.
+    fn cache_glyph(
+        &mut self,
+        queue: &Queue,
+        glyph_index: u16,
+        pixel_size: f32,
+    ) -> Result<Option<AtlasGlyph>> {
+        let key = (glyph_index, pixel_size.to_bits());
+        if let Some(&glyph) = self.glyphs.get(&key) {
+            return Ok(Some(glyph));
+        }
+
+        let bitmap = self.font.rasterize_glyph(glyph_index, pixel_size)?;
+        let (width, height) = (bitmap.width, bitmap.height);
+
+        if self.cursor.0 + width > Self::ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + Self::PADDING);
+            self.row_height = 0;
+        }
+
+        if self.cursor.0 + width > Self::ATLAS_SIZE || self.cursor.1 + height > Self::ATLAS_SIZE {
+            return Ok(None);
+        }
+
+        let (x, y) = self.cursor;
+        if width > 0 && height > 0 {
+            self.write_atlas(queue, x, y, width, &bitmap.coverage);
+        }
+
+        let glyph = AtlasGlyph {
+            x,
+            y,
+            width,
+            height,
+            left: bitmap.left,
+            top: bitmap.top,
+        };
+
+        self.cursor.0 += width + Self::PADDING;
+        self.row_height = self.row_height.max(height);
+        self.glyphs.insert(key, glyph);
+
+        Ok(Some(glyph))
+    }
+
+    /// Glyphs of a label with where each is drawn, in pixels, and where the pen ends up.
+    fn layout(&self, label: &TextLabel) -> (Vec<PlacedGlyph>, f32) {
+        let (mut x, mut y) = label.position;
+
+        let glyphs = TrueTypeFontShaper::from(&self.font)
+            .with_pixel_size(label.pixel_size)
+            .shape(&label.text)
+            .into_iter()
+            .map(|glyph| {
+                let position = (x + glyph.x_offset, y - glyph.y_offset);
+                x += glyph.x_advance;
+                y -= glyph.y_advance;
+
+                (glyph.glyph_index, position)
+            })
+            .collect();
+
+        (glyphs, x)
+    }
+
+    /// Builds the quads for `labels`, rasterizing glyphs the atlas doesn't have yet. Does nothing
+    /// when neither the labels nor the viewport changed.
+    pub(crate) fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        labels: &[TextLabel],
+        viewport: (u32, u32),
+    ) -> Result<()> {
+        if self
+            .prepared
+            .as_ref()
+            .is_some_and(|(prepared, size)| prepared == labels && *size == viewport)
+        {
+            return Ok(());
+        }
+
+        let vertices = match self.build_vertices(queue, labels)? {
+            Some(vertices) => vertices,
+            None => {
+                self.clear_atlas(queue);
+
+                match self.build_vertices(queue, labels)? {
+                    Some(vertices) => vertices,
+                    None => bail!("The labels need more glyphs than the atlas holds."),
+                }
+            }
+        };
+
+        queue.write_buffer(
+            &self.viewport_buffer,
+            0,
+            bytemuck::cast_slice(&[viewport.0 as f32, viewport.1 as f32, 0.0, 0.0]),
+        );
+
+        self.vertex_count = vertices.len() as u32;
+        self.vertex_buffer = (!vertices.is_empty()).then(|| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Text Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: BufferUsages::VERTEX,
+            })
+        });
+        self.prepared = Some((labels.to_vec(), viewport));
+
+        Ok(())
+    }
+
+    fn build_vertices(
+        &mut self,
+        queue: &Queue,
+        labels: &[TextLabel],
+    ) -> Result<Option<Vec<TextVertex>>> {
+        let mut vertices = vec![];
+        let texel = 1.0 / Self::ATLAS_SIZE as f32;
+
+        for label in labels {
+            let (glyphs, end) = self.layout(label);
+            let (x, baseline) = label.position;
+            let scale = self.font.pixel_scale(label.pixel_size);
+
+            if let Some(color) = label.background {
+                let ascent = self.font.hhea_table.ascent as f32 * scale;
+                let descent = self.font.hhea_table.descent as f32 * scale;
+
+                // Every corner samples the middle of the solid block.
+                let solid = [Self::SOLID_SIZE as f32 / 2.0 * texel; 2];
+                push_quad(
+                    &mut vertices,
+                    [x, baseline - ascent, end, baseline - descent],
+                    [solid[0], solid[1], solid[0], solid[1]],
+                    color,
+                );
+            }
+
+            for (glyph_index, (pen, baseline)) in glyphs {
+                let Some(glyph) = self.cache_glyph(queue, glyph_index, label.pixel_size)? else {
+                    return Ok(None);
+                };
+
+                if glyph.width == 0 || glyph.height == 0 {
+                    continue;
+                }
+
+                let left = pen.round() + glyph.left as f32;
+                let top = baseline.round() - glyph.top as f32;
+
+                push_quad(
+                    &mut vertices,
+                    [
+                        left,
+                        top,
+                        left + glyph.width as f32,
+                        top + glyph.height as f32,
+                    ],
+                    [
+                        glyph.x as f32 * texel,
+                        glyph.y as f32 * texel,
+                        (glyph.x + glyph.width) as f32 * texel,
+                        (glyph.y + glyph.height) as f32 * texel,
+                    ],
+                    label.color,
+                );
+            }
+        }
+
+        Ok(Some(vertices))
+    }
+
+    pub(crate) fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        let Some(vertex_buffer) = &self.vertex_buffer else {
+            return;
+        };
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Two triangles covering `[left, top, right, bottom]`, textured from the same rectangle of the
+/// atlas.
+fn push_quad(
+    vertices: &mut Vec<TextVertex>,
+    [left, top, right, bottom]: [f32; 4],
+    [u0, v0, u1, v1]: [f32; 4],
+    color: [f32; 4],
+) {
+    let vertex = |x, y, u, v| TextVertex {
+        position: [x, y],
+        tex_coords: [u, v],
+        color,
+    };
+
+    vertices.extend([
+        vertex(left, top, u0, v0),
+        vertex(left, bottom, u0, v1),
+        vertex(right, top, u1, v0),
+        vertex(right, top, u1, v0),
+        vertex(left, bottom, u0, v1),
+        vertex(right, bottom, u1, v1),
+    ]);
+}
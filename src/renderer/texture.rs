@@ -1,4 +1,7 @@
+use crate::cube::grammar::CubeLut;
+use crate::hdr::grammar::HdrImage;
 use crate::png::grammar::Png;
+use crate::util::color::f32_to_f16;
 use anyhow::*;
 use wgpu::{
     AddressMode, Device, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d, Queue,
@@ -7,7 +10,6 @@ use wgpu::{
 };
 
 pub struct Texture {
-    #[allow(unused)]
     pub texture: wgpu::Texture,
     pub view: TextureView,
     pub sampler: Sampler,
@@ -24,7 +26,6 @@ impl Texture {
         img: &Png,
         label: Option<&str>,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
 
         let size = Extent3d {
@@ -32,14 +33,84 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
-        let format = TextureFormat::Rgba8UnormSrgb;
+        let texture = Self::create_texture(device, size, TextureFormat::Rgba8UnormSrgb, label);
+
+        Self::write_rgba(queue, &texture, img);
+
+        Ok(Self::from_texture(device, texture))
+    }
+
+    /// Uploads linear float samples as an `Rgba16Float` texture, which unlike `Rgba32Float` can be
+    /// filtered without any optional device features.
+    pub fn from_hdr(
+        device: &Device,
+        queue: &Queue,
+        img: &HdrImage,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let (width, height) = img.dimensions();
+
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = Self::create_texture(device, size, TextureFormat::Rgba16Float, label);
+
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(&img.to_rgba_f16()),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(8 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        Ok(Self::from_texture(device, texture))
+    }
+
+    /// A 256x1 `Rgba16Float` lookup table of tone curves, sampled with linear filtering so the
+    /// shader interpolates between entries.
+    pub fn from_curve_lut(device: &Device, queue: &Queue, lut: &[[f32; 4]]) -> Self {
+        let size = Extent3d {
+            width: lut.len() as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture =
+            Self::create_texture(device, size, TextureFormat::Rgba16Float, Some("curve_lut"));
+        let lut_texture = Self {
+            view: texture.create_view(&TextureViewDescriptor::default()),
+            texture,
+            sampler: Self::lut_sampler(device),
+        };
+        lut_texture.write_curve_lut(queue, lut);
+
+        lut_texture
+    }
+
+    /// A `size`^3 `Rgba16Float` 3D texture holding the lattice of a cube LUT. Filtering does the
+    /// trilinear interpolation; tetrahedral interpolation loads the lattice points directly.
+    pub fn from_cube_lut(device: &Device, queue: &Queue, lut: &CubeLut) -> Self {
+        let size = Extent3d {
+            width: lut.size(),
+            height: lut.size(),
+            depth_or_array_layers: lut.size(),
+        };
         let texture = device.create_texture(&TextureDescriptor {
-            label,
+            label: Some("cube_lut"),
             size,
             mip_level_count: 1,
             sample_count: 1,
-            dimension: TextureDimension::D2,
-            format,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba16Float,
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
             view_formats: &[],
         });
@@ -51,30 +122,126 @@ impl Texture {
                 mip_level: 0,
                 origin: Origin3d::ZERO,
             },
-            &rgba,
+            bytemuck::cast_slice(&lut.to_rgba_f16()),
             ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(8 * lut.size()),
+                rows_per_image: Some(lut.size()),
             },
             size,
         );
 
+        Self {
+            view: texture.create_view(&TextureViewDescriptor::default()),
+            texture,
+            sampler: Self::lut_sampler(device),
+        }
+    }
+
+    fn lut_sampler(device: &Device) -> Sampler {
+        device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        })
+    }
+
+    pub fn write_curve_lut(&self, queue: &Queue, lut: &[[f32; 4]]) {
+        let half_floats = lut
+            .iter()
+            .flatten()
+            .map(|&c| f32_to_f16(c))
+            .collect::<Vec<_>>();
+
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(&half_floats),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(8 * lut.len() as u32),
+                rows_per_image: Some(1),
+            },
+            Extent3d {
+                width: lut.len() as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    fn create_texture(
+        device: &Device,
+        size: Extent3d,
+        format: TextureFormat,
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        device.create_texture(&TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    fn from_texture(device: &Device, texture: wgpu::Texture) -> Self {
         let view = texture.create_view(&TextureViewDescriptor::default());
         let sampler = device.create_sampler(&SamplerDescriptor {
             address_mode_u: AddressMode::ClampToEdge,
             address_mode_v: AddressMode::ClampToEdge,
             address_mode_w: AddressMode::ClampToEdge,
             mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Linear,
             mipmap_filter: FilterMode::Nearest,
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
+    }
+
+    /// Replaces the texture contents. `img` must have the dimensions the texture was created with.
+    pub fn write(&self, queue: &Queue, img: &Png) {
+        Self::write_rgba(queue, &self.texture, img);
+    }
+
+    fn write_rgba(queue: &Queue, texture: &wgpu::Texture, img: &Png) {
+        let rgba = img.to_rgba8();
+        let dimensions = img.dimensions();
+
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            &rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+        );
     }
 }
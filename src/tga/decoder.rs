@@ -0,0 +1,327 @@
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::png::grammar::{ColorType, Png};
+use crate::tga::grammar::{ImageType, TgaHeader};
+use crate::util::read_bytes::{U16_BYTES, U8_BYTES};
+use crate::{eof, read_le};
+
+#[derive(Debug)]
+pub struct TgaDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> TgaDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Png> {
+        let header = self.parse_header()?;
+        ensure!(
+            header.width > 0 && header.height > 0,
+            "TGA dimensions must be positive."
+        );
+
+        self.read_slice(header.id_length as usize)?;
+        let color_map = self.parse_color_map(&header)?;
+
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let bytes_per_pixel = (header.pixel_depth as usize).div_ceil(8);
+
+        let raw = if header.image_type.is_rle() {
+            self.decode_rle(width * height, bytes_per_pixel)?
+        } else {
+            self.read_slice(width * height * bytes_per_pixel)?.to_vec()
+        };
+
+        let (color_type, channels) = Self::output_format(&header);
+        let mut pixel_buffer = Vec::with_capacity(width * height * channels);
+
+        for pixel in raw.chunks_exact(bytes_per_pixel) {
+            match header.image_type.raw() {
+                ImageType::Grayscale => pixel_buffer.extend_from_slice(pixel),
+                ImageType::TrueColor => {
+                    let rgba = Self::color(pixel, header.pixel_depth, header.alpha_bits())?;
+                    pixel_buffer.extend_from_slice(&rgba[..channels]);
+                }
+                _ => {
+                    let index = match *pixel {
+                        [i] => i as usize,
+                        [lo, hi] => u16::from_le_bytes([lo, hi]) as usize,
+                        _ => bail!("Unsupported color map index size: {}", header.pixel_depth),
+                    };
+
+                    let rgba = index
+                        .checked_sub(header.color_map_first_entry as usize)
+                        .and_then(|i| color_map.get(i))
+                        .ok_or_else(|| anyhow!("Color map index {} is out of range.", index))?;
+
+                    pixel_buffer.extend_from_slice(&rgba[..channels]);
+                }
+            }
+        }
+
+        let stride = width * channels;
+
+        if !header.is_top_to_bottom() {
+            let rows = pixel_buffer.chunks_exact(stride).rev().flatten().copied();
+            pixel_buffer = rows.collect();
+        }
+
+        if header.is_right_to_left() {
+            for row in pixel_buffer.chunks_exact_mut(stride) {
+                row.reverse();
+                for pixel in row.chunks_exact_mut(channels) {
+                    pixel.reverse();
+                }
+            }
+        }
+
+        Ok(Png {
+            width: width as u32,
+            height: height as u32,
+            gamma: 0,
+            color_type,
+            pixel_buffer,
+            exif: None,
+        })
+    }
+
+    fn parse_header(&mut self) -> Result<TgaHeader> {
+        let id_length = self.read_u8()?;
+        let color_map_type = self.read_u8()?;
+        let image_type = ImageType::try_from(self.read_u8()?)?;
+        let color_map_first_entry = self.read_u16()?;
+        let color_map_length = self.read_u16()?;
+        let color_map_entry_size = self.read_u8()?;
+        let _x_origin = self.read_u16()?;
+        let _y_origin = self.read_u16()?;
+        let width = self.read_u16()?;
+        let height = self.read_u16()?;
+        let pixel_depth = self.read_u8()?;
+        let descriptor = self.read_u8()?;
+
+        ensure!(
+            color_map_type <= 1,
+            "Invalid color map type: {}",
+            color_map_type
+        );
+
+        let valid_depth = match image_type.raw() {
+            ImageType::ColorMapped => color_map_type == 1 && matches!(pixel_depth, 8 | 16),
+            ImageType::TrueColor => matches!(pixel_depth, 15 | 16 | 24 | 32),
+            _ => matches!(pixel_depth, 8 | 16),
+        };
+
+        ensure!(
+            valid_depth,
+            "Unsupported pixel depth {} for {:?} image.",
+            pixel_depth,
+            image_type
+        );
+
+        Ok(TgaHeader {
+            id_length,
+            color_map_type,
+            image_type,
+            color_map_first_entry,
+            color_map_length,
+            color_map_entry_size,
+            width,
+            height,
+            pixel_depth,
+            descriptor,
+        })
+    }
+
+    fn parse_color_map(&mut self, header: &TgaHeader) -> Result<Vec<[u8; 4]>> {
+        if header.color_map_type == 0 {
+            return Ok(vec![]);
+        }
+
+        let entry_size = header.color_map_entry_size;
+        ensure!(
+            matches!(entry_size, 15 | 16 | 24 | 32),
+            "Unsupported color map entry size: {}",
+            entry_size
+        );
+
+        let bytes = (entry_size as usize).div_ceil(8);
+        let entries = self.read_slice(header.color_map_length as usize * bytes)?;
+
+        entries
+            .chunks_exact(bytes)
+            .map(|entry| Self::color(entry, entry_size, header.alpha_bits()))
+            .collect()
+    }
+
+    fn decode_rle(&mut self, pixels: usize, bytes_per_pixel: usize) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(pixels * bytes_per_pixel);
+
+        while out.len() < pixels * bytes_per_pixel {
+            let packet = self.read_u8()?;
+            let count = (packet & 0x7F) as usize + 1;
+
+            if packet & 0x80 != 0 {
+                let pixel = self.read_slice(bytes_per_pixel)?;
+                for _ in 0..count {
+                    out.extend_from_slice(pixel);
+                }
+            } else {
+                out.extend_from_slice(self.read_slice(count * bytes_per_pixel)?);
+            }
+        }
+
+        // A packet may run past the end of the image in malformed files.
+        out.truncate(pixels * bytes_per_pixel);
+
+        Ok(out)
+    }
+
+    /// The color type of the decoded image and its number of channels.
+    const fn output_format(header: &TgaHeader) -> (ColorType, usize) {
+        match header.image_type.raw() {
+            ImageType::Grayscale if header.pixel_depth == 16 => (ColorType::GrayscaleAlpha, 2),
+            ImageType::Grayscale => (ColorType::Grayscale, 1),
+            _ => {
+                let color_depth = match header.image_type.raw() {
+                    ImageType::ColorMapped => header.color_map_entry_size,
+                    _ => header.pixel_depth,
+                };
+
+                if header.alpha_bits() > 0 && matches!(color_depth, 16 | 32) {
+                    (ColorType::RGBA, 4)
+                } else {
+                    (ColorType::RGB, 3)
+                }
+            }
+        }
+    }
+
+    /// Converts a little-endian BGR(A) color of `depth` bits into RGBA.
+    fn color(bytes: &[u8], depth: u8, alpha_bits: u8) -> Result<[u8; 4]> {
+        let color = match (depth, bytes) {
+            (15 | 16, &[lo, hi]) => {
+                let value = u16::from_le_bytes([lo, hi]);
+                let expand = |v: u16| ((v << 3) | (v >> 2)) as u8;
+
+                let alpha = if depth == 16 && alpha_bits > 0 && value & 0x8000 == 0 {
+                    0
+                } else {
+                    255
+                };
+
+                [
+                    expand((value >> 10) & 0x1F),
+                    expand((value >> 5) & 0x1F),
+                    expand(value & 0x1F),
+                    alpha,
+                ]
+            }
+            (24, &[b, g, r]) => [r, g, b, 255],
+            (32, &[b, g, r, a]) => [r, g, b, if alpha_bits > 0 { a } else { 255 }],
+            _ => bail!("Unsupported TGA color depth: {}", depth),
+        };
+
+        Ok(color)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        self.eof(len)?;
+
+        let slice = &self.data[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        Ok(slice)
+    }
+
+    eof!();
+    read_le!(read_u8, u8, U8_BYTES);
+    read_le!(read_u16, u16, U16_BYTES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::PngDecoder;
+    use crate::tga::TgaEncoder;
+    use image::ImageFormat;
+    use std::io::Cursor;
+
+    const IMAGES: [&str; 4] = [
+        "./test_suite/basn0g08.png",
+        "./test_suite/basn4a08.png",
+        "./test_suite/basn2c08.png",
+        "./test_suite/basn6a08.png",
+    ];
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        for path in IMAGES {
+            let content = std::fs::read(path)?;
+            let png = PngDecoder::new(&content).decode()?;
+
+            for rle in [false, true] {
+                let encoded = TgaEncoder::new(&png).with_rle(rle).encode()?;
+                let decoded = TgaDecoder::new(&encoded).decode()?;
+
+                assert_eq!(decoded.dimensions(), png.dimensions());
+                assert_eq!(decoded.color_type, png.color_type, "{path}");
+                assert_eq!(decoded.pixel_buffer, png.pixel_buffer, "{path}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_encoder() -> Result<()> {
+        for path in IMAGES {
+            let reference = image::open(path)?;
+
+            let mut encoded = Vec::new();
+            reference.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Tga)?;
+
+            let decoded = TgaDecoder::new(&encoded).decode()?;
+            assert_eq!(decoded.pixel_buffer, reference.as_bytes(), "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_color_mapped_bottom_up() -> Result<()> {
+        let mut file = vec![0, 1, 1, 0, 0, 2, 0, 24, 0, 0, 0, 0, 2, 0, 2, 0, 8, 0];
+        // Color map: red, blue.
+        file.extend_from_slice(&[0, 0, 255, 255, 0, 0]);
+        // Bottom row first.
+        file.extend_from_slice(&[0, 1, 1, 0]);
+
+        let png = TgaDecoder::new(&file).decode()?;
+        assert_eq!(png.color_type, ColorType::RGB);
+        assert_eq!(
+            png.pixel_buffer,
+            [0, 0, 255, 255, 0, 0, 255, 0, 0, 0, 0, 255]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rle_16_bit() -> Result<()> {
+        let mut file = vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, 16, 0x21];
+        // A run of two pure red pixels with the alpha bit set, then a transparent green one.
+        file.extend_from_slice(&[0x81, 0x00, 0xFC, 0x00, 0xE0, 0x03]);
+
+        let png = TgaDecoder::new(&file).decode()?;
+        assert_eq!(png.color_type, ColorType::RGBA);
+        assert_eq!(
+            png.pixel_buffer,
+            [255, 0, 0, 255, 255, 0, 0, 255, 0, 255, 0, 0]
+        );
+
+        Ok(())
+    }
+}
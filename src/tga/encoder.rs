@@ -0,0 +1,147 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::png::grammar::{ColorType, Png};
+use crate::tga::grammar::{ImageType, FOOTER_SIGNATURE, TOP_TO_BOTTOM};
+
+/// Writes truecolor or grayscale TGA files, stored top to bottom.
+#[derive(Debug)]
+pub struct TgaEncoder<'a> {
+    png: &'a Png,
+    rle: bool,
+}
+
+impl<'a> TgaEncoder<'a> {
+    pub const fn new(png: &'a Png) -> Self {
+        Self { png, rle: false }
+    }
+
+    /// Run-length encodes the pixel data. Packets never cross scanlines.
+    pub const fn with_rle(mut self, rle: bool) -> Self {
+        self.rle = rle;
+        self
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.png.dimensions();
+        ensure!(
+            width > 0 && height > 0 && width <= u16::MAX as u32 && height <= u16::MAX as u32,
+            "TGA dimensions must be between 1 and 65535, got {}x{}.",
+            width,
+            height
+        );
+
+        let (image_type, pixel_depth, alpha_bits) = match self.png.color_type {
+            ColorType::Grayscale => (ImageType::Grayscale, 8, 0),
+            ColorType::GrayscaleAlpha => (ImageType::Grayscale, 16, 8),
+            ColorType::RGB => (ImageType::TrueColor, 24, 0),
+            ColorType::RGBA => (ImageType::TrueColor, 32, 8),
+            ColorType::Palette => bail!("Palette images cannot be written as TGA."),
+        };
+
+        let image_type = if self.rle {
+            image_type.rle()
+        } else {
+            image_type
+        };
+
+        let mut out = vec![0, 0, image_type as u8, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        out.extend_from_slice(&(width as u16).to_le_bytes());
+        out.extend_from_slice(&(height as u16).to_le_bytes());
+        out.push(pixel_depth);
+        out.push(TOP_TO_BOTTOM | alpha_bits);
+
+        let bytes_per_pixel = pixel_depth as usize / 8;
+
+        // TGA stores color channels as BGR(A).
+        let mut pixels = self.png.pixel_buffer.clone();
+        if image_type.raw() == ImageType::TrueColor {
+            for pixel in pixels.chunks_exact_mut(bytes_per_pixel) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        if self.rle {
+            for row in pixels.chunks_exact(width as usize * bytes_per_pixel) {
+                Self::encode_rle(&mut out, row, bytes_per_pixel);
+            }
+        } else {
+            out.extend_from_slice(&pixels);
+        }
+
+        // Extension and developer area offsets, followed by the TGA 2.0 signature.
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(FOOTER_SIGNATURE);
+
+        Ok(out)
+    }
+
+    fn encode_rle(out: &mut Vec<u8>, row: &[u8], bytes_per_pixel: usize) {
+        let pixels = row.chunks_exact(bytes_per_pixel).collect::<Vec<_>>();
+
+        let mut i = 0;
+        while i < pixels.len() {
+            let run = pixels[i..]
+                .iter()
+                .take(128)
+                .take_while(|&&p| p == pixels[i])
+                .count();
+
+            if run > 1 {
+                out.push(0x80 | (run - 1) as u8);
+                out.extend_from_slice(pixels[i]);
+                i += run;
+                continue;
+            }
+
+            // Extend the raw packet until the next run starts.
+            let mut end = i + 1;
+            while end < pixels.len()
+                && end - i < 128
+                && (end + 1 == pixels.len() || pixels[end] != pixels[end + 1])
+            {
+                end += 1;
+            }
+
+            out.push((end - i - 1) as u8);
+            for pixel in &pixels[i..end] {
+                out.extend_from_slice(pixel);
+            }
+            i = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_reference_decoder() -> Result<()> {
+        let png = Png {
+            width: 40,
+            height: 2,
+            gamma: 0,
+            color_type: ColorType::RGBA,
+            pixel_buffer: (0..40 * 2 * 4).map(|i| (i / 24 * 13) as u8).collect(),
+            exif: None,
+        };
+
+        for rle in [false, true] {
+            let encoded = TgaEncoder::new(&png).with_rle(rle).encode()?;
+            let reference = image::load_from_memory_with_format(&encoded, image::ImageFormat::Tga)?;
+
+            assert_eq!(reference.into_rgba8().into_raw(), png.pixel_buffer);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rle_packets() {
+        let mut out = Vec::new();
+        TgaEncoder::encode_rle(&mut out, &[1, 1, 1, 2, 3, 4, 4], 1);
+
+        assert_eq!(out, [0x82, 1, 0x01, 2, 3, 0x81, 4]);
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{bail, Result};
+
+pub const HEADER_SIZE: usize = 18;
+
+/// The TGA 2.0 footer signature, preceded by the extension and developer area offsets.
+pub const FOOTER_SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+
+/// Image descriptor bit set when the first row stored is the top one.
+pub const TOP_TO_BOTTOM: u8 = 0b0010_0000;
+/// Image descriptor bit set when pixels within a row are stored right to left.
+pub const RIGHT_TO_LEFT: u8 = 0b0001_0000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageType {
+    ColorMapped = 1,
+    TrueColor = 2,
+    Grayscale = 3,
+    RleColorMapped = 9,
+    RleTrueColor = 10,
+    RleGrayscale = 11,
+}
+
+impl ImageType {
+    pub const fn is_rle(self) -> bool {
+        matches!(
+            self,
+            Self::RleColorMapped | Self::RleTrueColor | Self::RleGrayscale
+        )
+    }
+
+    /// The image type with the same pixel layout, without run-length encoding.
+    pub const fn raw(self) -> Self {
+        match self {
+            Self::RleColorMapped => Self::ColorMapped,
+            Self::RleTrueColor => Self::TrueColor,
+            Self::RleGrayscale => Self::Grayscale,
+            raw => raw,
+        }
+    }
+
+    pub const fn rle(self) -> Self {
+        match self {
+            Self::ColorMapped => Self::RleColorMapped,
+            Self::TrueColor => Self::RleTrueColor,
+            Self::Grayscale => Self::RleGrayscale,
+            rle => rle,
+        }
+    }
+}
+
+impl TryFrom<u8> for ImageType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let image_type = match value {
+            1 => Self::ColorMapped,
+            2 => Self::TrueColor,
+            3 => Self::Grayscale,
+            9 => Self::RleColorMapped,
+            10 => Self::RleTrueColor,
+            11 => Self::RleGrayscale,
+            0 => bail!("TGA file contains no image data."),
+            foreign => bail!("Unsupported TGA image type: {}", foreign),
+        };
+
+        Ok(image_type)
+    }
+}
+
+#[derive(Debug)]
+pub struct TgaHeader {
+    pub(crate) id_length: u8,
+    pub(crate) color_map_type: u8,
+    pub(crate) image_type: ImageType,
+    pub(crate) color_map_first_entry: u16,
+    pub(crate) color_map_length: u16,
+    pub(crate) color_map_entry_size: u8,
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) pixel_depth: u8,
+    pub(crate) descriptor: u8,
+}
+
+impl TgaHeader {
+    pub(crate) const fn alpha_bits(&self) -> u8 {
+        self.descriptor & 0x0F
+    }
+
+    pub(crate) const fn is_top_to_bottom(&self) -> bool {
+        self.descriptor & TOP_TO_BOTTOM != 0
+    }
+
+    pub(crate) const fn is_right_to_left(&self) -> bool {
+        self.descriptor & RIGHT_TO_LEFT != 0
+    }
+}
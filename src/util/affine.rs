@@ -0,0 +1,168 @@
+#![allow(clippy::suboptimal_flops)]
+
+/// A 2D affine transform, stored as the top two rows of a 3x3 matrix acting on column vectors:
+///
+/// ```text
+/// | x' |   | a  c  e |   | x |
+/// | y' | = | b  d  f | * | y |
+/// | 1  |   | 0  0  1 |   | 1 |
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Affine {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Affine {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    pub const fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub const fn translate(x: f32, y: f32) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub const fn scale(x: f32, y: f32) -> Self {
+        Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Rotates by `radians`, counterclockwise when y points up.
+    pub fn rotate(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Shears x by `tan(x_radians) * y` and y by `tan(y_radians) * x`.
+    pub fn skew(x_radians: f32, y_radians: f32) -> Self {
+        Self::new(1.0, y_radians.tan(), x_radians.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// The matrix product `self * other`: applies `other` first, then `self`.
+    #[allow(clippy::suspicious_operation_groupings)]
+    pub fn multiply(&self, other: &Self) -> Self {
+        Self {
+            a: self.a * other.a + self.c * other.b,
+            b: self.b * other.a + self.d * other.b,
+            c: self.a * other.c + self.c * other.d,
+            d: self.b * other.c + self.d * other.d,
+            e: self.a * other.e + self.c * other.f + self.e,
+            f: self.b * other.e + self.d * other.f + self.f,
+        }
+    }
+
+    /// Applies `self` first, then `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        next.multiply(self)
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// `None` for degenerate transforms that collapse the plane onto a line or point.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let (a, b, c, d) = (
+            self.d / determinant,
+            -self.b / determinant,
+            -self.c / determinant,
+            self.a / determinant,
+        );
+
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    /// Column major 4x4 matrix, the layout of a WGSL `mat4x4<f32>`.
+    pub const fn to_mat4(&self) -> [[f32; 4]; 4] {
+        [
+            [self.a, self.b, 0.0, 0.0],
+            [self.c, self.d, 0.0, 0.0],
+            [self.e, self.f, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_point(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-5 && (actual.1 - expected.1).abs() < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn test_composition_order() {
+        let transform = Affine::translate(10.0, 0.0).then(&Affine::rotate(FRAC_PI_2));
+
+        // Translated first, then rotated about the origin.
+        assert_point(transform.apply((1.0, 0.0)), (0.0, 11.0));
+        assert_eq!(
+            transform,
+            Affine::rotate(FRAC_PI_2).multiply(&Affine::translate(10.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_rotation_is_not_elementwise() {
+        // Four quarter turns, composed by multiplication, are a full turn.
+        let quarter = Affine::rotate(FRAC_PI_2);
+        let full = quarter.then(&quarter).then(&quarter).then(&quarter);
+
+        assert_point(full.apply((3.0, 4.0)), (3.0, 4.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Affine::scale(2.0, 3.0)
+            .then(&Affine::skew(0.3, -0.2))
+            .then(&Affine::rotate(1.1))
+            .then(&Affine::translate(-4.0, 7.0));
+        let inverse = transform.inverse().unwrap();
+
+        assert_point(inverse.apply(transform.apply((5.0, -6.0))), (5.0, -6.0));
+        assert!(Affine::scale(0.0, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn test_skew() {
+        let transform = Affine::skew(std::f32::consts::FRAC_PI_4, 0.0);
+
+        assert_point(transform.apply((0.0, 2.0)), (2.0, 2.0));
+        assert_point(transform.apply((2.0, 0.0)), (2.0, 0.0));
+    }
+}
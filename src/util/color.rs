@@ -0,0 +1,130 @@
+#![allow(clippy::suboptimal_flops)]
+
+/// Decodes an sRGB encoded value in [0, 1] to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear light value in [0, 1] with the sRGB transfer function.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Quantizes a value in [0, 1] to 8 bits, clamping anything outside.
+pub fn unit_to_u8(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Converts sRGB encoded values in [0, 1] to CIE L*a*b* with a D65 white point.
+pub fn srgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+
+    // Normalized by the D65 white so white maps to L* = 100, a* = b* = 0.
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+
+    let delta = 6.0 / 29.0;
+    let f = |t: f32| {
+        if t > delta * delta * delta {
+            t.cbrt()
+        } else {
+            t / (3.0 * delta * delta) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Converts to the bits of an IEEE 754 half precision float, rounding to nearest even.
+pub const fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x007F_FFFF;
+
+    // Infinity and NaN, keeping NaNs quiet.
+    if exponent == 0xFF {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+
+    if half_exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    if half_exponent <= 0 {
+        // Subnormal, or too small and flushed to zero.
+        if half_exponent < -10 {
+            return sign;
+        }
+
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+
+        let round_up = remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1FFF;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity.
+    sign | (half + round_up as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for i in 0..=255u8 {
+            let c = i as f32 / 255.0;
+            assert_eq!(unit_to_u8(linear_to_srgb(srgb_to_linear(c))), i);
+        }
+    }
+
+    #[test]
+    fn test_srgb_to_lab() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.01);
+
+        assert!(close(srgb_to_lab([1.0, 1.0, 1.0]), [100.0, 0.0, 0.0]));
+        assert!(close(srgb_to_lab([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0]));
+        assert!(close(srgb_to_lab([1.0, 0.0, 0.0]), [53.24, 80.09, 67.2]));
+        assert!(close(srgb_to_lab([0.0, 0.0, 1.0]), [32.3, 79.19, -107.86]));
+    }
+
+    #[test]
+    fn test_f32_to_f16() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3C00);
+        assert_eq!(f32_to_f16(-2.0), 0xC000);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(65504.0), 0x7BFF);
+        assert_eq!(f32_to_f16(1.0e6), 0x7C00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7E00, 0x7E00);
+        // The smallest subnormal half.
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 0x0001);
+        assert_eq!(f32_to_f16(1.0e-9), 0x0000);
+        // 1 + 2^-11 is halfway between two halves and rounds to the even one.
+        assert_eq!(f32_to_f16(1.000_488_3), 0x3C00);
+    }
+}
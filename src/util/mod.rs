@@ -1,3 +1,5 @@
+pub mod affine;
+pub mod color;
 pub mod event_log;
 
 pub(crate) mod read_bytes;
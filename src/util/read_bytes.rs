@@ -36,3 +36,18 @@ macro_rules! eof {
         }
     };
 }
+
+#[macro_export]
+macro_rules! read_le {
+    ($name:ident, $type:ty, $size:expr) => {
+        fn $name(&mut self) -> Result<$type> {
+            self.eof($size)?;
+
+            let slice = &self.data[self.cursor..self.cursor + $size];
+            let b = <$type>::from_le_bytes(slice.try_into()?);
+            self.cursor += $size;
+
+            Ok(b)
+        }
+    };
+}
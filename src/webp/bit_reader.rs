@@ -0,0 +1,64 @@
+use anyhow::{ensure, Result};
+
+/// Reads the VP8L bitstream least significant bit first.
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    buffer: u64,
+    len: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            buffer: 0,
+            len: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.len <= 56 {
+            let Some(&byte) = self.data.get(self.cursor) else {
+                break;
+            };
+
+            self.buffer |= (byte as u64) << self.len;
+            self.cursor += 1;
+            self.len += 8;
+        }
+    }
+
+    /// Returns the next `n` bits without consuming them, zero padded past the end of the data.
+    pub fn peek(&mut self, n: u32) -> u32 {
+        if self.len < n {
+            self.refill();
+        }
+
+        (self.buffer & ((1 << n) - 1)) as u32
+    }
+
+    pub fn consume(&mut self, n: u32) -> Result<()> {
+        ensure!(n <= self.len, "Unexpected EOF in VP8L bitstream.");
+
+        self.buffer >>= n;
+        self.len -= n;
+
+        Ok(())
+    }
+
+    pub fn read_bits(&mut self, n: u32) -> Result<u32> {
+        debug_assert!(n <= 32);
+
+        let bits = self.peek(n);
+        self.consume(n)?;
+
+        Ok(bits)
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::png::grammar::{ColorType, Png};
+use crate::util::read_bytes::U32_BYTES;
+use crate::webp::grammar::{EXIF, RIFF, VP8, VP8L, VP8X, WEBP};
+use crate::webp::vp8l::Vp8lDecoder;
+use crate::{eof, read_le};
+
+/// Decodes lossless (VP8L) WebP files, in either the simple or the extended container.
+#[derive(Debug)]
+pub struct WebpDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> WebpDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Png> {
+        ensure!(
+            self.read_slice(4)? == RIFF,
+            "Invalid WebP file: missing RIFF header."
+        );
+        let riff_size = self.read_u32()? as usize;
+        ensure!(
+            self.read_slice(4)? == WEBP,
+            "Invalid WebP file: incorrect signature."
+        );
+
+        // Ignore anything trailing the RIFF container.
+        let end = (riff_size + 8).min(self.data.len());
+        self.data = &self.data[..end];
+
+        let mut bitstream = None;
+        let mut exif = None;
+
+        while self.cursor < self.data.len() {
+            let fourcc = self.read_slice(4)?;
+            let size = self.read_u32()? as usize;
+            let payload = self.read_slice(size)?;
+
+            // Chunks are padded to an even size.
+            if size % 2 == 1 && self.cursor < self.data.len() {
+                self.cursor += 1;
+            }
+
+            match fourcc {
+                f if f == VP8L => bitstream = bitstream.or(Some(payload)),
+                f if f == VP8 => bail!("Lossy WebP (VP8) is not supported."),
+                f if f == EXIF => exif = Some(payload.to_vec()),
+                b"ANIM" => bail!("Animated WebP is not supported."),
+                f if f == VP8X => {
+                    ensure!(payload.len() >= 10, "VP8X chunk is too short.");
+                }
+                // ICC profiles, XMP metadata and unknown chunks are skipped.
+                _ => {}
+            }
+        }
+
+        let Some(bitstream) = bitstream else {
+            bail!("WebP file contains no VP8L image data.");
+        };
+
+        let image = Vp8lDecoder::new(bitstream).decode()?;
+
+        let (color_type, pixel_buffer) = if image.alpha_is_used {
+            let rgba = image
+                .argb
+                .iter()
+                .flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8, (p >> 24) as u8])
+                .collect();
+
+            (ColorType::RGBA, rgba)
+        } else {
+            let rgb = image
+                .argb
+                .iter()
+                .flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8])
+                .collect();
+
+            (ColorType::RGB, rgb)
+        };
+
+        Ok(Png {
+            width: image.width,
+            height: image.height,
+            gamma: 0,
+            color_type,
+            pixel_buffer,
+            exif,
+        })
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        self.eof(len)?;
+
+        let slice = &self.data[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        Ok(slice)
+    }
+
+    eof!();
+    read_le!(read_u32, u32, U32_BYTES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::PngDecoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::ExtendedColorType;
+
+    fn encode_reference(png: &Png) -> Result<Vec<u8>> {
+        let color_type = match png.color_type {
+            ColorType::Grayscale => ExtendedColorType::L8,
+            ColorType::GrayscaleAlpha => ExtendedColorType::La8,
+            ColorType::RGB => ExtendedColorType::Rgb8,
+            _ => ExtendedColorType::Rgba8,
+        };
+
+        let mut encoded = Vec::new();
+        WebPEncoder::new_lossless(&mut encoded).encode(
+            &png.pixel_buffer,
+            png.width,
+            png.height,
+            color_type,
+        )?;
+
+        Ok(encoded)
+    }
+
+    #[test]
+    fn test_reference_encoder() -> Result<()> {
+        for path in [
+            "./test_suite/basn2c08.png",
+            "./test_suite/basn6a08.png",
+            "./tests/obama.png",
+        ] {
+            let content = std::fs::read(path)?;
+            let png = PngDecoder::new(&content).decode()?;
+
+            let encoded = encode_reference(&png)?;
+            let decoded = WebpDecoder::new(&encoded).decode()?;
+
+            assert_eq!(decoded.dimensions(), png.dimensions());
+            assert_eq!(decoded.to_rgba8(), png.to_rgba8(), "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_grayscale() -> Result<()> {
+        let content = std::fs::read("./test_suite/basn0g08.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        let decoded = WebpDecoder::new(&encode_reference(&png)?).decode()?;
+        assert_eq!(decoded.to_rgb8(), png.to_rgb8());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_lossy_and_truncated() {
+        let mut lossy = b"RIFF\x0c\x00\x00\x00WEBPVP8 \x00\x00\x00\x00".to_vec();
+        assert!(WebpDecoder::new(&lossy).decode().is_err());
+
+        lossy.truncate(10);
+        assert!(WebpDecoder::new(&lossy).decode().is_err());
+    }
+}
@@ -0,0 +1,100 @@
+pub const RIFF: &[u8; 4] = b"RIFF";
+pub const WEBP: &[u8; 4] = b"WEBP";
+
+pub const VP8: &[u8; 4] = b"VP8 ";
+pub const VP8L: &[u8; 4] = b"VP8L";
+pub const VP8X: &[u8; 4] = b"VP8X";
+pub const EXIF: &[u8; 4] = b"EXIF";
+
+pub const VP8L_SIGNATURE: u8 = 0x2F;
+
+pub const NUM_LITERAL_CODES: usize = 256;
+pub const NUM_LENGTH_CODES: usize = 24;
+pub const NUM_DISTANCE_CODES: usize = 40;
+pub const MAX_COLOR_CACHE_BITS: u32 = 11;
+
+/// The order in which code length code lengths are stored.
+pub const CODE_LENGTH_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// (x, y) offsets of the first 120 distance codes, nearest neighbors first.
+#[rustfmt::skip]
+pub const DISTANCE_MAP: [(i8, i8); 120] = [
+    (0, 1),  (1, 0),  (1, 1),  (-1, 1), (0, 2),  (2, 0),  (1, 2),
+    (-1, 2), (2, 1),  (-2, 1), (2, 2),  (-2, 2), (0, 3),  (3, 0),
+    (1, 3),  (-1, 3), (3, 1),  (-3, 1), (2, 3),  (-2, 3), (3, 2),
+    (-3, 2), (0, 4),  (4, 0),  (1, 4),  (-1, 4), (4, 1),  (-4, 1),
+    (3, 3),  (-3, 3), (2, 4),  (-2, 4), (4, 2),  (-4, 2), (0, 5),
+    (3, 4),  (-3, 4), (4, 3),  (-4, 3), (5, 0),  (1, 5),  (-1, 5),
+    (5, 1),  (-5, 1), (2, 5),  (-2, 5), (5, 2),  (-5, 2), (4, 4),
+    (-4, 4), (3, 5),  (-3, 5), (5, 3),  (-5, 3), (0, 6),  (6, 0),
+    (1, 6),  (-1, 6), (6, 1),  (-6, 1), (2, 6),  (-2, 6), (6, 2),
+    (-6, 2), (4, 5),  (-4, 5), (5, 4),  (-5, 4), (3, 6),  (-3, 6),
+    (6, 3),  (-6, 3), (0, 7),  (7, 0),  (1, 7),  (-1, 7), (5, 5),
+    (-5, 5), (7, 1),  (-7, 1), (4, 6),  (-4, 6), (6, 4),  (-6, 4),
+    (2, 7),  (-2, 7), (7, 2),  (-7, 2), (3, 7),  (-3, 7), (7, 3),
+    (-7, 3), (5, 6),  (-5, 6), (6, 5),  (-6, 5), (8, 0),  (4, 7),
+    (-4, 7), (7, 4),  (-7, 4), (8, 1),  (8, 2),  (6, 6),  (-6, 6),
+    (8, 3),  (5, 7),  (-5, 7), (7, 5),  (-7, 5), (8, 4),  (6, 7),
+    (-6, 7), (7, 6),  (-7, 6), (8, 5),  (7, 7),  (-7, 7), (8, 6),
+    (8, 7),
+];
+
+/// A transform applied by the encoder, undone by the decoder in reverse order.
+///
+/// Each transform remembers the image width it was read at, since color indexing packs several
+/// pixels into one and narrows the image for every transform after it.
+#[derive(Debug)]
+pub enum Transform {
+    Predictor {
+        width: usize,
+        size_bits: u32,
+        modes: Vec<u32>,
+    },
+    Color {
+        width: usize,
+        size_bits: u32,
+        elements: Vec<u32>,
+    },
+    SubtractGreen,
+    ColorIndexing {
+        width: usize,
+        width_bits: u32,
+        palette: Vec<u32>,
+    },
+}
+
+/// Recently used colors, addressed by a multiplicative hash of their ARGB value.
+#[derive(Debug)]
+pub struct ColorCache {
+    bits: u32,
+    colors: Vec<u32>,
+}
+
+impl ColorCache {
+    pub fn new(bits: u32) -> Self {
+        Self {
+            bits,
+            colors: vec![0; 1 << bits],
+        }
+    }
+
+    pub const fn size(&self) -> usize {
+        1 << self.bits
+    }
+
+    pub fn insert(&mut self, argb: u32) {
+        let index = (0x1E35_A7BDu32.wrapping_mul(argb) >> (32 - self.bits)) as usize;
+        self.colors[index] = argb;
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        self.colors[index]
+    }
+}
+
+/// The image is split into blocks of `1 << bits` pixels a side.
+pub const fn subsample_size(size: usize, bits: u32) -> usize {
+    (size + (1 << bits) - 1) >> bits
+}
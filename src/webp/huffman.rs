@@ -0,0 +1,124 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::webp::bit_reader::BitReader;
+
+pub const MAX_CODE_LENGTH: usize = 15;
+
+/// A canonical prefix code, decoded by walking code lengths in increasing order.
+#[derive(Debug, Clone)]
+pub struct HuffmanCode {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+    /// Set when the code has a single symbol, which is then read without consuming any bits.
+    single: Option<u16>,
+}
+
+impl HuffmanCode {
+    pub fn from_code_lengths(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &len in lengths {
+            ensure!(
+                len as usize <= MAX_CODE_LENGTH,
+                "Invalid code length: {}",
+                len
+            );
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let used = lengths.iter().filter(|&&len| len > 0).count();
+        ensure!(used > 0, "Prefix code has no symbols.");
+
+        if used == 1 {
+            let symbol = lengths.iter().position(|&len| len > 0).unwrap_or(0);
+
+            return Ok(Self {
+                counts,
+                symbols: vec![],
+                single: Some(symbol as u16),
+            });
+        }
+
+        // Every code must be complete, neither over nor under subscribed.
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            ensure!(left >= 0, "Over-subscribed prefix code.");
+        }
+        ensure!(left == 0, "Incomplete prefix code.");
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for len in 1..=MAX_CODE_LENGTH {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0u16; used];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len > 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Self {
+            counts,
+            symbols,
+            single: None,
+        })
+    }
+
+    pub fn read_symbol(&self, reader: &mut BitReader) -> Result<u16> {
+        if let Some(symbol) = self.single {
+            return Ok(symbol);
+        }
+
+        let bits = reader.peek(MAX_CODE_LENGTH as u32);
+
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_CODE_LENGTH {
+            code |= ((bits >> (len - 1)) & 1) as i32;
+
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                reader.consume(len as u32)?;
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        bail!("Invalid prefix code.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_codes() -> Result<()> {
+        // Lengths [2, 1, 3, 3] assign the codes 10, 0, 110 and 111.
+        let code = HuffmanCode::from_code_lengths(&[2, 1, 3, 3])?;
+
+        // Codes are packed starting from their most significant bit.
+        let data = [0b1101_1010, 0b0000_0001];
+        let mut reader = BitReader::new(&data);
+
+        let symbols = (0..4)
+            .map(|_| code.read_symbol(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(symbols, [1, 0, 2, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        assert!(HuffmanCode::from_code_lengths(&[0, 0]).is_err());
+        assert!(HuffmanCode::from_code_lengths(&[1, 1, 1]).is_err());
+        assert!(HuffmanCode::from_code_lengths(&[1, 2]).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+pub use decoder::*;
+pub mod grammar;
+
+mod bit_reader;
+mod decoder;
+mod huffman;
+mod transform;
+mod vp8l;
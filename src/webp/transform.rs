@@ -0,0 +1,225 @@
+use crate::webp::grammar::{subsample_size, Transform};
+
+const fn channel(argb: u32, shift: u32) -> i32 {
+    ((argb >> shift) & 0xFF) as i32
+}
+
+/// Combines four per channel values, in ARGB order, into a pixel.
+fn from_channels(f: impl Fn(u32) -> i32) -> u32 {
+    [24, 16, 8, 0]
+        .into_iter()
+        .fold(0, |argb, shift| argb | ((f(shift) as u32 & 0xFF) << shift))
+}
+
+pub fn add_pixels(a: u32, b: u32) -> u32 {
+    from_channels(|shift| channel(a, shift) + channel(b, shift))
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    from_channels(|shift| (channel(a, shift) + channel(b, shift)) / 2)
+}
+
+fn select(left: u32, top: u32, top_left: u32) -> u32 {
+    // Manhattan distances of the left and top pixels to the gradient estimate L + T - TL.
+    let (mut distance_left, mut distance_top) = (0, 0);
+    for shift in [24, 16, 8, 0] {
+        let estimate = channel(left, shift) + channel(top, shift) - channel(top_left, shift);
+        distance_left += (estimate - channel(left, shift)).abs();
+        distance_top += (estimate - channel(top, shift)).abs();
+    }
+
+    if distance_left < distance_top {
+        left
+    } else {
+        top
+    }
+}
+
+fn clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    from_channels(|shift| (channel(a, shift) + channel(b, shift) - channel(c, shift)).clamp(0, 255))
+}
+
+fn clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    from_channels(|shift| {
+        let a = channel(a, shift);
+        (a + (a - channel(b, shift)) / 2).clamp(0, 255)
+    })
+}
+
+fn predict(mode: u32, pixels: &[u32], index: usize, width: usize) -> u32 {
+    let left = pixels[index - 1];
+    let top = pixels[index - width];
+    // The rightmost pixel's top-right neighbor wraps around to the leftmost pixel of its own row.
+    let top_right = pixels[index - width + 1];
+    let top_left = pixels[index - width - 1];
+
+    match mode {
+        1 => left,
+        2 => top,
+        3 => top_right,
+        4 => top_left,
+        5 => average2(average2(left, top_right), top),
+        6 => average2(left, top_left),
+        7 => average2(left, top),
+        8 => average2(top_left, top),
+        9 => average2(top, top_right),
+        10 => average2(average2(left, top_left), average2(top, top_right)),
+        11 => select(left, top, top_left),
+        12 => clamp_add_subtract_full(left, top, top_left),
+        13 => clamp_add_subtract_half(average2(left, top), top_left),
+        // Mode 0 and the unused modes 14 and 15 predict opaque black.
+        _ => 0xFF00_0000,
+    }
+}
+
+/// `(t * c) >> 5` of two signed 8-bit values.
+const fn color_transform_delta(t: u8, c: u8) -> i32 {
+    (t as i8 as i32 * c as i8 as i32) >> 5
+}
+
+impl Transform {
+    /// Undoes the transform, leaving `pixels` as the input image the encoder saw.
+    pub fn invert(&self, pixels: &mut Vec<u32>, height: usize) {
+        match self {
+            Self::Predictor {
+                width,
+                size_bits,
+                modes,
+            } => {
+                let width = *width;
+                let blocks_per_row = subsample_size(width, *size_bits);
+
+                for y in 0..height {
+                    for x in 0..width {
+                        let index = y * width + x;
+
+                        let prediction = match (x, y) {
+                            (0, 0) => 0xFF00_0000,
+                            (_, 0) => pixels[index - 1],
+                            (0, _) => pixels[index - width],
+                            _ => {
+                                let block = (y >> size_bits) * blocks_per_row + (x >> size_bits);
+                                let mode = (modes[block] >> 8) & 0x0F;
+
+                                predict(mode, pixels, index, width)
+                            }
+                        };
+
+                        pixels[index] = add_pixels(pixels[index], prediction);
+                    }
+                }
+            }
+            Self::Color {
+                width,
+                size_bits,
+                elements,
+            } => {
+                let width = *width;
+                let blocks_per_row = subsample_size(width, *size_bits);
+
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    let (x, y) = (index % width, index / width);
+                    let element = elements[(y >> size_bits) * blocks_per_row + (x >> size_bits)];
+
+                    let green_to_red = element as u8;
+                    let green_to_blue = (element >> 8) as u8;
+                    let red_to_blue = (element >> 16) as u8;
+
+                    let green = (*pixel >> 8) as u8;
+                    let red =
+                        ((*pixel >> 16) as i32 + color_transform_delta(green_to_red, green)) as u8;
+                    let blue = (*pixel as i32
+                        + color_transform_delta(green_to_blue, green)
+                        + color_transform_delta(red_to_blue, red))
+                        as u8;
+
+                    *pixel = (*pixel & 0xFF00_FF00) | ((red as u32) << 16) | blue as u32;
+                }
+            }
+            Self::SubtractGreen => {
+                for pixel in pixels.iter_mut() {
+                    let green = (*pixel >> 8) & 0xFF;
+                    let red = ((*pixel >> 16) + green) & 0xFF;
+                    let blue = ((*pixel & 0xFF) + green) & 0xFF;
+
+                    *pixel = (*pixel & 0xFF00_FF00) | (red << 16) | blue;
+                }
+            }
+            Self::ColorIndexing {
+                width,
+                width_bits,
+                palette,
+            } => {
+                let width = *width;
+                let packed_width = subsample_size(width, *width_bits);
+                let bits_per_index = 8 >> width_bits;
+                let index_mask = (1 << bits_per_index) - 1;
+                let x_mask = (1 << width_bits) - 1;
+
+                let mut out = Vec::with_capacity(width * height);
+
+                for y in 0..height {
+                    for x in 0..width {
+                        let packed = pixels[y * packed_width + (x >> width_bits)];
+                        let green = (packed >> 8) & 0xFF;
+                        let index = (green >> (bits_per_index * (x & x_mask) as u32)) & index_mask;
+
+                        // Indices past the end of the palette are transparent black.
+                        out.push(palette.get(index as usize).copied().unwrap_or(0));
+                    }
+                }
+
+                *pixels = out;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_predictors() {
+        let (left, top, top_left) = (0xFF10_2030, 0xFF20_1010, 0xFF00_0000);
+
+        assert_eq!(average2(left, top), 0xFF18_1820);
+        assert_eq!(select(left, top, top_left), left);
+        assert_eq!(clamp_add_subtract_full(left, top, top_left), 0xFF30_3040);
+        assert_eq!(
+            clamp_add_subtract_half(0xFF10_1010, 0xFF20_0000),
+            0xFF08_1818
+        );
+    }
+
+    #[test]
+    fn test_color_transform_delta() {
+        assert_eq!(color_transform_delta(32, 10), 10);
+        assert_eq!(color_transform_delta(0xE0, 10), -10);
+        assert_eq!(color_transform_delta(32, 0xF6), -10);
+    }
+
+    #[test]
+    fn test_color_indexing_unpacks_bits() {
+        // Four colors use two bits per index, so four pixels share one packed pixel.
+        let transform = Transform::ColorIndexing {
+            width: 5,
+            width_bits: 2,
+            palette: vec![0xFF00_0000, 0xFFFF_0000, 0xFF00_FF00, 0xFF00_00FF],
+        };
+
+        let mut pixels = vec![0b1110_0100 << 8, 0b0000_0010 << 8];
+        transform.invert(&mut pixels, 1);
+
+        assert_eq!(
+            pixels,
+            [
+                0xFF00_0000,
+                0xFFFF_0000,
+                0xFF00_FF00,
+                0xFF00_00FF,
+                0xFF00_FF00
+            ]
+        );
+    }
+}
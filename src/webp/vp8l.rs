@@ -0,0 +1,551 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::webp::bit_reader::BitReader;
+use crate::webp::grammar::{
+    subsample_size, ColorCache, Transform, CODE_LENGTH_ORDER, DISTANCE_MAP, MAX_COLOR_CACHE_BITS,
+    NUM_DISTANCE_CODES, NUM_LENGTH_CODES, NUM_LITERAL_CODES, VP8L_SIGNATURE,
+};
+use crate::webp::huffman::HuffmanCode;
+use crate::webp::transform::add_pixels;
+
+const GREEN: usize = 0;
+const RED: usize = 1;
+const BLUE: usize = 2;
+const ALPHA: usize = 3;
+const DISTANCE: usize = 4;
+
+/// The five prefix codes used to decode one region of an image.
+type HuffmanGroup = [HuffmanCode; 5];
+
+/// A decoded VP8L image, one ARGB value per pixel.
+#[derive(Debug)]
+pub struct Vp8lImage {
+    pub width: u32,
+    pub height: u32,
+    pub alpha_is_used: bool,
+    pub argb: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub struct Vp8lDecoder<'a> {
+    reader: BitReader<'a>,
+}
+
+impl<'a> Vp8lDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            reader: BitReader::new(data),
+        }
+    }
+
+    pub fn decode(&mut self) -> Result<Vp8lImage> {
+        ensure!(
+            self.reader.read_bits(8)? == VP8L_SIGNATURE as u32,
+            "Invalid VP8L signature."
+        );
+
+        let width = self.reader.read_bits(14)? as usize + 1;
+        let height = self.reader.read_bits(14)? as usize + 1;
+        let alpha_is_used = self.reader.read_bit()?;
+
+        let version = self.reader.read_bits(3)?;
+        ensure!(version == 0, "Unsupported VP8L version: {}", version);
+
+        let mut transforms = Vec::new();
+        let mut seen = 0u32;
+        let mut coded_width = width;
+
+        while self.reader.read_bit()? {
+            let transform_type = self.reader.read_bits(2)?;
+            ensure!(
+                seen & (1 << transform_type) == 0,
+                "Transform {} is used more than once.",
+                transform_type
+            );
+            seen |= 1 << transform_type;
+
+            transforms.push(self.read_transform(transform_type, &mut coded_width, height)?);
+        }
+
+        let mut argb = self.decode_image_stream(coded_width, height, true)?;
+
+        for transform in transforms.iter().rev() {
+            transform.invert(&mut argb, height);
+        }
+
+        Ok(Vp8lImage {
+            width: width as u32,
+            height: height as u32,
+            alpha_is_used,
+            argb,
+        })
+    }
+
+    fn read_transform(
+        &mut self,
+        transform_type: u32,
+        width: &mut usize,
+        height: usize,
+    ) -> Result<Transform> {
+        let transform = match transform_type {
+            0 | 1 => {
+                let size_bits = self.reader.read_bits(3)? + 2;
+                let data = self.decode_image_stream(
+                    subsample_size(*width, size_bits),
+                    subsample_size(height, size_bits),
+                    false,
+                )?;
+
+                if transform_type == 0 {
+                    Transform::Predictor {
+                        width: *width,
+                        size_bits,
+                        modes: data,
+                    }
+                } else {
+                    Transform::Color {
+                        width: *width,
+                        size_bits,
+                        elements: data,
+                    }
+                }
+            }
+            2 => Transform::SubtractGreen,
+            _ => {
+                let size = self.reader.read_bits(8)? as usize + 1;
+                let mut palette = self.decode_image_stream(size, 1, false)?;
+
+                // Palette entries are stored as differences to the previous entry.
+                for i in 1..palette.len() {
+                    palette[i] = add_pixels(palette[i], palette[i - 1]);
+                }
+
+                let width_bits = match size {
+                    0..=2 => 3,
+                    3..=4 => 2,
+                    5..=16 => 1,
+                    _ => 0,
+                };
+
+                let transform = Transform::ColorIndexing {
+                    width: *width,
+                    width_bits,
+                    palette,
+                };
+
+                *width = subsample_size(*width, width_bits);
+
+                transform
+            }
+        };
+
+        Ok(transform)
+    }
+
+    /// Decodes the main ARGB image (`is_main` set) or one of the entropy coded sub images that
+    /// carry transform data, meta prefix codes and palettes.
+    fn decode_image_stream(
+        &mut self,
+        width: usize,
+        height: usize,
+        is_main: bool,
+    ) -> Result<Vec<u32>> {
+        let color_cache = if self.reader.read_bit()? {
+            let bits = self.reader.read_bits(4)?;
+            ensure!(
+                (1..=MAX_COLOR_CACHE_BITS).contains(&bits),
+                "Invalid color cache size: {} bits",
+                bits
+            );
+
+            Some(ColorCache::new(bits))
+        } else {
+            None
+        };
+
+        // The main image may use different prefix codes for each block of pixels.
+        let (meta_bits, meta_codes) = if is_main && self.reader.read_bit()? {
+            let bits = self.reader.read_bits(3)? + 2;
+            let image = self.decode_image_stream(
+                subsample_size(width, bits),
+                subsample_size(height, bits),
+                false,
+            )?;
+
+            let codes = image.into_iter().map(|p| (p >> 8) & 0xFFFF).collect();
+            (bits, Some(codes))
+        } else {
+            (0, None)
+        };
+
+        let num_groups = meta_codes
+            .as_ref()
+            .and_then(|codes: &Vec<u32>| codes.iter().max())
+            .map_or(1, |&max| max as usize + 1);
+
+        let cache_size = color_cache.as_ref().map_or(0, ColorCache::size);
+
+        let groups = (0..num_groups)
+            .map(|_| self.read_group(cache_size))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.decode_pixels(
+            width,
+            height,
+            &groups,
+            meta_codes.as_deref().map(|codes| (meta_bits, codes)),
+            color_cache,
+        )
+    }
+
+    fn read_group(&mut self, cache_size: usize) -> Result<HuffmanGroup> {
+        Ok([
+            self.read_prefix_code(NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_size)?,
+            self.read_prefix_code(NUM_LITERAL_CODES)?,
+            self.read_prefix_code(NUM_LITERAL_CODES)?,
+            self.read_prefix_code(NUM_LITERAL_CODES)?,
+            self.read_prefix_code(NUM_DISTANCE_CODES)?,
+        ])
+    }
+
+    fn read_prefix_code(&mut self, alphabet_size: usize) -> Result<HuffmanCode> {
+        let mut lengths = vec![0u8; alphabet_size];
+
+        // A simple code lists one or two symbols directly.
+        if self.reader.read_bit()? {
+            let num_symbols = self.reader.read_bits(1)? + 1;
+            let first_symbol_bits = if self.reader.read_bit()? { 8 } else { 1 };
+
+            let mut symbols = vec![self.reader.read_bits(first_symbol_bits)?];
+            if num_symbols == 2 {
+                symbols.push(self.reader.read_bits(8)?);
+            }
+
+            for symbol in symbols {
+                ensure!(
+                    (symbol as usize) < alphabet_size,
+                    "Symbol {} is outside the alphabet of {} symbols.",
+                    symbol,
+                    alphabet_size
+                );
+                lengths[symbol as usize] = 1;
+            }
+
+            return HuffmanCode::from_code_lengths(&lengths);
+        }
+
+        let num_code_lengths = self.reader.read_bits(4)? as usize + 4;
+        let mut code_length_lengths = [0u8; CODE_LENGTH_ORDER.len()];
+        for &symbol in &CODE_LENGTH_ORDER[..num_code_lengths] {
+            code_length_lengths[symbol] = self.reader.read_bits(3)? as u8;
+        }
+
+        let code_length_code = HuffmanCode::from_code_lengths(&code_length_lengths)?;
+
+        let mut max_symbol = if self.reader.read_bit()? {
+            let length_bits = 2 + 2 * self.reader.read_bits(3)?;
+            let max_symbol = 2 + self.reader.read_bits(length_bits)? as usize;
+            ensure!(
+                max_symbol <= alphabet_size,
+                "Code length count {} exceeds the alphabet size {}.",
+                max_symbol,
+                alphabet_size
+            );
+
+            max_symbol
+        } else {
+            alphabet_size
+        };
+
+        let mut previous_length = 8;
+        let mut symbol = 0;
+
+        while symbol < alphabet_size && max_symbol > 0 {
+            max_symbol -= 1;
+
+            let (repeat, length) = match code_length_code.read_symbol(&mut self.reader)? {
+                length @ 0..=15 => {
+                    if length != 0 {
+                        previous_length = length as u8;
+                    }
+
+                    (1, length as u8)
+                }
+                16 => (3 + self.reader.read_bits(2)? as usize, previous_length),
+                17 => (3 + self.reader.read_bits(3)? as usize, 0),
+                _ => (11 + self.reader.read_bits(7)? as usize, 0),
+            };
+
+            ensure!(
+                symbol + repeat <= alphabet_size,
+                "Code lengths overflow the alphabet."
+            );
+
+            lengths[symbol..symbol + repeat].fill(length);
+            symbol += repeat;
+        }
+
+        HuffmanCode::from_code_lengths(&lengths)
+    }
+
+    fn decode_pixels(
+        &mut self,
+        width: usize,
+        height: usize,
+        groups: &[HuffmanGroup],
+        meta: Option<(u32, &[u32])>,
+        mut color_cache: Option<ColorCache>,
+    ) -> Result<Vec<u32>> {
+        let len = width * height;
+        let mut pixels = vec![0u32; len];
+        let mut position = 0;
+        // Every decoded pixel, literal or copied, is added to the color cache.
+        let mut cached = 0;
+
+        while position < len {
+            let group = match meta {
+                Some((bits, codes)) => {
+                    let (x, y) = (position % width, position / width);
+                    let index = (y >> bits) * subsample_size(width, bits) + (x >> bits);
+                    &groups[codes[index] as usize]
+                }
+                None => &groups[0],
+            };
+
+            let symbol = group[GREEN].read_symbol(&mut self.reader)? as usize;
+
+            if symbol < NUM_LITERAL_CODES {
+                let red = group[RED].read_symbol(&mut self.reader)? as u32;
+                let blue = group[BLUE].read_symbol(&mut self.reader)? as u32;
+                let alpha = group[ALPHA].read_symbol(&mut self.reader)? as u32;
+
+                pixels[position] = (alpha << 24) | (red << 16) | ((symbol as u32) << 8) | blue;
+                position += 1;
+            } else if symbol < NUM_LITERAL_CODES + NUM_LENGTH_CODES {
+                let length = self.read_prefix_value((symbol - NUM_LITERAL_CODES) as u32)? as usize;
+                let distance_symbol = group[DISTANCE].read_symbol(&mut self.reader)?;
+                let distance_code = self.read_prefix_value(distance_symbol as u32)? as usize;
+                let distance = Self::distance(distance_code, width);
+
+                ensure!(
+                    distance <= position,
+                    "Backward reference points before the start of the image."
+                );
+                ensure!(
+                    position + length <= len,
+                    "Backward reference runs past the end of the image."
+                );
+
+                for i in position..position + length {
+                    pixels[i] = pixels[i - distance];
+                }
+                position += length;
+            } else {
+                let Some(cache) = color_cache.as_ref() else {
+                    bail!("Color cache symbol without a color cache.");
+                };
+
+                pixels[position] = cache.get(symbol - NUM_LITERAL_CODES - NUM_LENGTH_CODES);
+                position += 1;
+            }
+
+            if let Some(cache) = color_cache.as_mut() {
+                for &argb in &pixels[cached..position] {
+                    cache.insert(argb);
+                }
+                cached = position;
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Reads the value of a length or distance prefix symbol, followed by its extra bits.
+    fn read_prefix_value(&mut self, prefix: u32) -> Result<u32> {
+        if prefix < 4 {
+            return Ok(prefix + 1);
+        }
+
+        let extra_bits = (prefix - 2) >> 1;
+        let offset = (2 + (prefix & 1)) << extra_bits;
+
+        Ok(offset + self.reader.read_bits(extra_bits)? + 1)
+    }
+
+    /// Maps a distance code to a distance in pixels. The first 120 codes are 2D neighborhood
+    /// offsets.
+    fn distance(code: usize, width: usize) -> usize {
+        if code > DISTANCE_MAP.len() {
+            return code - DISTANCE_MAP.len();
+        }
+
+        let (dx, dy) = DISTANCE_MAP[code - 1];
+        let distance = dx as isize + dy as isize * width as isize;
+
+        distance.max(1) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a VP8L bitstream. Prefix codes are written most significant bit first.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, n: u32) {
+            for i in 0..n {
+                if self.len % 8 == 0 {
+                    self.bytes.push(0);
+                }
+
+                let bit = ((value >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (self.len % 8);
+                self.len += 1;
+            }
+        }
+
+        fn write_code(&mut self, code: u32, len: u32) {
+            for i in (0..len).rev() {
+                self.write(code >> i, 1);
+            }
+        }
+
+        fn simple_code(&mut self, symbols: &[u8]) {
+            self.write(1, 1);
+            self.write(symbols.len() as u32 - 1, 1);
+            self.write(1, 1);
+
+            for &symbol in symbols {
+                self.write(symbol as u32, 8);
+            }
+        }
+
+        fn header(&mut self, width: u32, height: u32) {
+            self.write(VP8L_SIGNATURE as u32, 8);
+            self.write(width - 1, 14);
+            self.write(height - 1, 14);
+            self.write(1, 1);
+            self.write(0, 3);
+        }
+    }
+
+    #[test]
+    fn test_color_indexing() -> Result<()> {
+        let mut w = BitWriter::default();
+        w.header(4, 1);
+
+        // A color indexing transform with a two color palette.
+        w.write(1, 1);
+        w.write(3, 2);
+        w.write(1, 8);
+
+        // The palette is opaque red followed by the delta to opaque blue.
+        w.write(0, 1);
+        w.simple_code(&[0]);
+        w.simple_code(&[0xFF, 0x01]);
+        w.simple_code(&[0x00, 0xFF]);
+        w.simple_code(&[0xFF, 0x00]);
+        w.simple_code(&[0]);
+
+        for (red, blue, alpha) in [(1, 0, 1), (0, 1, 0)] {
+            w.write_code(red, 1);
+            w.write_code(blue, 1);
+            w.write_code(alpha, 1);
+        }
+
+        w.write(0, 1);
+
+        // With one bit per index, the four pixels pack into a single green value.
+        w.write(0, 1);
+        w.write(0, 1);
+        w.simple_code(&[0b0110]);
+        for _ in 0..4 {
+            w.simple_code(&[0]);
+        }
+
+        let image = Vp8lDecoder::new(&w.bytes).decode()?;
+        assert_eq!(
+            image.argb,
+            [0xFFFF_0000, 0xFF00_00FF, 0xFF00_00FF, 0xFFFF_0000]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_color_cache() -> Result<()> {
+        let argb = 0xFF11_2233u32;
+        let cache_index = (0x1E35_A7BDu32.wrapping_mul(argb) >> 28) as usize;
+        let cache_symbol = NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_index;
+
+        let mut w = BitWriter::default();
+        w.header(2, 1);
+        w.write(0, 1);
+
+        // A 16 entry color cache and no meta prefix codes.
+        w.write(1, 1);
+        w.write(4, 4);
+        w.write(0, 1);
+
+        // The green code has two symbols: the literal 0x22 and a color cache hit. Its code
+        // lengths are coded with 0, 1, 17 and 18 all two bits long.
+        w.write(0, 1);
+        w.write(0, 4);
+        for _ in 0..4 {
+            w.write(2, 3);
+        }
+        w.write(0, 1);
+
+        let (zero, one, zeros) = (0b00, 0b01, 0b11);
+        let zero_run = |w: &mut BitWriter, mut run: usize| {
+            while run >= 11 {
+                let n = run.min(138);
+                w.write_code(zeros, 2);
+                w.write((n - 11) as u32, 7);
+                run -= n;
+            }
+            for _ in 0..run {
+                w.write_code(zero, 2);
+            }
+        };
+
+        zero_run(&mut w, 0x22);
+        w.write_code(one, 2);
+        zero_run(&mut w, cache_symbol - 0x23);
+        w.write_code(one, 2);
+        zero_run(
+            &mut w,
+            NUM_LITERAL_CODES + NUM_LENGTH_CODES + 16 - cache_symbol - 1,
+        );
+
+        w.simple_code(&[0x11]);
+        w.simple_code(&[0x33]);
+        w.simple_code(&[0xFF]);
+        w.simple_code(&[0]);
+
+        // A literal, then the same color through the cache.
+        w.write_code(0, 1);
+        w.write_code(1, 1);
+
+        let image = Vp8lDecoder::new(&w.bytes).decode()?;
+        assert_eq!(image.argb, [argb, argb]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_distance_map() {
+        assert_eq!(Vp8lDecoder::distance(1, 10), 10);
+        assert_eq!(Vp8lDecoder::distance(2, 10), 1);
+        assert_eq!(Vp8lDecoder::distance(4, 10), 9);
+        // Offsets reaching before the start of a narrow row are clamped.
+        assert_eq!(Vp8lDecoder::distance(10, 1), 1);
+        assert_eq!(Vp8lDecoder::distance(125, 10), 5);
+    }
+}